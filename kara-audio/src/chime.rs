@@ -0,0 +1,120 @@
+//! The sound of a ringing alarm, played on the default output device
+use std::{f32::consts::TAU, thread, time::Duration};
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use tracing::error;
+
+/// Pitch of the beeps in Hz
+const PITCH: f32 = 880.0;
+/// Beeps come in groups of four, each `BEEP` seconds long with as long a gap, and groups
+/// repeat every `PERIOD` seconds
+const BEEP: f32 = 0.1;
+const BEEPS: f32 = 4.0;
+const PERIOD: f32 = 1.4;
+const VOLUME: f32 = 0.3;
+/// How long an alarm rings if nobody stops it
+pub const MAX_RING: Duration = Duration::from_secs(10 * 60);
+
+/// Rings until it is dropped, or for [`MAX_RING`] at most
+pub struct Chime {
+    stop: crossbeam_channel::Sender<()>,
+    /// Disconnected once the chime has stopped
+    ringing: crossbeam_channel::Receiver<()>,
+}
+
+impl Chime {
+    /// Starts ringing on the default output device
+    pub fn ring() -> anyhow::Result<Self> {
+        let (started, is_started) = crossbeam_channel::bounded(1);
+        let (stop, stopped) = crossbeam_channel::bounded(1);
+        let (rings, ringing) = crossbeam_channel::bounded::<()>(0);
+        // Streams cannot move between threads on every platform, so this one lives on its own
+        thread::spawn(move || match play() {
+            Ok(stream) => {
+                let _rings = rings;
+                let _ = started.send(Ok(()));
+                let _ = stopped.recv_timeout(MAX_RING);
+                drop(stream);
+            }
+            Err(e) => {
+                let _ = started.send(Err(e));
+            }
+        });
+        is_started.recv()??;
+        Ok(Self { stop, ringing })
+    }
+
+    /// Whether the chime is still ringing, as it stops by itself after [`MAX_RING`]
+    pub fn is_ringing(&self) -> bool {
+        self.ringing.try_recv() != Err(crossbeam_channel::TryRecvError::Disconnected)
+    }
+}
+
+impl Drop for Chime {
+    fn drop(&mut self) {
+        let _ = self.stop.send(());
+    }
+}
+
+fn play() -> anyhow::Result<cpal::Stream> {
+    let host = cpal::default_host();
+    let device = host
+        .default_output_device()
+        .ok_or_else(|| anyhow::anyhow!("no audio output device"))?;
+    let config = device.default_output_config()?;
+    let channels = config.channels() as usize;
+    let mut tone = Tone::new(config.sample_rate().0 as f32);
+    let err_fn = |err| error!("an error occurred on the alarm stream: {}", err);
+    let stream = match config.sample_format() {
+        cpal::SampleFormat::I16 => device.build_output_stream(
+            &config.into(),
+            move |data: &mut [i16], _| tone.fill(data, channels),
+            err_fn,
+        ),
+        cpal::SampleFormat::U16 => device.build_output_stream(
+            &config.into(),
+            move |data: &mut [u16], _| tone.fill(data, channels),
+            err_fn,
+        ),
+        cpal::SampleFormat::F32 => device.build_output_stream(
+            &config.into(),
+            move |data: &mut [f32], _| tone.fill(data, channels),
+            err_fn,
+        ),
+    }?;
+    stream.play()?;
+    Ok(stream)
+}
+
+/// A sine wave switched on and off in groups of beeps
+struct Tone {
+    sample_rate: f32,
+    position: u64,
+}
+
+impl Tone {
+    fn new(sample_rate: f32) -> Self {
+        Self {
+            sample_rate,
+            position: 0,
+        }
+    }
+
+    fn next(&mut self) -> f32 {
+        let time = self.position as f32 / self.sample_rate;
+        // Starting over each period keeps the time precise however long the alarm rings
+        self.position = (self.position + 1) % (PERIOD * self.sample_rate) as u64;
+        if time < 2.0 * BEEP * BEEPS && time % (2.0 * BEEP) < BEEP {
+            (time * PITCH * TAU).sin() * VOLUME
+        } else {
+            0.0
+        }
+    }
+
+    fn fill<T: cpal::Sample>(&mut self, data: &mut [T], channels: usize) {
+        for frame in data.chunks_mut(channels) {
+            let sample = T::from(&self.next());
+            frame.iter_mut().for_each(|channel| *channel = sample);
+        }
+    }
+}
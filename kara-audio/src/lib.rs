@@ -11,17 +11,23 @@ use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use crossbeam_channel::Sender;
 use dasp::{sample::ToSample, Sample};
 use iced_winit::winit::event_loop::EventLoopProxy;
-use kara_events::KaraEvents;
+use kara_events::{KaraEvents, Speaker, Utterance};
+use tokio::sync::watch;
 use tracing::{debug, error};
 
 use self::{
     helpers::set_sample_rate,
+    speaker::SpeakerIdentifier,
     stream::{AudioStream, Event},
     stt_sources::STTSource,
 };
 
 mod helpers;
 
+pub mod chime;
+pub mod native_logs;
+pub mod progress;
+pub mod speaker;
 pub mod stream;
 pub mod stt_sources;
 pub const SAMPLE_RATE: u32 = 16000;
@@ -66,10 +72,14 @@ impl Default for Config {
     }
 }
 
+/// Starts recording audio for the visualiser. Transcription begins once `stt_source`
+/// holds a source and switches over whenever a new source is sent. Commands are
+/// attributed to a speaker if `speakers` is set
 pub fn start_stream(
     vis_settings: Config,
     stt_proxy: EventLoopProxy<KaraEvents>,
-    stt_source: STTSource,
+    stt_source: watch::Receiver<Option<STTSource>>,
+    speakers: Option<SpeakerIdentifier>,
     is_processing: Arc<AtomicBool>,
     wake_up: Arc<AtomicBool>,
     is_ready: Arc<AtomicBool>,
@@ -80,6 +90,7 @@ pub fn start_stream(
         event_sender.clone(),
         stt_proxy,
         stt_source,
+        speakers,
         is_processing,
         wake_up,
         is_ready,
@@ -90,7 +101,8 @@ pub fn start_stream(
 pub fn init_audio_sender(
     event_sender: crossbeam_channel::Sender<Event>,
     event_proxy: EventLoopProxy<KaraEvents>,
-    stt_source: STTSource,
+    stt_source: watch::Receiver<Option<STTSource>>,
+    speakers: Option<SpeakerIdentifier>,
     is_processing: Arc<AtomicBool>,
     wake_up: Arc<AtomicBool>,
     is_ready: Arc<AtomicBool>,
@@ -174,12 +186,21 @@ pub fn init_audio_sender(
 
     let inner_wake = Arc::clone(&wake_up);
     tokio::spawn(async move {
+        let mut stt_source = stt_source;
+        while stt_source.borrow().is_none() {
+            if let Err(e) = stt_source.changed().await {
+                error!("speech to text source was not initialised: {e}");
+                return;
+            }
+        }
         loop {
             if !is_processing.load(Ordering::Relaxed) && is_ready.load(Ordering::Relaxed) {
-                match &stt_source {
+                let source = match stt_source.borrow().clone() {
+                    Some(source) => source,
+                    None => continue,
+                };
+                match &source {
                     STTSource::Kara(kara_transcriber) => {
-                        use gag::Gag;
-                        let _print_gag = Gag::stderr().unwrap();
                         let is_awake = inner_wake.load(Ordering::Relaxed);
                         let stream = if is_awake {
                             kara_transcriber.recogniser()
@@ -187,7 +208,11 @@ pub fn init_audio_sender(
                             kara_transcriber.recogniser_wake()
                         };
                         let mut recogniser = stream.lock().unwrap();
+                        let mut utterance = Vec::new();
                         while let Ok(val) = rx.clone().recv() {
+                            if is_awake && speakers.is_some() {
+                                utterance.extend_from_slice(&val);
+                            }
                             let state = recogniser.accept_waveform(&val);
                             match state {
                                 vosk::DecodingState::Finalized => {
@@ -210,19 +235,22 @@ pub fn init_audio_sender(
 
                         if is_awake {
                             // We're awake so process command
-                            if let Err(e) = event_proxy.send_event(KaraEvents::ProcessCommand(
-                                recogniser.result().single().unwrap().text.to_owned(),
-                            )) {
+                            let speaker = match &speakers {
+                                Some(speakers) => speakers.identify(&utterance),
+                                None => Speaker::Unknown,
+                            };
+                            if let Err(e) =
+                                event_proxy.send_event(KaraEvents::ProcessCommand(Utterance {
+                                    text: recogniser.result().single().unwrap().text.to_owned(),
+                                    speaker,
+                                }))
+                            {
                                 error!("{e}");
                             };
                         } else {
                             // check if wake word and send wake up event
-                            if recogniser
-                                .final_result()
-                                .single()
-                                .unwrap()
-                                .text
-                                .eq_ignore_ascii_case("hey kara")
+                            if kara_transcriber
+                                .is_wake_phrase(&recogniser.final_result().single().unwrap().text)
                             {
                                 inner_wake.store(true, Ordering::Relaxed);
                                 /*
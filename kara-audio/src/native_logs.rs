@@ -0,0 +1,123 @@
+//! Vosk and Kaldi write their logs straight to stderr. Rather than silencing stderr while
+//! models are in use, stderr is redirected to a pipe and every line is inspected: lines in
+//! Kaldi's log format become `tracing` events under the `kara_audio::vosk` target, and
+//! everything else is passed through to the original stderr untouched.
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    os::raw::c_int,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+};
+
+use tracing::{debug, error, info, trace, warn, Level};
+
+static CAPTURING: AtomicBool = AtomicBool::new(false);
+
+/// Redirects native library logs into `tracing`.
+///
+/// `level` is the most verbose level that vosk should produce. Subsequent calls only
+/// update the level.
+pub fn capture(level: Level) -> io::Result<()> {
+    unsafe { vosk_sys::vosk_set_log_level(vosk_log_level(level)) };
+    if CAPTURING.swap(true, Ordering::SeqCst) {
+        return Ok(());
+    }
+    let result = redirect_stderr();
+    if result.is_err() {
+        CAPTURING.store(false, Ordering::SeqCst);
+    }
+    result
+}
+
+fn redirect_stderr() -> io::Result<()> {
+    let stderr = os_pipe::dup_stderr()?;
+    let (reader, writer) = os_pipe::pipe()?;
+    let redirect = gag::Redirect::stderr(writer)?;
+    thread::Builder::new()
+        .name("native-logs".to_owned())
+        .spawn(move || forward(reader, stderr))?;
+    // stderr stays redirected for the lifetime of the process
+    std::mem::forget(redirect);
+    trace!(target: "kara_audio::vosk", "capturing native library logs");
+    Ok(())
+}
+
+fn forward(reader: os_pipe::PipeReader, mut stderr: os_pipe::PipeWriter) {
+    let mut reader = BufReader::new(reader);
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf) {
+            Ok(0) => break,
+            Ok(_) => {
+                let line = String::from_utf8_lossy(&buf);
+                let line = line.trim_end_matches(&['\r', '\n'][..]);
+                if !log_line(line) {
+                    let _ = stderr.write_all(&buf);
+                }
+            }
+            Err(e) => {
+                let _ = writeln!(stderr, "failed to read native library logs: {e}");
+                break;
+            }
+        }
+    }
+}
+
+/// Emits a Kaldi formatted line as a `tracing` event. Returns `false` if `line` is not a
+/// Kaldi log line
+fn log_line(line: &str) -> bool {
+    match parse_line(line) {
+        Some(KaldiLine {
+            level,
+            origin,
+            message,
+        }) => {
+            match level {
+                Level::ERROR => error!(target: "kara_audio::vosk", origin, "{message}"),
+                Level::WARN => warn!(target: "kara_audio::vosk", origin, "{message}"),
+                Level::INFO => info!(target: "kara_audio::vosk", origin, "{message}"),
+                Level::DEBUG => debug!(target: "kara_audio::vosk", origin, "{message}"),
+                _ => trace!(target: "kara_audio::vosk", origin, "{message}"),
+            }
+            true
+        }
+        None => false,
+    }
+}
+
+struct KaldiLine<'a> {
+    level: Level,
+    origin: &'a str,
+    message: &'a str,
+}
+
+/// Kaldi lines look like `LOG (VoskAPI:ReadDataFiles():model.cc:213) Decoding params...`
+fn parse_line(line: &str) -> Option<KaldiLine<'_>> {
+    let (severity, rest) = line.split_once(" (")?;
+    let level = match severity {
+        "ERROR" | "ASSERTION_FAILED" => Level::ERROR,
+        "WARNING" => Level::WARN,
+        "LOG" => Level::INFO,
+        "VLOG[1]" => Level::DEBUG,
+        vlog if vlog.starts_with("VLOG[") => Level::TRACE,
+        _ => return None,
+    };
+    let (origin, message) = rest.split_once(") ").unwrap_or((rest, ""));
+    Some(KaldiLine {
+        level,
+        origin,
+        message: message.trim(),
+    })
+}
+
+/// vosk prints info messages at 0, only warnings and errors below that and Kaldi's
+/// verbose logs above it
+fn vosk_log_level(level: Level) -> c_int {
+    match level {
+        Level::TRACE => 2,
+        Level::DEBUG => 1,
+        Level::INFO => 0,
+        _ => -1,
+    }
+}
@@ -0,0 +1,151 @@
+use std::time::{Duration, Instant};
+
+use iced_winit::winit::event_loop::EventLoopProxy;
+use indicatif::{ProgressBar, ProgressStyle};
+use kara_events::{KaraEvents, Progress, ProgressPhase, ProgressTask};
+use tracing::error;
+
+/// How often progress events are published to the GUI
+const GUI_REFRESH: Duration = Duration::from_millis(100);
+
+/// Where progress of downloading, extracting and loading models should be shown
+#[derive(Clone, Default)]
+pub enum ProgressReporter {
+    /// Draw progress bars on the terminal
+    #[default]
+    Terminal,
+    /// Publish [`KaraEvents::Progress`] events to the GUI's event loop
+    Gui(EventLoopProxy<KaraEvents>),
+}
+
+impl ProgressReporter {
+    /// Starts tracking a new phase. `total` is `None` when the size of the work is unknown
+    pub fn start(&self, phase: ProgressPhase, total: Option<u64>, message: &str) -> PhaseTracker {
+        self.start_at(phase, 0, total, message)
+    }
+
+    /// Starts tracking a new phase that has already processed `bytes`, e.g. a resumed download
+    pub fn start_at(
+        &self,
+        phase: ProgressPhase,
+        bytes: u64,
+        total: Option<u64>,
+        message: &str,
+    ) -> PhaseTracker {
+        let bar = match self {
+            ProgressReporter::Terminal => {
+                let bar = match total {
+                    Some(total) => {
+                        let bar = ProgressBar::new(total);
+                        bar.set_style(ProgressStyle::default_bar()
+                            .template("{msg}\n{spinner:.green} [{elapsed_precise}] [{wide_bar:.white/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")
+                            .progress_chars("█  "));
+                        bar.set_position(bytes);
+                        bar
+                    }
+                    None => {
+                        let bar = ProgressBar::new_spinner();
+                        bar.enable_steady_tick(120);
+                        bar
+                    }
+                };
+                bar.set_message(message.to_owned());
+                Some(bar)
+            }
+            ProgressReporter::Gui(_) => None,
+        };
+        let mut tracker = PhaseTracker {
+            reporter: self.clone(),
+            bar,
+            progress: Progress {
+                phase,
+                bytes,
+                total,
+                eta: None,
+            },
+            initial: bytes,
+            started: Instant::now(),
+            last_sent: None,
+        };
+        tracker.publish(true);
+        tracker
+    }
+
+    /// Tells the GUI that there is no more work in progress for `task`
+    pub fn finish(&self, task: ProgressTask) {
+        if let ProgressReporter::Gui(proxy) = self {
+            send(proxy, Progress::new(ProgressPhase::Finished(task)));
+        }
+    }
+}
+
+/// Tracks the progress of a single phase and works out its ETA
+pub struct PhaseTracker {
+    reporter: ProgressReporter,
+    bar: Option<ProgressBar>,
+    progress: Progress,
+    initial: u64,
+    started: Instant,
+    last_sent: Option<Instant>,
+}
+
+impl PhaseTracker {
+    pub fn set_position(&mut self, bytes: u64) {
+        self.progress.bytes = match self.progress.total {
+            Some(total) => bytes.min(total),
+            None => bytes,
+        };
+        self.progress.eta = self.eta();
+        if let Some(bar) = &self.bar {
+            bar.set_position(self.progress.bytes);
+        }
+        self.publish(false);
+    }
+
+    pub fn advance(&mut self, delta: u64) {
+        self.set_position(self.progress.bytes + delta);
+    }
+
+    pub fn finish(mut self, message: &str) {
+        if let Some(total) = self.progress.total {
+            self.progress.bytes = total;
+        }
+        self.progress.eta = Some(Duration::ZERO);
+        if let Some(bar) = &self.bar {
+            bar.finish_with_message(message.to_owned());
+        }
+        self.publish(true);
+    }
+
+    fn eta(&self) -> Option<Duration> {
+        let total = self.progress.total?;
+        let done = self.progress.bytes.checked_sub(self.initial)?;
+        if done == 0 {
+            return None;
+        }
+        let remaining = total.saturating_sub(self.progress.bytes);
+        let elapsed = self.started.elapsed().as_secs_f64();
+        Some(Duration::from_secs_f64(
+            elapsed * remaining as f64 / done as f64,
+        ))
+    }
+
+    fn publish(&mut self, force: bool) {
+        if let ProgressReporter::Gui(proxy) = &self.reporter {
+            let due = match self.last_sent {
+                Some(last) => last.elapsed() >= GUI_REFRESH,
+                None => true,
+            };
+            if force || due {
+                send(proxy, self.progress.clone());
+                self.last_sent = Some(Instant::now());
+            }
+        }
+    }
+}
+
+fn send(proxy: &EventLoopProxy<KaraEvents>, progress: Progress) {
+    if let Err(e) = proxy.send_event(KaraEvents::Progress(progress)) {
+        error!("{e}");
+    }
+}
@@ -0,0 +1,504 @@
+//! Speaker identification.
+//!
+//! An utterance is reduced to an [`Embedding`]: the mean and standard deviation of its mel
+//! frequency cepstral coefficients over the frames that contain speech. Enrolled speakers
+//! keep a few of these in a [`SpeakerProfile`] and an utterance is attributed to the
+//! speaker whose profile is most similar to it.
+use std::{
+    f32::consts::PI,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use dasp::{sample::ToSample, Sample};
+use kara_events::Speaker;
+use rustfft::{num_complex::Complex, FftPlanner};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, trace};
+
+use crate::{helpers, StreamDevice, SAMPLE_RATE};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// 25ms frames every 10ms
+const FRAME_LEN: usize = 400;
+const FRAME_HOP: usize = 160;
+const FFT_LEN: usize = 512;
+const MEL_BANDS: usize = 26;
+/// Cepstral coefficients kept per frame. The first one only tracks loudness and is dropped
+const COEFFICIENTS: usize = 13;
+/// Frames quieter than this fraction of the loudest frame are treated as silence
+const SILENCE: f32 = 0.01;
+/// Frames of speech needed for a usable embedding
+const MIN_VOICED_FRAMES: usize = 50;
+/// Enrolment recordings are split into windows of this many samples
+const ENROLMENT_WINDOW: usize = 3 * SAMPLE_RATE as usize;
+
+/// A fixed length summary of a speaker's voice
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Embedding(Vec<f32>);
+
+impl Embedding {
+    /// Computes an embedding from 16kHz mono audio. Returns `None` if there is not enough
+    /// speech in `samples`
+    pub fn from_samples(samples: &[i16]) -> Option<Self> {
+        let window: Vec<_> = apodize::hanning_iter(FRAME_LEN).map(|w| w as f32).collect();
+        let filters = mel_filters();
+        let fft = FftPlanner::new().plan_fft_forward(FFT_LEN);
+        let mut buffer = vec![Complex::default(); FFT_LEN];
+        let mut frames = Vec::new();
+        for frame in samples.windows(FRAME_LEN).step_by(FRAME_HOP) {
+            buffer.fill(Complex::default());
+            let mut energy = 0.0;
+            for ((bin, sample), w) in buffer.iter_mut().zip(frame).zip(&window) {
+                let sample = sample.to_sample::<f32>();
+                energy += sample * sample;
+                bin.re = sample * w;
+            }
+            fft.process(&mut buffer);
+            let log_mel: Vec<_> = filters
+                .iter()
+                .map(|filter| {
+                    filter
+                        .iter()
+                        .zip(&buffer)
+                        .map(|(weight, bin)| weight * bin.norm_sqr())
+                        .sum::<f32>()
+                        .max(f32::EPSILON)
+                        .ln()
+                })
+                .collect();
+            frames.push((energy, cepstrum(&log_mel)));
+        }
+
+        let loudest = frames.iter().map(|(energy, _)| *energy).fold(0.0, f32::max);
+        let voiced: Vec<_> = frames
+            .iter()
+            .filter(|(energy, _)| *energy > 0.0 && *energy >= loudest * SILENCE)
+            .map(|(_, coefficients)| coefficients)
+            .collect();
+        if voiced.len() < MIN_VOICED_FRAMES {
+            return None;
+        }
+
+        let count = voiced.len() as f32;
+        let mut mean = vec![0.0; COEFFICIENTS - 1];
+        for coefficients in &voiced {
+            for (mean, val) in mean.iter_mut().zip(coefficients.iter()) {
+                *mean += val / count;
+            }
+        }
+        let mut deviation = vec![0.0; COEFFICIENTS - 1];
+        for coefficients in &voiced {
+            for ((deviation, val), mean) in deviation.iter_mut().zip(coefficients.iter()).zip(&mean)
+            {
+                *deviation += (val - mean).powi(2) / count;
+            }
+        }
+        mean.extend(deviation.into_iter().map(f32::sqrt));
+        Some(Self(mean))
+    }
+
+    /// Cosine similarity in the range -1.0 <= val <= 1.0
+    pub fn similarity(&self, other: &Embedding) -> f32 {
+        let dot: f32 = self.0.iter().zip(&other.0).map(|(a, b)| a * b).sum();
+        let norm = |e: &Embedding| e.0.iter().map(|v| v * v).sum::<f32>().sqrt();
+        let norms = norm(self) * norm(other);
+        if norms == 0.0 {
+            0.0
+        } else {
+            dot / norms
+        }
+    }
+}
+
+/// Triangular filters spaced evenly on the mel scale up to the Nyquist frequency
+fn mel_filters() -> Vec<Vec<f32>> {
+    let hz_to_mel = |hz: f32| 2595.0 * (1.0 + hz / 700.0).log10();
+    let mel_to_hz = |mel: f32| 700.0 * (10_f32.powf(mel / 2595.0) - 1.0);
+    let max_mel = hz_to_mel(SAMPLE_RATE as f32 / 2.0);
+    let centres: Vec<_> = (0..MEL_BANDS + 2)
+        .map(|i| {
+            let hz = mel_to_hz(max_mel * i as f32 / (MEL_BANDS + 1) as f32);
+            hz * FFT_LEN as f32 / SAMPLE_RATE as f32
+        })
+        .collect();
+    centres
+        .windows(3)
+        .map(|band| {
+            (0..FFT_LEN / 2 + 1)
+                .map(|bin| {
+                    let bin = bin as f32;
+                    if bin <= band[0] || bin >= band[2] {
+                        0.0
+                    } else if bin <= band[1] {
+                        (bin - band[0]) / (band[1] - band[0])
+                    } else {
+                        (band[2] - bin) / (band[2] - band[1])
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// DCT-II of the log mel energies without the first coefficient
+fn cepstrum(log_mel: &[f32]) -> Vec<f32> {
+    let bands = log_mel.len() as f32;
+    (1..COEFFICIENTS)
+        .map(|k| {
+            log_mel
+                .iter()
+                .enumerate()
+                .map(|(i, val)| val * (PI * k as f32 * (i as f32 + 0.5) / bands).cos())
+                .sum()
+        })
+        .collect()
+}
+
+/// The embeddings recorded for one speaker
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpeakerProfile {
+    pub name: String,
+    embeddings: Vec<Embedding>,
+}
+
+impl SpeakerProfile {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            embeddings: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, embedding: Embedding) {
+        self.embeddings.push(embedding);
+    }
+
+    /// Number of embeddings in the profile
+    pub fn samples(&self) -> usize {
+        self.embeddings.len()
+    }
+
+    fn similarity(&self, embedding: &Embedding) -> f32 {
+        self.embeddings
+            .iter()
+            .map(|e| e.similarity(embedding))
+            .fold(f32::MIN, f32::max)
+    }
+}
+
+/// Adds an embedding to `profile` for every window of speech in an enrolment recording.
+/// Returns the number of embeddings that were added
+pub fn enrol(profile: &mut SpeakerProfile, samples: &[i16]) -> usize {
+    let mut added = 0;
+    for window in samples.chunks(ENROLMENT_WINDOW) {
+        if let Some(embedding) = Embedding::from_samples(window) {
+            profile.add(embedding);
+            added += 1;
+        }
+    }
+    added
+}
+
+/// Speaker profiles saved as one JSON file per speaker
+pub struct ProfileStore {
+    dir: PathBuf,
+}
+
+impl Default for ProfileStore {
+    fn default() -> Self {
+        Self::new(default_profile_path())
+    }
+}
+
+impl ProfileStore {
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        Self {
+            dir: dir.as_ref().to_owned(),
+        }
+    }
+
+    pub fn load(&self, name: &str) -> io::Result<Option<SpeakerProfile>> {
+        match fs::read_to_string(self.path(name)?) {
+            Ok(contents) => Ok(Some(serde_json::from_str(&contents)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn load_all(&self) -> io::Result<Vec<SpeakerProfile>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut profiles = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            profiles.push(serde_json::from_str(&fs::read_to_string(path)?)?);
+        }
+        Ok(profiles)
+    }
+
+    pub fn save(&self, profile: &SpeakerProfile) -> io::Result<()> {
+        let path = self.path(&profile.name)?;
+        fs::create_dir_all(&self.dir)?;
+        fs::write(path, serde_json::to_string(profile)?)
+    }
+
+    /// Deletes a profile. Returns `false` if there was no profile for `name`
+    pub fn remove(&self, name: &str) -> io::Result<bool> {
+        match fs::remove_file(self.path(name)?) {
+            Ok(_) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn path(&self, name: &str) -> io::Result<PathBuf> {
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid speaker name {name:?}: use letters, numbers, '-' and '_'"),
+            ));
+        }
+        Ok(self.dir.join(format!("{name}.json")))
+    }
+}
+
+pub fn default_profile_path() -> PathBuf {
+    let mut dir = dirs::data_dir().expect("could not find data dir");
+    dir.push("kara");
+    dir.push("speakers");
+    dir
+}
+
+/// Attributes utterances to enrolled speakers
+#[derive(Clone)]
+pub struct SpeakerIdentifier {
+    profiles: Arc<Vec<SpeakerProfile>>,
+    threshold: f32,
+}
+
+impl SpeakerIdentifier {
+    /// `threshold` is the lowest similarity, in the range 0.0 <= val <= 1.0, at which an
+    /// utterance is attributed to a speaker
+    pub fn new(store: &ProfileStore, threshold: f32) -> io::Result<Self> {
+        let profiles = store.load_all()?;
+        debug!(speakers = profiles.len(), "loaded speaker profiles");
+        Ok(Self {
+            profiles: Arc::new(profiles),
+            threshold,
+        })
+    }
+
+    pub fn identify(&self, samples: &[i16]) -> Speaker {
+        let embedding = match Embedding::from_samples(samples) {
+            Some(embedding) => embedding,
+            None => return Speaker::Unknown,
+        };
+        let best = self
+            .profiles
+            .iter()
+            .map(|profile| (profile, profile.similarity(&embedding)))
+            .max_by(|(_, a), (_, b)| a.total_cmp(b));
+        match best {
+            Some((profile, similarity)) if similarity >= self.threshold => {
+                trace!(speaker = %profile.name, similarity, "identified speaker");
+                Speaker::Known(profile.name.clone())
+            }
+            _ => Speaker::Unknown,
+        }
+    }
+}
+
+/// Records `duration` of 16kHz mono audio from the default input device
+pub fn record(duration: Duration) -> Result<Vec<i16>> {
+    let host = cpal::default_host();
+    let device = host
+        .default_input_device()
+        .ok_or("no audio input device available")?;
+    let config = device.default_input_config()?;
+    let stream_device = StreamDevice {
+        channel_count: config.channels() as u8,
+        sample_rate: config.sample_rate().0,
+    };
+    let (tx, rx) = mpsc::channel();
+    let err_fn = |err| error!("an error occurred on stream: {}", err);
+    let stream = match config.sample_format() {
+        cpal::SampleFormat::I16 => device.build_input_stream(
+            &config.into(),
+            move |data: &[i16], _| {
+                let _ = tx.send(to_mono(data, &stream_device));
+            },
+            err_fn,
+        ),
+        cpal::SampleFormat::U16 => device.build_input_stream(
+            &config.into(),
+            move |data: &[u16], _| {
+                let _ = tx.send(to_mono(data, &stream_device));
+            },
+            err_fn,
+        ),
+        cpal::SampleFormat::F32 => device.build_input_stream(
+            &config.into(),
+            move |data: &[f32], _| {
+                let _ = tx.send(to_mono(data, &stream_device));
+            },
+            err_fn,
+        ),
+    }?;
+    stream.play()?;
+
+    let deadline = Instant::now() + duration;
+    let mut samples = Vec::with_capacity(duration.as_secs() as usize * SAMPLE_RATE as usize);
+    while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+        match rx.recv_timeout(left) {
+            Ok(data) => samples.extend(data),
+            Err(RecvTimeoutError::Timeout) => break,
+            Err(RecvTimeoutError::Disconnected) => return Err("audio stream stopped".into()),
+        }
+    }
+    Ok(samples)
+}
+
+fn to_mono(data: &[impl Sample + ToSample<f32>], stream_device: &StreamDevice) -> Vec<i16> {
+    let data: Vec<_> = data.iter().map(|f| f.to_sample::<f32>()).collect();
+    let resampled = helpers::set_sample_rate(&data, stream_device);
+    if stream_device.channel_count != 1 {
+        helpers::stereo_to_mono(&resampled)
+    } else {
+        resampled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A steady voice: harmonics of `pitch` in Hz, weighted by `harmonics`
+    fn voice(pitch: f32, harmonics: &[f32], seconds: f32) -> Vec<i16> {
+        let len = (seconds * SAMPLE_RATE as f32) as usize;
+        (0..len)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                let sample: f32 = harmonics
+                    .iter()
+                    .enumerate()
+                    .map(|(k, weight)| weight * (2.0 * PI * pitch * (k + 1) as f32 * t).sin())
+                    .sum();
+                (sample * 8000.0) as i16
+            })
+            .collect()
+    }
+
+    fn alice(seconds: f32) -> Vec<i16> {
+        voice(210.0, &[1.0, 0.6, 0.3, 0.2], seconds)
+    }
+
+    fn bob(seconds: f32) -> Vec<i16> {
+        voice(110.0, &[0.3, 1.0, 0.1, 0.8, 0.5], seconds)
+    }
+
+    fn identifier(threshold: f32) -> SpeakerIdentifier {
+        let profiles = [("alice", alice(2.0)), ("bob", bob(2.0))]
+            .into_iter()
+            .map(|(name, samples)| {
+                let mut profile = SpeakerProfile::new(name);
+                profile.add(Embedding::from_samples(&samples).unwrap());
+                profile
+            })
+            .collect();
+        SpeakerIdentifier {
+            profiles: Arc::new(profiles),
+            threshold,
+        }
+    }
+
+    #[test]
+    fn needs_enough_speech() {
+        assert_eq!(Embedding::from_samples(&[0; SAMPLE_RATE as usize]), None);
+        assert_eq!(Embedding::from_samples(&alice(0.2)), None);
+        let embedding = Embedding::from_samples(&alice(1.0)).unwrap();
+        assert_eq!(embedding.0.len(), 2 * (COEFFICIENTS - 1));
+    }
+
+    #[test]
+    fn cosine_similarity() {
+        let embedding = Embedding(vec![1.0, 0.0]);
+        assert!((embedding.similarity(&embedding) - 1.0).abs() < 1e-6);
+        assert!((embedding.similarity(&Embedding(vec![2.0, 0.0])) - 1.0).abs() < 1e-6);
+        assert!(embedding.similarity(&Embedding(vec![0.0, 3.0])).abs() < 1e-6);
+        assert!((embedding.similarity(&Embedding(vec![-1.0, 0.0])) + 1.0).abs() < 1e-6);
+        assert_eq!(embedding.similarity(&Embedding(vec![0.0, 0.0])), 0.0);
+    }
+
+    #[test]
+    fn embeds_the_same_voice_alike() {
+        let first = Embedding::from_samples(&alice(1.0)).unwrap();
+        let louder: Vec<_> = alice(2.0).iter().map(|sample| sample * 2).collect();
+        let second = Embedding::from_samples(&louder).unwrap();
+        let other = Embedding::from_samples(&bob(1.0)).unwrap();
+        assert!(first.similarity(&second) > first.similarity(&other));
+    }
+
+    #[test]
+    fn identifies_speakers_above_the_threshold() {
+        let speakers = identifier(0.9);
+        assert_eq!(
+            speakers.identify(&alice(1.5)),
+            Speaker::Known("alice".to_owned())
+        );
+        assert_eq!(
+            speakers.identify(&bob(1.5)),
+            Speaker::Known("bob".to_owned())
+        );
+        assert_eq!(speakers.identify(&[0; 16000]), Speaker::Unknown);
+        // Nobody is similar enough to a threshold above the highest similarity
+        assert_eq!(identifier(1.01).identify(&alice(1.5)), Speaker::Unknown);
+        let nobody = SpeakerIdentifier {
+            profiles: Arc::new(Vec::new()),
+            threshold: 0.0,
+        };
+        assert_eq!(nobody.identify(&alice(1.5)), Speaker::Unknown);
+    }
+
+    #[test]
+    fn enrols_every_window_of_speech() {
+        let mut profile = SpeakerProfile::new("alice");
+        let mut samples = alice(6.0);
+        samples.extend(vec![0; ENROLMENT_WINDOW]);
+        assert_eq!(enrol(&mut profile, &samples), 2);
+        assert_eq!(profile.samples(), 2);
+    }
+
+    #[test]
+    fn stores_profiles() {
+        let dir = std::env::temp_dir().join(format!("kara-speakers-{}", std::process::id()));
+        let store = ProfileStore::new(&dir);
+        let mut profile = SpeakerProfile::new("alice");
+        profile.add(Embedding(vec![1.0, 2.0]));
+        store.save(&profile).unwrap();
+        assert_eq!(store.load("alice").unwrap().unwrap().samples(), 1);
+        assert!(store.load("bob").unwrap().is_none());
+        assert_eq!(store.load_all().unwrap().len(), 1);
+        assert!(store.save(&SpeakerProfile::new("../alice")).is_err());
+        assert!(store.remove("alice").unwrap());
+        assert!(!store.remove("alice").unwrap());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,11 +1,12 @@
 use futures_util::StreamExt;
-use indicatif::{ProgressBar, ProgressStyle};
+use kara_events::ProgressPhase;
 use reqwest::{
     header::{ACCEPT_RANGES, CONTENT_LENGTH, RANGE},
     Client, StatusCode,
 };
 use std::{
     cmp::min,
+    fs,
     path::{Path, PathBuf},
     sync::{Arc, Mutex},
 };
@@ -16,27 +17,47 @@ use tokio::{
 use tracing::{error, trace, warn};
 use vosk::Recognizer;
 
-use crate::SAMPLE_RATE;
+use serde::Deserialize;
+
+use crate::{progress::ProgressReporter, SAMPLE_RATE};
 
 use super::STTSource;
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
-const VOSK_MODEL_URL: &str = "https://alphacephei.com/vosk/models/vosk-model-small-en-us-0.15.zip";
 
-const WAKE_WORD: &[&str] = &["hey kara", "[unk]"];
+/// A vosk model for one language
+#[derive(Debug, Clone, Deserialize)]
+pub struct KaraModel {
+    /// Directory the model is loaded from
+    pub path: String,
+    /// Where the model is downloaded from if `path` does not contain a model
+    pub url: String,
+    /// The phrase that wakes Kara up, in the model's language
+    pub wake_phrase: String,
+}
 
 #[derive(Clone)]
 pub struct KaraTranscriber {
     recogniser_main: Arc<Mutex<vosk::Recognizer>>,
     recogniser_wake: Arc<Mutex<vosk::Recognizer>>,
+    wake_phrase: String,
 }
 
 impl KaraTranscriber {
-    pub fn new(recogniser_main: vosk::Recognizer, recogniser_wake: vosk::Recognizer) -> Self {
+    pub fn new(
+        recogniser_main: vosk::Recognizer,
+        recogniser_wake: vosk::Recognizer,
+        wake_phrase: impl Into<String>,
+    ) -> Self {
         Self {
             recogniser_main: Arc::new(Mutex::new(recogniser_main)),
             recogniser_wake: Arc::new(Mutex::new(recogniser_wake)),
+            wake_phrase: wake_phrase.into(),
         }
     }
+
+    pub fn is_wake_phrase(&self, text: &str) -> bool {
+        text.trim().eq_ignore_ascii_case(&self.wake_phrase)
+    }
     pub fn recogniser(&self) -> Arc<Mutex<vosk::Recognizer>> {
         Arc::clone(&self.recogniser_main)
     }
@@ -46,39 +67,62 @@ impl KaraTranscriber {
     }
 }
 
-#[tracing::instrument]
-pub(crate) async fn init_kara_model(model: &str) -> Result<STTSource> {
-    use gag::Gag;
-    let _print_gag = Gag::stderr().unwrap();
+#[tracing::instrument(skip(reporter))]
+pub(crate) async fn init_kara_model(
+    model: &KaraModel,
+    reporter: &ProgressReporter,
+) -> Result<STTSource> {
     trace!("initialising kara stt model");
-    match vosk::Model::new(model).ok_or(format!(
+    let loading = reporter.start(
+        ProgressPhase::LoadSpeechModel,
+        None,
+        &format!("Loading {}", model.path),
+    );
+    match vosk::Model::new(&model.path).ok_or(format!(
         "failed to initialise kara stt model from path: {}",
-        model
+        model.path
     )) {
         Ok(vosk_model) => {
-            let mut recogniser = Recognizer::new(&vosk_model, SAMPLE_RATE as f32)
-                .ok_or("failed to initialise recogniser")?;
-            recogniser.set_words(true);
-            recogniser.set_partial_words(true);
-
-            trace!(path = %model, "located model");
-
-            let words_rec =
-                Recognizer::new_with_grammar(&vosk_model, SAMPLE_RATE as f32, WAKE_WORD).unwrap();
+            trace!(path = %model.path, "located model");
+            let source = transcriber(&vosk_model, &model.wake_phrase)?;
+            loading.finish("Speech model loaded");
             trace!("kara stt model initialised");
-            Ok(STTSource::Kara(KaraTranscriber::new(recogniser, words_rec)))
+            Ok(source)
         }
         Err(e) => {
+            loading.finish("Speech model not found");
             warn!("{e}");
             trace!("trying to get fallback");
             let mut data_dir = data_dir().await;
-            drop(_print_gag);
 
-            download_model(&Client::new(), VOSK_MODEL_URL, &mut data_dir).await
+            download_model(
+                &Client::new(),
+                &model.url,
+                &mut data_dir,
+                &model.wake_phrase,
+                reporter,
+            )
+            .await
         }
     }
 }
 
+fn transcriber(vosk_model: &vosk::Model, wake_phrase: &str) -> Result<STTSource> {
+    let mut recogniser =
+        Recognizer::new(vosk_model, SAMPLE_RATE as f32).ok_or("failed to initialise recogniser")?;
+    recogniser.set_words(true);
+    recogniser.set_partial_words(true);
+
+    let words_rec =
+        Recognizer::new_with_grammar(vosk_model, SAMPLE_RATE as f32, &[wake_phrase, "[unk]"])
+            .ok_or("failed to initialise wake phrase recogniser")?;
+    Ok(STTSource::Kara(KaraTranscriber::new(
+        recogniser,
+        words_rec,
+        wake_phrase,
+    )))
+}
+
 #[derive(Debug)]
 struct PartialRangeIter {
     start: u64,
@@ -119,8 +163,14 @@ impl Iterator for PartialRangeIter {
     }
 }
 
-#[tracing::instrument]
-async fn download_model(client: &Client, url: &str, path_buf: &mut PathBuf) -> Result<STTSource> {
+#[tracing::instrument(skip(reporter))]
+async fn download_model(
+    client: &Client,
+    url: &str,
+    path_buf: &mut PathBuf,
+    wake_phrase: &str,
+    reporter: &ProgressReporter,
+) -> Result<STTSource> {
     let head = client.head(url).send().await?;
     let content_length = head.headers().get(CONTENT_LENGTH);
     let accept_range = head.headers().get(ACCEPT_RANGES);
@@ -147,18 +197,19 @@ async fn download_model(client: &Client, url: &str, path_buf: &mut PathBuf) -> R
         .unwrap();
     let mut downloaded = file_size;
 
-    let c: u64 = content_length.unwrap().to_str().unwrap().parse().unwrap();
-    let pb = ProgressBar::new(c);
-    pb.set_style(ProgressStyle::default_bar()
-        .template("{msg}\n{spinner:.green} [{elapsed_precise}] [{wide_bar:.white/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")
-        .progress_chars("█  "));
-    pb.set_message(format!("Downloading {}", url));
+    let content_length = content_length
+        .and_then(|content_length| content_length.to_str().ok())
+        .and_then(|content_length| content_length.parse::<u64>().ok());
     match content_length {
         Some(content_length) => {
-            let content_length = content_length.to_str().unwrap();
-            let content_length: u64 = content_length.parse().unwrap();
             match accept_range {
                 Some(_) => {
+                    let mut progress = reporter.start_at(
+                        ProgressPhase::Download,
+                        file_size,
+                        Some(content_length),
+                        &format!("Downloading {}", url),
+                    );
                     // resume download if file exists
                     // check file size
                     for range in PartialRangeIter::new(file_size, content_length - 1, 10240)? {
@@ -173,8 +224,9 @@ async fn download_model(client: &Client, url: &str, path_buf: &mut PathBuf) -> R
                         }
                         let new = min(downloaded + 10240, content_length);
                         downloaded = new;
-                        pb.set_position(downloaded);
+                        progress.set_position(downloaded);
                     }
+                    progress.finish(&format!("Downloaded {} to {}", url, path_buf.display()));
                     let file = File::open(&path_buf).await.unwrap();
                     let content = head.bytes().await.unwrap();
                     let mut content = content.as_ref();
@@ -183,18 +235,33 @@ async fn download_model(client: &Client, url: &str, path_buf: &mut PathBuf) -> R
                         file,
                         path_buf.parent().unwrap(),
                         &path_buf.display().to_string(),
+                        wake_phrase,
+                        reporter,
                     )
                     .await
                 }
                 None => {
                     // redownload file
-                    Ok(download_no_resume(client, path_buf, url, &file_name).await?)
+                    Ok(
+                        download_no_resume(
+                            client,
+                            path_buf,
+                            url,
+                            &file_name,
+                            wake_phrase,
+                            reporter,
+                        )
+                        .await?,
+                    )
                 }
             }
         }
         None => {
             //redownload file
-            Ok(download_no_resume(client, path_buf, url, &file_name).await?)
+            Ok(
+                download_no_resume(client, path_buf, url, &file_name, wake_phrase, reporter)
+                    .await?,
+            )
         }
     }
 }
@@ -204,6 +271,8 @@ async fn download_no_resume(
     path_buf: &Path,
     url: &str,
     file_name: &str,
+    wake_phrase: &str,
+    reporter: &ProgressReporter,
 ) -> Result<STTSource> {
     let res = client
         .get(url)
@@ -213,11 +282,11 @@ async fn download_no_resume(
     let total_size = res
         .content_length()
         .ok_or(format!("Failed to get content length from '{}'", &url))?;
-    let pb = ProgressBar::new(total_size);
-    pb.set_style(ProgressStyle::default_bar()
-        .template("{msg}\n{spinner:.green} [{elapsed_precise}] [{wide_bar:.white/blue}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})")
-        .progress_chars("█  "));
-    pb.set_message(format!("Downloading {}", url));
+    let mut progress = reporter.start(
+        ProgressPhase::Download,
+        Some(total_size),
+        &format!("Downloading {}", url),
+    );
     let mut file = File::create(path_buf).await?;
     let mut stream = res.bytes_stream();
     let mut downloaded: u64 = 0;
@@ -226,18 +295,29 @@ async fn download_no_resume(
         file.write_all(&chunk).await?;
         let new = min(downloaded + (chunk.len() as u64), total_size);
         downloaded = new;
-        pb.set_position(new);
+        progress.set_position(new);
     }
-    pb.finish_with_message(format!("Downloaded {} to {}", url, path_buf.display()));
-    extract_file(file, path_buf.parent().unwrap(), file_name).await
+    progress.finish(&format!("Downloaded {} to {}", url, path_buf.display()));
+    extract_file(
+        file,
+        path_buf.parent().unwrap(),
+        file_name,
+        wake_phrase,
+        reporter,
+    )
+    .await
 }
 
-#[tracing::instrument]
-async fn extract_file(file: File, parent: &Path, file_name: &str) -> Result<STTSource> {
+#[tracing::instrument(skip(reporter))]
+async fn extract_file(
+    file: File,
+    parent: &Path,
+    file_name: &str,
+    wake_phrase: &str,
+    reporter: &ProgressReporter,
+) -> Result<STTSource> {
     trace!("extracting file");
-    use gag::Gag;
     let file = file.into_std().await;
-    let _print_gag = Gag::stderr().unwrap();
     let file_name = PathBuf::from(file_name);
     let file_name = file_name.file_name();
     let file_name = file_name.unwrap().to_string_lossy().to_string();
@@ -245,23 +325,47 @@ async fn extract_file(file: File, parent: &Path, file_name: &str) -> Result<STTS
 
     tokio::fs::create_dir_all(parent).await?;
 
-    archive.extract(parent).unwrap();
+    let total = (0..archive.len())
+        .filter_map(|i| archive.by_index(i).ok().map(|entry| entry.size()))
+        .sum();
+    let mut progress = reporter.start(
+        ProgressPhase::Extract,
+        Some(total),
+        &format!("Extracting {file_name}"),
+    );
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let out_path = match entry.enclosed_name() {
+            Some(path) => parent.join(path),
+            None => continue,
+        };
+        if entry.is_dir() {
+            fs::create_dir_all(&out_path)?;
+        } else {
+            if let Some(dir) = out_path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            let mut out_file = fs::File::create(&out_path)?;
+            let written = std::io::copy(&mut entry, &mut out_file)?;
+            progress.advance(written);
+        }
+    }
+    progress.finish(&format!("Extracted {file_name}"));
     let file_name = Path::new(&file_name).file_stem().unwrap();
     let file_name = file_name.to_str().unwrap();
 
+    let loading = reporter.start(
+        ProgressPhase::LoadSpeechModel,
+        None,
+        &format!("Loading {file_name}"),
+    );
     let vosk_model = vosk::Model::new(format!("{}/{file_name}", parent.display()))
         .ok_or("failed to initialise model")?;
-
-    let mut recogniser = Recognizer::new(&vosk_model, SAMPLE_RATE as f32)
-        .ok_or("failed to initialise recogniser")?;
-    recogniser.set_words(true);
-    recogniser.set_partial_words(true);
-
-    let words_rec =
-        Recognizer::new_with_grammar(&vosk_model, SAMPLE_RATE as f32, WAKE_WORD).unwrap();
+    let source = transcriber(&vosk_model, wake_phrase)?;
+    loading.finish("Speech model loaded");
 
     trace!("kara stt model initialised");
-    Ok(STTSource::Kara(KaraTranscriber::new(recogniser, words_rec)))
+    Ok(source)
 }
 
 async fn data_dir() -> PathBuf {
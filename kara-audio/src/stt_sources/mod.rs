@@ -1,32 +1,23 @@
 use std::fs::create_dir_all;
 
+use kara_events::ProgressTask;
 use serde::Deserialize;
 
-use self::kara::{init_kara_model, KaraTranscriber};
+use crate::progress::ProgressReporter;
+
+use self::kara::{init_kara_model, KaraModel, KaraTranscriber};
 
 pub mod kara;
 
 /// Store the configurations/credentials for all the services that
 /// provide STT
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub enum STTConfig {
-    Kara(String),
+    Kara(KaraModel),
     Gcp,
     Watson,
 }
 
-impl STTConfig {
-    pub fn base(path: &str) -> Self {
-        STTConfig::Kara(path.to_owned())
-    }
-}
-
-impl Default for STTConfig {
-    fn default() -> Self {
-        Self::Kara(default_stt_model_path())
-    }
-}
-
 pub fn default_stt_model_path() -> String {
     let mut dir = dirs::data_dir().expect("could not find data dir");
     dir.push("kara");
@@ -43,11 +34,18 @@ pub enum STTSource {
     Watson,
 }
 
-#[tracing::instrument]
-pub async fn stt_source(source: &STTConfig) -> anyhow::Result<STTSource> {
-    match source {
-        STTConfig::Kara(model) => init_kara_model(model).await.map_err(anyhow::Error::msg),
+#[tracing::instrument(skip(reporter))]
+pub async fn stt_source(
+    source: &STTConfig,
+    reporter: ProgressReporter,
+) -> anyhow::Result<STTSource> {
+    let source = match source {
+        STTConfig::Kara(model) => init_kara_model(model, &reporter)
+            .await
+            .map_err(anyhow::Error::msg),
         STTConfig::Gcp => todo!(),
         STTConfig::Watson => todo!(),
-    }
+    };
+    reporter.finish(ProgressTask::SpeechModel);
+    source
 }
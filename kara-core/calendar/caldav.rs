@@ -0,0 +1,388 @@
+//! A CalDAV client (RFC 4791). It finds an account's calendars, lists their events with
+//! their ETags, downloads them in bulk and uploads and deletes them, only if they did not
+//! change on the server in the meantime.
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use reqwest::{
+    header::{CONTENT_TYPE, ETAG, IF_MATCH, IF_NONE_MATCH},
+    Client, Method, RequestBuilder, StatusCode, Url,
+};
+use tracing::trace;
+
+use crate::config::state;
+
+const DAV: &str = "DAV:";
+const CALDAV: &str = "urn:ietf:params:xml:ns:caldav";
+
+/// A calendar on the server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Collection {
+    pub href: String,
+    pub name: String,
+}
+
+/// An event, with everything that shares its uid, as the server has it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resource {
+    pub href: String,
+    pub etag: String,
+    pub data: String,
+}
+
+/// Why an upload or deletion failed
+#[derive(Debug)]
+pub enum WriteError {
+    /// The resource changed on the server since its ETag was read
+    Conflict,
+    Failed(anyhow::Error),
+}
+
+impl From<anyhow::Error> for WriteError {
+    fn from(e: anyhow::Error) -> Self {
+        WriteError::Failed(e)
+    }
+}
+
+/// Where calendars are synced with. Hrefs are paths on the server
+#[async_trait]
+pub trait Remote: Send + Sync {
+    /// The calendars of the account that can hold events
+    async fn collections(&self) -> anyhow::Result<Vec<Collection>>;
+    /// The resources in a calendar, with their ETags
+    async fn list(&self, collection: &str) -> anyhow::Result<HashMap<String, String>>;
+    async fn fetch(&self, collection: &str, hrefs: &[String]) -> anyhow::Result<Vec<Resource>>;
+    /// Uploads a resource, replacing the one with `etag`, or only if it does not exist yet
+    /// if `etag` is `None`. Returns the new ETag if the server says it
+    async fn put(
+        &self,
+        href: &str,
+        data: String,
+        etag: Option<&str>,
+    ) -> Result<Option<String>, WriteError>;
+    /// Deletes a resource if it still has `etag`
+    async fn delete(&self, href: &str, etag: &str) -> Result<(), WriteError>;
+}
+
+pub struct CalDav {
+    client: Client,
+    url: Url,
+    username: Option<String>,
+    password: Option<String>,
+}
+
+impl CalDav {
+    pub fn new(
+        url: &str,
+        username: Option<String>,
+        password: Option<String>,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            client: Client::new(),
+            url: Url::parse(url).with_context(|| format!("invalid CalDAV url {url}"))?,
+            username,
+            password,
+        })
+    }
+
+    pub fn configured(config: &state::CalDav) -> anyhow::Result<Self> {
+        Self::new(
+            &config.url,
+            config.username.clone(),
+            config.password.clone(),
+        )
+    }
+
+    fn request(&self, method: &str, href: &str) -> anyhow::Result<RequestBuilder> {
+        let method = Method::from_bytes(method.as_bytes())?;
+        let mut request = self.client.request(method, self.url.join(href)?);
+        if let Some(username) = &self.username {
+            request = request.basic_auth(username, self.password.as_ref());
+        }
+        Ok(request)
+    }
+
+    /// Sends a PROPFIND or REPORT and reads the multistatus answer
+    async fn query(
+        &self,
+        method: &str,
+        href: &str,
+        depth: u8,
+        body: String,
+    ) -> anyhow::Result<Vec<Response>> {
+        trace!(method, href, "CalDAV request");
+        let response = self
+            .request(method, href)?
+            .header("Depth", depth.to_string())
+            .header(CONTENT_TYPE, "application/xml; charset=utf-8")
+            .body(body)
+            .send()
+            .await?;
+        let status = response.status();
+        if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
+            return Err(anyhow!(
+                "the CalDAV server refused the credentials ({status})"
+            ));
+        }
+        if status != StatusCode::MULTI_STATUS {
+            return Err(anyhow!("{method} {href} failed: {status}"));
+        }
+        self.multistatus(&response.text().await?)
+    }
+
+    async fn propfind(&self, href: &str, depth: u8, props: &str) -> anyhow::Result<Vec<Response>> {
+        let body = format!(
+            r#"<?xml version="1.0" encoding="utf-8"?><d:propfind xmlns:d="{DAV}" xmlns:c="{CALDAV}"><d:prop>{props}</d:prop></d:propfind>"#
+        );
+        self.query("PROPFIND", href, depth, body).await
+    }
+
+    /// An href as a path on the server, whether it is relative, a path or a whole url
+    fn path(&self, href: &str) -> anyhow::Result<String> {
+        Ok(self.url.join(href)?.path().to_owned())
+    }
+
+    fn multistatus(&self, xml: &str) -> anyhow::Result<Vec<Response>> {
+        let document = roxmltree::Document::parse(xml)?;
+        let is = |node: &roxmltree::Node, namespace: &str, name: &str| {
+            node.tag_name().namespace() == Some(namespace) && node.tag_name().name() == name
+        };
+        let mut responses = Vec::new();
+        for node in document
+            .descendants()
+            .filter(|node| is(node, DAV, "response"))
+        {
+            let href = match node
+                .children()
+                .find(|child| is(child, DAV, "href"))
+                .and_then(|href| href.text())
+            {
+                Some(href) => self.path(href.trim())?,
+                None => continue,
+            };
+            let mut response = Response {
+                href,
+                ..Response::default()
+            };
+            // Properties the server does not have come in a propstat with another status
+            let props = node
+                .children()
+                .filter(|child| is(child, DAV, "propstat"))
+                .filter(|propstat| {
+                    propstat
+                        .children()
+                        .find(|child| is(child, DAV, "status"))
+                        .and_then(|status| status.text())
+                        .map_or(true, |status| status.contains(" 200 "))
+                })
+                .flat_map(|propstat| propstat.children().filter(|child| is(child, DAV, "prop")))
+                .flat_map(|prop| prop.children().filter(roxmltree::Node::is_element));
+            for prop in props {
+                let inner_href = || {
+                    prop.children()
+                        .find(|child| is(child, DAV, "href"))
+                        .and_then(|href| href.text())
+                        .map(|href| self.path(href.trim()))
+                        .transpose()
+                };
+                match (prop.tag_name().namespace(), prop.tag_name().name()) {
+                    (Some(DAV), "getetag") => response.etag = prop.text().map(str::to_owned),
+                    (Some(DAV), "displayname") => {
+                        response.name = prop.text().map(|name| name.trim().to_owned())
+                    }
+                    (Some(DAV), "resourcetype") => {
+                        response.is_calendar =
+                            prop.children().any(|kind| is(&kind, CALDAV, "calendar"))
+                    }
+                    (Some(DAV), "current-user-principal") => response.principal = inner_href()?,
+                    (Some(CALDAV), "calendar-home-set") => response.home = inner_href()?,
+                    (Some(CALDAV), "calendar-data") => {
+                        response.data = prop.text().map(str::to_owned)
+                    }
+                    (Some(CALDAV), "supported-calendar-component-set") => {
+                        response.events = prop.children().any(|component| {
+                            is(&component, CALDAV, "comp")
+                                && component.attribute("name") == Some("VEVENT")
+                        })
+                    }
+                    _ => {}
+                }
+            }
+            responses.push(response);
+        }
+        Ok(responses)
+    }
+}
+
+/// The properties of one resource in a multistatus answer that Kara uses
+#[derive(Debug)]
+struct Response {
+    href: String,
+    etag: Option<String>,
+    name: Option<String>,
+    is_calendar: bool,
+    /// The calendar can hold events. Servers that do not say are taken to allow them
+    events: bool,
+    principal: Option<String>,
+    home: Option<String>,
+    data: Option<String>,
+}
+
+impl Default for Response {
+    fn default() -> Self {
+        Self {
+            href: String::new(),
+            etag: None,
+            name: None,
+            is_calendar: false,
+            events: true,
+            principal: None,
+            home: None,
+            data: None,
+        }
+    }
+}
+
+impl Response {
+    fn collection(self) -> Collection {
+        let name = self
+            .name
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| {
+                self.href
+                    .trim_end_matches('/')
+                    .rsplit('/')
+                    .next()
+                    .unwrap_or_default()
+                    .to_owned()
+            });
+        Collection {
+            href: self.href,
+            name,
+        }
+    }
+}
+
+const COLLECTION_PROPS: &str =
+    "<d:resourcetype/><d:displayname/><c:supported-calendar-component-set/>";
+
+#[async_trait]
+impl Remote for CalDav {
+    async fn collections(&self) -> anyhow::Result<Vec<Collection>> {
+        let url = self.url.path().to_owned();
+        // The url may be a calendar already
+        if let Some(own) = self
+            .propfind(&url, 0, COLLECTION_PROPS)
+            .await?
+            .into_iter()
+            .find(|response| response.is_calendar)
+        {
+            return Ok(vec![own.collection()]);
+        }
+        let principal = self
+            .propfind(&url, 0, "<d:current-user-principal/>")
+            .await?
+            .into_iter()
+            .find_map(|response| response.principal)
+            .unwrap_or(url);
+        let home = self
+            .propfind(&principal, 0, "<c:calendar-home-set/>")
+            .await?
+            .into_iter()
+            .find_map(|response| response.home)
+            .unwrap_or(principal);
+        Ok(self
+            .propfind(&home, 1, COLLECTION_PROPS)
+            .await?
+            .into_iter()
+            .filter(|response| response.is_calendar && response.events)
+            .map(Response::collection)
+            .collect())
+    }
+
+    async fn list(&self, collection: &str) -> anyhow::Result<HashMap<String, String>> {
+        let body = format!(
+            r#"<?xml version="1.0" encoding="utf-8"?><c:calendar-query xmlns:d="{DAV}" xmlns:c="{CALDAV}"><d:prop><d:getetag/></d:prop><c:filter><c:comp-filter name="VCALENDAR"><c:comp-filter name="VEVENT"/></c:comp-filter></c:filter></c:calendar-query>"#
+        );
+        Ok(self
+            .query("REPORT", collection, 1, body)
+            .await?
+            .into_iter()
+            .filter_map(|response| Some((response.href, response.etag?)))
+            .collect())
+    }
+
+    async fn fetch(&self, collection: &str, hrefs: &[String]) -> anyhow::Result<Vec<Resource>> {
+        if hrefs.is_empty() {
+            return Ok(Vec::new());
+        }
+        let hrefs: String = hrefs
+            .iter()
+            .map(|href| format!("<d:href>{}</d:href>", escape(href)))
+            .collect();
+        let body = format!(
+            r#"<?xml version="1.0" encoding="utf-8"?><c:calendar-multiget xmlns:d="{DAV}" xmlns:c="{CALDAV}"><d:prop><d:getetag/><c:calendar-data/></d:prop>{hrefs}</c:calendar-multiget>"#
+        );
+        Ok(self
+            .query("REPORT", collection, 1, body)
+            .await?
+            .into_iter()
+            .filter_map(|response| {
+                Some(Resource {
+                    href: response.href,
+                    etag: response.etag?,
+                    data: response.data?,
+                })
+            })
+            .collect())
+    }
+
+    async fn put(
+        &self,
+        href: &str,
+        data: String,
+        etag: Option<&str>,
+    ) -> Result<Option<String>, WriteError> {
+        trace!(href, "CalDAV PUT");
+        let request = self
+            .request("PUT", href)?
+            .header(CONTENT_TYPE, "text/calendar; charset=utf-8")
+            .body(data);
+        let request = match etag {
+            Some(etag) => request.header(IF_MATCH, etag),
+            None => request.header(IF_NONE_MATCH, "*"),
+        };
+        let response = request.send().await.map_err(anyhow::Error::from)?;
+        match response.status() {
+            status if status.is_success() => Ok(response
+                .headers()
+                .get(ETAG)
+                .and_then(|etag| etag.to_str().ok())
+                .map(str::to_owned)),
+            StatusCode::PRECONDITION_FAILED => Err(WriteError::Conflict),
+            status => Err(anyhow!("PUT {href} failed: {status}").into()),
+        }
+    }
+
+    async fn delete(&self, href: &str, etag: &str) -> Result<(), WriteError> {
+        trace!(href, "CalDAV DELETE");
+        let response = self
+            .request("DELETE", href)?
+            .header(IF_MATCH, etag)
+            .send()
+            .await
+            .map_err(anyhow::Error::from)?;
+        match response.status() {
+            status if status.is_success() || status == StatusCode::NOT_FOUND => Ok(()),
+            StatusCode::PRECONDITION_FAILED => Err(WriteError::Conflict),
+            status => Err(anyhow!("DELETE {href} failed: {status}").into()),
+        }
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
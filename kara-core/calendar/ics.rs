@@ -0,0 +1,661 @@
+//! Reading and writing iCalendar files (RFC 5545). Only events are read; other components
+//! and the properties of events Kara does not use are kept as they were, so that a file is
+//! written back without losing them.
+use std::io;
+
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use chrono_tz::Tz;
+use tracing::{debug, warn};
+
+use super::{End, Event, Time, Zone};
+
+/// Lines are folded after this many bytes
+const LINE_LENGTH: usize = 75;
+/// The enrolled speaker an event belongs to
+const OWNER_PROPERTY: &str = "X-KARA-OWNER";
+
+/// The contents of an iCalendar file
+#[derive(Debug, Clone, PartialEq)]
+pub struct File {
+    pub events: Vec<Event>,
+    /// The lines of the calendar that are not part of its events
+    other: Vec<String>,
+}
+
+impl Default for File {
+    fn default() -> Self {
+        Self {
+            events: Vec::new(),
+            other: vec![
+                "VERSION:2.0".to_owned(),
+                "PRODID:-//Kara//Kara assistant//EN".to_owned(),
+            ],
+        }
+    }
+}
+
+impl File {
+    /// A calendar of `events` with nothing else in it
+    pub fn new(events: Vec<Event>) -> Self {
+        Self {
+            events,
+            ..Self::default()
+        }
+    }
+
+    /// The value of a property of the calendar itself, such as `PRODID`
+    pub fn property(&self, name: &str) -> Option<&str> {
+        self.properties()
+            .filter_map(|line| property(line))
+            .find(|property| property.name.eq_ignore_ascii_case(name))
+            .map(|property| property.value)
+    }
+
+    /// Sets a property of the calendar itself, replacing the one with the same name
+    pub fn set_property(&mut self, name: &str, value: &str) {
+        let line = format!("{}:{value}", name.to_uppercase());
+        let mut depth = 0;
+        for other in &mut self.other {
+            depth = nesting(other, depth);
+            if depth == 0
+                && property(other)
+                    .map_or(false, |property| property.name.eq_ignore_ascii_case(name))
+            {
+                *other = line;
+                return;
+            }
+        }
+        let first_component = self
+            .components()
+            .first()
+            .map_or(self.other.len(), |component| component.start);
+        self.other.insert(first_component, line);
+    }
+
+    /// Adds the components of `other` that are not events, such as time zones, which this
+    /// calendar does not have yet
+    pub fn merge_components(&mut self, other: &File) {
+        for component in other.components() {
+            let lines = &other.other[component.clone()];
+            let known = self.components().into_iter().any(|known| {
+                let known = &self.other[known];
+                known == lines || (tzid(known).is_some() && tzid(known) == tzid(lines))
+            });
+            if !known {
+                self.other.extend(lines.iter().cloned());
+            }
+        }
+    }
+
+    /// A calendar of `events` with the time zones of this one they use
+    pub fn subset(&self, events: Vec<Event>) -> File {
+        let zones: Vec<_> = events
+            .iter()
+            .flat_map(|event| {
+                let end = match &event.end {
+                    Some(End::At(end)) => Some(end),
+                    _ => None,
+                };
+                std::iter::once(&event.start)
+                    .chain(end)
+                    .chain(&event.exceptions)
+                    .chain(&event.recurrence_id)
+            })
+            .filter_map(|time| match time {
+                Time::DateTime(_, Zone::Named(tz)) => Some(tz.name()),
+                _ => None,
+            })
+            .collect();
+        let mut subset = File::new(events);
+        for component in self.components() {
+            let lines = &self.other[component];
+            if tzid(lines).map_or(false, |tzid| zones.contains(&tzid.trim_start_matches('/'))) {
+                subset.other.extend(lines.iter().cloned());
+            }
+        }
+        subset
+    }
+
+    /// The lines of the calendar's own properties, outside its components
+    fn properties(&self) -> impl Iterator<Item = &String> {
+        let mut depth = 0;
+        self.other.iter().filter(move |line| {
+            let outside = depth == 0;
+            depth = nesting(line, depth);
+            outside && depth == 0
+        })
+    }
+
+    /// Where the components in [`Self::other`] are, from their `BEGIN` to their `END` line
+    fn components(&self) -> Vec<std::ops::Range<usize>> {
+        let mut components = Vec::new();
+        let (mut depth, mut start) = (0, 0);
+        for (i, line) in self.other.iter().enumerate() {
+            let before = depth;
+            depth = nesting(line, depth);
+            if before == 0 && depth > 0 {
+                start = i;
+            } else if before > 0 && depth == 0 {
+                components.push(start..i + 1);
+            }
+        }
+        components
+    }
+}
+
+/// How deeply nested in components the line after `line` is
+fn nesting(line: &str, depth: usize) -> usize {
+    let upper = line.to_uppercase();
+    if upper.starts_with("BEGIN:") {
+        depth + 1
+    } else if upper.starts_with("END:") {
+        depth.saturating_sub(1)
+    } else {
+        depth
+    }
+}
+
+/// The `TZID` of a time zone component
+fn tzid(lines: &[String]) -> Option<&str> {
+    lines
+        .iter()
+        .filter_map(|line| property(line))
+        .find(|property| property.name == "TZID")
+        .map(|property| property.value)
+}
+
+/// A property, e.g. `DTSTART;TZID=Europe/Paris:20220801T090000`
+struct Property<'a> {
+    name: String,
+    params: Vec<(String, String)>,
+    value: &'a str,
+}
+
+impl Property<'_> {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(param, _)| param == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+fn property(line: &str) -> Option<Property<'_>> {
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(i),
+        _ => None,
+    })?;
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+    let mut parts = head.split(';');
+    let name = parts.next()?.trim().to_uppercase();
+    if name.is_empty() {
+        return None;
+    }
+    let params = parts
+        .filter_map(|param| param.split_once('='))
+        .map(|(name, value)| (name.to_uppercase(), value.trim_matches('"').to_owned()))
+        .collect();
+    Some(Property {
+        name,
+        params,
+        value,
+    })
+}
+
+/// Reads a calendar. Events that cannot be read are logged and left out
+pub fn parse(text: &str) -> io::Result<File> {
+    let mut lines = unfold(text).into_iter();
+    match lines.next() {
+        Some(line) if line.eq_ignore_ascii_case("BEGIN:VCALENDAR") => {}
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not an iCalendar file",
+            ))
+        }
+    }
+    let mut file = File {
+        events: Vec::new(),
+        other: Vec::new(),
+    };
+    let mut event: Option<Vec<String>> = None;
+    for line in lines {
+        let upper = line.to_uppercase();
+        match (&mut event, upper.as_str()) {
+            (None, "BEGIN:VEVENT") => event = Some(Vec::new()),
+            (Some(lines), "END:VEVENT") => {
+                match self::event(lines) {
+                    Some(event) => file.events.push(event),
+                    None => warn!("skipping an event without a start or uid"),
+                }
+                event = None;
+            }
+            (Some(lines), _) => lines.push(line),
+            (None, "END:VCALENDAR") => return Ok(file),
+            (None, _) => file.other.push(line),
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "the calendar does not end",
+    ))
+}
+
+/// Joins lines that were folded, i.e. continued on the next line after a space or tab
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match (
+            line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')),
+            lines.last_mut(),
+        ) {
+            (Some(continued), Some(last)) => last.push_str(continued),
+            _ if line.is_empty() => {}
+            _ => lines.push(line.to_owned()),
+        }
+    }
+    lines
+}
+
+fn event(lines: &[String]) -> Option<Event> {
+    let (mut uid, mut summary, mut location, mut owner, mut start, mut end) =
+        (None, String::new(), None, None, None, None);
+    let (mut rule, mut recurrence_id) = (None, None);
+    let (mut exceptions, mut other, mut cancelled) = (Vec::new(), Vec::new(), false);
+    // Components inside the event, such as reminders, are kept as they are
+    let mut depth = 0;
+    for line in lines {
+        let upper = line.to_uppercase();
+        if upper.starts_with("BEGIN:") {
+            depth += 1;
+        }
+        let property = match property(line) {
+            Some(property) if depth == 0 => property,
+            _ => {
+                if upper.starts_with("END:") {
+                    depth -= 1;
+                }
+                other.push(line.clone());
+                continue;
+            }
+        };
+        match property.name.as_str() {
+            "UID" => uid = Some(property.value.to_owned()),
+            "SUMMARY" => summary = unescape(property.value),
+            "LOCATION" => location = Some(unescape(property.value)),
+            OWNER_PROPERTY => owner = Some(unescape(property.value)),
+            "DTSTART" => start = time(&property),
+            "DTEND" => end = time(&property).map(End::At),
+            "DURATION" => end = duration(property.value).map(End::For),
+            "RRULE" => match property.value.parse() {
+                Ok(parsed) => rule = Some(parsed),
+                Err(e) => {
+                    warn!("{}, only the first occurrence is used", e);
+                    other.push(line.clone());
+                }
+            },
+            "EXDATE" => exceptions.extend(
+                property
+                    .value
+                    .split(',')
+                    .filter_map(|value| time_value(value, &property)),
+            ),
+            "RECURRENCE-ID" => recurrence_id = time(&property),
+            name => {
+                if name == "STATUS" {
+                    cancelled = property.value.eq_ignore_ascii_case("CANCELLED");
+                }
+                other.push(line.clone());
+            }
+        }
+    }
+    Some(Event {
+        uid: uid.filter(|uid| !uid.is_empty())?,
+        summary,
+        location,
+        owner,
+        start: start?,
+        end,
+        rule,
+        exceptions,
+        recurrence_id,
+        cancelled,
+        other,
+    })
+}
+
+fn time(property: &Property<'_>) -> Option<Time> {
+    time_value(property.value, property)
+}
+
+/// A date, or a date and time in UTC, in the property's time zone or floating
+fn time_value(value: &str, property: &Property<'_>) -> Option<Time> {
+    let value = value.trim();
+    if property.param("VALUE") == Some("DATE") || value.len() == 8 {
+        return NaiveDate::parse_from_str(value, "%Y%m%d")
+            .ok()
+            .map(Time::Date);
+    }
+    if let Some(utc) = value.strip_suffix('Z') {
+        return naive(utc).map(|time| Time::DateTime(time, Zone::Utc));
+    }
+    let zone = match property.param("TZID") {
+        Some(tzid) => match tzid.trim_start_matches('/').parse::<Tz>() {
+            Ok(tz) => Zone::Named(tz),
+            Err(_) => {
+                debug!(tzid, "unknown time zone, using local time");
+                Zone::Floating
+            }
+        },
+        None => Zone::Floating,
+    };
+    naive(value).map(|time| Time::DateTime(time, zone))
+}
+
+/// The value of `UNTIL` in a recurrence rule
+pub(super) fn until(value: &str) -> Option<Time> {
+    time_value(
+        value,
+        &Property {
+            name: "UNTIL".to_owned(),
+            params: Vec::new(),
+            value,
+        },
+    )
+}
+
+fn naive(value: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()
+}
+
+/// A duration such as `PT1H30M`, `P1D` or `-P2W`
+fn duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let (sign, value) = match value.strip_prefix('-') {
+        Some(value) => (-1, value),
+        None => (1, value.trim_start_matches('+')),
+    };
+    let mut value = value.strip_prefix('P')?;
+    let mut parts = Vec::new();
+    let mut in_time = false;
+    while !value.is_empty() {
+        if let Some(rest) = value.strip_prefix('T') {
+            in_time = true;
+            value = rest;
+            continue;
+        }
+        let digits = value.find(|c: char| !c.is_ascii_digit())?;
+        let number: i64 = value[..digits].parse().ok()?;
+        parts.push(match (in_time, value[digits..].chars().next()?) {
+            (false, 'W') => Duration::weeks(number),
+            (false, 'D') => Duration::days(number),
+            (true, 'H') => Duration::hours(number),
+            (true, 'M') => Duration::minutes(number),
+            (true, 'S') => Duration::seconds(number),
+            _ => return None,
+        });
+        value = &value[digits + 1..];
+    }
+    let total = parts
+        .into_iter()
+        .fold(Duration::zero(), |total, part| total + part);
+    Some(total * sign)
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n' | 'N')) => {
+                chars.next();
+                unescaped.push('\n');
+            }
+            ('\\', Some(escaped)) => {
+                chars.next();
+                unescaped.push(escaped);
+            }
+            (c, _) => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Writes a calendar, with lines folded and ended as RFC 5545 requires
+pub fn write(file: &File) -> String {
+    let mut lines = vec!["BEGIN:VCALENDAR".to_owned()];
+    lines.extend(file.other.iter().cloned());
+    for event in &file.events {
+        lines.push("BEGIN:VEVENT".to_owned());
+        lines.push(format!("UID:{}", event.uid));
+        lines.push(format!("SUMMARY:{}", escape(&event.summary)));
+        if let Some(location) = &event.location {
+            lines.push(format!("LOCATION:{}", escape(location)));
+        }
+        if let Some(owner) = &event.owner {
+            lines.push(format!("{OWNER_PROPERTY}:{}", escape(owner)));
+        }
+        lines.push(time_property("DTSTART", &event.start));
+        match &event.end {
+            Some(End::At(end)) => lines.push(time_property("DTEND", end)),
+            Some(End::For(duration)) => {
+                lines.push(format!("DURATION:{}", duration_value(*duration)))
+            }
+            None => {}
+        }
+        if let Some(rule) = &event.rule {
+            lines.push(format!("RRULE:{rule}"));
+        }
+        for exception in &event.exceptions {
+            lines.push(time_property("EXDATE", exception));
+        }
+        if let Some(recurrence_id) = &event.recurrence_id {
+            lines.push(time_property("RECURRENCE-ID", recurrence_id));
+        }
+        lines.extend(event.other.iter().cloned());
+        lines.push("END:VEVENT".to_owned());
+    }
+    lines.push("END:VCALENDAR".to_owned());
+    lines.iter().map(|line| fold(line) + "\r\n").collect()
+}
+
+fn time_property(name: &str, time: &Time) -> String {
+    match time {
+        Time::Date(date) => format!("{name};VALUE=DATE:{}", date.format("%Y%m%d")),
+        Time::DateTime(time, zone) => {
+            let value = time.format("%Y%m%dT%H%M%S");
+            match zone {
+                Zone::Utc => format!("{name}:{value}Z"),
+                Zone::Floating => format!("{name}:{value}"),
+                Zone::Named(tz) => format!("{name};TZID={}:{value}", tz.name()),
+            }
+        }
+    }
+}
+
+fn duration_value(duration: Duration) -> String {
+    let sign = if duration < Duration::zero() { "-" } else { "" };
+    let seconds = duration.num_seconds().abs();
+    let (days, hours, minutes, seconds) = (
+        seconds / 86400,
+        seconds % 86400 / 3600,
+        seconds % 3600 / 60,
+        seconds % 60,
+    );
+    let mut value = format!("{sign}P");
+    if days > 0 {
+        value.push_str(&format!("{days}D"));
+    }
+    if hours > 0 || minutes > 0 || seconds > 0 || days == 0 {
+        value.push('T');
+        if hours > 0 {
+            value.push_str(&format!("{hours}H"));
+        }
+        if minutes > 0 {
+            value.push_str(&format!("{minutes}M"));
+        }
+        if seconds > 0 || (hours == 0 && minutes == 0) {
+            value.push_str(&format!("{seconds}S"));
+        }
+    }
+    value
+}
+
+/// Splits a line longer than [`LINE_LENGTH`] bytes, without splitting characters
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > LINE_LENGTH {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        date(year, month, day).and_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn calendar(event: &str) -> String {
+        format!(
+            "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VEVENT\r\nUID:1@test\r\n{event}END:VEVENT\r\n\
+             END:VCALENDAR\r\n"
+        )
+    }
+
+    fn only_event(event: &str) -> Event {
+        let mut file = parse(&calendar(event)).unwrap();
+        assert_eq!(file.events.len(), 1);
+        file.events.remove(0)
+    }
+
+    #[test]
+    fn unfolds_lines() {
+        assert_eq!(
+            unfold("SUMMARY:A long\r\n  meeting\r\n\tabout plans\r\n\r\nLOCATION:Here\r\n"),
+            vec!["SUMMARY:A long meetingabout plans", "LOCATION:Here"]
+        );
+    }
+
+    #[test]
+    fn folds_long_lines() {
+        let line = format!("SUMMARY:{}", "é".repeat(60));
+        let folded = fold(&line);
+        assert!(folded.split("\r\n").all(|part| part.len() <= LINE_LENGTH));
+        assert_eq!(unfold(&folded), vec![line]);
+        assert_eq!(fold("SUMMARY:Short"), "SUMMARY:Short");
+    }
+
+    #[test]
+    fn escapes_text() {
+        let text = "Lunch, then; a\\walk\nwith Sam";
+        assert_eq!(escape(text), r"Lunch\, then\; a\\walk\nwith Sam");
+        assert_eq!(unescape(&escape(text)), text);
+        assert_eq!(unescape("Line\\NBreak"), "Line\nBreak");
+        let event = only_event("DTSTART:20220801T090000\r\nSUMMARY:Tea\\, cake\r\n");
+        assert_eq!(event.summary, "Tea, cake");
+    }
+
+    #[test]
+    fn reads_durations() {
+        assert_eq!(duration("PT1H30M"), Some(Duration::minutes(90)));
+        assert_eq!(duration("P1DT2H"), Some(Duration::hours(26)));
+        assert_eq!(duration("-P2W"), Some(Duration::weeks(-2)));
+        assert_eq!(duration("+PT15S"), Some(Duration::seconds(15)));
+        for invalid in ["1H", "PT1X", "P1H", "P1"] {
+            assert_eq!(duration(invalid), None, "{invalid}");
+        }
+        for written in [
+            Duration::minutes(90),
+            Duration::days(1),
+            Duration::zero(),
+            Duration::minutes(-15),
+        ] {
+            assert_eq!(duration(&duration_value(written)), Some(written));
+        }
+        assert_eq!(duration_value(Duration::minutes(90)), "PT1H30M");
+    }
+
+    #[test]
+    fn reads_time_values() {
+        let event = only_event(
+            "DTSTART;TZID=Europe/Paris:20220801T090000\r\nDTEND:20220801T100000Z\r\n\
+             EXDATE;VALUE=DATE:20220802,20220803\r\n",
+        );
+        let paris = "Europe/Paris".parse().unwrap();
+        assert_eq!(
+            event.start,
+            Time::DateTime(at(2022, 8, 1, 9, 0), Zone::Named(paris))
+        );
+        assert_eq!(
+            event.end,
+            Some(End::At(Time::DateTime(at(2022, 8, 1, 10, 0), Zone::Utc)))
+        );
+        assert_eq!(
+            event.exceptions,
+            vec![Time::Date(date(2022, 8, 2)), Time::Date(date(2022, 8, 3))]
+        );
+        let event = only_event("DTSTART;TZID=Nowhere/Else:20220801T090000\r\nDURATION:PT45M\r\n");
+        assert_eq!(
+            event.start,
+            Time::DateTime(at(2022, 8, 1, 9, 0), Zone::Floating)
+        );
+        assert_eq!(event.end, Some(End::For(Duration::minutes(45))));
+        assert_eq!(
+            only_event("DTSTART:20220801\r\n").start,
+            Time::Date(date(2022, 8, 1))
+        );
+    }
+
+    #[test]
+    fn skips_events_without_a_start_or_uid() {
+        let file = parse(
+            "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nUID:1@test\r\nEND:VEVENT\r\nBEGIN:VEVENT\r\n\
+             DTSTART:20220801\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n",
+        )
+        .unwrap();
+        assert!(file.events.is_empty());
+        assert!(parse("BEGIN:VEVENT\r\n").is_err());
+        assert!(parse("BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\n").is_err());
+    }
+
+    #[test]
+    fn writes_back_what_it_reads() {
+        let text = calendar(
+            "SUMMARY:Weekly, long meeting about the plans for the rest of the year and beyond\r\n\
+             LOCATION:Room 1\r\nDTSTART;TZID=Europe/Paris:20220801T090000\r\nDURATION:PT1H\r\n\
+             RRULE:FREQ=WEEKLY;BYDAY=MO\r\nSTATUS:CANCELLED\r\nBEGIN:VALARM\r\n\
+             ACTION:DISPLAY\r\nEND:VALARM\r\n",
+        );
+        let file = parse(&text).unwrap();
+        let event = &file.events[0];
+        assert!(event.cancelled);
+        assert_eq!(event.location.as_deref(), Some("Room 1"));
+        assert!(event.other.contains(&"ACTION:DISPLAY".to_owned()));
+        assert_eq!(parse(&write(&file)).unwrap(), file);
+    }
+}
@@ -0,0 +1,432 @@
+//! Events from iCalendar files. Kara's own calendars are the `.ics` files in
+//! `$XDG_DATA_HOME/kara/calendar`, which she reads and changes; events she is asked to add
+//! go to `kara.ics` there. Other calendars, such as ones synced by another program, can be
+//! configured to be read as well but are never changed.
+//!
+//! Events added for an enrolled speaker are marked with `X-KARA-OWNER` and are theirs alone,
+//! other events are everyone's.
+//!
+//! Recurring events are expanded from their `RRULE`, skipping the occurrences in `EXDATE`
+//! and the ones replaced by a separate event with a `RECURRENCE-ID`. See [`rrule`] for the
+//! rules that are understood.
+//!
+//! Kara's calendars can be synced with a CalDAV server, see [`CalendarSync`].
+mod caldav;
+mod ics;
+mod rrule;
+mod sync;
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex,
+    },
+};
+
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use tokio::sync::Notify;
+use tracing::{debug, error, warn};
+
+use crate::config::state;
+
+pub use self::{
+    caldav::{CalDav, Remote},
+    rrule::{Frequency, Rule},
+    sync::{CalendarSync, Synchronised, DEFAULT_SYNC_INTERVAL},
+};
+
+/// The file events are added to
+const KARA_CALENDAR: &str = "kara.ics";
+
+/// A time zone of a time in a calendar
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Zone {
+    Utc,
+    /// The same wall clock time wherever Kara is, i.e. Kara's time zone
+    Floating,
+    Named(Tz),
+}
+
+/// When an event starts or ends
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Time {
+    /// A whole day
+    Date(chrono::NaiveDate),
+    DateTime(NaiveDateTime, Zone),
+}
+
+impl Time {
+    /// The wall clock time, midnight for dates
+    pub fn naive(&self) -> NaiveDateTime {
+        match self {
+            Time::Date(date) => date.and_hms_opt(0, 0, 0).expect("every day has a midnight"),
+            Time::DateTime(time, _) => *time,
+        }
+    }
+
+    /// The same kind of time at another wall clock time
+    fn with_naive(&self, naive: NaiveDateTime) -> Self {
+        match self {
+            Time::Date(_) => Time::Date(naive.date()),
+            Time::DateTime(_, zone) => Time::DateTime(naive, *zone),
+        }
+    }
+
+    /// When the time is in Kara's time zone. Wall clock times skipped when clocks go forward
+    /// are taken an hour later
+    pub fn local(&self) -> DateTime<Local> {
+        let naive = self.naive();
+        let later = naive + chrono::Duration::hours(1);
+        match self {
+            Time::DateTime(_, Zone::Utc) => Utc.from_utc_datetime(&naive).with_timezone(&Local),
+            Time::DateTime(_, Zone::Named(tz)) => tz
+                .from_local_datetime(&naive)
+                .earliest()
+                .or_else(|| tz.from_local_datetime(&later).earliest())
+                .map_or_else(
+                    || Utc.from_utc_datetime(&naive).with_timezone(&Local),
+                    |time| time.with_timezone(&Local),
+                ),
+            _ => Local
+                .from_local_datetime(&naive)
+                .earliest()
+                .or_else(|| Local.from_local_datetime(&later).earliest())
+                .unwrap_or_else(|| Utc.from_utc_datetime(&naive).with_timezone(&Local)),
+        }
+    }
+
+    pub fn is_date(&self) -> bool {
+        matches!(self, Time::Date(_))
+    }
+}
+
+/// How long an event lasts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum End {
+    At(Time),
+    For(chrono::Duration),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub uid: String,
+    pub summary: String,
+    pub location: Option<String>,
+    /// The enrolled speaker the event belongs to, `None` for everyone's events
+    pub owner: Option<String>,
+    pub start: Time,
+    /// Events without an end last the day they start on if they are on a date, and no
+    /// time at all otherwise
+    pub end: Option<End>,
+    pub rule: Option<Rule>,
+    /// Occurrences of a recurring event that do not happen
+    pub exceptions: Vec<Time>,
+    /// The occurrence of the recurring event with the same uid this event replaces
+    pub recurrence_id: Option<Time>,
+    pub cancelled: bool,
+    /// Properties Kara does not use, kept so that they are written back
+    other: Vec<String>,
+}
+
+impl Event {
+    /// A new event with a unique id
+    pub fn new(summary: impl Into<String>, start: Time, end: Option<End>) -> Self {
+        static COUNT: AtomicU32 = AtomicU32::new(0);
+        let now = Utc::now();
+        Self {
+            uid: format!(
+                "{}{:09}-{}@kara",
+                now.format("%Y%m%dT%H%M%S"),
+                now.timestamp_subsec_nanos(),
+                COUNT.fetch_add(1, Ordering::Relaxed)
+            ),
+            summary: summary.into(),
+            location: None,
+            owner: None,
+            start,
+            end,
+            rule: None,
+            exceptions: Vec::new(),
+            recurrence_id: None,
+            cancelled: false,
+            other: vec![format!("DTSTAMP:{}", now.format("%Y%m%dT%H%M%SZ"))],
+        }
+    }
+
+    pub fn with_rule(mut self, rule: Rule) -> Self {
+        self.rule = Some(rule);
+        self
+    }
+
+    fn length(&self) -> chrono::Duration {
+        match self.end {
+            Some(End::At(end)) => end.local() - self.start.local(),
+            Some(End::For(duration)) => duration,
+            None if self.start.is_date() => chrono::Duration::days(1),
+            None => chrono::Duration::zero(),
+        }
+    }
+
+    /// When each occurrence starts, in order. The last time a recurring event may start is
+    /// read in the time zone of its start
+    fn starts(&self) -> Box<dyn Iterator<Item = Time> + '_> {
+        let rule = match &self.rule {
+            Some(rule) => rule,
+            None => return Box::new(std::iter::once(self.start)),
+        };
+        let until =
+            rule.until
+                .as_deref()
+                .and_then(ics::until)
+                .map(|until| match (until, self.start) {
+                    (Time::Date(date), _) => {
+                        date.and_hms_opt(23, 59, 59).expect("every day has an end")
+                    }
+                    (until, Time::DateTime(_, Zone::Named(tz))) => {
+                        until.local().with_timezone(&tz).naive_local()
+                    }
+                    (until, Time::DateTime(_, Zone::Utc)) => until.local().naive_utc(),
+                    (until, _) => until.local().naive_local(),
+                });
+        Box::new(
+            rule.occurrences(self.start.naive(), until)
+                .map(move |start| self.start.with_naive(start)),
+        )
+    }
+}
+
+/// A time an event happens
+#[derive(Debug, Clone, PartialEq)]
+pub struct Occurrence {
+    pub uid: String,
+    pub summary: String,
+    pub location: Option<String>,
+    pub owner: Option<String>,
+    pub start: DateTime<Local>,
+    pub end: DateTime<Local>,
+    pub all_day: bool,
+    /// The start of the occurrence as the calendar has it, for recurring events
+    pub recurrence: Option<Time>,
+    /// The calendar can be changed
+    pub writable: bool,
+}
+
+/// What [`Calendar::remove`] removes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Removal {
+    /// The event and all its occurrences
+    Event,
+    /// One occurrence of a recurring event
+    Occurrence(Time),
+}
+
+/// Kara's calendars and the other calendars she reads
+pub struct Calendar {
+    dir: PathBuf,
+    paths: Vec<PathBuf>,
+    /// Held while a file is rewritten
+    writing: Mutex<()>,
+    /// Woken when Kara changes an event
+    changed: Notify,
+}
+
+impl Default for Calendar {
+    fn default() -> Self {
+        let mut dir = dirs::data_dir().expect("could not find data dir");
+        dir.push("kara");
+        dir.push("calendar");
+        Self::new(dir, Vec::new())
+    }
+}
+
+impl Calendar {
+    /// Keeps Kara's calendars in `dir` and reads the `.ics` files or directories of them
+    /// in `paths` too
+    pub fn new(dir: impl Into<PathBuf>, paths: Vec<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            paths,
+            writing: Mutex::new(()),
+            changed: Notify::new(),
+        }
+    }
+
+    /// Kara's calendars in her data directory, and the other calendars `config` names
+    pub fn configured(config: &state::Calendar) -> Self {
+        Self {
+            paths: config.paths.clone(),
+            ..Self::default()
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The calendar files and whether Kara may change them
+    fn files(&self) -> Vec<(PathBuf, bool)> {
+        let mut files: Vec<_> = ics_files(&self.dir)
+            .into_iter()
+            .map(|path| (path, true))
+            .collect();
+        for path in &self.paths {
+            if path.is_dir() {
+                files.extend(ics_files(path).into_iter().map(|path| (path, false)));
+            } else if path.is_file() {
+                files.push((path.clone(), false));
+            } else {
+                warn!("calendar {} does not exist", path.display());
+            }
+        }
+        files
+    }
+
+    /// The occurrences of events that happen at some point from `from` until `to`, the
+    /// earliest first
+    pub fn between(&self, from: DateTime<Local>, to: DateTime<Local>) -> Vec<Occurrence> {
+        let mut occurrences = Vec::new();
+        for (path, writable) in self.files() {
+            let file = match read(&path) {
+                Ok(file) => file,
+                Err(e) => {
+                    error!("could not read calendar {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+            // Occurrences replaced by another event
+            let replaced: Vec<_> = file
+                .events
+                .iter()
+                .filter_map(|event| Some((event.uid.as_str(), event.recurrence_id?.local())))
+                .collect();
+            for event in file.events.iter().filter(|event| !event.cancelled) {
+                let length = event.length();
+                let skipped: Vec<_> = event.exceptions.iter().map(Time::local).collect();
+                for start in event.starts() {
+                    let local = start.local();
+                    if local >= to {
+                        break;
+                    }
+                    let end = local + length;
+                    let recurring = event.rule.is_some();
+                    if (end <= from && local < from)
+                        || (recurring
+                            && (skipped.contains(&local)
+                                || replaced.contains(&(event.uid.as_str(), local))))
+                    {
+                        continue;
+                    }
+                    occurrences.push(Occurrence {
+                        uid: event.uid.clone(),
+                        summary: event.summary.clone(),
+                        location: event.location.clone(),
+                        owner: event.owner.clone(),
+                        start: local,
+                        end,
+                        all_day: start.is_date(),
+                        recurrence: if recurring { Some(start) } else { None },
+                        writable,
+                    });
+                }
+            }
+        }
+        occurrences.sort_by_key(|occurrence| occurrence.start);
+        occurrences
+    }
+
+    /// Adds an event to Kara's calendar
+    pub fn add(&self, event: Event) -> io::Result<()> {
+        let path = self.dir.join(KARA_CALENDAR);
+        let _writing = self.lock();
+        let mut file = match read(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => ics::File::default(),
+            Err(e) => {
+                error!("could not read calendar {}: {}", path.display(), e);
+                fs::rename(&path, path.with_extension("ics.invalid"))?;
+                ics::File::default()
+            }
+        };
+        debug!(uid = %event.uid, summary = %event.summary, "adding event");
+        file.events.push(event);
+        write(&path, &file)?;
+        self.changed.notify_one();
+        Ok(())
+    }
+
+    /// Removes an event with `uid` from Kara's calendars, or one of its occurrences.
+    /// `false` if she has no such event
+    pub fn remove(&self, uid: &str, removal: Removal) -> io::Result<bool> {
+        let _writing = self.lock();
+        for path in ics_files(&self.dir) {
+            let mut file = read(&path)?;
+            if !file.events.iter().any(|event| event.uid == uid) {
+                continue;
+            }
+            match removal {
+                Removal::Event => file.events.retain(|event| event.uid != uid),
+                Removal::Occurrence(start) => {
+                    file.events.retain(|event| {
+                        event.uid != uid
+                            || event.recurrence_id.map(|id| id.local()) != Some(start.local())
+                    });
+                    file.events
+                        .iter_mut()
+                        .filter(|event| event.uid == uid && event.recurrence_id.is_none())
+                        .for_each(|event| event.exceptions.push(start));
+                }
+            }
+            debug!(uid, ?removal, "removing event");
+            write(&path, &file)?;
+            self.changed.notify_one();
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ()> {
+        self.writing
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// The `.ics` files in `dir`, by name
+fn ics_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<_> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| {
+                path.is_file()
+                    && path
+                        .extension()
+                        .map_or(false, |extension| extension.eq_ignore_ascii_case("ics"))
+            })
+            .collect(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => {
+            error!("could not read calendars in {}: {}", dir.display(), e);
+            Vec::new()
+        }
+    };
+    files.sort();
+    files
+}
+
+fn read(path: &Path) -> io::Result<ics::File> {
+    ics::parse(&fs::read_to_string(path)?)
+}
+
+/// Replaces a calendar. The file is written next to the old one and renamed over it, so a
+/// crash never leaves it half written
+fn write(path: &Path, file: &ics::File) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let temporary = path.with_extension("ics.tmp");
+    fs::write(&temporary, ics::write(file))?;
+    fs::rename(temporary, path)
+}
@@ -0,0 +1,501 @@
+//! Recurrence rules (RRULE) of recurring events. Rules repeat daily, weekly, monthly or
+//! yearly and may be limited with `BYDAY`, `BYMONTHDAY` and `BYMONTH`. Rules with other
+//! parts, such as `BYSETPOS` or `FREQ=HOURLY`, are not supported.
+use std::{collections::VecDeque, fmt::Display, str::FromStr};
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Weekday};
+
+/// Periods in a row without an occurrence before a rule is taken to never occur again, e.g.
+/// the 30th of February
+const MAX_EMPTY_PERIODS: u32 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    pub frequency: Frequency,
+    /// Every how many periods the event occurs
+    pub interval: u32,
+    /// How many times the event occurs, counting the first
+    pub count: Option<u32>,
+    /// The last time the event may start, as written
+    pub until: Option<String>,
+    /// Days of the week, with an ordinal for monthly and yearly rules such as -1 for the
+    /// last Friday, or 0 for every one of them
+    pub by_day: Vec<(i32, Weekday)>,
+    /// Days of the month, negative ones count from the end
+    pub by_month_day: Vec<i32>,
+    pub by_month: Vec<u32>,
+    /// The rule as it was read, so that it is written back unchanged
+    text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleError(String);
+
+impl Display for RuleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unsupported recurrence rule: {}", self.0)
+    }
+}
+
+impl std::error::Error for RuleError {}
+
+impl FromStr for Rule {
+    type Err = RuleError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let error = |reason: &str| RuleError(format!("{text} ({reason})"));
+        let mut frequency = None;
+        let mut rule = Rule {
+            frequency: Frequency::Daily,
+            interval: 1,
+            count: None,
+            until: None,
+            by_day: Vec::new(),
+            by_month_day: Vec::new(),
+            by_month: Vec::new(),
+            text: text.to_owned(),
+        };
+        for part in text.split(';').filter(|part| !part.is_empty()) {
+            let (name, value) = part.split_once('=').ok_or_else(|| error(part))?;
+            let numbers = || {
+                value
+                    .split(',')
+                    .map(|number| number.parse().map_err(|_| error(part)))
+                    .collect::<Result<Vec<_>, _>>()
+            };
+            match name.to_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.to_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        _ => return Err(error(part)),
+                    })
+                }
+                "INTERVAL" => {
+                    rule.interval = value.parse().map_err(|_| error(part))?;
+                    if rule.interval == 0 {
+                        return Err(error(part));
+                    }
+                }
+                "COUNT" => rule.count = Some(value.parse().map_err(|_| error(part))?),
+                "UNTIL" => rule.until = Some(value.to_owned()),
+                "BYDAY" => {
+                    rule.by_day = value
+                        .split(',')
+                        .map(|day| by_day(day).ok_or_else(|| error(part)))
+                        .collect::<Result<_, _>>()?
+                }
+                "BYMONTHDAY" => rule.by_month_day = numbers()?,
+                "BYMONTH" => {
+                    rule.by_month = value
+                        .split(',')
+                        .map(|month| match month.parse() {
+                            Ok(month @ 1..=12) => Ok(month),
+                            _ => Err(error(part)),
+                        })
+                        .collect::<Result<_, _>>()?
+                }
+                // Weeks starting on another day only change which days weekly rules with
+                // an interval skip
+                "WKST" => {}
+                _ => return Err(error(part)),
+            }
+        }
+        rule.frequency = frequency.ok_or_else(|| error("no FREQ"))?;
+        Ok(rule)
+    }
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// A day of the week with an optional ordinal, e.g. "MO" or "-1FR"
+fn by_day(day: &str) -> Option<(i32, Weekday)> {
+    let day = day.trim().to_uppercase();
+    if day.len() < 2 || !day.is_char_boundary(day.len() - 2) {
+        return None;
+    }
+    let (ordinal, name) = day.split_at(day.len() - 2);
+    let weekday = match name {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    };
+    let ordinal = match ordinal.trim_start_matches('+') {
+        "" => 0,
+        ordinal => ordinal.parse().ok()?,
+    };
+    Some((ordinal, weekday))
+}
+
+impl Rule {
+    /// A rule that repeats every period of `frequency`, on `by_day` if any are given
+    pub fn new(frequency: Frequency, by_day: &[Weekday]) -> Self {
+        let name = match frequency {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+            Frequency::Yearly => "YEARLY",
+        };
+        let mut text = format!("FREQ={name}");
+        if !by_day.is_empty() {
+            let days: Vec<_> = by_day
+                .iter()
+                .map(|day| day.to_string()[..2].to_uppercase())
+                .collect();
+            text.push_str(&format!(";BYDAY={}", days.join(",")));
+        }
+        text.parse()
+            .expect("rules built from a frequency and days are supported")
+    }
+
+    /// The start of every occurrence of an event first starting at `start`, in order.
+    /// `until` is the last time an occurrence may start, in the same time zone as `start`
+    pub fn occurrences(
+        &self,
+        start: NaiveDateTime,
+        until: Option<NaiveDateTime>,
+    ) -> impl Iterator<Item = NaiveDateTime> + '_ {
+        Occurrences {
+            rule: self,
+            start,
+            until,
+            period: 0,
+            empty_periods: 0,
+            pending: VecDeque::new(),
+            produced: 0,
+        }
+    }
+
+    /// The dates of the `period`th period after the one `start` is in that match the rule,
+    /// or `None` once periods are past the last date there is
+    fn dates(&self, start: NaiveDate, period: u32) -> Option<Vec<NaiveDate>> {
+        let steps = i64::from(period.checked_mul(self.interval)?);
+        let mut dates = match self.frequency {
+            Frequency::Daily => {
+                let date = start.checked_add_signed(Duration::days(steps))?;
+                let by_weekday = self.by_day.iter().any(|(_, day)| *day == date.weekday());
+                if (self.by_day.is_empty() || by_weekday)
+                    && (self.by_month_day.is_empty() || self.month_days(date).contains(&date))
+                {
+                    vec![date]
+                } else {
+                    Vec::new()
+                }
+            }
+            Frequency::Weekly => {
+                let monday = start
+                    .checked_sub_signed(Duration::days(
+                        start.weekday().num_days_from_monday() as i64
+                    ))?
+                    .checked_add_signed(Duration::weeks(steps))?;
+                let mut days: Vec<_> = self.by_day.iter().map(|(_, day)| *day).collect();
+                if days.is_empty() {
+                    days.push(start.weekday());
+                }
+                days.into_iter()
+                    .filter_map(|day| {
+                        monday.checked_add_signed(Duration::days(day.num_days_from_monday() as i64))
+                    })
+                    .collect()
+            }
+            Frequency::Monthly => {
+                let months = start.year() as i64 * 12 + start.month0() as i64 + steps;
+                let (year, month) = (i32::try_from(months / 12).ok()?, (months % 12) as u32 + 1);
+                NaiveDate::from_ymd_opt(year, month, 1)?;
+                self.in_month(start, year, month)
+            }
+            Frequency::Yearly => {
+                let year = start.year().checked_add(i32::try_from(steps).ok()?)?;
+                NaiveDate::from_ymd_opt(year, 1, 1)?;
+                let months = if !self.by_month.is_empty() {
+                    self.by_month.clone()
+                } else if self.by_day.is_empty() && self.by_month_day.is_empty() {
+                    vec![start.month()]
+                } else {
+                    (1..=12).collect()
+                };
+                months
+                    .into_iter()
+                    .flat_map(|month| self.in_month(start, year, month))
+                    .collect()
+            }
+        };
+        dates.retain(|date| self.by_month.is_empty() || self.by_month.contains(&date.month()));
+        dates.sort();
+        dates.dedup();
+        Some(dates)
+    }
+
+    /// The dates of a month that match the rule's days
+    fn in_month(&self, start: NaiveDate, year: i32, month: u32) -> Vec<NaiveDate> {
+        let first = match NaiveDate::from_ymd_opt(year, month, 1) {
+            Some(first) => first,
+            None => return Vec::new(),
+        };
+        if self.by_day.is_empty() && self.by_month_day.is_empty() {
+            return NaiveDate::from_ymd_opt(year, month, start.day())
+                .into_iter()
+                .collect();
+        }
+        let mut dates = if self.by_month_day.is_empty() {
+            days_of_month(first)
+        } else {
+            self.month_days(first)
+        };
+        if !self.by_day.is_empty() {
+            let days = days_of_month(first);
+            dates.retain(|date| {
+                self.by_day.iter().any(|(ordinal, weekday)| {
+                    let same: Vec<_> = days
+                        .iter()
+                        .filter(|day| day.weekday() == *weekday)
+                        .collect();
+                    match *ordinal {
+                        0 => date.weekday() == *weekday,
+                        n if n > 0 => same.get(n as usize - 1) == Some(&date),
+                        n => {
+                            same.len() >= n.unsigned_abs() as usize
+                                && same[same.len() - n.unsigned_abs() as usize] == date
+                        }
+                    }
+                })
+            });
+        }
+        dates
+    }
+
+    /// The dates `by_month_day` names in the month of `date`
+    fn month_days(&self, date: NaiveDate) -> Vec<NaiveDate> {
+        let days = days_of_month(date.with_day(1).unwrap_or(date));
+        self.by_month_day
+            .iter()
+            .filter_map(|&day| match day {
+                day if day > 0 => days.get(day as usize - 1).copied(),
+                day if day < 0 && days.len() >= day.unsigned_abs() as usize => {
+                    Some(days[days.len() - day.unsigned_abs() as usize])
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+fn days_of_month(first: NaiveDate) -> Vec<NaiveDate> {
+    first
+        .iter_days()
+        .take_while(|date| date.month() == first.month())
+        .collect()
+}
+
+struct Occurrences<'a> {
+    rule: &'a Rule,
+    start: NaiveDateTime,
+    until: Option<NaiveDateTime>,
+    period: u32,
+    empty_periods: u32,
+    pending: VecDeque<NaiveDateTime>,
+    produced: u32,
+}
+
+impl Iterator for Occurrences<'_> {
+    type Item = NaiveDateTime;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self
+            .rule
+            .count
+            .map_or(false, |count| self.produced >= count)
+        {
+            return None;
+        }
+        while self.pending.is_empty() {
+            if self.empty_periods >= MAX_EMPTY_PERIODS {
+                return None;
+            }
+            let dates = match self.rule.dates(self.start.date(), self.period) {
+                Some(dates) => dates,
+                None => {
+                    self.finish();
+                    return None;
+                }
+            };
+            self.period += 1;
+            let time = self.start.time();
+            self.pending.extend(
+                dates
+                    .into_iter()
+                    .map(|date| date.and_time(time))
+                    .filter(|start| *start >= self.start),
+            );
+            if self.pending.is_empty() {
+                self.empty_periods += 1;
+            } else {
+                self.empty_periods = 0;
+            }
+        }
+        let next = self.pending.pop_front()?;
+        if self.until.map_or(false, |until| next > until) {
+            self.finish();
+            return None;
+        }
+        self.produced += 1;
+        Some(next)
+    }
+}
+
+impl Occurrences<'_> {
+    /// Stops the iterator for good
+    fn finish(&mut self) {
+        self.empty_periods = MAX_EMPTY_PERIODS;
+        self.pending.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(year: i32, month: u32, day: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .and_then(|date| date.and_hms_opt(9, 30, 0))
+            .unwrap()
+    }
+
+    fn starts(
+        rule: &str,
+        start: NaiveDateTime,
+        until: Option<NaiveDateTime>,
+    ) -> Vec<NaiveDateTime> {
+        let rule: Rule = rule.parse().unwrap();
+        rule.occurrences(start, until).take(5).collect()
+    }
+
+    #[test]
+    fn reads_and_writes_rules() {
+        let rule: Rule = "FREQ=MONTHLY;INTERVAL=2;BYDAY=-1FR,+2MO,SU;WKST=SU"
+            .parse()
+            .unwrap();
+        assert_eq!(rule.frequency, Frequency::Monthly);
+        assert_eq!(rule.interval, 2);
+        assert_eq!(
+            rule.by_day,
+            vec![(-1, Weekday::Fri), (2, Weekday::Mon), (0, Weekday::Sun)]
+        );
+        assert_eq!(
+            rule.to_string(),
+            "FREQ=MONTHLY;INTERVAL=2;BYDAY=-1FR,+2MO,SU;WKST=SU"
+        );
+        assert_eq!(
+            Rule::new(Frequency::Weekly, &[Weekday::Tue, Weekday::Thu]).to_string(),
+            "FREQ=WEEKLY;BYDAY=TU,TH"
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_rules() {
+        for rule in [
+            "INTERVAL=2",
+            "FREQ=HOURLY",
+            "FREQ=DAILY;INTERVAL=0",
+            "FREQ=MONTHLY;BYSETPOS=-1",
+            "FREQ=WEEKLY;BYDAY=XX",
+            "FREQ=YEARLY;BYMONTH=13",
+        ] {
+            assert!(rule.parse::<Rule>().is_err(), "{rule}");
+        }
+    }
+
+    #[test]
+    fn stops_after_count() {
+        assert_eq!(
+            starts("FREQ=DAILY;INTERVAL=2;COUNT=3", at(2022, 8, 1), None),
+            vec![at(2022, 8, 1), at(2022, 8, 3), at(2022, 8, 5)]
+        );
+    }
+
+    #[test]
+    fn stops_after_until() {
+        assert_eq!(
+            starts(
+                "FREQ=WEEKLY;BYDAY=MO,TH",
+                at(2022, 8, 1),
+                Some(at(2022, 8, 8))
+            ),
+            vec![at(2022, 8, 1), at(2022, 8, 4), at(2022, 8, 8)]
+        );
+    }
+
+    #[test]
+    fn skips_days_before_the_start() {
+        // The 3rd of August 2022 is a Wednesday
+        assert_eq!(
+            starts("FREQ=WEEKLY;BYDAY=MO,FR;COUNT=3", at(2022, 8, 3), None),
+            vec![at(2022, 8, 5), at(2022, 8, 8), at(2022, 8, 12)]
+        );
+    }
+
+    #[test]
+    fn repeats_on_ordinal_weekdays() {
+        assert_eq!(
+            starts("FREQ=MONTHLY;BYDAY=-1FR;COUNT=3", at(2022, 8, 1), None),
+            vec![at(2022, 8, 26), at(2022, 9, 30), at(2022, 10, 28)]
+        );
+        assert_eq!(
+            starts(
+                "FREQ=YEARLY;BYMONTH=11;BYDAY=4TH;COUNT=2",
+                at(2022, 1, 1),
+                None
+            ),
+            vec![at(2022, 11, 24), at(2023, 11, 23)]
+        );
+    }
+
+    #[test]
+    fn repeats_on_days_of_the_month() {
+        assert_eq!(
+            starts("FREQ=MONTHLY;BYMONTHDAY=-1;COUNT=3", at(2022, 8, 1), None),
+            vec![at(2022, 8, 31), at(2022, 9, 30), at(2022, 10, 31)]
+        );
+        assert_eq!(
+            starts("FREQ=MONTHLY;BYMONTHDAY=31;COUNT=3", at(2022, 8, 1), None),
+            vec![at(2022, 8, 31), at(2022, 10, 31), at(2022, 12, 31)]
+        );
+        // Monthly rules without days repeat on the day they start, skipping short months
+        assert_eq!(
+            starts("FREQ=MONTHLY;COUNT=2", at(2022, 1, 31), None),
+            vec![at(2022, 1, 31), at(2022, 3, 31)]
+        );
+    }
+
+    #[test]
+    fn ends_rules_that_never_occur() {
+        assert!(starts("FREQ=YEARLY;BYMONTH=2;BYMONTHDAY=30", at(2022, 1, 1), None).is_empty());
+    }
+
+    #[test]
+    fn ends_rules_past_the_last_date() {
+        for frequency in ["DAILY", "WEEKLY", "MONTHLY", "YEARLY"] {
+            let rule = format!("FREQ={frequency};INTERVAL=4294967295");
+            assert_eq!(starts(&rule, at(2022, 8, 1), None), vec![at(2022, 8, 1)]);
+        }
+    }
+}
@@ -0,0 +1,983 @@
+//! Two way sync of Kara's calendars with a CalDAV server.
+//!
+//! Each calendar on the server is mirrored in a file in Kara's calendar directory:
+//! `kara.ics` for the calendar Kara adds events to, `caldav-<name>.ics` for the others.
+//! Unless `calendar` names the one Kara adds events to, she picks the first and keeps its
+//! href in `.sync/default`, so that it stays hers when the server lists calendars in
+//! another order. What each event looked like at the last sync is kept next to them in
+//! `.sync/<name>-<hash of the href>.json`, one for each calendar on the server:
+//!
+//! ```json
+//! {
+//!   "version": 2,
+//!   "collection": "/dav/kara/personal/",
+//!   "file": "kara.ics",
+//!   "resources": {
+//!     "20220801T090000-1@kara": {
+//!       "href": "/dav/kara/personal/20220801T090000-1-kara.ics",
+//!       "etag": "\"5d41402a\"",
+//!       "hash": 1190253112498116401
+//!     }
+//!   }
+//! }
+//! ```
+//!
+//! - `collection`: the calendar on the server the state is for
+//! - `file`: the file in Kara's calendar directory the calendar is mirrored in. The state
+//!   is dropped if it changes
+//! - `resources`: by uid, where the event is on the server, its ETag and a hash of the
+//!   event as Kara last synced it, which tells whether it changed in Kara since
+//!
+//! Each file is marked with the calendar on the server it was last synced with, in its
+//! `X-KARA-SYNCED` property. When the file is missing or lost its mark, e.g. because it
+//! could not be read and Kara started a new one, its events are downloaded again rather
+//! than deleted on the server. So are events that disappeared from it, when more than ten
+//! did since the last sync.
+//!
+//! Events changed only in Kara are uploaded, events changed only on the server are
+//! downloaded, with the time zones they use. When an event changed on both sides, or was changed on one side and
+//! deleted on the other, the server's version wins and Kara's is saved to
+//! `.sync/<file>-conflicts.ics` so that nothing is lost.
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    path::Path,
+    sync::Arc,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, info, warn};
+
+use super::{
+    caldav::{CalDav, Collection, Remote, WriteError},
+    ics, read, write, Calendar, Event, KARA_CALENDAR,
+};
+use crate::config::state;
+
+/// How long Kara waits between syncs
+pub const DEFAULT_SYNC_INTERVAL: Duration = Duration::from_secs(15 * 60);
+const VERSION: u32 = 2;
+/// Where the href of the calendar Kara adds events to is kept, in `.sync`
+const DEFAULT_FILE: &str = "default";
+/// The property of a file with the href of the calendar it was synced with
+const SYNCED_PROPERTY: &str = "X-KARA-SYNCED";
+/// The most events that are deleted on the server in one sync, in case a file lost them
+/// by accident rather than because Kara was asked to remove them
+const MAX_DELETIONS: usize = 10;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct State {
+    version: u32,
+    collection: String,
+    file: String,
+    resources: BTreeMap<String, Synced>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Synced {
+    href: String,
+    etag: String,
+    hash: u64,
+}
+
+/// What a sync changed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Synchronised {
+    pub downloaded: usize,
+    pub uploaded: usize,
+    pub deleted: usize,
+    pub conflicts: usize,
+}
+
+impl std::ops::AddAssign for Synchronised {
+    fn add_assign(&mut self, other: Self) {
+        self.downloaded += other.downloaded;
+        self.uploaded += other.uploaded;
+        self.deleted += other.deleted;
+        self.conflicts += other.conflicts;
+    }
+}
+
+/// Keeps Kara's calendars in sync with the calendars on a server
+pub struct CalendarSync<R: Remote = CalDav> {
+    calendar: Arc<Calendar>,
+    remote: R,
+    /// The name of the calendar on the server Kara's own events go to
+    default: Option<String>,
+    interval: Duration,
+}
+
+impl CalendarSync {
+    /// Syncs with the CalDAV server `config` names
+    pub fn configured(calendar: Arc<Calendar>, config: &state::CalDav) -> anyhow::Result<Self> {
+        let sync = Self::new(calendar, CalDav::configured(config)?).with_interval(config.interval);
+        Ok(match &config.calendar {
+            Some(name) => sync.with_default(name.clone()),
+            None => sync,
+        })
+    }
+}
+
+impl<R: Remote> CalendarSync<R> {
+    pub fn new(calendar: Arc<Calendar>, remote: R) -> Self {
+        Self {
+            calendar,
+            remote,
+            default: None,
+            interval: DEFAULT_SYNC_INTERVAL,
+        }
+    }
+
+    /// Events added by voice go to the calendar on the server named `name`, instead of the
+    /// one picked at the first sync
+    pub fn with_default(mut self, name: impl Into<String>) -> Self {
+        self.default = Some(name.into());
+        self
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Syncs every calendar on the server once
+    pub async fn sync(&self) -> anyhow::Result<Synchronised> {
+        let collections = self.remote.collections().await?;
+        let default = self.default_collection(&collections)?;
+        let mut total = Synchronised::default();
+        for (i, collection) in collections.iter().enumerate() {
+            let file = if i == default {
+                KARA_CALENDAR.to_owned()
+            } else {
+                format!("caldav-{}.ics", slug(collection))
+            };
+            match self.sync_collection(collection, &file).await {
+                Ok(synchronised) => total += synchronised,
+                Err(e) => error!("could not sync calendar {}: {}", collection.name, e),
+            }
+        }
+        Ok(total)
+    }
+
+    /// Which of `collections` Kara adds events to: the one named in the config, otherwise
+    /// the one she picked before, otherwise the first, which is then remembered
+    fn default_collection(&self, collections: &[Collection]) -> io::Result<usize> {
+        let path = self.calendar.dir().join(".sync").join(DEFAULT_FILE);
+        let remembered = match fs::read_to_string(&path) {
+            Ok(href) => Some(href.trim().to_owned()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+        let named = self.default.as_ref().and_then(|name| {
+            let position = collections
+                .iter()
+                .position(|collection| collection.name.eq_ignore_ascii_case(name));
+            if position.is_none() {
+                warn!(calendar = %name, "the CalDAV server has no such calendar");
+            }
+            position
+        });
+        let default = named
+            .or_else(|| {
+                let remembered = remembered.as_deref()?;
+                collections
+                    .iter()
+                    .position(|collection| collection.href == remembered)
+            })
+            .unwrap_or(0);
+        if let Some(collection) = collections.get(default) {
+            if remembered.as_deref() != Some(collection.href.as_str()) {
+                info!(calendar = %collection.name, "adding Kara's events to this calendar");
+                write_atomically(&path, &collection.href)?;
+            }
+        }
+        Ok(default)
+    }
+
+    async fn sync_collection(
+        &self,
+        collection: &Collection,
+        file: &str,
+    ) -> anyhow::Result<Synchronised> {
+        let path = self.calendar.dir().join(file);
+        let state_path = self.calendar.dir().join(".sync").join(format!(
+            "{}-{:08x}.json",
+            slug(collection),
+            hash(&collection.href) as u32
+        ));
+        let mut state = load_state(&state_path)?
+            .filter(|state| state.collection == collection.href && state.file == file)
+            .unwrap_or_else(|| State {
+                version: VERSION,
+                collection: collection.href.clone(),
+                file: file.to_owned(),
+                resources: BTreeMap::new(),
+            });
+        let local_file = match read(&path) {
+            Ok(file) => Some(file),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+        // Events missing from a file that is not the one synced last time were not deleted
+        let marked = local_file.as_ref().map_or(false, |file| {
+            file.property(SYNCED_PROPERTY) == Some(collection.href.as_str())
+        });
+        if !marked && !state.resources.is_empty() {
+            warn!(
+                "{} is missing or was replaced since the last sync, downloading its events again",
+                path.display()
+            );
+            state.resources.clear();
+        }
+        let local_file = local_file.unwrap_or_default();
+        let local = by_uid(local_file.events.clone());
+        let hashes: HashMap<_, _> = local
+            .iter()
+            .map(|(uid, events)| (uid.clone(), hash(&serialise(events))))
+            .collect();
+        let changed_locally =
+            |uid: &str, state: &State| match (hashes.get(uid), state.resources.get(uid)) {
+                (Some(hash), Some(synced)) => *hash != synced.hash,
+                (Some(_), None) => true,
+                (None, _) => false,
+            };
+        let remote = self.remote.list(&collection.href).await?;
+        let mut done = Synchronised::default();
+        // The events to replace in the local file, `None` to remove them
+        let mut changes: BTreeMap<String, Option<Vec<Event>>> = BTreeMap::new();
+        let mut conflicts = Vec::new();
+        // The time zones of downloaded events
+        let mut components = ics::File::default();
+
+        // Changes on the server win
+        let known: HashMap<_, _> = state
+            .resources
+            .iter()
+            .map(|(uid, synced)| (synced.href.clone(), uid.clone()))
+            .collect();
+        let mut fetch: Vec<_> = remote
+            .iter()
+            .filter(|(href, etag)| {
+                known
+                    .get(*href)
+                    .map_or(true, |uid| state.resources[uid].etag != **etag)
+            })
+            .map(|(href, _)| href.clone())
+            .collect();
+        let removed: Vec<_> = state
+            .resources
+            .iter()
+            .filter(|(_, synced)| !remote.contains_key(&synced.href))
+            .map(|(uid, _)| uid.clone())
+            .collect();
+        for uid in removed {
+            if changed_locally(&uid, &state) {
+                conflicts.extend(local[&uid].iter().cloned());
+            }
+            state.resources.remove(&uid);
+            changes.insert(uid, None);
+            done.deleted += 1;
+        }
+        let fetched = self.remote.fetch(&collection.href, &fetch).await?;
+        for resource in fetched {
+            let events = match ics::parse(&resource.data) {
+                Ok(file) if !file.events.is_empty() => {
+                    components.merge_components(&file);
+                    file.events
+                }
+                Ok(_) => continue,
+                Err(e) => {
+                    warn!("skipping {} from the CalDAV server: {}", resource.href, e);
+                    continue;
+                }
+            };
+            let uid = events[0].uid.clone();
+            let remote_hash = hash(&serialise(&events));
+            if changed_locally(&uid, &state) && hashes.get(&uid) != Some(&remote_hash) {
+                conflicts.extend(local[&uid].iter().cloned());
+            }
+            state.resources.insert(
+                uid.clone(),
+                Synced {
+                    href: resource.href,
+                    etag: resource.etag,
+                    hash: remote_hash,
+                },
+            );
+            changes.insert(uid, Some(events));
+            done.downloaded += 1;
+        }
+
+        // Then changes in Kara are sent to the server
+        fetch.clear();
+        let deleted: Vec<_> = state
+            .resources
+            .keys()
+            .filter(|uid| !local.contains_key(*uid) && !changes.contains_key(*uid))
+            .cloned()
+            .collect();
+        let deleted = if deleted.len() > MAX_DELETIONS {
+            warn!(
+                "{} events are gone from {} since the last sync, downloading them again rather than deleting them on the server",
+                deleted.len(),
+                path.display()
+            );
+            fetch.extend(deleted.iter().map(|uid| state.resources[uid].href.clone()));
+            Vec::new()
+        } else {
+            deleted
+        };
+        for uid in deleted {
+            let synced = state.resources[&uid].clone();
+            match self.remote.delete(&synced.href, &synced.etag).await {
+                Ok(()) => {
+                    state.resources.remove(&uid);
+                    done.deleted += 1;
+                }
+                Err(WriteError::Conflict) => fetch.push(synced.href),
+                Err(WriteError::Failed(e)) => error!("could not delete {}: {}", synced.href, e),
+            }
+        }
+        for (uid, events) in &local {
+            if changes.contains_key(uid) || !changed_locally(uid, &state) {
+                continue;
+            }
+            let synced = state.resources.get(uid).cloned();
+            let href = synced
+                .as_ref()
+                .map(|synced| synced.href.clone())
+                .unwrap_or_else(|| new_href(collection, uid));
+            let data = ics::write(&local_file.subset(events.clone()));
+            let etag = synced.as_ref().map(|synced| synced.etag.as_str());
+            match self.remote.put(&href, data, etag).await {
+                Ok(etag) => {
+                    state.resources.insert(
+                        uid.clone(),
+                        Synced {
+                            href,
+                            // Unknown ETags make the next sync download the event again
+                            etag: etag.unwrap_or_default(),
+                            hash: hashes[uid],
+                        },
+                    );
+                    done.uploaded += 1;
+                }
+                Err(WriteError::Conflict) => {
+                    conflicts.extend(events.iter().cloned());
+                    fetch.push(href);
+                }
+                Err(WriteError::Failed(e)) => error!("could not upload {}: {}", href, e),
+            }
+        }
+        for resource in self.remote.fetch(&collection.href, &fetch).await? {
+            if let Ok(file) = ics::parse(&resource.data) {
+                components.merge_components(&file);
+                if let Some(uid) = file.events.first().map(|event| event.uid.clone()) {
+                    state.resources.insert(
+                        uid.clone(),
+                        Synced {
+                            href: resource.href,
+                            etag: resource.etag,
+                            hash: hash(&serialise(&file.events)),
+                        },
+                    );
+                    changes.insert(uid, Some(file.events));
+                    done.downloaded += 1;
+                }
+            }
+        }
+
+        done.conflicts = conflicts.len();
+        if !conflicts.is_empty() {
+            let conflicts_path = self
+                .calendar
+                .dir()
+                .join(".sync")
+                .join(format!("{}-conflicts.ics", file.trim_end_matches(".ics")));
+            warn!(
+                "events changed both in Kara and on the CalDAV server, Kara's versions are in {}",
+                conflicts_path.display()
+            );
+            let mut saved = read(&conflicts_path).unwrap_or_default();
+            saved.events.extend(conflicts);
+            write(&conflicts_path, &saved)?;
+        }
+        if !changes.is_empty() || !marked {
+            self.apply(&path, changes, &components, &collection.href)?;
+        }
+        save_state(&state_path, &state)?;
+        Ok(done)
+    }
+
+    /// Replaces the events with the uids in `changes` in the file at `path`, keeping any
+    /// other change made to it during the sync, adds the time zones in `components` and
+    /// marks the file as synced with `collection`
+    fn apply(
+        &self,
+        path: &Path,
+        changes: BTreeMap<String, Option<Vec<Event>>>,
+        components: &ics::File,
+        collection: &str,
+    ) -> io::Result<()> {
+        let _writing = self.calendar.lock();
+        let mut file = match read(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => ics::File::default(),
+            Err(e) => return Err(e),
+        };
+        file.events
+            .retain(|event| !changes.contains_key(&event.uid));
+        file.events
+            .extend(changes.into_values().flatten().flatten());
+        file.merge_components(components);
+        file.set_property(SYNCED_PROPERTY, collection);
+        write(path, &file)
+    }
+
+    /// Syncs every `interval`, and soon after events are changed in Kara, until the task is
+    /// dropped
+    pub async fn run(self: Arc<Self>) {
+        loop {
+            match self.sync().await {
+                Ok(done) if done != Synchronised::default() => info!(
+                    downloaded = done.downloaded,
+                    uploaded = done.uploaded,
+                    deleted = done.deleted,
+                    conflicts = done.conflicts,
+                    "synced calendars"
+                ),
+                Ok(_) => debug!("calendars are in sync"),
+                Err(e) => error!("could not sync calendars: {}", e),
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(self.interval) => {}
+                _ = self.calendar.changed.notified() => {}
+            }
+        }
+    }
+}
+
+/// Events grouped by uid, as the server keeps them
+fn by_uid(events: Vec<Event>) -> BTreeMap<String, Vec<Event>> {
+    let mut grouped: BTreeMap<_, Vec<_>> = BTreeMap::new();
+    for event in events {
+        grouped.entry(event.uid.clone()).or_default().push(event);
+    }
+    grouped
+}
+
+/// The events that share a uid as a calendar, without the time zones they use, to tell
+/// whether they changed
+fn serialise(events: &[Event]) -> String {
+    ics::write(&ics::File::new(events.to_vec()))
+}
+
+/// FNV-1a, which unlike the hasher of the standard library is the same in every build
+fn hash(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Where a new event is uploaded to
+fn new_href(collection: &Collection, uid: &str) -> String {
+    let name: String = uid
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '-',
+        })
+        .collect();
+    format!("{}/{name}.ics", collection.href.trim_end_matches('/'))
+}
+
+/// A file name for a calendar on the server
+fn slug(collection: &Collection) -> String {
+    let name = collection
+        .href
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or_default();
+    let slug: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    if slug.is_empty() {
+        "calendar".to_owned()
+    } else {
+        slug
+    }
+}
+
+fn load_state(path: &Path) -> io::Result<Option<State>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    match serde_json::from_str::<State>(&contents) {
+        Ok(state) if state.version == VERSION => Ok(Some(state)),
+        _ => {
+            error!(
+                "could not read calendar sync state {}, syncing from scratch",
+                path.display()
+            );
+            Ok(None)
+        }
+    }
+}
+
+fn save_state(path: &Path, state: &State) -> io::Result<()> {
+    write_atomically(path, &serde_json::to_string_pretty(state)?)
+}
+
+fn write_atomically(path: &Path, contents: &str) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    fs::write(&temporary, contents)?;
+    fs::rename(temporary, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, sync::Mutex};
+
+    use async_trait::async_trait;
+    use chrono::NaiveDate;
+
+    use super::*;
+    use crate::calendar::{caldav::Resource, Removal, Time, Zone};
+
+    /// A CalDAV server in memory
+    #[derive(Default)]
+    struct Memory {
+        collections: Mutex<Vec<Collection>>,
+        /// By href
+        resources: Mutex<BTreeMap<String, Resource>>,
+        etags: Mutex<u32>,
+        /// A change made on the server between Kara listing a calendar and writing to it
+        racing: Mutex<Option<Event>>,
+        /// The hrefs Kara deleted
+        deleted: Mutex<Vec<String>>,
+    }
+
+    impl Memory {
+        fn new(names: &[&str]) -> Self {
+            let collections = names
+                .iter()
+                .map(|name| Collection {
+                    href: format!("/dav/kara/{name}/"),
+                    name: name.to_string(),
+                })
+                .collect();
+            Self {
+                collections: Mutex::new(collections),
+                ..Self::default()
+            }
+        }
+
+        /// Adds or changes an event as another client would
+        fn change(&self, href: &str, event: &Event) {
+            self.store(href, serialise(std::slice::from_ref(event)));
+        }
+
+        fn store(&self, href: &str, data: String) {
+            let etag = self.etag();
+            self.resources.lock().unwrap().insert(
+                href.to_owned(),
+                Resource {
+                    href: href.to_owned(),
+                    etag,
+                    data,
+                },
+            );
+        }
+
+        fn remove(&self, href: &str) {
+            self.resources.lock().unwrap().remove(href);
+        }
+
+        /// The events in the calendar `name`
+        fn events(&self, name: &str) -> Vec<Event> {
+            let collection = format!("/dav/kara/{name}/");
+            self.resources
+                .lock()
+                .unwrap()
+                .values()
+                .filter(|resource| resource.href.starts_with(&collection))
+                .flat_map(|resource| ics::parse(&resource.data).unwrap().events)
+                .collect()
+        }
+
+        fn etag(&self) -> String {
+            let mut etags = self.etags.lock().unwrap();
+            *etags += 1;
+            format!("\"{etags}\"")
+        }
+
+        fn race(&self, href: &str) {
+            if let Some(event) = self.racing.lock().unwrap().take() {
+                self.change(href, &event);
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Remote for Memory {
+        async fn collections(&self) -> anyhow::Result<Vec<Collection>> {
+            Ok(self.collections.lock().unwrap().clone())
+        }
+
+        async fn list(&self, collection: &str) -> anyhow::Result<HashMap<String, String>> {
+            Ok(self
+                .resources
+                .lock()
+                .unwrap()
+                .values()
+                .filter(|resource| resource.href.starts_with(collection))
+                .map(|resource| (resource.href.clone(), resource.etag.clone()))
+                .collect())
+        }
+
+        async fn fetch(&self, _: &str, hrefs: &[String]) -> anyhow::Result<Vec<Resource>> {
+            let resources = self.resources.lock().unwrap();
+            Ok(hrefs
+                .iter()
+                .filter_map(|href| resources.get(href).cloned())
+                .collect())
+        }
+
+        async fn put(
+            &self,
+            href: &str,
+            data: String,
+            etag: Option<&str>,
+        ) -> Result<Option<String>, WriteError> {
+            self.race(href);
+            let current = self.resources.lock().unwrap().get(href).cloned();
+            if current.map(|resource| resource.etag).as_deref() != etag {
+                return Err(WriteError::Conflict);
+            }
+            let etag = self.etag();
+            self.resources.lock().unwrap().insert(
+                href.to_owned(),
+                Resource {
+                    href: href.to_owned(),
+                    etag: etag.clone(),
+                    data,
+                },
+            );
+            Ok(Some(etag))
+        }
+
+        async fn delete(&self, href: &str, etag: &str) -> Result<(), WriteError> {
+            self.race(href);
+            self.deleted.lock().unwrap().push(href.to_owned());
+            let mut resources = self.resources.lock().unwrap();
+            match resources.get(href) {
+                Some(resource) if resource.etag == etag => {
+                    resources.remove(href);
+                    Ok(())
+                }
+                _ => Err(WriteError::Conflict),
+            }
+        }
+    }
+
+    /// A calendar directory of its own for each test, removed when the test ends
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("kara-calendar-{}-{name}", std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            Self(path)
+        }
+
+        fn sync(&self, remote: Memory) -> CalendarSync<Memory> {
+            CalendarSync::new(Arc::new(Calendar::new(&self.0, Vec::new())), remote)
+        }
+
+        fn events(&self, file: &str) -> Vec<Event> {
+            read(&self.0.join(file)).map_or_else(|_| Vec::new(), |file| file.events)
+        }
+
+        /// Changes the summary of an event in Kara's calendar, as if it was edited
+        fn rename(&self, uid: &str, summary: &str) {
+            let path = self.0.join(KARA_CALENDAR);
+            let mut file = read(&path).unwrap();
+            for event in file.events.iter_mut().filter(|event| event.uid == uid) {
+                event.summary = summary.to_owned();
+            }
+            write(&path, &file).unwrap();
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn event(summary: &str) -> Event {
+        let start = NaiveDate::from_ymd_opt(2022, 8, 1)
+            .unwrap()
+            .and_hms_opt(9, 0, 0)
+            .unwrap();
+        Event::new(summary, Time::DateTime(start, Zone::Floating), None)
+    }
+
+    fn renamed(event: &Event, summary: &str) -> Event {
+        Event {
+            summary: summary.to_owned(),
+            ..event.clone()
+        }
+    }
+
+    fn summaries(events: &[Event]) -> Vec<&str> {
+        events.iter().map(|event| event.summary.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn uploads_events_added_in_kara() {
+        let dir = TempDir::new("upload");
+        let sync = dir.sync(Memory::new(&["personal"]));
+        sync.calendar.add(event("dentist")).unwrap();
+
+        let done = sync.sync().await.unwrap();
+        assert_eq!(done.uploaded, 1);
+        assert_eq!(summaries(&sync.remote.events("personal")), ["dentist"]);
+        assert_eq!(sync.sync().await.unwrap(), Synchronised::default());
+
+        let uid = dir.events(KARA_CALENDAR)[0].uid.clone();
+        dir.rename(&uid, "dentist at noon");
+        assert_eq!(sync.sync().await.unwrap().uploaded, 1);
+        assert_eq!(
+            summaries(&sync.remote.events("personal")),
+            ["dentist at noon"]
+        );
+    }
+
+    #[tokio::test]
+    async fn downloads_events_changed_on_the_server() {
+        let dir = TempDir::new("download");
+        let sync = dir.sync(Memory::new(&["personal", "work"]));
+        let standup = event("standup");
+        sync.remote.change("/dav/kara/work/standup.ics", &standup);
+        sync.remote
+            .change("/dav/kara/personal/gym.ics", &event("gym"));
+
+        let done = sync.sync().await.unwrap();
+        assert_eq!(done.downloaded, 2);
+        assert_eq!(summaries(&dir.events(KARA_CALENDAR)), ["gym"]);
+        assert_eq!(summaries(&dir.events("caldav-work.ics")), ["standup"]);
+
+        sync.remote
+            .change("/dav/kara/work/standup.ics", &renamed(&standup, "retro"));
+        assert_eq!(sync.sync().await.unwrap().downloaded, 1);
+        assert_eq!(summaries(&dir.events("caldav-work.ics")), ["retro"]);
+        assert_eq!(sync.sync().await.unwrap(), Synchronised::default());
+    }
+
+    #[tokio::test]
+    async fn deletes_events_on_both_sides() {
+        let dir = TempDir::new("delete");
+        let sync = dir.sync(Memory::new(&["personal"]));
+        let (kept, removed) = (event("gym"), event("dentist"));
+        sync.remote.change("/dav/kara/personal/gym.ics", &kept);
+        sync.remote
+            .change("/dav/kara/personal/dentist.ics", &removed);
+        sync.sync().await.unwrap();
+
+        sync.remote.remove("/dav/kara/personal/gym.ics");
+        assert_eq!(sync.sync().await.unwrap().deleted, 1);
+        assert_eq!(summaries(&dir.events(KARA_CALENDAR)), ["dentist"]);
+
+        assert!(sync.calendar.remove(&removed.uid, Removal::Event).unwrap());
+        assert_eq!(sync.sync().await.unwrap().deleted, 1);
+        assert!(sync.remote.events("personal").is_empty());
+        assert!(dir.events(KARA_CALENDAR).is_empty());
+    }
+
+    #[tokio::test]
+    async fn changes_on_both_sides_keep_kara_version_as_a_conflict() {
+        let dir = TempDir::new("conflict");
+        let sync = dir.sync(Memory::new(&["personal"]));
+        let dentist = event("dentist");
+        sync.remote
+            .change("/dav/kara/personal/dentist.ics", &dentist);
+        sync.sync().await.unwrap();
+
+        dir.rename(&dentist.uid, "dentist at noon");
+        sync.remote.change(
+            "/dav/kara/personal/dentist.ics",
+            &renamed(&dentist, "dentist at two"),
+        );
+        let done = sync.sync().await.unwrap();
+        assert_eq!((done.downloaded, done.uploaded, done.conflicts), (1, 0, 1));
+        assert_eq!(summaries(&dir.events(KARA_CALENDAR)), ["dentist at two"]);
+        assert_eq!(
+            summaries(&dir.events(".sync/kara-conflicts.ics")),
+            ["dentist at noon"]
+        );
+    }
+
+    #[tokio::test]
+    async fn etag_conflicts_keep_the_server_version() {
+        let dir = TempDir::new("etag");
+        let sync = dir.sync(Memory::new(&["personal"]));
+        sync.calendar.add(event("dentist")).unwrap();
+        sync.sync().await.unwrap();
+        let uid = dir.events(KARA_CALENDAR)[0].uid.clone();
+        let href = sync
+            .remote
+            .resources
+            .lock()
+            .unwrap()
+            .keys()
+            .next()
+            .cloned()
+            .unwrap();
+
+        // The server's copy changes after Kara listed it but before her upload
+        dir.rename(&uid, "dentist at noon");
+        let server = renamed(&dir.events(KARA_CALENDAR)[0], "dentist at two");
+        *sync.remote.racing.lock().unwrap() = Some(server);
+        let done = sync.sync().await.unwrap();
+        assert_eq!((done.uploaded, done.conflicts), (0, 1));
+        assert_eq!(
+            summaries(&sync.remote.events("personal")),
+            ["dentist at two"]
+        );
+        assert_eq!(summaries(&dir.events(KARA_CALENDAR)), ["dentist at two"]);
+        assert_eq!(
+            summaries(&dir.events(".sync/kara-conflicts.ics")),
+            ["dentist at noon"]
+        );
+        assert!(sync.remote.resources.lock().unwrap().contains_key(&href));
+        assert_eq!(sync.sync().await.unwrap(), Synchronised::default());
+    }
+
+    #[tokio::test]
+    async fn keeps_adding_to_the_same_calendar() {
+        let dir = TempDir::new("default");
+        let sync = dir.sync(Memory::new(&["personal", "work"]));
+        sync.calendar.add(event("dentist")).unwrap();
+        sync.sync().await.unwrap();
+        assert_eq!(summaries(&sync.remote.events("personal")), ["dentist"]);
+
+        // The server now lists its calendars the other way round
+        sync.remote.collections.lock().unwrap().reverse();
+        sync.calendar.add(event("gym")).unwrap();
+        let done = sync.sync().await.unwrap();
+        assert_eq!((done.uploaded, done.deleted), (1, 0));
+        let mut personal: Vec<_> = sync
+            .remote
+            .events("personal")
+            .into_iter()
+            .map(|event| event.summary)
+            .collect();
+        personal.sort();
+        assert_eq!(personal, ["dentist", "gym"]);
+        assert!(sync.remote.events("work").is_empty());
+        assert!(dir.events("caldav-work.ics").is_empty());
+    }
+
+    #[tokio::test]
+    async fn downloads_missing_and_replaced_files_again() {
+        let dir = TempDir::new("missing");
+        let sync = dir.sync(Memory::new(&["personal"]));
+        sync.remote
+            .change("/dav/kara/personal/gym.ics", &event("gym"));
+        sync.remote
+            .change("/dav/kara/personal/dentist.ics", &event("dentist"));
+        sync.sync().await.unwrap();
+
+        fs::remove_file(dir.0.join(KARA_CALENDAR)).unwrap();
+        let done = sync.sync().await.unwrap();
+        assert_eq!((done.downloaded, done.deleted), (2, 0));
+        assert_eq!(dir.events(KARA_CALENDAR).len(), 2);
+
+        // A file that cannot be read is put aside and a new one started
+        fs::write(dir.0.join(KARA_CALENDAR), "not a calendar").unwrap();
+        sync.calendar.add(event("standup")).unwrap();
+        let done = sync.sync().await.unwrap();
+        assert_eq!((done.downloaded, done.uploaded, done.deleted), (2, 1, 0));
+        assert_eq!(dir.events(KARA_CALENDAR).len(), 3);
+        assert_eq!(sync.remote.events("personal").len(), 3);
+        assert!(sync.remote.deleted.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn does_not_delete_many_events_at_once() {
+        let dir = TempDir::new("many");
+        let sync = dir.sync(Memory::new(&["personal"]));
+        for i in 0..=MAX_DELETIONS {
+            sync.remote.change(
+                &format!("/dav/kara/personal/{i}.ics"),
+                &event(&i.to_string()),
+            );
+        }
+        sync.sync().await.unwrap();
+
+        let path = dir.0.join(KARA_CALENDAR);
+        let mut file = read(&path).unwrap();
+        file.events.clear();
+        write(&path, &file).unwrap();
+        let done = sync.sync().await.unwrap();
+        assert_eq!((done.downloaded, done.deleted), (MAX_DELETIONS + 1, 0));
+        assert_eq!(dir.events(KARA_CALENDAR).len(), MAX_DELETIONS + 1);
+        assert!(sync.remote.deleted.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn keeps_the_time_zones_of_events() {
+        let dir = TempDir::new("zones");
+        let sync = dir.sync(Memory::new(&["personal"]));
+        let href = "/dav/kara/personal/dinner.ics";
+        sync.remote.store(
+            href,
+            "BEGIN:VCALENDAR\r\n\
+             VERSION:2.0\r\n\
+             PRODID:-//Example//EN\r\n\
+             BEGIN:VTIMEZONE\r\n\
+             TZID:Europe/Paris\r\n\
+             BEGIN:STANDARD\r\n\
+             DTSTART:19701025T030000\r\n\
+             TZOFFSETFROM:+0200\r\n\
+             TZOFFSETTO:+0100\r\n\
+             END:STANDARD\r\n\
+             END:VTIMEZONE\r\n\
+             BEGIN:VEVENT\r\n\
+             UID:dinner@example.com\r\n\
+             SUMMARY:dinner\r\n\
+             DTSTART;TZID=Europe/Paris:20220801T200000\r\n\
+             END:VEVENT\r\n\
+             END:VCALENDAR\r\n"
+                .to_owned(),
+        );
+        sync.sync().await.unwrap();
+        let local = fs::read_to_string(dir.0.join(KARA_CALENDAR)).unwrap();
+        assert_eq!(local.matches("BEGIN:VTIMEZONE").count(), 1);
+
+        dir.rename("dinner@example.com", "dinner with friends");
+        assert_eq!(sync.sync().await.unwrap().uploaded, 1);
+        let uploaded = sync.remote.resources.lock().unwrap()[href].data.clone();
+        assert!(uploaded.contains("TZID:Europe/Paris"));
+        assert!(uploaded.contains("SUMMARY:dinner with friends"));
+        assert_eq!(sync.sync().await.unwrap(), Synchronised::default());
+    }
+}
@@ -1,4 +1,6 @@
-use clap::{ArgEnum, Parser};
+use std::path::PathBuf;
+
+use clap::{ArgEnum, Parser, Subcommand};
 use serde::Deserialize;
 use tracing::Level;
 
@@ -15,6 +17,153 @@ pub struct Args {
     /// Specify alternative configuration file [default: $XDG_CONFIG_HOME/kara/kara.toml]
     #[clap(short, long)]
     config: Option<String>,
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Manage the speakers Kara can identify
+    Speaker {
+        #[clap(subcommand)]
+        action: SpeakerCommand,
+    },
+    /// Manage values added to the language model's entities, such as contact names
+    Entity {
+        #[clap(subcommand)]
+        action: EntityCommand,
+    },
+    /// Work with language models
+    Nlu {
+        #[clap(subcommand)]
+        action: NluCommand,
+    },
+    /// Manage named lists, such as the shopping list
+    Lists {
+        /// Use the lists of this enrolled speaker as well as the shared ones
+        #[clap(short, long)]
+        speaker: Option<String>,
+        #[clap(subcommand)]
+        action: ListsCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ListsCommand {
+    /// Show the items of a list, or every list and how many items it has
+    Show { list: Option<String> },
+    /// Add items to a list, creating it if it does not exist
+    Add {
+        list: String,
+        #[clap(required = true)]
+        items: Vec<String>,
+    },
+    /// Remove the item most like the one given
+    Remove { list: String, item: String },
+    /// Tick off the item most like the one given
+    Done {
+        list: String,
+        item: String,
+        /// Mark the item as not done again
+        #[clap(short, long)]
+        undo: bool,
+    },
+    /// Remove the items of a list
+    Clear {
+        list: String,
+        /// Only remove the items that are done
+        #[clap(short, long)]
+        done: bool,
+    },
+    /// Delete a list and its items
+    Delete { list: String },
+    /// Print a list, or every list, as Markdown or JSON
+    Export {
+        list: Option<String>,
+        #[clap(short, long, arg_enum, default_value = "markdown")]
+        format: ExportFormat,
+    },
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ArgEnum, Debug)]
+pub enum ExportFormat {
+    /// A task list per list
+    Markdown,
+    /// Every item with when it was added and done
+    Json,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum NluCommand {
+    /// Measure how well a language model understands a labelled dataset
+    Eval {
+        /// A snips dataset: a JSON file, a YAML file or a directory of YAML files
+        dataset: PathBuf,
+        /// The model to evaluate [default: the model for the configured language]
+        #[clap(short, long)]
+        model: Option<PathBuf>,
+        #[clap(short, long, arg_enum, default_value = "text")]
+        format: ReportFormat,
+    },
+    /// Train a language model without the snips toolchain
+    Train {
+        /// A snips dataset: a JSON file, a YAML file or a directory of YAML files
+        dataset: PathBuf,
+        /// Where to write the model [default: the user model directory for the language]
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+        /// ISO 639-1 code of the dataset's language [default: the configured language]
+        #[clap(short, long)]
+        language: Option<String>,
+    },
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ArgEnum, Debug)]
+pub enum ReportFormat {
+    /// A summary for reading
+    Text,
+    /// The full report as JSON, for comparing model versions
+    Json,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SpeakerCommand {
+    /// Record a speaker's voice so that their commands can be attributed to them
+    Enrol {
+        /// User id of the speaker. Enrolling an existing speaker adds to their profile
+        name: String,
+        /// How long to record for, in seconds
+        #[clap(short, long, default_value_t = 15)]
+        seconds: u64,
+    },
+    /// List enrolled speakers
+    List,
+    /// Delete a speaker's profile
+    Remove { name: String },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum EntityCommand {
+    /// Teach Kara new values for an entity. The values are used from the next start
+    Add {
+        /// Entity name in the language model, or one of contact, playlist, device and room
+        entity: String,
+        #[clap(required = true)]
+        values: Vec<String>,
+    },
+    /// Remove values that were added to an entity
+    Remove {
+        entity: String,
+        #[clap(required = true)]
+        values: Vec<String>,
+    },
+    /// List added values, or the entities of the language model that can be extended
+    List {
+        entity: Option<String>,
+        /// List the entities of the language model instead
+        #[clap(short, long)]
+        available: bool,
+    },
 }
 
 impl Args {
@@ -24,7 +173,7 @@ impl Args {
             None => Args::map_log_level(config_file_level),
         }
     }
-    fn map_log_level(mode: DebugMode) -> Level {
+    pub fn map_log_level(mode: DebugMode) -> Level {
         match mode {
             DebugMode::Trace => Level::TRACE,
             DebugMode::Debug => Level::DEBUG,
@@ -41,6 +190,10 @@ impl Args {
     pub fn config_path(&self) -> Option<&String> {
         self.config.as_ref()
     }
+
+    pub fn into_command(self) -> Option<Command> {
+        self.command
+    }
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ArgEnum, Debug, Deserialize)]
@@ -0,0 +1,339 @@
+use std::{collections::BTreeSet, iter, time::Duration};
+
+use anyhow::{anyhow, Result};
+use kara_audio::speaker::{self, ProfileStore, SpeakerProfile};
+use kara_events::Speaker;
+use kara_nlu::{
+    engine::{LinearEngine, NluEngine},
+    evaluation::{self, Report},
+    injection::{self, default_entity_path, InjectedEntities},
+    Language, NLUParser,
+};
+
+use crate::{
+    cli::{
+        Command, EntityCommand, ExportFormat, ListsCommand, NluCommand, ReportFormat,
+        SpeakerCommand,
+    },
+    config::state::ParsedConfig,
+    lists::{self, Added, ListStore},
+    locale,
+};
+
+/// Runs a subcommand instead of starting a session
+pub async fn run(command: Command, config: &ParsedConfig) -> Result<()> {
+    match command {
+        Command::Speaker { action } => speaker(action).await,
+        Command::Entity { action } => entity(action, config),
+        Command::Nlu { action } => nlu(action, config).await,
+        Command::Lists { speaker, action } => lists(speaker, action),
+    }
+}
+
+async fn speaker(action: SpeakerCommand) -> Result<()> {
+    let store = ProfileStore::default();
+    match action {
+        SpeakerCommand::Enrol { name, seconds } => {
+            let mut profile = store
+                .load(&name)?
+                .unwrap_or_else(|| SpeakerProfile::new(&name));
+            println!("Recording for {seconds} seconds. Read something aloud in your normal voice");
+            let samples =
+                tokio::task::spawn_blocking(move || speaker::record(Duration::from_secs(seconds)))
+                    .await?
+                    .map_err(anyhow::Error::msg)?;
+            let added = speaker::enrol(&mut profile, &samples);
+            if added == 0 {
+                return Err(anyhow!(
+                    "no speech was detected. Try again closer to the microphone"
+                ));
+            }
+            store.save(&profile)?;
+            println!("Enrolled {name} ({} samples)", profile.samples());
+        }
+        SpeakerCommand::List => {
+            for profile in store.load_all()? {
+                println!("{}\t{} samples", profile.name, profile.samples());
+            }
+        }
+        SpeakerCommand::Remove { name } => {
+            if !store.remove(&name)? {
+                return Err(anyhow!("no speaker named {name} is enrolled"));
+            }
+            println!("Removed {name}");
+        }
+    }
+    Ok(())
+}
+
+fn entity(action: EntityCommand, config: &ParsedConfig) -> Result<()> {
+    let path = default_entity_path();
+    let mut injected = InjectedEntities::load(&path)?;
+    let model_entities = || {
+        injection::model_entities(locale::nlu_model_path(
+            config.general_settings.language,
+            config.nlu.model_path.as_deref(),
+        ))
+    };
+    match action {
+        EntityCommand::Add { entity, values } => {
+            let entity = injection::entity_name(&entity);
+            if !model_entities()?.iter().any(|name| name == entity) {
+                return Err(anyhow!(
+                    "the language model has no entity named {entity}. See `kara entity list --available`"
+                ));
+            }
+            for value in values {
+                if injected.add(entity, &value) {
+                    println!("Added {value} to {entity}");
+                }
+            }
+            injected.save(&path)?;
+        }
+        EntityCommand::Remove { entity, values } => {
+            let entity = injection::entity_name(&entity);
+            let mut missing = Vec::new();
+            for value in values {
+                if injected.remove(entity, &value) {
+                    println!("Removed {value} from {entity}");
+                } else {
+                    missing.push(value);
+                }
+            }
+            injected.save(&path)?;
+            if !missing.is_empty() {
+                let verb = if missing.len() == 1 { "was" } else { "were" };
+                return Err(anyhow!(
+                    "{} {verb} not added to {entity}",
+                    missing.join(", ")
+                ));
+            }
+        }
+        EntityCommand::List {
+            available: true, ..
+        } => {
+            for entity in model_entities()? {
+                println!("{entity}");
+            }
+        }
+        EntityCommand::List {
+            entity: Some(entity),
+            ..
+        } => {
+            for value in injected.values(injection::entity_name(&entity)) {
+                println!("{value}");
+            }
+        }
+        EntityCommand::List { entity: None, .. } => {
+            for entity in injected.entities() {
+                println!(
+                    "{entity}\t{}",
+                    injected.values(entity).collect::<Vec<_>>().join(", ")
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn nlu(action: NluCommand, config: &ParsedConfig) -> Result<()> {
+    match action {
+        NluCommand::Eval {
+            dataset,
+            model,
+            format,
+        } => {
+            let dataset = evaluation::load(dataset)?;
+            if dataset.is_empty() {
+                return Err(anyhow!("the dataset has no labelled utterances"));
+            }
+            let model = model.unwrap_or_else(|| {
+                locale::nlu_model_path(
+                    config.general_settings.language,
+                    config.nlu.model_path.as_deref(),
+                )
+            });
+            let options = config.nlu.intents.parse_options();
+            let report = tokio::task::spawn_blocking(move || {
+                let parser = NLUParser::new(&model)?.with_options(options);
+                evaluation::evaluate(&parser, &dataset)
+            })
+            .await??;
+            match format {
+                ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+                ReportFormat::Text => print_report(&report),
+            }
+        }
+        NluCommand::Train {
+            dataset,
+            output,
+            language,
+        } => {
+            let language = match language {
+                Some(code) => Language::from_code(&code)
+                    .ok_or_else(|| anyhow!("unsupported language: {code}"))?,
+                None => config.general_settings.language,
+            };
+            let dataset = evaluation::load(dataset)?;
+            let utterances = dataset.len();
+            let engine =
+                tokio::task::spawn_blocking(move || LinearEngine::train(&dataset, language))
+                    .await??;
+            let output = output.unwrap_or_else(|| locale::user_model_dir().join(language.code()));
+            engine.save(&output)?;
+            println!(
+                "Trained {} intents on {utterances} utterances, saved to {}",
+                engine.intents().len(),
+                output.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+fn lists(speaker: Option<String>, action: ListsCommand) -> Result<()> {
+    let speaker = speaker.map_or(Speaker::Unknown, Speaker::Known);
+    let store = ListStore::configured()?.for_speaker(&speaker);
+    let no_list = |name: &str| anyhow!("there is no list called {name}");
+    let no_item = |item: &str, list: &str| anyhow!("{list} has no item like {item}");
+    match action {
+        ListsCommand::Show { list: None } => {
+            for list in store.lists()? {
+                let open = list.open_items().count();
+                println!("{}\t{open} of {} items left", list.name, list.items.len());
+            }
+        }
+        ListsCommand::Show { list: Some(name) } => {
+            let list = store.list(&name)?.ok_or_else(|| no_list(&name))?;
+            for item in &list.items {
+                let tick = if item.done { 'x' } else { ' ' };
+                println!(
+                    "[{tick}] {}\t{}",
+                    item.text,
+                    item.added
+                        .with_timezone(&chrono::Local)
+                        .format("%Y-%m-%d %H:%M")
+                );
+            }
+        }
+        ListsCommand::Add { list, items } => {
+            for item in items {
+                match store.add(&list, &item)? {
+                    Added::Added { created_list } => {
+                        if created_list {
+                            println!("Created {list}");
+                        }
+                        println!("Added {item} to {list}");
+                    }
+                    Added::AlreadyThere(existing) => {
+                        println!("{} is already on {list}", existing.text)
+                    }
+                }
+            }
+        }
+        ListsCommand::Remove { list, item } => {
+            let removed = store
+                .remove(&list, &item)?
+                .ok_or_else(|| no_item(&item, &list))?;
+            println!("Removed {} from {list}", removed.text);
+        }
+        ListsCommand::Done { list, item, undo } => {
+            let changed = store
+                .set_done(&list, &item, !undo)?
+                .ok_or_else(|| no_item(&item, &list))?;
+            if undo {
+                println!("{} is not done any more", changed.text);
+            } else {
+                println!("Ticked off {}", changed.text);
+            }
+        }
+        ListsCommand::Clear { list, done } => {
+            if store.list(&list)?.is_none() {
+                return Err(no_list(&list));
+            }
+            println!("Removed {} items from {list}", store.clear(&list, done)?);
+        }
+        ListsCommand::Delete { list } => {
+            if !store.delete(&list)? {
+                return Err(no_list(&list));
+            }
+            println!("Deleted {list}");
+        }
+        ListsCommand::Export { list, format } => {
+            let lists = match list {
+                Some(name) => vec![store.list(&name)?.ok_or_else(|| no_list(&name))?],
+                None => store.lists()?,
+            };
+            match format {
+                ExportFormat::Markdown => print!("{}", lists::export::markdown(&lists)),
+                ExportFormat::Json => println!("{}", lists::export::json(&lists)?),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_report(report: &Report) {
+    println!("{} utterances", report.utterances);
+    println!("intent accuracy  {:.3}", report.intent_accuracy);
+    println!("exact match      {:.3}", report.exact_match);
+    println!(
+        "slots            precision {:.3}  recall {:.3}  f1 {:.3}",
+        report.slots.precision, report.slots.recall, report.slots.f1
+    );
+    println!();
+    println!(
+        "{:<32} {:>9} {:>9} {:>9} {:>9}",
+        "intent", "precision", "recall", "f1", "support"
+    );
+    for (intent, metrics) in &report.intents {
+        println!(
+            "{:<32} {:>9.3} {:>9.3} {:>9.3} {:>9}",
+            intent, metrics.precision, metrics.recall, metrics.f1, metrics.support
+        );
+    }
+    print_confusion(report);
+    if !report.worst_confusions.is_empty() {
+        println!();
+        println!("most confused");
+        for confusion in &report.worst_confusions {
+            println!(
+                "{:>5}  {} -> {}",
+                confusion.count, confusion.expected, confusion.predicted
+            );
+        }
+    }
+}
+
+/// Expected intents down the side, predicted intents along the top by their row number
+fn print_confusion(report: &Report) {
+    let labels: BTreeSet<_> = report
+        .confusion
+        .iter()
+        .flat_map(|(expected, predicted)| iter::once(expected).chain(predicted.keys()))
+        .collect();
+    if labels.is_empty() {
+        return;
+    }
+    println!();
+    println!("confusion (rows expected, columns predicted)");
+    print!("{:<36}", "");
+    for column in 1..=labels.len() {
+        print!(" {column:>4}");
+    }
+    println!();
+    for (row, expected) in labels.iter().enumerate() {
+        print!("{:>3} {:<32}", row + 1, expected);
+        for predicted in &labels {
+            match report
+                .confusion
+                .get(*expected)
+                .and_then(|counts| counts.get(*predicted))
+            {
+                Some(count) => print!(" {count:>4}"),
+                None => print!(" {:>4}", "."),
+            }
+        }
+        println!();
+    }
+}
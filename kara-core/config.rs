@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use serde::Deserialize;
 
 #[derive(Debug, Deserialize)]
@@ -7,6 +9,30 @@ pub struct ConfigFile {
     #[serde(rename = "natural-language-understanding")]
     nlu: Option<Nlu>,
     window: Option<Window>,
+    alarms: Option<Alarms>,
+    calendar: Option<Calendar>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Alarms {
+    snooze: Option<u64>,
+    #[serde(rename = "missed-within")]
+    missed_within: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Calendar {
+    paths: Option<Vec<String>>,
+    caldav: Option<CalDav>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CalDav {
+    url: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+    calendar: Option<String>,
+    interval: Option<u64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -23,12 +49,42 @@ struct GeneralSettings {
     #[serde(rename = "log-level")]
     log_level: Option<String>,
     units: Option<String>,
+    language: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 struct Nlu {
     #[serde(rename = "speech-to-text")]
     stt: Option<SpeechToText>,
+    punctuation: Option<Punctuation>,
+    #[serde(rename = "speaker-identification")]
+    speakers: Option<Speakers>,
+    threshold: Option<f32>,
+    alternatives: Option<usize>,
+    thresholds: Option<HashMap<String, f32>>,
+    #[serde(rename = "confirm-threshold")]
+    confirm_threshold: Option<f32>,
+    #[serde(rename = "confirm-thresholds")]
+    confirm_thresholds: Option<HashMap<String, f32>>,
+    #[serde(rename = "context-window")]
+    context_window: Option<u64>,
+    #[serde(rename = "web-fallback")]
+    web_fallback: Option<bool>,
+    #[serde(rename = "model-path")]
+    model_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Speakers {
+    enabled: Option<bool>,
+    threshold: Option<f32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Punctuation {
+    gui: Option<bool>,
+    history: Option<bool>,
+    dictation: Option<bool>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -42,16 +98,30 @@ struct SpeechToText {
 struct STTKara {
     #[serde(rename = "model-path")]
     model_path: Option<String>,
+    #[serde(rename = "log-level")]
+    log_level: Option<String>,
 }
 
 pub mod state {
 
-    use kara_audio::stt_sources::{default_stt_model_path, STTConfig};
+    use kara_audio::stt_sources::STTConfig;
+    use std::{collections::HashMap, path::PathBuf, time::Duration};
+
+    use kara_nlu::{context, punctuation::Outputs, Language, ParseOptions};
     use serde::Deserialize;
 
-    use crate::cli::{DebugMode, Interface};
+    use crate::{
+        calendar,
+        cli::{DebugMode, Interface},
+        locale::{self, ModelSettings},
+        scheduler,
+        skills::Confirmation,
+    };
 
-    use super::ConfigFile;
+    use super::{
+        ConfigFile, GeneralSettings as GeneralSettingsFile, Nlu as NluFile,
+        SpeechToText as SpeechToTextFile,
+    };
 
     #[derive(Debug, Deserialize)]
     pub enum Units {
@@ -65,6 +135,58 @@ pub mod state {
         #[serde(rename = "natural-language-understanding")]
         pub nlu: Nlu,
         pub window: Window,
+        pub alarms: Alarms,
+        pub calendar: Calendar,
+    }
+
+    #[derive(Debug, Default, Deserialize)]
+    pub struct Calendar {
+        /// iCalendar files, or directories of them, that Kara reads but does not change
+        pub paths: Vec<PathBuf>,
+        pub caldav: Option<CalDav>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct CalDav {
+        /// The server, an account's principal or a single calendar
+        pub url: String,
+        pub username: Option<String>,
+        pub password: Option<String>,
+        /// The name of the calendar Kara's own events are synced with, the first one found
+        /// if unset
+        pub calendar: Option<String>,
+        /// How long Kara waits between syncs
+        pub interval: Duration,
+    }
+
+    impl std::fmt::Debug for CalDav {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("CalDav")
+                .field("url", &self.url)
+                .field("username", &self.username)
+                .field("password", &self.password.as_ref().map(|_| "<hidden>"))
+                .field("calendar", &self.calendar)
+                .field("interval", &self.interval)
+                .finish()
+        }
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct Alarms {
+        /// How long a snoozed alarm waits before ringing again
+        pub snooze: Duration,
+        /// Alarms missed while Kara was not running ring at startup if they were due
+        /// within this long
+        pub missed_within: Duration,
+    }
+
+    impl Default for Alarms {
+        fn default() -> Self {
+            Self {
+                snooze: scheduler::DEFAULT_SNOOZE,
+                missed_within: scheduler::DEFAULT_MISSED_WITHIN,
+            }
+        }
     }
 
     #[derive(Debug, Deserialize)]
@@ -98,16 +220,104 @@ pub mod state {
         #[serde(rename = "log-level")]
         pub log_level: DebugMode,
         pub units: Units,
+        /// The language Kara starts in
+        pub language: Language,
     }
 
     #[derive(Debug, Deserialize)]
     pub struct Nlu {
         pub stt: SpeechToText,
+        /// Outputs that should have punctuation and casing restored
+        pub punctuation: Outputs,
+        pub speakers: Speakers,
+        pub intents: Intents,
+        /// A directory of language models named by language code
+        pub model_path: Option<PathBuf>,
+    }
+
+    impl Nlu {
+        pub fn model_settings(&self) -> ModelSettings {
+            ModelSettings {
+                path: self.model_path.clone(),
+                options: self.intents.parse_options(),
+            }
+        }
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct Intents {
+        /// Intents recognised with a lower confidence are treated as not understood
+        pub threshold: f32,
+        /// Thresholds for individual intents
+        pub thresholds: HashMap<String, f32>,
+        /// Intents recognised with a lower confidence are confirmed before they are acted on
+        pub confirm_threshold: f32,
+        /// Confirmation thresholds for individual intents
+        pub confirm_thresholds: HashMap<String, f32>,
+        /// Number of alternative intents to keep
+        pub alternatives: usize,
+        /// How long follow ups can refer to an earlier command
+        pub context_window: Duration,
+        /// Look up commands that were not understood on the web
+        pub web_fallback: bool,
+    }
+
+    impl Default for Intents {
+        fn default() -> Self {
+            Self {
+                threshold: 0.0,
+                thresholds: HashMap::new(),
+                confirm_threshold: 0.0,
+                confirm_thresholds: HashMap::new(),
+                alternatives: 0,
+                context_window: context::DEFAULT_WINDOW,
+                web_fallback: false,
+            }
+        }
+    }
+
+    impl Intents {
+        pub fn parse_options(&self) -> ParseOptions {
+            self.thresholds.iter().fold(
+                ParseOptions::default()
+                    .with_threshold(self.threshold)
+                    .with_alternatives(self.alternatives, 0),
+                |options, (intent, threshold)| options.with_intent_threshold(intent, *threshold),
+            )
+        }
+
+        pub fn confirmation(&self) -> Confirmation {
+            self.confirm_thresholds.iter().fold(
+                Confirmation::new(self.confirm_threshold),
+                |confirmation, (intent, threshold)| {
+                    confirmation.with_intent_threshold(intent, *threshold)
+                },
+            )
+        }
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct Speakers {
+        pub enabled: bool,
+        /// Lowest similarity at which a command is attributed to an enrolled speaker
+        pub threshold: f32,
+    }
+
+    impl Default for Speakers {
+        fn default() -> Self {
+            Self {
+                enabled: false,
+                threshold: 0.85,
+            }
+        }
     }
 
-    #[derive(Default, Debug, Deserialize)]
+    #[derive(Debug, Deserialize)]
     pub struct SpeechToText {
         pub source: STTConfig,
+        /// Verbosity of the logs produced by the speech to text engine's native libraries
+        #[serde(rename = "log-level")]
+        pub log_level: DebugMode,
     }
 
     impl From<ConfigFile> for ParsedConfig {
@@ -149,24 +359,121 @@ pub mod state {
             };
 
             let log_level = match &conf.general_settings {
-                Some(val) => {
-                    let log_level = match &val.log_level {
-                        Some(level) => {
-                            let level = level.trim().to_lowercase();
-                            match level.as_str() {
-                                "trace" => DebugMode::Trace,
-                                "debug" => DebugMode::Debug,
-                                "info" => DebugMode::Info,
-                                "warn" => DebugMode::Warn,
-                                "error" => DebugMode::Error,
-                                _ => DebugMode::Warn,
-                            }
+                Some(val) => parse_log_level(&val.log_level),
+                None => DebugMode::Warn,
+            };
+
+            let stt_log_level = match &conf.nlu {
+                Some(NluFile {
+                    stt:
+                        Some(SpeechToTextFile {
+                            kara_config: Some(kara),
+                            ..
+                        }),
+                    ..
+                }) => parse_log_level(&kara.log_level),
+                _ => DebugMode::Warn,
+            };
+
+            let punctuation = match conf.nlu.as_ref().and_then(|nlu| nlu.punctuation.as_ref()) {
+                Some(punctuation) => {
+                    let defaults = Outputs::default();
+                    Outputs {
+                        gui: punctuation.gui.unwrap_or(defaults.gui),
+                        history: punctuation.history.unwrap_or(defaults.history),
+                        dictation: punctuation.dictation.unwrap_or(defaults.dictation),
+                    }
+                }
+                None => Outputs::default(),
+            };
+
+            let speakers = match conf.nlu.as_ref().and_then(|nlu| nlu.speakers.as_ref()) {
+                Some(speakers) => {
+                    let defaults = Speakers::default();
+                    let threshold = match speakers.threshold {
+                        Some(threshold) if (0.0..=1.0).contains(&threshold) => threshold,
+                        Some(_) => {
+                            eprintln!("error reading speaker identification config: threshold must be in the range 0.0 <= val <= 1.0");
+                            defaults.threshold
                         }
-                        None => DebugMode::Warn,
+                        None => defaults.threshold,
                     };
-                    log_level
+                    Speakers {
+                        enabled: speakers.enabled.unwrap_or(defaults.enabled),
+                        threshold,
+                    }
                 }
-                None => DebugMode::Warn,
+                None => Speakers::default(),
+            };
+
+            let intents = match &conf.nlu {
+                Some(nlu) => {
+                    let in_range = |threshold: &f32| {
+                        let valid = (0.0..=1.0).contains(threshold);
+                        if !valid {
+                            eprintln!("error reading intent thresholds: thresholds must be in the range 0.0 <= val <= 1.0");
+                        }
+                        valid
+                    };
+                    let per_intent = |thresholds: &Option<HashMap<String, f32>>| {
+                        thresholds
+                            .iter()
+                            .flatten()
+                            .filter(|(_, threshold)| in_range(threshold))
+                            .map(|(intent, threshold)| (intent.clone(), *threshold))
+                            .collect()
+                    };
+                    Intents {
+                        threshold: nlu.threshold.filter(in_range).unwrap_or_default(),
+                        thresholds: per_intent(&nlu.thresholds),
+                        confirm_threshold: nlu
+                            .confirm_threshold
+                            .filter(in_range)
+                            .unwrap_or_default(),
+                        confirm_thresholds: per_intent(&nlu.confirm_thresholds),
+                        alternatives: nlu.alternatives.unwrap_or_default(),
+                        context_window: nlu
+                            .context_window
+                            .map_or(context::DEFAULT_WINDOW, Duration::from_secs),
+                        web_fallback: nlu.web_fallback.unwrap_or_default(),
+                    }
+                }
+                None => Intents::default(),
+            };
+
+            let language = match &conf.general_settings {
+                Some(GeneralSettingsFile {
+                    language: Some(language),
+                    ..
+                }) => match language.trim().to_lowercase().as_str() {
+                    "auto" => Language::from_locale().unwrap_or_default(),
+                    code => Language::from_code(code).unwrap_or_else(|| {
+                        eprintln!("error reading language config: acceptable values are auto, en, fr, de and es");
+                        Language::default()
+                    }),
+                },
+                _ => Language::default(),
+            };
+
+            let model_path = conf
+                .nlu
+                .as_ref()
+                .and_then(|nlu| nlu.model_path.as_deref())
+                .map(str::trim)
+                .filter(|path| !path.is_empty())
+                .map(expand_home);
+
+            // Only the English language model is bundled with Kara
+            let language = if language == Language::English
+                || locale::has_nlu_model(language, model_path.as_deref())
+            {
+                language
+            } else {
+                eprintln!(
+                    "there is no {} language model, speaking English instead",
+                    language.name()
+                );
+                Language::English
             };
 
             let nlu = match &conf.nlu {
@@ -175,30 +482,13 @@ pub mod state {
                         let source = match &stt.source {
                             Some(source) => match source.trim().to_lowercase().as_str() {
                                 "kara" => {
-                                    let model_path: String = match &stt.kara_config {
-                                        Some(paths) => {
-                                            let mp = paths.model_path.as_ref();
-                                            match mp {
-                                                Some(mp) => match mp.is_empty() {
-                                                    true => default_stt_model_path(),
-                                                    false => mp.to_string(),
-                                                },
-                                                None => default_stt_model_path(),
-                                            }
-                                        }
-                                        None => default_stt_model_path(),
-                                    };
-                                    match &stt.kara_config {
-                                        Some(k_conf) => STTConfig::Kara(
-                                            k_conf
-                                                .model_path
-                                                .as_ref()
-                                                .unwrap_or(&model_path)
-                                                .to_string(),
-                                        ),
-
-                                        None => STTConfig::Kara(model_path),
-                                    }
+                                    let model_path = stt
+                                        .kara_config
+                                        .as_ref()
+                                        .and_then(|paths| paths.model_path.as_ref())
+                                        .filter(|mp| !mp.is_empty())
+                                        .cloned();
+                                    locale::speech_model(language, model_path)
                                 }
                                 "watson" => {
                                     todo!()
@@ -207,13 +497,13 @@ pub mod state {
                                     todo!()
                                 }
                             },
-                            None => STTConfig::default(),
+                            None => locale::speech_model(language, None),
                         };
                         source
                     }
-                    None => STTConfig::default(),
+                    None => locale::speech_model(language, None),
                 },
-                None => STTConfig::default(),
+                None => locale::speech_model(language, None),
             };
             let window = match &conf.window {
                 Some(win) => {
@@ -233,17 +523,130 @@ pub mod state {
                 }
                 None => Window::default(),
             };
+            let alarms = match &conf.alarms {
+                Some(alarms) => {
+                    let defaults = Alarms::default();
+                    let snooze = match alarms.snooze {
+                        Some(0) => {
+                            eprintln!(
+                                "error reading alarms config: snooze must be at least 1 minute"
+                            );
+                            defaults.snooze
+                        }
+                        Some(minutes) => Duration::from_secs(minutes * 60),
+                        None => defaults.snooze,
+                    };
+                    Alarms {
+                        snooze,
+                        missed_within: alarms
+                            .missed_within
+                            .map_or(defaults.missed_within, |minutes| {
+                                Duration::from_secs(minutes * 60)
+                            }),
+                    }
+                }
+                None => Alarms::default(),
+            };
+            let caldav = conf
+                .calendar
+                .as_ref()
+                .and_then(|calendar| calendar.caldav.as_ref())
+                .and_then(|caldav| {
+                    let url = caldav.url.as_deref().map(str::trim).unwrap_or_default();
+                    if !url.starts_with("http://") && !url.starts_with("https://") {
+                        eprintln!("error reading caldav config: url must be an http or https url, calendars will not be synced");
+                        return None;
+                    }
+                    let interval = match caldav.interval {
+                        Some(0) => {
+                            eprintln!(
+                                "error reading caldav config: interval must be at least 1 minute"
+                            );
+                            calendar::DEFAULT_SYNC_INTERVAL
+                        }
+                        Some(minutes) => Duration::from_secs(minutes * 60),
+                        None => calendar::DEFAULT_SYNC_INTERVAL,
+                    };
+                    Some(CalDav {
+                        url: url.to_owned(),
+                        username: caldav.username.clone(),
+                        password: caldav.password.clone(),
+                        calendar: caldav
+                            .calendar
+                            .as_deref()
+                            .map(str::trim)
+                            .filter(|name| !name.is_empty())
+                            .map(str::to_owned),
+                        interval,
+                    })
+                });
+            let calendar = Calendar {
+                paths: conf
+                    .calendar
+                    .as_ref()
+                    .and_then(|calendar| calendar.paths.as_ref())
+                    .into_iter()
+                    .flatten()
+                    .map(|path| path.trim())
+                    .filter(|path| !path.is_empty())
+                    .map(expand_home)
+                    .collect(),
+                caldav,
+            };
             Self {
                 general_settings: GeneralSettings {
                     startup_mode: ui,
                     log_level,
                     units,
+                    language,
                 },
                 nlu: Nlu {
-                    stt: SpeechToText { source: nlu },
+                    stt: SpeechToText {
+                        source: nlu,
+                        log_level: stt_log_level,
+                    },
+                    punctuation,
+                    speakers,
+                    intents,
+                    model_path,
                 },
                 window,
+                alarms,
+                calendar,
+            }
+        }
+    }
+
+    /// `path`, with a leading `~/` replaced by the home directory. Without a home directory
+    /// the path is taken as written
+    fn expand_home(path: &str) -> PathBuf {
+        let relative = match path.strip_prefix("~/") {
+            Some(relative) => relative,
+            None => return PathBuf::from(path),
+        };
+        match dirs::home_dir() {
+            Some(home) => home.join(relative),
+            None => {
+                eprintln!("error reading model path config: could not find the home directory to expand {path}");
+                PathBuf::from(path)
+            }
+        }
+    }
+
+    fn parse_log_level(level: &Option<String>) -> DebugMode {
+        match level {
+            Some(level) => {
+                let level = level.trim().to_lowercase();
+                match level.as_str() {
+                    "trace" => DebugMode::Trace,
+                    "debug" => DebugMode::Debug,
+                    "info" => DebugMode::Info,
+                    "warn" => DebugMode::Warn,
+                    "error" => DebugMode::Error,
+                    _ => DebugMode::Warn,
+                }
             }
+            None => DebugMode::Warn,
         }
     }
 }
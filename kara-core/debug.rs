@@ -1,23 +1,23 @@
 use std::path::{Path, PathBuf};
 
 use kara_audio::crossbeam_channel;
-use tracing::{info, trace};
+use tracing::{error, info, trace, warn};
 use tracing_subscriber::{
     filter, prelude::__tracing_subscriber_SubscriberExt, util::SubscriberInitExt,
 };
 
-use crate::config::{state::ParsedConfig, ConfigFile};
+use crate::{
+    cli::{Args, Command},
+    config::{state::ParsedConfig, ConfigFile},
+    locale,
+};
 
 pub fn initialise() -> (
     tracing_appender::non_blocking::WorkerGuard,
     ParsedConfig,
     crossbeam_channel::Receiver<kara_nlu::NLUParser>,
+    Option<Command>,
 ) {
-    let (tx, rx) = crossbeam_channel::bounded(1);
-    tokio::spawn(async move {
-        let engine = kara_nlu::NLUParser::new("kara-assets/nlu");
-        tx.send(engine).unwrap();
-    });
     let args = crate::cli::initialise();
     let config: ConfigFile = if let Some(file) = args.config_path() {
         match std::fs::read_to_string(file) {
@@ -39,8 +39,10 @@ pub fn initialise() -> (
     };
     let mut config: ParsedConfig = ParsedConfig::from(config);
     config.general_settings.startup_mode = args.interface(config.general_settings.startup_mode);
-    let filter =
-        filter::Targets::new().with_target("kara", args.debug(config.general_settings.log_level));
+    let native_log_level = Args::map_log_level(config.nlu.stt.log_level);
+    let filter = filter::Targets::new()
+        .with_target("kara", args.debug(config.general_settings.log_level))
+        .with_target("kara_audio::vosk", native_log_level);
     let file_appender = tracing_appender::rolling::daily(log_dir(), "kara.log");
     let (non_blocking, guard) = tracing_appender::non_blocking(file_appender);
     tracing_subscriber::registry()
@@ -54,6 +56,20 @@ pub fn initialise() -> (
         )
         .with(filter)
         .init();
+    if let Err(e) = kara_audio::native_logs::capture(native_log_level) {
+        warn!("could not capture native library logs: {e}");
+    }
+    // Loading the model blocks, and its errors are logged, so it starts once logging is set up
+    let (tx, rx) = crossbeam_channel::bounded(1);
+    let language = config.general_settings.language;
+    let model_settings = config.nlu.model_settings();
+    tokio::task::spawn_blocking(move || {
+        match locale::language_model(language, &model_settings) {
+            Ok(engine) => tx.send(engine).unwrap(),
+            // Dropping the sender tells the interface that there is no model
+            Err(e) => error!("{}", e),
+        }
+    });
     trace!(
         "starting in {} mode",
         args.interface(config.general_settings.startup_mode)
@@ -64,7 +80,7 @@ pub fn initialise() -> (
         env!("CARGO_BIN_NAME"),
         env!("CARGO_PKG_VERSION")
     );
-    (guard, config, rx)
+    (guard, config, rx, args.into_command())
 }
 
 fn config_path_1() -> ConfigFile {
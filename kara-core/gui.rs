@@ -21,15 +21,34 @@ use iced_winit::{
     winit::{
         dpi::PhysicalPosition,
         event::{Event, ModifiersState, WindowEvent},
-        event_loop::{ControlFlow, EventLoop},
+        event_loop::{ControlFlow, EventLoop, EventLoopProxy},
     },
     Clipboard, Debug, Size,
 };
-use kara_audio::{crossbeam_channel, stt_sources::stt_source, Config};
-use tokio::runtime::Handle;
-use tracing::{error, trace};
-
-use crate::config::state::ParsedConfig;
+use kara_audio::{
+    chime::Chime,
+    crossbeam_channel,
+    progress::ProgressReporter,
+    speaker::{ProfileStore, SpeakerIdentifier},
+    stt_sources::{stt_source, STTConfig, STTSource},
+    Config,
+};
+use kara_events::{KaraEvents, Progress, ProgressPhase, ProgressTask};
+use kara_nlu::{
+    normaliser::Normaliser,
+    punctuation::{Output, Restorer},
+    Language,
+};
+use tokio::{runtime::Handle, sync::watch};
+use tracing::{debug, error, info, trace, warn};
+
+use crate::{
+    config::state::ParsedConfig,
+    history::{Dictation, History},
+    locale::{self, DictationControl, ModelSettings, Response},
+    reload::ReloadSignal,
+    skills::{Context, Skills},
+};
 
 use self::{controls::Controls, scene::Scene};
 
@@ -42,12 +61,29 @@ pub async fn start(
     config: &ParsedConfig,
     rx_nlu_model: crossbeam_channel::Receiver<kara_nlu::NLUParser>,
 ) -> anyhow::Result<()> {
-    let stt_source = stt_source(&config.nlu.stt.source).await?;
     let handle = Handle::current();
+    let runtime = handle.clone();
     // Create EventLoop with 'String' user events
     let event_loop = EventLoop::with_user_event();
     let proxy = event_loop.create_proxy(); // Sends the user events which we can retrieve in the loop
                                            /* TODO: Create an enum for events?*/
+    // Prepare the speech to text source in the background so that download and loading
+    // progress can be shown in the window
+    let (tx_stt, stt_source_rx) = watch::channel(None);
+    let tx_stt = Arc::new(tx_stt);
+    let inner_tx_stt = Arc::clone(&tx_stt);
+    let stt_config = config.nlu.stt.source.clone();
+    let reporter = ProgressReporter::Gui(proxy.clone());
+    tokio::spawn(async move {
+        match stt_source(&stt_config, reporter).await {
+            Ok(source) => {
+                if inner_tx_stt.send(Some(source)).is_err() {
+                    error!("audio stream stopped before the speech to text source was ready");
+                }
+            }
+            Err(e) => error!("{e}"),
+        }
+    });
     // Keep an event that's activated when a wake word has been detected so that transcription may
     // begin. When the request has been processed, reset the flag
     let is_processing = Arc::new(AtomicBool::new(false));
@@ -55,10 +91,25 @@ pub async fn start(
     let is_ready = Arc::new(AtomicBool::new(false));
     // set this to true when wake word has been detected
     let wake_up = Arc::new(AtomicBool::new(false));
+    let speakers = if config.nlu.speakers.enabled {
+        match SpeakerIdentifier::new(&ProfileStore::default(), config.nlu.speakers.threshold) {
+            Ok(speakers) => Some(speakers),
+            Err(e) => {
+                warn!(
+                    "could not load speaker profiles, every speaker is unknown: {}",
+                    e
+                );
+                None
+            }
+        }
+    } else {
+        None
+    };
     let stream = kara_audio::start_stream(
         Config::default(),
         proxy.clone(),
-        stt_source,
+        stt_source_rx,
+        speakers,
         Arc::clone(&is_processing),
         Arc::clone(&wake_up),
         Arc::clone(&is_ready),
@@ -158,15 +209,84 @@ pub async fn start(
     let inner_is_ready = Arc::clone(&is_ready);
     let model = Arc::new(Mutex::new(Model::Initialising));
     let inner_model = Arc::clone(&model);
+    let inner_proxy = proxy.clone();
+    let alarm_proxy = proxy.clone();
+    let skills = Skills::configured(config, move |ringing| {
+        if let Err(e) = alarm_proxy.send_event(KaraEvents::AlarmRinging(ringing)) {
+            error!("{}", e);
+        }
+    });
+    let scheduler = skills.scheduler;
+    let inner_dispatcher = skills.dispatcher;
+    let dialogue = Arc::new(skills.dialogue);
     std::thread::spawn(move || {
-        let nlu_model = rx_nlu_model.recv().unwrap();
+        let send_progress = |phase| {
+            if let Err(e) = inner_proxy.send_event(KaraEvents::Progress(Progress::new(phase))) {
+                error!("{}", e);
+            }
+        };
+        send_progress(ProgressPhase::LoadLanguageModel);
+        let nlu_model = match rx_nlu_model.recv() {
+            Ok(nlu_model) => nlu_model,
+            Err(_) => {
+                error!("no language model could be loaded, commands will not be understood");
+                send_progress(ProgressPhase::Finished(ProgressTask::LanguageModel));
+                return;
+            }
+        };
+        let unhandled = inner_dispatcher.unhandled(nlu_model.intents());
+        if !unhandled.is_empty() {
+            debug!(
+                ?unhandled,
+                "no skill handles some intents of the language model"
+            );
+        }
         inner_is_ready.store(true, Ordering::Relaxed);
         let mut model_mut = inner_model.lock().unwrap();
         *model_mut = Model::Ready(nlu_model);
+        send_progress(ProgressPhase::Finished(ProgressTask::LanguageModel));
     });
 
     let inner_model = Arc::clone(&model);
     let inner_is_ready = Arc::clone(&is_ready);
+    let configured_language = config.general_settings.language;
+    let configured_stt = config.nlu.stt.source.clone();
+    let punctuation = config.nlu.punctuation;
+    let loader = ModelLoader {
+        runtime: runtime.clone(),
+        tx_stt: Arc::clone(&tx_stt),
+        model: Arc::clone(&model),
+        is_ready: Arc::clone(&is_ready),
+        proxy: proxy.clone(),
+        settings: config.nlu.model_settings(),
+    };
+    let reload_proxy = proxy.clone();
+    tokio::spawn(async move {
+        let mut reload = ReloadSignal::new();
+        loop {
+            reload.recv().await;
+            if reload_proxy
+                .send_event(KaraEvents::ReloadLanguageModel)
+                .is_err()
+            {
+                // The event loop has closed
+                break;
+            }
+        }
+    });
+    let mut language = configured_language;
+    let mut normaliser = Normaliser::new(language);
+    let mut restorer = Restorer::new(language).with_outputs(punctuation);
+    let history = History::configured()
+        .map_err(|e| error!("transcripts will not be recorded: {}", e))
+        .ok()
+        .map(Arc::new);
+    let dictation = Dictation::configured()
+        .map_err(|e| error!("dictation is unavailable: {}", e))
+        .ok()
+        .map(Arc::new);
+    let mut dictating = false;
+    let mut chime: Option<Chime> = None;
 
     // Run event_loop
     event_loop.run(move |event, _, control_flow| {
@@ -355,54 +475,303 @@ pub async fn start(
                 kara_events::KaraEvents::SpeechFeed(feed) => {
                     state.queue_message(controls::Message::TextChanged(feed));
                 }
-                kara_events::KaraEvents::ProcessCommand(transcription) => {
+                kara_events::KaraEvents::ProcessCommand(utterance) => {
                     // Set to busy
                     if let Err(e) = proxy.send_event(kara_events::KaraEvents::IsBusy(true)) {
                         error!("{}", e);
                     }
+                    // Skills respond in the background and finish the command themselves
+                    let mut dispatched = false;
                     if let Model::Ready(val) = &*inner_model.lock().unwrap() {
                         // arg is the final transcription result, do nlp/intent classification
                         // When this is done, start listening for wake word again
-                        state.queue_message(controls::Message::TextChanged(transcription.clone()));
-                        val.parse_text(transcription)
-                    }
-                    // process command here;
-                    //
-                    // No longer busy and ready for more commands
-                    if let Err(e) = proxy.send_event(kara_events::KaraEvents::IsBusy(false)) {
-                        error!("{}", e);
+                        let text = normaliser.normalise(&utterance.text);
+                        trace!(raw = %text.raw, normalised = %text.normalised, speaker = utterance.speaker.id(), "normalised transcript");
+                        if let Some(reply) = scheduler
+                            .as_ref()
+                            .and_then(|scheduler| scheduler.control(language, &text.normalised))
+                        {
+                            state.queue_message(controls::Message::TextChanged(reply));
+                            if let Err(e) = proxy.send_event(KaraEvents::AlarmStopped) {
+                                error!("{}", e);
+                            }
+                        } else if dictating {
+                            if locale::dictation_control(language, &text.normalised)
+                                == Some(DictationControl::Stop)
+                            {
+                                dictating = false;
+                                state.queue_message(controls::Message::TextChanged(
+                                    locale::response(language, Response::DictationSaved),
+                                ));
+                            } else {
+                                let dictated =
+                                    restorer.restore_for(Output::Dictation, &text.normalised);
+                                state.queue_message(controls::Message::TextChanged(dictated.clone()));
+                                if let Some(dictation) = &dictation {
+                                    let dictation = Arc::clone(dictation);
+                                    runtime.spawn_blocking(move || {
+                                        if let Err(e) = dictation.write(&dictated) {
+                                            error!("could not write dictation: {}", e);
+                                        }
+                                    });
+                                }
+                                // What is said next is dictated too, without the wake phrase
+                                finish_command(&proxy, true);
+                                dispatched = true;
+                            }
+                        } else if let Some(requested) = locale::language_switch(language, &text.normalised) {
+                            let reply = if requested == language {
+                                locale::response(language, Response::AlreadySpeaking)
+                            } else if !locale::has_nlu_model(requested, loader.settings.path.as_deref()) {
+                                locale::response(language, Response::Unavailable(requested))
+                            } else {
+                                let stt_config = if requested == configured_language {
+                                    configured_stt.clone()
+                                } else {
+                                    locale::speech_model(requested, None)
+                                };
+                                inner_is_ready.store(false, Ordering::Relaxed);
+                                loader.switch_language(language, requested, stt_config);
+                                locale::response(requested, Response::Switching)
+                            };
+                            state.queue_message(controls::Message::TextChanged(reply));
+                        } else if locale::dictation_control(language, &text.normalised)
+                            == Some(DictationControl::Start)
+                        {
+                            dictating = dictation.is_some();
+                            let reply = if dictating {
+                                Response::Dictating
+                            } else {
+                                Response::Failed
+                            };
+                            state.queue_message(controls::Message::TextChanged(
+                                locale::response(language, reply),
+                            ));
+                            if dictating {
+                                finish_command(&proxy, true);
+                                dispatched = true;
+                            }
+                        } else {
+                            state.queue_message(controls::Message::TextChanged(
+                                restorer.restore_for(Output::Gui, &text.normalised),
+                            ));
+                            if let Some(history) = &history {
+                                let history = Arc::clone(history);
+                                let speaker = utterance.speaker.clone();
+                                let transcript =
+                                    restorer.restore_for(Output::History, &text.normalised);
+                                runtime.spawn_blocking(move || {
+                                    if let Err(e) = history.record(&speaker, &transcript) {
+                                        error!("could not record transcript: {}", e);
+                                    }
+                                });
+                            }
+                            match val.parse_in_context(&text.normalised, &dialogue.conversation()) {
+                                Ok(commands) => {
+                                    for parsed in &commands {
+                                        debug!(
+                                            intent = ?parsed.intent.intent_name,
+                                            confidence = parsed.intent.confidence_score,
+                                            "parsed command"
+                                        );
+                                    }
+                                    let context = Context {
+                                        speaker: utterance.speaker.clone(),
+                                        language,
+                                        ..Context::default()
+                                    };
+                                    let dialogue = Arc::clone(&dialogue);
+                                    let proxy = proxy.clone();
+                                    let text = text.normalised.clone();
+                                    runtime.spawn(async move {
+                                        let turn = dialogue.respond(&text, commands, context).await;
+                                        if let Err(e) = proxy
+                                            .send_event(KaraEvents::Response(turn.text().to_owned()))
+                                        {
+                                            error!("{}", e);
+                                        }
+                                        finish_command(&proxy, turn.expects_answer());
+                                    });
+                                    dispatched = true;
+                                }
+                                Err(e) => error!("{}", e),
+                            }
+                        }
                     }
-
-                    // Reset wake word so we listen for transcription again
-                    if let Err(e) = proxy.send_event(kara_events::KaraEvents::WakeUp(false)) {
-                        error!("{}", e);
+                    if !dispatched {
+                        finish_command(&proxy, false);
                     }
                 }
                 kara_events::KaraEvents::IsBusy(val) => {
                     inner_is_processing.store(val, Ordering::Relaxed)
                 }
+                kara_events::KaraEvents::Progress(progress) => {
+                    state.queue_message(controls::Message::Progress(progress));
+                }
+                kara_events::KaraEvents::Response(text) => {
+                    state.queue_message(controls::Message::TextChanged(text));
+                }
+                kara_events::KaraEvents::LanguageChanged(code) => match Language::from_code(&code) {
+                    Some(new_language) => {
+                        language = new_language;
+                        normaliser = Normaliser::new(language);
+                        restorer = Restorer::new(language).with_outputs(punctuation);
+                        trace!(language = language.code(), "switched language");
+                    }
+                    None => error!("unsupported language: {}", code),
+                },
+                kara_events::KaraEvents::ReloadLanguageModel => loader.reload(language),
+                kara_events::KaraEvents::AlarmRinging(ringing) => {
+                    state.queue_message(controls::Message::TextChanged(locale::response(
+                        language,
+                        Response::Ringing(ringing),
+                    )));
+                    // A chime that rang for too long has stopped by itself
+                    if !chime.as_ref().map_or(false, Chime::is_ringing) {
+                        chime = Chime::ring()
+                            .map_err(|e| error!("could not ring the alarm: {}", e))
+                            .ok();
+                    }
+                    // Listen for "stop" or "snooze" without the wake phrase
+                    inner_is_awake.store(true, Ordering::Relaxed);
+                }
+                kara_events::KaraEvents::AlarmStopped => chime = None,
             },
             _ => {}
         }
     });
 }
+
+/// Marks the current command as done. Kara listens for the wake phrase again unless
+/// `expects_answer`, when the next utterance is taken as a command straight away
+fn finish_command(proxy: &EventLoopProxy<KaraEvents>, expects_answer: bool) {
+    // No longer busy and ready for more commands
+    if let Err(e) = proxy.send_event(KaraEvents::IsBusy(false)) {
+        error!("{}", e);
+    }
+
+    // Reset wake word so we listen for transcription again
+    if let Err(e) = proxy.send_event(KaraEvents::WakeUp(expects_answer)) {
+        error!("{}", e);
+    }
+}
+
+/// What loading the models of another language needs, shared with the tasks that load them
+#[derive(Clone)]
+struct ModelLoader {
+    runtime: Handle,
+    tx_stt: Arc<watch::Sender<Option<STTSource>>>,
+    model: Arc<Mutex<Model>>,
+    is_ready: Arc<AtomicBool>,
+    proxy: EventLoopProxy<KaraEvents>,
+    settings: ModelSettings,
+}
+
+impl ModelLoader {
+    /// Loads the speech and language models for `language` in the background. The current
+    /// models stay in use until both are ready
+    fn switch_language(&self, current: Language, language: Language, stt_config: STTConfig) {
+        let loader = self.clone();
+        self.runtime.spawn(async move {
+            let reporter = ProgressReporter::Gui(loader.proxy.clone());
+            let stt = stt_source(&stt_config, reporter.clone()).await;
+            let loading = reporter.start(
+                ProgressPhase::LoadLanguageModel,
+                None,
+                "Loading language model",
+            );
+            let nlu = tokio::task::spawn_blocking(move || {
+                locale::language_model(language, &loader.settings)
+            })
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|nlu| nlu.map_err(anyhow::Error::from));
+            loading.finish("Language model loaded");
+            reporter.finish(ProgressTask::LanguageModel);
+            let loaded = match (stt, nlu) {
+                (Ok(source), Ok(nlu)) => {
+                    if loader.tx_stt.send(Some(source)).is_err() {
+                        error!("audio stream stopped before the speech to text source was ready");
+                    }
+                    *loader.model.lock().unwrap() = Model::Ready(nlu);
+                    if let Err(e) = loader
+                        .proxy
+                        .send_event(KaraEvents::LanguageChanged(language.code().to_owned()))
+                    {
+                        error!("{}", e);
+                    }
+                    true
+                }
+                (Err(e), _) => {
+                    error!("could not load the {} speech model: {}", language.name(), e);
+                    false
+                }
+                (_, Err(e)) => {
+                    error!(
+                        "could not load the {} language model: {}",
+                        language.name(),
+                        e
+                    );
+                    false
+                }
+            };
+            // Kara keeps speaking the current language
+            if !loaded {
+                let reply = locale::response(current, Response::Unavailable(language));
+                if let Err(e) = loader.proxy.send_event(KaraEvents::Response(reply)) {
+                    error!("{}", e);
+                }
+            }
+            loader.is_ready.store(true, Ordering::Relaxed);
+        });
+    }
+
+    /// Reads the language model for `language` again and swaps it in once it has loaded.
+    /// Commands keep using the current model in the meantime, and if loading fails
+    fn reload(&self, language: Language) {
+        let loader = self.clone();
+        self.runtime.spawn(async move {
+            info!(language = language.code(), "reloading language model");
+            match tokio::task::spawn_blocking(move || {
+                locale::language_model(language, &loader.settings)
+            })
+            .await
+            {
+                Ok(Ok(nlu)) => {
+                    *loader.model.lock().unwrap() = Model::Ready(nlu);
+                    // The model may not have loaded at startup
+                    loader.is_ready.store(true, Ordering::Relaxed);
+                    info!(language = language.code(), "reloaded language model");
+                }
+                Ok(Err(e)) => error!("{}, keeping the current language model", e),
+                Err(e) => error!("{}", e),
+            }
+        });
+    }
+}
+
 mod controls {
+    use std::time::Duration;
+
     use iced_wgpu::Renderer;
     use iced_winit::{
         alignment,
-        widget::{Column, Container, Text},
+        widget::{Column, Container, ProgressBar, Text},
         Color, Length, Program,
     };
+    use kara_events::{Progress, ProgressPhase};
 
     pub struct Controls {
         background_color: Color,
         text: String,
+        /// The latest progress of each task that is still in progress
+        progress: Vec<Progress>,
     }
 
-    #[allow(dead_code)]
     #[derive(Debug, Clone)]
     pub enum Message {
         TextChanged(String),
+        Progress(Progress),
     }
 
     impl Controls {
@@ -415,6 +784,7 @@ mod controls {
                     a: opacity,
                 },
                 text: String::from("Getting ready, please wait..."),
+                progress: Vec::new(),
             }
         }
 
@@ -431,28 +801,86 @@ mod controls {
         fn update(&mut self, message: Self::Message) -> iced_winit::Command<Self::Message> {
             match message {
                 Message::TextChanged(val) => self.text = val,
+                Message::Progress(progress) => {
+                    let task = progress.phase.task();
+                    self.progress.retain(|shown| shown.phase.task() != task);
+                    if !matches!(progress.phase, ProgressPhase::Finished(_)) {
+                        self.progress.push(progress);
+                        self.progress.sort_by_key(|shown| shown.phase.task());
+                    }
+                }
             }
             iced_winit::Command::none()
         }
 
         fn view(&mut self) -> iced_winit::Element<'_, Self::Message, Self::Renderer> {
-            Container::new(
-                Column::new()
-                    .align_items(iced_winit::Alignment::Center)
-                    .spacing(20)
-                    .padding(10)
-                    .push(
-                        Text::new(&self.text)
-                            .style(Color::new(0.949_019_6, 0.898_039_2, 0.737_254_9, 1.0))
-                            .size(28),
-                    ),
-            )
-            .padding(100)
-            .width(Length::Fill)
-            .height(Length::Fill)
-            .align_y(alignment::Vertical::Bottom)
-            .center_x()
-            .into()
+            let text_color = Color::new(0.949_019_6, 0.898_039_2, 0.737_254_9, 1.0);
+            let mut column = Column::new()
+                .align_items(iced_winit::Alignment::Center)
+                .spacing(20)
+                .padding(10);
+            if self.progress.is_empty() {
+                column = column.push(Text::new(&self.text).style(text_color).size(28));
+            }
+            for progress in &self.progress {
+                column = column.push(
+                    Text::new(progress_label(progress))
+                        .style(text_color)
+                        .size(20),
+                );
+                if let Some(fraction) = progress.fraction() {
+                    column = column.push(
+                        ProgressBar::new(0.0..=1.0, fraction)
+                            .width(Length::Units(480))
+                            .height(Length::Units(8)),
+                    );
+                }
+            }
+            Container::new(column)
+                .padding(100)
+                .width(Length::Fill)
+                .height(Length::Fill)
+                .align_y(alignment::Vertical::Bottom)
+                .center_x()
+                .into()
+        }
+    }
+
+    fn progress_label(progress: &Progress) -> String {
+        let mut label = progress.phase.to_string();
+        if let Some(total) = progress.total {
+            label.push_str(&format!(
+                " ({} / {})",
+                human_bytes(progress.bytes),
+                human_bytes(total)
+            ));
+        }
+        if let Some(eta) = progress.eta.filter(|eta| !eta.is_zero()) {
+            label.push_str(&format!(", {} left", human_duration(eta)));
+        }
+        label
+    }
+
+    fn human_bytes(bytes: u64) -> String {
+        const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+        let mut value = bytes as f64;
+        let mut unit = 0;
+        while value >= 1024.0 && unit < UNITS.len() - 1 {
+            value /= 1024.0;
+            unit += 1;
+        }
+        match unit {
+            0 => format!("{bytes} {}", UNITS[unit]),
+            _ => format!("{value:.1} {}", UNITS[unit]),
+        }
+    }
+
+    fn human_duration(duration: Duration) -> String {
+        let secs = duration.as_secs();
+        match secs {
+            0..=59 => format!("{secs}s"),
+            60..=3599 => format!("{}m {}s", secs / 60, secs % 60),
+            _ => format!("{}h {}m", secs / 3600, (secs % 3600) / 60),
         }
     }
 }
@@ -0,0 +1,71 @@
+//! Files of what Kara heard: the history of final transcripts and the text dictated to her.
+//! Both are appended to, so they are written from a blocking task rather than the thread
+//! that received the transcript.
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use kara_events::Speaker;
+
+/// A record of the final transcripts Kara has received
+pub struct History {
+    path: PathBuf,
+}
+
+impl History {
+    /// The history in Kara's data directory
+    pub fn configured() -> io::Result<Self> {
+        Ok(Self {
+            path: data_file("history.tsv")?,
+        })
+    }
+
+    /// Appends `transcript` to the history file with the current time and its speaker
+    pub fn record(&self, speaker: &Speaker, transcript: &str) -> io::Result<()> {
+        append(
+            &self.path,
+            &format!(
+                "{}\t{}\t{}",
+                chrono::Local::now().to_rfc3339(),
+                speaker.id(),
+                transcript
+            ),
+        )
+    }
+}
+
+/// Text dictated to Kara, one utterance a line
+pub struct Dictation {
+    path: PathBuf,
+}
+
+impl Dictation {
+    /// The dictation file in Kara's data directory
+    pub fn configured() -> io::Result<Self> {
+        Ok(Self {
+            path: data_file("dictation.txt")?,
+        })
+    }
+
+    pub fn write(&self, text: &str) -> io::Result<()> {
+        append(&self.path, text)
+    }
+}
+
+fn data_file(name: &str) -> io::Result<PathBuf> {
+    let mut path = dirs::data_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "could not find data dir"))?;
+    path.push("kara");
+    path.push(name);
+    Ok(path)
+}
+
+fn append(path: &Path, line: &str) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{line}")
+}
@@ -0,0 +1,56 @@
+//! Lists as Markdown task lists or as JSON, for `kara lists export`.
+//!
+//! Markdown has a heading per list and a task per item, ticked if it is done:
+//!
+//! ```markdown
+//! # shopping
+//!
+//! - [ ] milk
+//! - [x] eggs
+//! ```
+//!
+//! JSON has the lists with all their items and when they were added and done:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "lists": [
+//!     { "name": "shopping", "created": "2022-08-01T09:00:00Z", "items": [
+//!       { "text": "milk", "added": "2022-08-01T09:00:00Z", "done": false },
+//!       { "text": "eggs", "added": "2022-08-01T09:01:00Z", "done": true,
+//!         "done-at": "2022-08-02T17:30:00Z" }
+//!     ] }
+//!   ]
+//! }
+//! ```
+use serde::Serialize;
+
+use super::{List, VERSION};
+
+pub fn markdown(lists: &[List]) -> String {
+    lists
+        .iter()
+        .map(|list| {
+            let mut text = format!("# {}\n\n", list.name);
+            for item in &list.items {
+                let tick = if item.done { 'x' } else { ' ' };
+                // Lines that are not part of the item would end the task
+                text.push_str(&format!("- [{tick}] {}\n", item.text.replace('\n', " ")));
+            }
+            text
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn json(lists: &[List]) -> serde_json::Result<String> {
+    #[derive(Serialize)]
+    struct Export<'a> {
+        version: u32,
+        lists: &'a [List],
+    }
+    serde_json::to_string_pretty(&Export {
+        version: VERSION,
+        lists,
+    })
+}
@@ -0,0 +1,580 @@
+//! Named lists, such as a shopping list or a todo list, kept between runs.
+//!
+//! Lists are stored in an SQLite database, `lists.sqlite3` in Kara's data directory
+//! (`$XDG_DATA_HOME/kara/lists.sqlite3` on Linux), so that the assistant and `kara lists`
+//! can use them at the same time. It has two tables:
+//!
+//! - `lists`: `id`, `owner`, `name` and `created`. `owner` is the enrolled speaker the list
+//!   belongs to, empty for lists everyone shares. Names are unique per owner, ignoring case
+//! - `items`: `id`, `list`, `text`, `added`, `done` and `done_at`. Items are kept in the
+//!   order they were added and are deleted with their list
+//!
+//! Times are in UTC. The version of the schema is kept in `PRAGMA user_version`.
+//!
+//! A speaker sees their own lists and the shared ones; their own list wins when both have
+//! the same name. Lists created by a speaker who was not recognised are shared.
+//!
+//! Items are looked up by what they are roughly called, see [`find`], so that "remove
+//! eggs" finds "Eggs" and "tick off the milk" finds "oat milk".
+pub mod export;
+
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use kara_events::Speaker;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use tracing::debug;
+
+const VERSION: u32 = 2;
+/// How long to wait for another process, e.g. `kara lists`, to finish writing
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+/// How alike an item must be to what was said to be taken for it, from 0 to 1, see
+/// [`similarity`]
+const MIN_SIMILARITY: f64 = 0.9;
+/// Plural endings, which are left out when words are compared
+const ENDINGS: [&str; 5] = ["es", "en", "s", "n", "e"];
+
+const SCHEMA: &str = "
+CREATE TABLE lists (
+    id INTEGER PRIMARY KEY,
+    owner TEXT NOT NULL DEFAULT '',
+    name TEXT NOT NULL COLLATE NOCASE,
+    created TEXT NOT NULL,
+    UNIQUE (owner, name)
+);
+CREATE TABLE items (
+    id INTEGER PRIMARY KEY,
+    list INTEGER NOT NULL REFERENCES lists (id) ON DELETE CASCADE,
+    text TEXT NOT NULL,
+    added TEXT NOT NULL,
+    done INTEGER NOT NULL DEFAULT 0,
+    done_at TEXT
+);
+CREATE INDEX items_by_list ON items (list);
+";
+
+/// Gives the lists of a version 1 database an owner. Run with foreign keys off, so that
+/// dropping the old table does not delete the items
+const MIGRATE_FROM_1: &str = "
+BEGIN;
+CREATE TABLE lists_v2 (
+    id INTEGER PRIMARY KEY,
+    owner TEXT NOT NULL DEFAULT '',
+    name TEXT NOT NULL COLLATE NOCASE,
+    created TEXT NOT NULL,
+    UNIQUE (owner, name)
+);
+INSERT INTO lists_v2 (id, name, created) SELECT id, name, created FROM lists;
+DROP TABLE lists;
+ALTER TABLE lists_v2 RENAME TO lists;
+PRAGMA user_version = 2;
+COMMIT;
+";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct List {
+    pub name: String,
+    pub created: DateTime<Utc>,
+    pub items: Vec<Item>,
+}
+
+impl List {
+    /// The items that are not done yet
+    pub fn open_items(&self) -> impl Iterator<Item = &Item> {
+        self.items.iter().filter(|item| !item.done)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Item {
+    #[serde(skip)]
+    id: i64,
+    pub text: String,
+    pub added: DateTime<Utc>,
+    pub done: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub done_at: Option<DateTime<Utc>>,
+}
+
+/// What adding an item did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Added {
+    Added {
+        /// The list did not exist and was created for the item
+        created_list: bool,
+    },
+    /// An item that is not done yet is already called that
+    AlreadyThere(Item),
+}
+
+pub struct ListStore {
+    connection: Arc<Mutex<Connection>>,
+    /// Whose lists are used, empty for the shared ones only
+    owner: String,
+}
+
+impl ListStore {
+    /// Opens the database at `path`, creating it if it does not exist
+    pub fn open(path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let path = path.into();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let connection = Connection::open(&path)?;
+        connection.busy_timeout(BUSY_TIMEOUT)?;
+        let version: u32 = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        match version {
+            0 => {
+                debug!(path = %path.display(), "creating list database");
+                connection.execute_batch(SCHEMA)?;
+                connection.pragma_update(None, "user_version", VERSION)?;
+            }
+            1 => {
+                debug!(path = %path.display(), "giving lists owners");
+                connection.pragma_update(None, "foreign_keys", false)?;
+                connection.execute_batch(MIGRATE_FROM_1)?;
+            }
+            VERSION => {}
+            version => {
+                return Err(anyhow!(
+                    "list database {} has unknown version {version}",
+                    path.display()
+                ))
+            }
+        }
+        connection.pragma_update(None, "foreign_keys", true)?;
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+            owner: String::new(),
+        })
+    }
+
+    /// The lists `speaker` sees: their own and the shared ones. Lists created through the
+    /// returned store belong to `speaker`, or are shared if they were not recognised
+    pub fn for_speaker(&self, speaker: &Speaker) -> Self {
+        Self {
+            connection: Arc::clone(&self.connection),
+            owner: match speaker {
+                Speaker::Known(id) => id.clone(),
+                Speaker::Unknown => String::new(),
+            },
+        }
+    }
+
+    /// Opens the database in Kara's data directory
+    pub fn configured() -> anyhow::Result<Self> {
+        Self::open(Self::default_path())
+    }
+
+    pub fn default_path() -> PathBuf {
+        let mut path = dirs::data_dir().expect("could not find data dir");
+        path.push("kara");
+        path.push("lists.sqlite3");
+        path
+    }
+
+    fn connection(&self) -> MutexGuard<'_, Connection> {
+        self.connection.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The names of the lists, in the order they were created
+    pub fn names(&self) -> anyhow::Result<Vec<String>> {
+        let connection = self.connection();
+        let mut statement = connection.prepare(
+            "SELECT name FROM lists AS list
+             WHERE owner = ?1 OR (owner = '' AND NOT EXISTS (
+                 SELECT 1 FROM lists AS own WHERE own.owner = ?1 AND own.name = list.name
+             ))
+             ORDER BY id",
+        )?;
+        let names = statement
+            .query_map([&self.owner], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        Ok(names)
+    }
+
+    /// Every list with its items
+    pub fn lists(&self) -> anyhow::Result<Vec<List>> {
+        self.names()?
+            .iter()
+            .filter_map(|name| self.list(name).transpose())
+            .collect()
+    }
+
+    /// The list called `name`, ignoring case
+    pub fn list(&self, name: &str) -> anyhow::Result<Option<List>> {
+        let connection = self.connection();
+        let (id, name, created) = match self.row(&connection, name)? {
+            Some(list) => list,
+            None => return Ok(None),
+        };
+        let mut statement = connection.prepare(
+            "SELECT id, text, added, done, done_at FROM items WHERE list = ? ORDER BY id",
+        )?;
+        let items = statement
+            .query_map([id], |row| {
+                Ok(Item {
+                    id: row.get(0)?,
+                    text: row.get(1)?,
+                    added: row.get(2)?,
+                    done: row.get(3)?,
+                    done_at: row.get(4)?,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+        Ok(Some(List {
+            name,
+            created,
+            items,
+        }))
+    }
+
+    /// Creates an empty list. Returns false if there already is one called `name`
+    pub fn create(&self, name: &str) -> anyhow::Result<bool> {
+        let connection = self.connection();
+        if self.row(&connection, name.trim())?.is_some() {
+            return Ok(false);
+        }
+        let created = connection.execute(
+            "INSERT OR IGNORE INTO lists (owner, name, created) VALUES (?, ?, ?)",
+            params![self.owner, name.trim(), Utc::now()],
+        )?;
+        Ok(created == 1)
+    }
+
+    /// Adds an item to the end of a list, creating the list if it does not exist
+    pub fn add(&self, list: &str, text: &str) -> anyhow::Result<Added> {
+        let created_list = self.create(list)?;
+        if let Some(existing) = self.list(list)?.and_then(|list| {
+            list.open_items()
+                .find(|item| same(&item.text, text))
+                .cloned()
+        }) {
+            return Ok(Added::AlreadyThere(existing));
+        }
+        let connection = self.connection();
+        if let Some((id, ..)) = self.row(&connection, list)? {
+            connection.execute(
+                "INSERT INTO items (list, text, added) VALUES (?, ?, ?)",
+                params![id, text.trim(), Utc::now()],
+            )?;
+        }
+        Ok(Added::Added { created_list })
+    }
+
+    /// Removes the item of a list that is most like `text`, and returns it
+    pub fn remove(&self, list: &str, text: &str) -> anyhow::Result<Option<Item>> {
+        let item = match self.find(list, text)? {
+            Some(item) => item,
+            None => return Ok(None),
+        };
+        self.connection()
+            .execute("DELETE FROM items WHERE id = ?", [item.id])?;
+        Ok(Some(item))
+    }
+
+    /// Marks the item of a list that is most like `text` as done, or as not done, and
+    /// returns it as it is now
+    pub fn set_done(&self, list: &str, text: &str, done: bool) -> anyhow::Result<Option<Item>> {
+        let mut item = match self.find(list, text)? {
+            Some(item) => item,
+            None => return Ok(None),
+        };
+        if item.done != done {
+            item.done = done;
+            item.done_at = if done { Some(Utc::now()) } else { None };
+            self.connection().execute(
+                "UPDATE items SET done = ?, done_at = ? WHERE id = ?",
+                params![item.done, item.done_at, item.id],
+            )?;
+        }
+        Ok(Some(item))
+    }
+
+    /// Removes the items of a list, or only those that are done. Returns how many there were
+    pub fn clear(&self, list: &str, only_done: bool) -> anyhow::Result<usize> {
+        let connection = self.connection();
+        let id = match self.row(&connection, list)? {
+            Some((id, ..)) => id,
+            None => return Ok(0),
+        };
+        let removed = connection.execute(
+            "DELETE FROM items WHERE list = ? AND (done OR NOT ?)",
+            params![id, only_done],
+        )?;
+        Ok(removed)
+    }
+
+    /// Deletes a list and its items. Returns false if there is no list called `name`
+    pub fn delete(&self, name: &str) -> anyhow::Result<bool> {
+        let connection = self.connection();
+        let id = match self.row(&connection, name)? {
+            Some((id, ..)) => id,
+            None => return Ok(false),
+        };
+        let deleted = connection.execute("DELETE FROM lists WHERE id = ?", [id])?;
+        Ok(deleted == 1)
+    }
+
+    /// The id, name and creation time of the list called `name` the owner sees, preferring
+    /// their own to a shared one
+    fn row(
+        &self,
+        connection: &Connection,
+        name: &str,
+    ) -> anyhow::Result<Option<(i64, String, DateTime<Utc>)>> {
+        Ok(connection
+            .query_row(
+                "SELECT id, name, created FROM lists
+                 WHERE name = ? AND owner IN (?, '')
+                 ORDER BY owner = '' LIMIT 1",
+                params![name, self.owner],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()?)
+    }
+
+    fn find(&self, list: &str, text: &str) -> anyhow::Result<Option<Item>> {
+        Ok(self
+            .list(list)?
+            .and_then(|list| find(&list.items, text).cloned()))
+    }
+}
+
+/// The item most like `text`, if any is alike enough. Items that are not done yet win ties
+pub fn find<'a>(items: &'a [Item], text: &str) -> Option<&'a Item> {
+    items
+        .iter()
+        .map(|item| (similarity(&item.text, text), item))
+        .filter(|(similarity, _)| *similarity >= MIN_SIMILARITY)
+        .max_by(|(a, first), (b, second)| {
+            a.total_cmp(b)
+                .then_with(|| second.done.cmp(&first.done))
+                // The earliest item of equally good ones
+                .then_with(|| second.id.cmp(&first.id))
+        })
+        .map(|(_, item)| item)
+}
+
+/// How alike two names of an item are, from 0 to 1. Case, punctuation and spacing are
+/// ignored. Names are only alike if one is contained word for word in the other, plurals
+/// aside, as names that are spelled alike are often different things: "beer" and "beef"
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (words(a), words(b));
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    if a == b {
+        return 1.0;
+    }
+    let (shorter, longer) = if a.len() <= b.len() {
+        (&a, &b)
+    } else {
+        (&b, &a)
+    };
+    let contained = longer.windows(shorter.len()).any(|window| {
+        window
+            .iter()
+            .zip(shorter.iter())
+            .all(|(a, b)| same_word(a, b))
+    });
+    if !contained {
+        0.0
+    } else if shorter.len() == longer.len() {
+        // "eggs" and "egg"
+        0.95
+    } else {
+        strsim::jaro_winkler(&a.join(" "), &b.join(" ")).max(MIN_SIMILARITY)
+    }
+}
+
+/// Whether two words are the same, or one is the plural of the other
+fn same_word(a: &str, b: &str) -> bool {
+    let stems = |word: &str| -> Vec<String> {
+        let mut stems = vec![word.to_owned()];
+        stems.extend(
+            ENDINGS
+                .iter()
+                .filter_map(|ending| word.strip_suffix(ending))
+                .filter(|stem| stem.chars().count() >= 3)
+                .map(str::to_owned),
+        );
+        stems
+    };
+    let a = stems(a);
+    stems(b).iter().any(|stem| a.contains(stem))
+}
+
+fn same(a: &str, b: &str) -> bool {
+    words(a) == words(b)
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A database of its own for each test, removed when the test ends
+    struct TempDb(PathBuf);
+
+    impl TempDb {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir()
+                .join(format!("kara-lists-{}-{name}.sqlite3", std::process::id()));
+            let _ = std::fs::remove_file(&path);
+            Self(path)
+        }
+
+        fn open(&self) -> ListStore {
+            ListStore::open(&self.0).unwrap()
+        }
+    }
+
+    impl Drop for TempDb {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn texts(store: &ListStore, list: &str) -> Vec<String> {
+        store.list(list).unwrap().map_or_else(Vec::new, |list| {
+            list.items.into_iter().map(|item| item.text).collect()
+        })
+    }
+
+    #[test]
+    fn compares_items_word_for_word() {
+        assert_eq!(similarity("Milk", "milk."), 1.0);
+        assert_eq!(similarity("eggs", "egg"), 0.95);
+        assert_eq!(similarity("lemon", "lemons"), 0.95);
+        assert_eq!(similarity("tomatoes", "Tomato"), 0.95);
+        assert!(similarity("oat milk", "milk") >= MIN_SIMILARITY);
+        assert!(similarity("milk", "semi skimmed milk") >= MIN_SIMILARITY);
+        // Spelled alike, but other things
+        assert_eq!(similarity("beer", "beef"), 0.0);
+        assert_eq!(similarity("oat milk", "whole milk"), 0.0);
+        assert_eq!(similarity("bread", ""), 0.0);
+    }
+
+    #[test]
+    fn changes_items() {
+        let db = TempDb::new("items");
+        let store = db.open();
+        assert_eq!(
+            store.add("shopping", "beef").unwrap(),
+            Added::Added { created_list: true }
+        );
+        assert_eq!(
+            store.add("shopping", "oat milk").unwrap(),
+            Added::Added {
+                created_list: false
+            }
+        );
+        assert!(matches!(
+            store.add("Shopping", "Beef").unwrap(),
+            Added::AlreadyThere(item) if item.text == "beef"
+        ));
+
+        assert_eq!(store.remove("shopping", "beer").unwrap(), None);
+        let ticked = store.set_done("shopping", "milk", true).unwrap().unwrap();
+        assert_eq!(ticked.text, "oat milk");
+        assert!(ticked.done && ticked.done_at.is_some());
+        // A done item is added again rather than being there already
+        store.add("shopping", "oat milk").unwrap();
+        assert_eq!(texts(&store, "shopping"), ["beef", "oat milk", "oat milk"]);
+        // The open one is the one found
+        let removed = store.remove("shopping", "oat milk").unwrap().unwrap();
+        assert!(!removed.done);
+
+        assert_eq!(store.clear("shopping", true).unwrap(), 1);
+        assert_eq!(texts(&store, "shopping"), ["beef"]);
+        assert_eq!(store.clear("shopping", false).unwrap(), 1);
+        assert_eq!(texts(&store, "shopping"), Vec::<String>::new());
+        assert!(store.delete("shopping").unwrap());
+        assert!(!store.delete("shopping").unwrap());
+        assert_eq!(store.clear("shopping", false).unwrap(), 0);
+    }
+
+    #[test]
+    fn keeps_lists_of_each_speaker_apart() {
+        let db = TempDb::new("owners");
+        let shared = db.open();
+        let alice = shared.for_speaker(&Speaker::Known("alice".to_owned()));
+        let bob = shared.for_speaker(&Speaker::Known("bob".to_owned()));
+        shared.add("shopping", "milk").unwrap();
+        alice.add("todo", "call mum").unwrap();
+        bob.add("todo", "feed the cat").unwrap();
+
+        // Shared lists are everyone's, other lists only their owner's
+        alice.add("shopping", "eggs").unwrap();
+        assert_eq!(texts(&bob, "shopping"), ["milk", "eggs"]);
+        assert_eq!(texts(&alice, "todo"), ["call mum"]);
+        assert_eq!(texts(&bob, "todo"), ["feed the cat"]);
+        assert_eq!(shared.list("todo").unwrap(), None);
+        assert_eq!(alice.names().unwrap(), ["shopping", "todo"]);
+        assert_eq!(shared.names().unwrap(), ["shopping"]);
+
+        // A speaker's own list hides a shared one with the same name
+        shared.add("Todo", "recycling").unwrap();
+        assert_eq!(shared.names().unwrap(), ["shopping", "Todo"]);
+        assert_eq!(alice.names().unwrap(), ["shopping", "todo"]);
+        assert!(!alice.create("TODO").unwrap());
+        assert!(alice.delete("todo").unwrap());
+        assert_eq!(texts(&alice, "todo"), ["recycling"]);
+        assert_eq!(texts(&bob, "todo"), ["feed the cat"]);
+    }
+
+    #[test]
+    fn gives_lists_of_earlier_versions_to_everyone() {
+        let db = TempDb::new("migration");
+        {
+            let connection = Connection::open(&db.0).unwrap();
+            connection
+                .execute_batch(
+                    "CREATE TABLE lists (
+                         id INTEGER PRIMARY KEY,
+                         name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+                         created TEXT NOT NULL
+                     );
+                     CREATE TABLE items (
+                         id INTEGER PRIMARY KEY,
+                         list INTEGER NOT NULL REFERENCES lists (id) ON DELETE CASCADE,
+                         text TEXT NOT NULL,
+                         added TEXT NOT NULL,
+                         done INTEGER NOT NULL DEFAULT 0,
+                         done_at TEXT
+                     );
+                     INSERT INTO lists VALUES (1, 'shopping', '2022-08-01T09:00:00Z');
+                     INSERT INTO items (list, text, added) VALUES (1, 'milk', '2022-08-01T09:00:00Z');
+                     PRAGMA user_version = 1;",
+                )
+                .unwrap();
+        }
+        let store = db.open();
+        assert_eq!(texts(&store, "shopping"), ["milk"]);
+        let alice = store.for_speaker(&Speaker::Known("alice".to_owned()));
+        assert_eq!(texts(&alice, "shopping"), ["milk"]);
+        assert!(alice.delete("shopping").unwrap());
+        assert_eq!(store.names().unwrap(), Vec::<String>::new());
+        let version: u32 = store
+            .connection()
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, VERSION);
+    }
+}
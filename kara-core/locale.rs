@@ -0,0 +1,553 @@
+//! Everything that depends on the language Kara speaks: speech and language models, the
+//! wake phrase and the responses Kara shows.
+use std::path::{Path, PathBuf};
+
+use kara_audio::stt_sources::{default_stt_model_path, kara::KaraModel, STTConfig};
+use kara_events::{AlarmKind, Ringing};
+use kara_nlu::{
+    engine,
+    injection::{self, default_entity_path, InjectedEntities},
+    rules::RuleSet,
+    Language, NLUParser, NluError, ParseOptions,
+};
+use tracing::{debug, warn};
+
+use crate::skills::DispatchError;
+
+const VOSK_MODELS_URL: &str = "https://alphacephei.com/vosk/models";
+
+fn vosk_model(language: Language) -> &'static str {
+    match language {
+        Language::English => "vosk-model-small-en-us-0.15",
+        Language::French => "vosk-model-small-fr-0.22",
+        Language::German => "vosk-model-small-de-0.15",
+        Language::Spanish => "vosk-model-small-es-0.42",
+    }
+}
+
+pub fn wake_phrase(language: Language) -> &'static str {
+    match language {
+        Language::English => "hey kara",
+        Language::French => "salut kara",
+        Language::German => "hallo kara",
+        Language::Spanish => "hola kara",
+    }
+}
+
+/// The speech to text source for `language`. `model_path` overrides where the vosk model
+/// is loaded from
+pub fn speech_model(language: Language, model_path: Option<String>) -> STTConfig {
+    let name = vosk_model(language);
+    let path = model_path.unwrap_or_else(|| {
+        Path::new(&default_stt_model_path())
+            .join(name)
+            .display()
+            .to_string()
+    });
+    STTConfig::Kara(KaraModel {
+        path,
+        url: format!("{VOSK_MODELS_URL}/{name}.zip"),
+        wake_phrase: wake_phrase(language).to_owned(),
+    })
+}
+
+/// Where language models are read from and how they parse commands
+#[derive(Debug, Clone, Default)]
+pub struct ModelSettings {
+    /// A directory of models named by language code, tried before the default locations
+    pub path: Option<PathBuf>,
+    pub options: ParseOptions,
+}
+
+/// Directories that may hold language models, in the order they are tried: the configured
+/// directory, `$XDG_DATA_HOME/kara/nlu`, `kara/nlu` in each of `$XDG_DATA_DIRS`, and the
+/// models bundled next to the executable or in the source tree
+fn model_dirs(configured: Option<&Path>) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = configured.map(Path::to_owned).into_iter().collect();
+    dirs.push(user_model_dir());
+    let data_dirs = std::env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_owned());
+    dirs.extend(std::env::split_paths(&data_dirs).map(|dir| dir.join("kara/nlu")));
+    if let Some(exe_dir) = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_owned))
+    {
+        dirs.push(exe_dir.join("kara-assets/nlu"));
+    }
+    dirs.push(Path::new(env!("CARGO_MANIFEST_DIR")).join("kara-assets/nlu"));
+    dirs
+}
+
+/// Where models trained with `kara nlu train` are written to by default
+pub fn user_model_dir() -> PathBuf {
+    let mut path = dirs::data_dir().expect("could not find data dir");
+    path.push("kara");
+    path.push("nlu");
+    path
+}
+
+/// The language model for `language`, from the first directory in [`model_dirs`] that has
+/// one. When none has, the bundled location is returned so that errors name it
+pub fn nlu_model_path(language: Language, configured: Option<&Path>) -> PathBuf {
+    let candidates: Vec<_> = model_dirs(configured)
+        .into_iter()
+        .map(|dir| dir.join(language.code()))
+        .collect();
+    let found = candidates.iter().find(|path| engine::is_model(path));
+    if let (Some(configured), Some(found)) = (configured, found) {
+        if !found.starts_with(configured) {
+            warn!(
+                "no {} language model in {}, using {}",
+                language.name(),
+                configured.display(),
+                found.display()
+            );
+        }
+    }
+    found
+        .or_else(|| candidates.last())
+        .cloned()
+        .expect("the bundled location is always a candidate")
+}
+
+/// Where user defined rules for commands are read from
+pub fn rules_dir() -> PathBuf {
+    let mut dir = dirs::config_dir().expect("could not find config dir");
+    dir.push("kara");
+    dir.push("rules");
+    dir
+}
+
+/// Loads the language model for `language` along with the rules written for it. Blocks
+/// while the model is read
+pub fn language_model(language: Language, settings: &ModelSettings) -> Result<NLUParser, NluError> {
+    let path = with_entities(nlu_model_path(language, settings.path.as_deref()), language);
+    let model = NLUParser::new(&path)?.with_options(settings.options.clone());
+    debug!(path = %path.display(), "loaded language model");
+    match RuleSet::load_dir(rules_dir(), language.code()) {
+        Ok(rules) => {
+            debug!(
+                rules = rules.len(),
+                language = language.code(),
+                "loaded command rules"
+            );
+            Ok(model.with_rules(rules))
+        }
+        Err(e) => {
+            warn!("could not load command rules: {}", e);
+            Ok(model)
+        }
+    }
+}
+
+/// The model to load instead of the one in `path`, with the entity values added with
+/// `kara entity add`. They are injected into a copy of it in the user's data directory,
+/// as `path` may be shared or read-only
+fn with_entities(path: PathBuf, language: Language) -> PathBuf {
+    let entities = default_entity_path();
+    // Models are only copied once values have been added
+    if !entities.exists() {
+        return path;
+    }
+    let copy = injection::injected_model_path(language);
+    let result = InjectedEntities::load(entities)
+        .map_err(|e| e.to_string())
+        .and_then(|injected| {
+            injected
+                .inject_copy(&path, &copy)
+                .map_err(|e| e.to_string())
+        });
+    match result {
+        Ok(()) => copy,
+        Err(e) => {
+            warn!("could not add entity values to the language model: {}", e);
+            path
+        }
+    }
+}
+
+/// Whether there is a trained language model for `language`
+pub fn has_nlu_model(language: Language, configured: Option<&Path>) -> bool {
+    engine::is_model(nlu_model_path(language, configured))
+}
+
+pub enum Response {
+    /// Kara is loading the models for a new language
+    Switching,
+    /// Kara was asked to speak the language she is already speaking
+    AlreadySpeaking,
+    /// There is no language model for the requested language
+    Unavailable(Language),
+    /// The command did not match any intent
+    NotUnderstood,
+    /// The command was understood but could not be carried out
+    Failed,
+    /// The command is only carried out for enrolled speakers
+    UnknownSpeaker,
+    /// A follow up question was cancelled
+    Cancelled,
+    /// A follow up question was not answered after several attempts
+    GaveUp,
+    /// Asks whether the command meant the intent with this label
+    ConfirmIntent(String),
+    /// Asks whether a command that is hard to undo should be carried out
+    ConfirmAction,
+    /// An alarm or timer went off
+    Ringing(Ringing),
+    /// Ringing alarms were stopped
+    AlarmStopped,
+    /// Ringing alarms were snoozed for this many minutes
+    Snoozed(u64),
+    /// What is said from now on is written down
+    Dictating,
+    /// Dictation has ended
+    DictationSaved,
+}
+
+impl From<&DispatchError> for Response {
+    /// What to tell the user when a command could not be carried out
+    fn from(error: &DispatchError) -> Self {
+        match error {
+            DispatchError::NotUnderstood => Response::NotUnderstood,
+            DispatchError::UnknownSpeaker(_) => Response::UnknownSpeaker,
+            _ => Response::Failed,
+        }
+    }
+}
+
+/// A response in `language`
+pub fn response(language: Language, response: Response) -> String {
+    use Language::*;
+    use Response::*;
+    match (language, response) {
+        (English, Switching) => "Switching to English".to_owned(),
+        (French, Switching) => "Je passe au français".to_owned(),
+        (German, Switching) => "Ich wechsle zu Deutsch".to_owned(),
+        (Spanish, Switching) => "Cambio a español".to_owned(),
+        (English, AlreadySpeaking) => "I'm already speaking English".to_owned(),
+        (French, AlreadySpeaking) => "Je parle déjà français".to_owned(),
+        (German, AlreadySpeaking) => "Ich spreche schon Deutsch".to_owned(),
+        (Spanish, AlreadySpeaking) => "Ya hablo español".to_owned(),
+        (English, Unavailable(requested)) => format!("I can't speak {} yet", requested.name()),
+        (French, Unavailable(requested)) => format!("Je ne parle pas encore {}", requested.name()),
+        (German, Unavailable(requested)) => format!("Ich spreche noch kein {}", requested.name()),
+        (Spanish, Unavailable(requested)) => format!("Todavía no hablo {}", requested.name()),
+        (English, NotUnderstood) => "Sorry, I didn't understand that".to_owned(),
+        (French, NotUnderstood) => "Désolée, je n'ai pas compris".to_owned(),
+        (German, NotUnderstood) => "Entschuldigung, das habe ich nicht verstanden".to_owned(),
+        (Spanish, NotUnderstood) => "Lo siento, no lo he entendido".to_owned(),
+        (English, Failed) => "Sorry, I can't do that right now".to_owned(),
+        (French, Failed) => "Désolée, je ne peux pas faire ça pour le moment".to_owned(),
+        (German, Failed) => "Entschuldigung, das kann ich gerade nicht".to_owned(),
+        (Spanish, Failed) => "Lo siento, ahora no puedo hacerlo".to_owned(),
+        (English, UnknownSpeaker) => "Sorry, I only do that for people I recognise".to_owned(),
+        (French, UnknownSpeaker) => {
+            "Désolée, je ne fais ça que pour les personnes que je reconnais".to_owned()
+        }
+        (German, UnknownSpeaker) => {
+            "Entschuldigung, das mache ich nur für Personen, die ich erkenne".to_owned()
+        }
+        (Spanish, UnknownSpeaker) => {
+            "Lo siento, solo hago eso para las personas que reconozco".to_owned()
+        }
+        (English, Cancelled) => "Okay, cancelled".to_owned(),
+        (French, Cancelled) => "D'accord, c'est annulé".to_owned(),
+        (German, Cancelled) => "Okay, abgebrochen".to_owned(),
+        (Spanish, Cancelled) => "Vale, cancelado".to_owned(),
+        (English, GaveUp) => "Sorry, I still didn't get that. Let's try again later".to_owned(),
+        (French, GaveUp) => {
+            "Désolée, je n'ai toujours pas compris. Réessayons plus tard".to_owned()
+        }
+        (German, GaveUp) => {
+            "Entschuldigung, das habe ich immer noch nicht verstanden. Versuchen wir es später"
+                .to_owned()
+        }
+        (Spanish, GaveUp) => "Lo siento, sigo sin entenderlo. Inténtalo más tarde".to_owned(),
+        (English, ConfirmIntent(label)) => format!("Did you mean \"{label}\"?"),
+        (French, ConfirmIntent(label)) => format!("Tu voulais dire « {label} » ?"),
+        (German, ConfirmIntent(label)) => format!("Meintest du „{label}“?"),
+        (Spanish, ConfirmIntent(label)) => format!("¿Querías decir «{label}»?"),
+        (English, ConfirmAction) => "Are you sure?".to_owned(),
+        (French, ConfirmAction) => "Tu confirmes ?".to_owned(),
+        (German, ConfirmAction) => "Bist du sicher?".to_owned(),
+        (Spanish, ConfirmAction) => "¿Seguro?".to_owned(),
+        (language, Response::Ringing(ringing)) => ringing_text(language, &ringing),
+        (English, AlarmStopped) => "Alarm stopped".to_owned(),
+        (French, AlarmStopped) => "Alarme arrêtée".to_owned(),
+        (German, AlarmStopped) => "Wecker ausgeschaltet".to_owned(),
+        (Spanish, AlarmStopped) => "Alarma apagada".to_owned(),
+        (English, Snoozed(minutes)) => format!("Snoozed for {minutes} minutes"),
+        (French, Snoozed(minutes)) => format!("Je sonne à nouveau dans {minutes} minutes"),
+        (German, Snoozed(minutes)) => format!("Ich klingle in {minutes} Minuten wieder"),
+        (Spanish, Snoozed(minutes)) => format!("Vuelvo a sonar en {minutes} minutos"),
+        (English, Dictating) => "I'm listening. Say \"stop dictation\" when you're done".to_owned(),
+        (French, Dictating) => "Je t'écoute. Dis « arrête la dictée » quand tu as fini".to_owned(),
+        (German, Dictating) => "Ich höre zu. Sag „Diktat beenden“, wenn du fertig bist".to_owned(),
+        (Spanish, Dictating) => "Te escucho. Di «termina el dictado» cuando acabes".to_owned(),
+        (English, DictationSaved) => "Dictation saved".to_owned(),
+        (French, DictationSaved) => "Dictée enregistrée".to_owned(),
+        (German, DictationSaved) => "Diktat gespeichert".to_owned(),
+        (Spanish, DictationSaved) => "Dictado guardado".to_owned(),
+    }
+}
+
+fn ringing_text(language: Language, ringing: &Ringing) -> String {
+    use AlarmKind::*;
+    use Language::*;
+    let text = match (language, ringing.kind, ringing.missed) {
+        (English, Alarm, false) => "Alarm",
+        (English, Alarm, true) => "You missed an alarm",
+        (English, Timer, false) => "Time's up",
+        (English, Timer, true) => "A timer went off while I was away",
+        (French, Alarm, false) => "Alarme",
+        (French, Alarm, true) => "Tu as manqué une alarme",
+        (French, Timer, false) => "Le minuteur est terminé",
+        (French, Timer, true) => "Un minuteur s'est terminé pendant mon absence",
+        (German, Alarm, false) => "Wecker",
+        (German, Alarm, true) => "Du hast einen Wecker verpasst",
+        (German, Timer, false) => "Die Zeit ist um",
+        (German, Timer, true) => "Ein Timer ist abgelaufen, während ich weg war",
+        (Spanish, Alarm, false) => "Alarma",
+        (Spanish, Alarm, true) => "Te has perdido una alarma",
+        (Spanish, Timer, false) => "Se acabó el tiempo",
+        (Spanish, Timer, true) => "Un temporizador terminó mientras no estaba",
+    };
+    match (&ringing.label, language) {
+        (Some(label), French) => format!("{text} : {label}"),
+        (Some(label), _) => format!("{text}: {label}"),
+        (None, _) => text.to_owned(),
+    }
+}
+
+/// Phrases that ask Kara to speak another language, followed by the language's name
+fn switch_phrases(language: Language) -> &'static [&'static str] {
+    match language {
+        Language::English => &["speak", "speak in", "switch to", "change language to"],
+        Language::French => &["parle", "parle en", "passe au", "passe en"],
+        Language::German => &["sprich", "sprich auf", "wechsle zu", "wechsle auf"],
+        Language::Spanish => &["habla", "habla en", "cambia a", "cambia al"],
+    }
+}
+
+/// Checks whether `text`, spoken in `language`, asks Kara to speak another language.
+/// Language names are recognised in any supported language so that "speak français"
+/// works too
+pub fn language_switch(language: Language, text: &str) -> Option<Language> {
+    let text = text.trim().to_lowercase();
+    let text = text.trim_end_matches(|c: char| c.is_ascii_punctuation());
+    switch_phrases(language).iter().find_map(|phrase| {
+        let name = text.strip_prefix(phrase)?.strip_prefix(' ')?;
+        Language::from_name(name)
+    })
+}
+
+/// Whether `text` answers a yes or no question with yes, e.g. "yes please", or no. `None`
+/// if it is neither
+pub fn yes_or_no(language: Language, text: &str) -> Option<bool> {
+    let (yes, no): (&[&str], &[&str]) = match language {
+        Language::English => (
+            &[
+                "yes", "yeah", "yep", "sure", "correct", "right", "ok", "okay",
+            ],
+            &["no", "nope", "nah", "wrong"],
+        ),
+        Language::French => (
+            &["oui", "ouais", "d'accord", "exactement", "absolument", "ok"],
+            &["non", "pas du tout"],
+        ),
+        Language::German => (
+            &["ja", "genau", "richtig", "klar", "sicher", "okay", "ok"],
+            &["nein", "nee", "falsch"],
+        ),
+        Language::Spanish => (
+            &["sí", "si", "claro", "vale", "correcto", "exacto"],
+            &["no", "para nada"],
+        ),
+    };
+    let text = text.trim().to_lowercase();
+    let starts_with = |phrase: &&str| {
+        text.strip_prefix(phrase).map_or(false, |rest| {
+            rest.chars()
+                .next()
+                .map_or(true, |c| !c.is_alphanumeric() && c != '\'')
+        })
+    };
+    if no.iter().any(starts_with) {
+        Some(false)
+    } else if yes.iter().any(starts_with) {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+/// Whether `text` cancels a follow up question, e.g. "never mind"
+pub fn is_cancel(language: Language, text: &str) -> bool {
+    let phrases: &[&str] = match language {
+        Language::English => &["cancel", "never mind", "nevermind", "forget it", "stop"],
+        Language::French => &["annule", "annuler", "laisse tomber", "oublie", "stop"],
+        Language::German => &["abbrechen", "vergiss es", "egal", "stopp", "stop"],
+        Language::Spanish => &["cancela", "cancelar", "olvídalo", "déjalo", "para"],
+    };
+    let text = text.trim().to_lowercase();
+    let text = text.trim_end_matches(|c: char| c.is_ascii_punctuation());
+    phrases.contains(&text)
+}
+
+/// Starting or ending dictation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DictationControl {
+    Start,
+    Stop,
+}
+
+/// Whether `text` starts dictation, e.g. "take dictation", or ends it
+pub fn dictation_control(language: Language, text: &str) -> Option<DictationControl> {
+    let (start, stop): (&[&str], &[&str]) = match language {
+        Language::English => (
+            &["take dictation", "start dictation", "take a note"],
+            &["stop dictation", "end dictation", "finish dictation"],
+        ),
+        Language::French => (
+            &["prends en dictée", "commence la dictée", "prends une note"],
+            &["arrête la dictée", "fin de la dictée", "termine la dictée"],
+        ),
+        Language::German => (
+            &["diktat starten", "starte das diktat", "nimm eine notiz auf"],
+            &["diktat beenden", "beende das diktat", "ende des diktats"],
+        ),
+        Language::Spanish => (
+            &["toma dictado", "empieza el dictado", "toma una nota"],
+            &["termina el dictado", "fin del dictado", "para el dictado"],
+        ),
+    };
+    let text = text.trim().to_lowercase();
+    let text = text.trim_end_matches(|c: char| c.is_ascii_punctuation());
+    if start.contains(&text) {
+        Some(DictationControl::Start)
+    } else if stop.contains(&text) {
+        Some(DictationControl::Stop)
+    } else {
+        None
+    }
+}
+
+/// What to do with a ringing alarm
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlarmControl {
+    Stop,
+    Snooze,
+}
+
+/// Whether `text` stops or snoozes a ringing alarm, e.g. "stop" or "snooze it". Snoozing
+/// wins if both are asked for. Words that are common in other sentences, such as "para"
+/// or "aus", only stop the alarm on their own or in a phrase such as "Alarm aus"
+pub fn alarm_control(language: Language, text: &str) -> Option<AlarmControl> {
+    let (stop, alone, snooze): (&[&str], &[&str], &[&str]) = match language {
+        Language::English => (
+            &[
+                "stop",
+                "dismiss",
+                "turn off",
+                "switch off",
+                "i'm up",
+                "enough",
+            ],
+            &[],
+            &["snooze", "later", "five more minutes"],
+        ),
+        Language::French => (
+            &["stop", "arrête", "arrêter", "éteins", "coupe", "ça suffit"],
+            &[],
+            &["répète", "plus tard", "encore cinq minutes", "snooze"],
+        ),
+        Language::German => (
+            &[
+                "stopp",
+                "stop",
+                "alarm aus",
+                "wecker aus",
+                "mach aus",
+                "ausschalten",
+                "halt",
+                "genug",
+            ],
+            &["aus"],
+            &["schlummern", "später", "noch fünf minuten", "snooze"],
+        ),
+        Language::Spanish => (
+            &[
+                "para la alarma",
+                "para el despertador",
+                "para ya",
+                "párala",
+                "detente",
+                "apaga",
+                "apágala",
+                "basta",
+                "ya estoy",
+            ],
+            &["para"],
+            &["pospón", "posponer", "más tarde", "cinco minutos más"],
+        ),
+    };
+    let words: Vec<_> = text
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .filter(|word| !word.is_empty())
+        .map(str::to_owned)
+        .collect();
+    let text = format!(" {} ", words.join(" "));
+    let said = |phrases: &[&str]| {
+        phrases
+            .iter()
+            .any(|phrase| text.contains(&format!(" {phrase} ")))
+    };
+    if said(snooze) {
+        Some(AlarmControl::Snooze)
+    } else if said(stop) || alone.contains(&words.join(" ").as_str()) {
+        Some(AlarmControl::Stop)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn controls_alarms_with_whole_phrases() {
+        let control = alarm_control;
+        assert_eq!(
+            control(Language::English, "Stop!"),
+            Some(AlarmControl::Stop)
+        );
+        assert_eq!(
+            control(Language::English, "stop and snooze it"),
+            Some(AlarmControl::Snooze)
+        );
+        assert_eq!(control(Language::English, "what time is it"), None);
+        assert_eq!(control(Language::Spanish, "para"), Some(AlarmControl::Stop));
+        assert_eq!(
+            control(Language::Spanish, "para la alarma"),
+            Some(AlarmControl::Stop)
+        );
+        assert_eq!(
+            control(Language::Spanish, "pon una alarma para mañana"),
+            None
+        );
+        assert_eq!(control(Language::German, "aus"), Some(AlarmControl::Stop));
+        assert_eq!(
+            control(Language::German, "Alarm aus"),
+            Some(AlarmControl::Stop)
+        );
+        assert_eq!(control(Language::German, "wie sieht es aus"), None);
+        assert_eq!(
+            control(Language::French, "encore cinq minutes"),
+            Some(AlarmControl::Snooze)
+        );
+    }
+}
@@ -1,15 +1,33 @@
+mod calendar;
 mod cli;
+mod commands;
 mod config;
 mod debug;
 mod gui;
+mod history;
+mod lists;
+mod locale;
+mod reload;
+mod scheduler;
+mod session;
+mod skills;
 
 #[tokio::main]
 async fn main() {
-    let (_guard, config, model_receiver) = debug::initialise();
+    let (_guard, config, model_receiver, command) = debug::initialise();
+
+    if let Some(command) = command {
+        if let Err(e) = commands::run(command, &config).await {
+            tracing::error!("{}", e);
+        }
+        return;
+    }
 
     match config.general_settings.startup_mode {
         cli::Interface::Cli => {
-            println!("Hello, world!");
+            if let Err(e) = session::start(&config, model_receiver).await {
+                tracing::error!("{}", e);
+            }
         }
         cli::Interface::Gui => {
             if let Err(e) = gui::start(&config, model_receiver).await {
@@ -0,0 +1,39 @@
+//! Requests to load the language model again without restarting, e.g. after retraining it.
+//! On Unix they are sent with SIGHUP: `pkill -HUP kara`
+
+pub struct ReloadSignal {
+    #[cfg(unix)]
+    hangup: Option<tokio::signal::unix::Signal>,
+}
+
+impl ReloadSignal {
+    /// Must be called from within the tokio runtime
+    pub fn new() -> Self {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            let hangup = match signal(SignalKind::hangup()) {
+                Ok(hangup) => Some(hangup),
+                Err(e) => {
+                    tracing::warn!("language model reloading is unavailable: {}", e);
+                    None
+                }
+            };
+            Self { hangup }
+        }
+        #[cfg(not(unix))]
+        Self {}
+    }
+
+    /// Waits for the next request. Never resolves where reloading is unavailable
+    pub async fn recv(&mut self) {
+        #[cfg(unix)]
+        if let Some(hangup) = &mut self.hangup {
+            if hangup.recv().await.is_some() {
+                return;
+            }
+            self.hangup = None;
+        }
+        std::future::pending::<()>().await
+    }
+}
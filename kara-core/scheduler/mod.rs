@@ -0,0 +1,556 @@
+//! Alarms and timers that survive restarts. The [`Scheduler`] keeps them in a [`Store`],
+//! rings them when they are due and reschedules recurring ones. Alarms that were due while
+//! Kara was not running ring as missed at startup, unless they are too old to matter.
+mod store;
+
+use std::{
+    io,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use chrono::{DateTime, Datelike, Local, NaiveTime, TimeZone, Utc, Weekday};
+use kara_events::{AlarmKind, Ringing};
+use kara_nlu::Language;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+use tracing::{debug, error, info};
+
+use crate::{
+    config::state::Alarms,
+    locale::{self, AlarmControl, Response},
+};
+
+use self::store::Saved;
+pub use self::store::Store;
+
+/// How long a snoozed alarm waits before ringing again
+pub const DEFAULT_SNOOZE: Duration = Duration::from_secs(9 * 60);
+/// Alarms that were due longer ago than this when Kara started are dropped without ringing
+pub const DEFAULT_MISSED_WITHIN: Duration = Duration::from_secs(60 * 60);
+/// Alarms that go off later than this are reported as missed
+const LATE: Duration = Duration::from_secs(60);
+/// The longest the scheduler sleeps, so that changes to the system clock are noticed
+const MAX_WAIT: Duration = Duration::from_secs(30);
+
+/// Where the scheduler reads the time from. Replace it to drive the scheduler by hand
+pub trait TimeSource: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemTimeSource;
+
+impl TimeSource for SystemTimeSource {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Alarm {
+    pub id: u64,
+    #[serde(with = "Kind")]
+    pub kind: AlarmKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// When the alarm next goes off
+    pub due: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repeat: Option<Repeat>,
+}
+
+impl Alarm {
+    fn ringing(&self, missed: bool) -> Ringing {
+        Ringing {
+            id: self.id,
+            kind: self.kind,
+            label: self.label.clone(),
+            missed,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "AlarmKind", rename_all = "lowercase")]
+enum Kind {
+    Alarm,
+    Timer,
+}
+
+/// The days of the week a recurring alarm goes off on, and the local time it goes off at
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Repeat {
+    pub days: Vec<Weekday>,
+    pub time: NaiveTime,
+}
+
+impl Repeat {
+    pub const EVERY_DAY: [Weekday; 7] = [
+        Weekday::Mon,
+        Weekday::Tue,
+        Weekday::Wed,
+        Weekday::Thu,
+        Weekday::Fri,
+        Weekday::Sat,
+        Weekday::Sun,
+    ];
+
+    /// The first time after `after` the alarm goes off. Days on which `time` does not exist,
+    /// because clocks go forward, are skipped
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let today = after.with_timezone(&Local).naive_local().date();
+        (0..=7)
+            .map(|days| today + chrono::Duration::days(days))
+            .filter(|date| self.days.contains(&date.weekday()))
+            .filter_map(|date| {
+                Local
+                    .from_local_datetime(&date.and_time(self.time))
+                    .earliest()
+            })
+            .map(|time| time.with_timezone(&Utc))
+            .find(|time| *time > after)
+    }
+}
+
+/// When a new alarm goes off
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum When {
+    At(DateTime<Utc>),
+    Every(Repeat),
+}
+
+#[derive(Debug, Default)]
+struct State {
+    saved: Saved,
+    /// Alarms that went off and have not been stopped or snoozed
+    ringing: Vec<Alarm>,
+    /// Whether the alarms that were due before loading have been rung or dropped
+    caught_up: bool,
+}
+
+/// Keeps track of alarms and rings them. Share it between tasks with an `Arc`
+pub struct Scheduler<T: TimeSource = SystemTimeSource> {
+    time: T,
+    store: Store,
+    state: Mutex<State>,
+    /// Woken when the next alarm may have changed
+    changed: Notify,
+    snooze: Duration,
+    missed_within: Duration,
+}
+
+impl Scheduler {
+    /// A scheduler on the system clock with the alarms saved in `store`
+    pub fn load(store: Store) -> io::Result<Self> {
+        Self::with_time_source(store, SystemTimeSource)
+    }
+
+    /// A scheduler for the alarms in Kara's data directory, set up as `config` says
+    pub fn configured(config: &Alarms) -> io::Result<Self> {
+        Ok(Self::load(Store::default())?
+            .with_snooze(config.snooze)
+            .with_missed_within(config.missed_within))
+    }
+}
+
+impl<T: TimeSource> Scheduler<T> {
+    pub fn with_time_source(store: Store, time: T) -> io::Result<Self> {
+        let saved = store.load()?;
+        debug!(
+            alarms = saved.alarms.len(),
+            path = %store.path().display(),
+            "loaded alarms"
+        );
+        Ok(Self {
+            time,
+            store,
+            state: Mutex::new(State {
+                saved,
+                ringing: Vec::new(),
+                caught_up: false,
+            }),
+            changed: Notify::new(),
+            snooze: DEFAULT_SNOOZE,
+            missed_within: DEFAULT_MISSED_WITHIN,
+        })
+    }
+
+    pub fn with_snooze(mut self, snooze: Duration) -> Self {
+        self.snooze = snooze;
+        self
+    }
+
+    /// Alarms that were due longer ago than `missed_within` when Kara started are dropped
+    /// instead of ringing as missed
+    pub fn with_missed_within(mut self, missed_within: Duration) -> Self {
+        self.missed_within = missed_within;
+        self
+    }
+
+    /// The time on the scheduler's time source
+    pub fn now(&self) -> DateTime<Utc> {
+        self.time.now()
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Schedules a new alarm and saves it. `None` if a recurring alarm has no days to go
+    /// off on
+    pub fn add(
+        &self,
+        kind: AlarmKind,
+        label: Option<String>,
+        when: When,
+    ) -> io::Result<Option<Alarm>> {
+        let (due, repeat) = match when {
+            When::At(due) => (due, None),
+            When::Every(repeat) => match repeat.next_after(self.time.now()) {
+                Some(due) => (due, Some(repeat)),
+                None => return Ok(None),
+            },
+        };
+        let mut state = self.state();
+        let alarm = Alarm {
+            id: state.saved.next_id,
+            kind,
+            label,
+            due,
+            repeat,
+        };
+        state.saved.next_id += 1;
+        state.saved.alarms.push(alarm.clone());
+        self.store.save(&state.saved)?;
+        drop(state);
+        info!(id = alarm.id, due = %alarm.due, "scheduled alarm");
+        self.changed.notify_one();
+        Ok(Some(alarm))
+    }
+
+    /// Scheduled alarms, the next one to go off first
+    pub fn alarms(&self) -> Vec<Alarm> {
+        let mut alarms = self.state().saved.alarms.clone();
+        alarms.sort_by_key(|alarm| alarm.due);
+        alarms
+    }
+
+    /// Removes the alarms `matching` accepts and returns them
+    pub fn remove(&self, matching: impl Fn(&Alarm) -> bool) -> io::Result<Vec<Alarm>> {
+        let mut state = self.state();
+        let (removed, kept) = state
+            .saved
+            .alarms
+            .drain(..)
+            .partition::<Vec<_>, _>(|alarm| matching(alarm));
+        state.saved.alarms = kept;
+        if !removed.is_empty() {
+            self.store.save(&state.saved)?;
+            self.changed.notify_one();
+        }
+        Ok(removed)
+    }
+
+    /// When the next alarm goes off
+    pub fn next_due(&self) -> Option<DateTime<Utc>> {
+        self.state()
+            .saved
+            .alarms
+            .iter()
+            .map(|alarm| alarm.due)
+            .min()
+    }
+
+    /// Rings the alarms that are due. Recurring alarms are moved to their next day and the
+    /// others are removed. On the first call after loading, alarms due longer ago than
+    /// `missed_within` are dropped. Later alarms always ring, however late, e.g. after the
+    /// computer was asleep
+    pub fn ring_due(&self) -> Vec<Ringing> {
+        let now = self.time.now();
+        let mut state = self.state();
+        let startup = !std::mem::replace(&mut state.caught_up, true);
+        let (due, mut kept): (Vec<_>, Vec<_>) = state
+            .saved
+            .alarms
+            .drain(..)
+            .partition(|alarm| alarm.due <= now);
+        if due.is_empty() {
+            state.saved.alarms = kept;
+            return Vec::new();
+        }
+        let mut ringing = Vec::new();
+        for alarm in due {
+            let late = (now - alarm.due).to_std().unwrap_or_default();
+            if startup && late > self.missed_within {
+                info!(id = alarm.id, due = %alarm.due, "dropping alarm that was missed long ago");
+            } else {
+                info!(id = alarm.id, "alarm went off");
+                ringing.push(alarm.ringing(late > LATE));
+                state.ringing.push(alarm.clone());
+            }
+            if let Some(due) = alarm
+                .repeat
+                .as_ref()
+                .and_then(|repeat| repeat.next_after(now))
+            {
+                kept.push(Alarm { due, ..alarm });
+            }
+        }
+        state.saved.alarms = kept;
+        if let Err(e) = self.store.save(&state.saved) {
+            error!("could not save alarms: {}", e);
+        }
+        ringing
+    }
+
+    pub fn is_ringing(&self) -> bool {
+        !self.state().ringing.is_empty()
+    }
+
+    /// Silences the ringing alarms and returns them
+    pub fn stop(&self) -> Vec<Alarm> {
+        std::mem::take(&mut self.state().ringing)
+    }
+
+    /// Silences the ringing alarms and rings them again after the snooze duration
+    pub fn snooze(&self) -> io::Result<Vec<Alarm>> {
+        let due = self.time.now()
+            + chrono::Duration::from_std(self.snooze).unwrap_or_else(|_| chrono::Duration::zero());
+        let mut state = self.state();
+        let snoozed = std::mem::take(&mut state.ringing);
+        for alarm in &snoozed {
+            let id = state.saved.next_id;
+            state.saved.next_id += 1;
+            state.saved.alarms.push(Alarm {
+                id,
+                due,
+                repeat: None,
+                ..alarm.clone()
+            });
+        }
+        if !snoozed.is_empty() {
+            self.store.save(&state.saved)?;
+            self.changed.notify_one();
+        }
+        Ok(snoozed)
+    }
+
+    /// Stops or snoozes the ringing alarms if `text` asks to, and says what was done
+    pub fn control(&self, language: Language, text: &str) -> Option<String> {
+        if !self.is_ringing() {
+            return None;
+        }
+        let response = match locale::alarm_control(language, text)? {
+            AlarmControl::Stop => {
+                self.stop();
+                Response::AlarmStopped
+            }
+            AlarmControl::Snooze => match self.snooze() {
+                Ok(_) => Response::Snoozed(self.snooze.as_secs() / 60),
+                Err(e) => {
+                    error!("could not snooze alarms: {}", e);
+                    self.stop();
+                    Response::Failed
+                }
+            },
+        };
+        Some(locale::response(language, response))
+    }
+
+    /// Rings alarms as they become due, starting with the ones missed while Kara was not
+    /// running, until the task is dropped
+    pub async fn run(self: Arc<Self>, mut on_ring: impl FnMut(Ringing) + Send) {
+        loop {
+            self.ring_due().into_iter().for_each(&mut on_ring);
+            let wait = self
+                .next_due()
+                .and_then(|due| (due - self.time.now()).to_std().ok())
+                .map_or(MAX_WAIT, |wait| wait.min(MAX_WAIT));
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = self.changed.notified() => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    /// Time that only moves when a test moves it
+    #[derive(Clone)]
+    struct ManualTime(Arc<Mutex<DateTime<Utc>>>);
+
+    impl ManualTime {
+        fn new() -> Self {
+            let start = DateTime::parse_from_rfc3339("2022-08-01T06:00:00Z").unwrap();
+            Self(Arc::new(Mutex::new(start.with_timezone(&Utc))))
+        }
+
+        fn advance(&self, by: chrono::Duration) {
+            let mut now = self.0.lock().unwrap();
+            *now += by;
+        }
+    }
+
+    impl TimeSource for ManualTime {
+        fn now(&self) -> DateTime<Utc> {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    /// An alarms file of its own for each test, removed when the test ends
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir()
+                .join(format!("kara-alarms-{}-{name}.json", std::process::id()));
+            let _ = std::fs::remove_file(&path);
+            Self(path)
+        }
+
+        fn store(&self) -> Store {
+            Store::new(&self.0)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn minutes(minutes: i64) -> chrono::Duration {
+        chrono::Duration::minutes(minutes)
+    }
+
+    #[test]
+    fn rings_when_due() {
+        let file = TempFile::new("due");
+        let time = ManualTime::new();
+        let scheduler = Scheduler::with_time_source(file.store(), time.clone()).unwrap();
+        let due = time.now() + minutes(5);
+        let alarm = scheduler
+            .add(AlarmKind::Timer, Some("pasta".to_owned()), When::At(due))
+            .unwrap()
+            .unwrap();
+        assert_eq!(scheduler.next_due(), Some(due));
+        assert!(scheduler.ring_due().is_empty());
+
+        time.advance(minutes(5));
+        let ringing = scheduler.ring_due();
+        assert_eq!(ringing.len(), 1);
+        assert_eq!(ringing[0].id, alarm.id);
+        assert_eq!(ringing[0].label.as_deref(), Some("pasta"));
+        assert!(!ringing[0].missed);
+        assert!(scheduler.is_ringing());
+        assert!(scheduler.alarms().is_empty());
+        assert_eq!(scheduler.stop().len(), 1);
+        assert!(!scheduler.is_ringing());
+    }
+
+    #[test]
+    fn recurring_alarms_are_rescheduled() {
+        let file = TempFile::new("recurring");
+        let time = ManualTime::new();
+        let scheduler = Scheduler::with_time_source(file.store(), time.clone()).unwrap();
+        let repeat = Repeat {
+            days: Repeat::EVERY_DAY.to_vec(),
+            time: NaiveTime::from_hms_opt(7, 30, 0).unwrap(),
+        };
+        let alarm = scheduler
+            .add(AlarmKind::Alarm, None, When::Every(repeat.clone()))
+            .unwrap()
+            .unwrap();
+        assert_eq!(Some(alarm.due), repeat.next_after(time.now()));
+
+        time.advance(alarm.due - time.now());
+        assert_eq!(scheduler.ring_due().len(), 1);
+        let alarms = scheduler.alarms();
+        assert_eq!(alarms.len(), 1);
+        assert_eq!(alarms[0].id, alarm.id);
+        assert_eq!(Some(alarms[0].due), repeat.next_after(alarm.due));
+        assert!(alarms[0].due > time.now());
+    }
+
+    #[test]
+    fn snoozed_alarms_ring_again() {
+        let file = TempFile::new("snooze");
+        let time = ManualTime::new();
+        let scheduler = Scheduler::with_time_source(file.store(), time.clone())
+            .unwrap()
+            .with_snooze(Duration::from_secs(10 * 60));
+        scheduler
+            .add(AlarmKind::Alarm, None, When::At(time.now()))
+            .unwrap();
+        assert_eq!(scheduler.ring_due().len(), 1);
+
+        let snoozed = scheduler.snooze().unwrap();
+        assert_eq!(snoozed.len(), 1);
+        assert!(!scheduler.is_ringing());
+        assert_eq!(scheduler.next_due(), Some(time.now() + minutes(10)));
+
+        time.advance(minutes(9));
+        assert!(scheduler.ring_due().is_empty());
+        time.advance(minutes(1));
+        let ringing = scheduler.ring_due();
+        assert_eq!(ringing.len(), 1);
+        assert!(ringing[0].id > snoozed[0].id);
+    }
+
+    #[test]
+    fn missed_alarms_ring_at_startup() {
+        let file = TempFile::new("missed");
+        let time = ManualTime::new();
+        {
+            let scheduler = Scheduler::with_time_source(file.store(), time.clone()).unwrap();
+            for (label, due) in [("long ago", minutes(10)), ("recent", minutes(120))] {
+                scheduler
+                    .add(
+                        AlarmKind::Alarm,
+                        Some(label.to_owned()),
+                        When::At(time.now() + due),
+                    )
+                    .unwrap();
+            }
+        }
+
+        // Kara was not running while both were due
+        time.advance(minutes(130));
+        let scheduler = Scheduler::with_time_source(file.store(), time.clone())
+            .unwrap()
+            .with_missed_within(Duration::from_secs(60 * 60));
+        assert_eq!(scheduler.alarms().len(), 2);
+        let ringing = scheduler.ring_due();
+        assert_eq!(ringing.len(), 1);
+        assert_eq!(ringing[0].label.as_deref(), Some("recent"));
+        assert!(ringing[0].missed);
+        assert!(scheduler.alarms().is_empty());
+    }
+
+    #[test]
+    fn late_alarms_ring_after_startup() {
+        let file = TempFile::new("late");
+        let time = ManualTime::new();
+        let scheduler = Scheduler::with_time_source(file.store(), time.clone())
+            .unwrap()
+            .with_missed_within(Duration::from_secs(60 * 60));
+        assert!(scheduler.ring_due().is_empty());
+        scheduler
+            .add(AlarmKind::Alarm, None, When::At(time.now() + minutes(1)))
+            .unwrap();
+
+        // The computer slept through the alarm
+        time.advance(minutes(180));
+        let ringing = scheduler.ring_due();
+        assert_eq!(ringing.len(), 1);
+        assert!(ringing[0].missed);
+    }
+}
@@ -0,0 +1,118 @@
+//! Where alarms are kept between runs.
+//!
+//! Alarms are stored as JSON in `alarms.json` in Kara's data directory
+//! (`$XDG_DATA_HOME/kara/alarms.json` on Linux). The file is rewritten whenever an alarm
+//! is added, removed, goes off or is snoozed:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "next-id": 4,
+//!   "alarms": [
+//!     { "id": 1, "kind": "alarm", "label": "work", "due": "2022-08-01T05:30:00Z",
+//!       "repeat": { "days": ["Mon", "Tue", "Wed", "Thu", "Fri"], "time": "07:30:00" } },
+//!     { "id": 3, "kind": "timer", "label": "pasta", "due": "2022-07-31T18:12:00Z" }
+//!   ]
+//! }
+//! ```
+//!
+//! - `version`: the version of the format, currently 1
+//! - `next-id`: the id the next alarm will get. Ids are never reused
+//! - `kind`: `alarm` or `timer`
+//! - `label`: what the alarm is for, optional
+//! - `due`: when the alarm next goes off, in UTC
+//! - `repeat`: for recurring alarms, the days of the week they go off on and the local
+//!   time they go off at. `due` is moved to the next of those days each time they go off.
+//!   Optional
+//!
+//! A file that cannot be read is moved aside to `alarms.json.invalid` so that it is not
+//! overwritten.
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use super::Alarm;
+
+const VERSION: u32 = 1;
+
+/// The contents of the alarms file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(super) struct Saved {
+    version: u32,
+    pub next_id: u64,
+    pub alarms: Vec<Alarm>,
+}
+
+impl Default for Saved {
+    fn default() -> Self {
+        Self {
+            version: VERSION,
+            next_id: 1,
+            alarms: Vec::new(),
+        }
+    }
+}
+
+pub struct Store {
+    path: PathBuf,
+}
+
+impl Default for Store {
+    fn default() -> Self {
+        let mut path = dirs::data_dir().expect("could not find data dir");
+        path.push("kara");
+        path.push("alarms.json");
+        Self { path }
+    }
+}
+
+impl Store {
+    /// Keeps alarms in the file at `path`
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The saved alarms, none if the file does not exist yet
+    pub(super) fn load(&self) -> io::Result<Saved> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Saved::default()),
+            Err(e) => return Err(e),
+        };
+        match serde_json::from_str::<Saved>(&contents) {
+            Ok(saved) if saved.version == VERSION => Ok(saved),
+            result => {
+                match result {
+                    Ok(saved) => error!(
+                        "alarms file {} has unknown version {}",
+                        self.path.display(),
+                        saved.version
+                    ),
+                    Err(e) => error!("could not read {}: {}", self.path.display(), e),
+                }
+                fs::rename(&self.path, self.path.with_extension("json.invalid"))?;
+                Ok(Saved::default())
+            }
+        }
+    }
+
+    /// Replaces the saved alarms. The file is written next to the old one and renamed over
+    /// it, so a crash never leaves it half written
+    pub(super) fn save(&self, saved: &Saved) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let temporary = self.path.with_extension("json.tmp");
+        fs::write(&temporary, serde_json::to_string_pretty(saved)?)?;
+        fs::rename(temporary, &self.path)
+    }
+}
@@ -0,0 +1,217 @@
+//! A text session for the command line interface. Each line read from stdin is handled as
+//! a command, the same way the GUI handles a final transcript. After "take dictation",
+//! lines are written to the dictation file instead until "stop dictation".
+use std::sync::Arc;
+
+use kara_audio::{chime::Chime, crossbeam_channel, progress::ProgressReporter};
+use kara_events::{ProgressPhase, Speaker};
+use kara_nlu::{
+    normaliser::Normaliser,
+    punctuation::{Output, Restorer},
+    NLUParser,
+};
+use tokio::{
+    io::{self, AsyncBufReadExt, AsyncWriteExt, BufReader},
+    sync::mpsc,
+};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    config::state::ParsedConfig,
+    history::Dictation,
+    locale::{self, DictationControl, Response},
+    reload::ReloadSignal,
+    skills::{Context, Skills},
+};
+
+pub async fn start(
+    config: &ParsedConfig,
+    model_receiver: crossbeam_channel::Receiver<NLUParser>,
+) -> anyhow::Result<()> {
+    let mut language = config.general_settings.language;
+    let reporter = ProgressReporter::Terminal;
+    let loading = reporter.start(
+        ProgressPhase::LoadLanguageModel,
+        None,
+        "Loading language model",
+    );
+    let received = tokio::task::spawn_blocking(move || model_receiver.recv()).await?;
+    loading.finish(match received {
+        Ok(_) => "Language model loaded",
+        Err(_) => "No language model",
+    });
+    let mut model = received.map_err(|_| anyhow::anyhow!("no language model could be loaded"))?;
+    let mut normaliser = Normaliser::new(language);
+    let punctuation = config.nlu.punctuation;
+    let mut restorer = Restorer::new(language).with_outputs(punctuation);
+    let dictation = Dictation::configured()
+        .map_err(|e| error!("dictation is unavailable: {}", e))
+        .ok()
+        .map(Arc::new);
+    let mut dictating = false;
+    let (tx_ringing, mut rx_ringing) = mpsc::unbounded_channel();
+    let Skills {
+        dispatcher,
+        dialogue,
+        scheduler,
+    } = Skills::configured(config, move |ringing| {
+        // The session has ended if this fails
+        let _ = tx_ringing.send(ringing);
+    });
+    let unhandled = dispatcher.unhandled(model.intents());
+    if !unhandled.is_empty() {
+        debug!(
+            ?unhandled,
+            "no skill handles some intents of the language model"
+        );
+    }
+    let mut context = Context {
+        speaker: Speaker::Unknown,
+        language,
+        ..Context::default()
+    };
+
+    let mut lines = BufReader::new(io::stdin()).lines();
+    let mut stdout = io::stdout();
+    stdout.write_all(b"> ").await?;
+    stdout.flush().await?;
+    let mut reload = ReloadSignal::new();
+    let mut chime: Option<Chime> = None;
+    loop {
+        let line = tokio::select! {
+            line = lines.next_line() => match line? {
+                Some(line) => line,
+                None => break,
+            },
+            _ = reload.recv() => {
+                let settings = config.nlu.model_settings();
+                info!(language = language.code(), "reloading language model");
+                let loading = reporter.start(
+                    ProgressPhase::LoadLanguageModel,
+                    None,
+                    "Reloading language model",
+                );
+                let reloaded =
+                    tokio::task::spawn_blocking(move || locale::language_model(language, &settings))
+                        .await?;
+                loading.finish("Language model reloaded");
+                match reloaded {
+                    Ok(reloaded) => {
+                        model = reloaded;
+                        info!(language = language.code(), "reloaded language model");
+                    }
+                    Err(e) => error!("{}, keeping the current language model", e),
+                }
+                continue;
+            }
+            Some(ringing) = rx_ringing.recv() => {
+                let text = locale::response(language, Response::Ringing(ringing));
+                stdout.write_all(format!("\n{text}\n> ").as_bytes()).await?;
+                stdout.flush().await?;
+                // A chime that rang for too long has stopped by itself
+                if !chime.as_ref().map_or(false, Chime::is_ringing) {
+                    chime = Chime::ring()
+                        .map_err(|e| error!("could not ring the alarm: {}", e))
+                        .ok();
+                }
+                continue;
+            }
+        };
+        let text = normaliser.normalise(&line);
+        if let Some(reply) = scheduler
+            .as_ref()
+            .and_then(|scheduler| scheduler.control(language, &text.normalised))
+        {
+            chime = None;
+            stdout.write_all(format!("{reply}\n").as_bytes()).await?;
+        } else if dictating {
+            if locale::dictation_control(language, &text.normalised) == Some(DictationControl::Stop)
+            {
+                dictating = false;
+                let reply = locale::response(language, Response::DictationSaved);
+                stdout.write_all(format!("{reply}\n").as_bytes()).await?;
+            } else if let Some(dictation) = &dictation {
+                let dictation = Arc::clone(dictation);
+                let dictated = restorer.restore_for(Output::Dictation, &text.normalised);
+                tokio::task::spawn_blocking(move || {
+                    if let Err(e) = dictation.write(&dictated) {
+                        error!("could not write dictation: {}", e);
+                    }
+                });
+            }
+        } else if locale::dictation_control(language, &text.normalised)
+            == Some(DictationControl::Start)
+        {
+            dictating = dictation.is_some();
+            let reply = if dictating {
+                Response::Dictating
+            } else {
+                Response::Failed
+            };
+            let reply = locale::response(language, reply);
+            stdout.write_all(format!("{reply}\n").as_bytes()).await?;
+        } else if let Some(requested) = locale::language_switch(language, &text.normalised) {
+            let settings = config.nlu.model_settings();
+            let reply = if requested == language {
+                locale::response(language, Response::AlreadySpeaking)
+            } else if !locale::has_nlu_model(requested, settings.path.as_deref()) {
+                locale::response(language, Response::Unavailable(requested))
+            } else {
+                let loading = reporter.start(
+                    ProgressPhase::LoadLanguageModel,
+                    None,
+                    "Loading language model",
+                );
+                let loaded = tokio::task::spawn_blocking(move || {
+                    locale::language_model(requested, &settings)
+                })
+                .await?;
+                loading.finish("Language model loaded");
+                match loaded {
+                    Ok(loaded) => {
+                        model = loaded;
+                        language = requested;
+                        normaliser = Normaliser::new(language);
+                        restorer = Restorer::new(language).with_outputs(punctuation);
+                        context.language = language;
+                        locale::response(language, Response::Switching)
+                    }
+                    Err(e) => {
+                        error!(
+                            "could not load the {} language model: {}",
+                            requested.name(),
+                            e
+                        );
+                        locale::response(language, Response::Unavailable(requested))
+                    }
+                }
+            };
+            stdout.write_all(format!("{reply}\n").as_bytes()).await?;
+        } else if !text.normalised.trim().is_empty() {
+            let reply = match model.parse_in_context(&text.normalised, &dialogue.conversation()) {
+                Ok(commands) => {
+                    for parsed in &commands {
+                        debug!(
+                            intent = ?parsed.intent.intent_name,
+                            confidence = parsed.intent.confidence_score,
+                            "parsed command"
+                        );
+                    }
+                    dialogue
+                        .respond(&text.normalised, commands, context.clone())
+                        .await
+                        .text()
+                        .to_owned()
+                }
+                Err(e) => {
+                    warn!("{}", e);
+                    locale::response(language, Response::NotUnderstood)
+                }
+            };
+            stdout.write_all(format!("{reply}\n").as_bytes()).await?;
+        }
+        stdout.write_all(b"> ").await?;
+        stdout.flush().await?;
+    }
+    Ok(())
+}
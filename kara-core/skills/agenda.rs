@@ -0,0 +1,641 @@
+//! Adds, looks up and removes events in the [`Calendar`], e.g. "add the dentist to my
+//! calendar tomorrow at 3pm" or "what's on my calendar tomorrow". Events an enrolled
+//! speaker adds are theirs, and are left out of what other speakers hear about.
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, TimeZone, Utc};
+use kara_events::Speaker;
+use kara_nlu::{
+    intents::{Grain, Intent, SlotValue},
+    Language,
+};
+
+use super::{
+    alarms::{moment, repeat_days, unclear_time},
+    datetime::{short_date, time, weekday},
+    Context, Request, RequiredSlot, Response, Skill,
+};
+use crate::calendar::{Calendar, End, Event, Frequency, Occurrence, Removal, Rule, Time, Zone};
+
+/// Slots that say when an event is
+const TIME_SLOTS: [&str; 3] = ["date", "time", "timeofday"];
+/// How long events are when the command does not say
+const DEFAULT_LENGTH: i64 = 60;
+/// How far ahead events named in a command are looked for, in days
+const LOOK_AHEAD: i64 = 366;
+/// The most events read out at once
+const MAX_LISTED: usize = 8;
+
+/// When a command says an event is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Span {
+    /// A time of day
+    At(DateTime<Local>),
+    /// Whole days, from the first until the day before the second
+    Days(NaiveDate, NaiveDate),
+    Between(DateTime<Local>, DateTime<Local>),
+}
+
+impl Span {
+    /// The span as a range of times
+    fn range(&self) -> (DateTime<Local>, DateTime<Local>) {
+        match *self {
+            Span::At(at) => (at, at + Duration::minutes(DEFAULT_LENGTH)),
+            Span::Days(first, end) => (midnight(first), midnight(end)),
+            Span::Between(from, to) => (from, to),
+        }
+    }
+}
+
+pub struct Agenda {
+    calendar: Arc<Calendar>,
+}
+
+impl Agenda {
+    pub fn new(calendar: Arc<Calendar>) -> Self {
+        Self { calendar }
+    }
+
+    /// The occurrences between `from` and `to` of the events `speaker` may see
+    fn between(
+        &self,
+        from: DateTime<Local>,
+        to: DateTime<Local>,
+        speaker: &Speaker,
+    ) -> Vec<Occurrence> {
+        let mut occurrences = self.calendar.between(from, to);
+        occurrences.retain(|occurrence| match (&occurrence.owner, speaker) {
+            (None, _) => true,
+            (Some(owner), Speaker::Known(id)) => owner == id,
+            (Some(_), Speaker::Unknown) => false,
+        });
+        occurrences
+    }
+
+    fn set(&self, request: &Request, context: &Context) -> anyhow::Result<String> {
+        let language = context.language;
+        let summary = match request.slot("event_name").and_then(SlotValue::as_text) {
+            Some(summary) => summary.trim().to_owned(),
+            None => return Ok(unclear_event(language)),
+        };
+        let span = match span(request) {
+            Some(span) => span,
+            None => return Ok(unclear_time(language)),
+        };
+        let frequency = request
+            .slot("general_frequency")
+            .and_then(SlotValue::as_text)
+            .unwrap_or(&request.input);
+        let rule = repeat_days(language, frequency).map(|days| {
+            if days.len() == 7 {
+                Rule::new(Frequency::Daily, &[])
+            } else {
+                Rule::new(Frequency::Weekly, &days)
+            }
+        });
+        let (mut start, end) = match span {
+            Span::Days(first, end) if (end - first).num_days() > 1 => {
+                (Time::Date(first), Some(End::At(Time::Date(end))))
+            }
+            Span::Days(first, _) => (Time::Date(first), None),
+            Span::At(at) => (
+                at_time(at, rule.is_some()),
+                Some(End::For(Duration::minutes(DEFAULT_LENGTH))),
+            ),
+            Span::Between(from, to) => (at_time(from, rule.is_some()), Some(End::For(to - from))),
+        };
+        // Recurring events start on the first day they recur on
+        if let Some(first) = rule
+            .as_ref()
+            .and_then(|rule| rule.occurrences(start.naive(), None).next())
+        {
+            start = match start {
+                Time::Date(_) => Time::Date(first.date()),
+                Time::DateTime(_, zone) => Time::DateTime(first, zone),
+            };
+        }
+        let mut event = Event::new(summary.clone(), start, end);
+        event.location = request
+            .slot("place_name")
+            .and_then(SlotValue::as_text)
+            .map(str::to_owned);
+        if let Speaker::Known(id) = &context.speaker {
+            event.owner = Some(id.clone());
+        }
+        let recurring = rule.is_some();
+        if let Some(rule) = rule {
+            event = event.with_rule(rule);
+        }
+        self.calendar.add(event)?;
+        let when = describe(language, start.local(), start.is_date());
+        Ok(match (language, recurring) {
+            (Language::English, false) => format!("Added {summary} {when}"),
+            (Language::English, true) => format!("Added {summary} {when}, recurring"),
+            (Language::French, false) => format!("{summary} ajouté {when}"),
+            (Language::French, true) => format!("{summary} ajouté {when}, de façon récurrente"),
+            (Language::German, false) => format!("{summary} {when} eingetragen"),
+            (Language::German, true) => format!("{summary} {when} eingetragen, wiederkehrend"),
+            (Language::Spanish, false) => format!("He añadido {summary} {when}"),
+            (Language::Spanish, true) => {
+                format!("He añadido {summary} {when}, de forma recurrente")
+            }
+        })
+    }
+
+    /// Lists the events in the span the command names, today if it names none, or says
+    /// when the event it names is next
+    fn query(&self, request: &Request, context: &Context) -> String {
+        let (language, speaker) = (context.language, &context.speaker);
+        let now = Local::now();
+        if let Some(name) = request.slot("event_name").and_then(SlotValue::as_text) {
+            let (from, to) = span(request)
+                .map(|span| span.range())
+                .unwrap_or((now, now + Duration::days(LOOK_AHEAD)));
+            return match named(&self.between(from, to, speaker), name).first() {
+                Some(occurrence) => {
+                    let summary = &occurrence.summary;
+                    let when = occurrence_time(language, occurrence);
+                    let details = details(language, occurrence);
+                    match language {
+                        Language::English => format!("{summary} is {when}{details}"),
+                        Language::French => format!("{summary} est prévu {when}{details}"),
+                        Language::German => format!("{summary} ist {when}{details}"),
+                        Language::Spanish => format!("{summary} es {when}{details}"),
+                    }
+                }
+                None => not_found(language),
+            };
+        }
+        let today = now.naive_local().date();
+        let span = span(request).unwrap_or(Span::Days(today, today + Duration::days(1)));
+        let (from, to) = span.range();
+        let occurrences = self.between(from, to, speaker);
+        if occurrences.is_empty() {
+            return match language {
+                Language::English => "There's nothing on your calendar",
+                Language::French => "Il n'y a rien dans ton agenda",
+                Language::German => "Es steht nichts in deinem Kalender",
+                Language::Spanish => "No tienes nada en tu calendario",
+            }
+            .to_owned();
+        }
+        let one_day = matches!(span, Span::Days(first, end) if (end - first).num_days() == 1);
+        let mut items: Vec<_> = occurrences
+            .iter()
+            .take(MAX_LISTED)
+            .map(|occurrence| {
+                let summary = &occurrence.summary;
+                match (one_day, occurrence.all_day) {
+                    (true, true) => summary.clone(),
+                    (true, false) => format!("{} {summary}", time(language, &occurrence.start)),
+                    (false, _) => format!("{summary} {}", occurrence_time(language, occurrence)),
+                }
+            })
+            .collect();
+        if occurrences.len() > MAX_LISTED {
+            let more = occurrences.len() - MAX_LISTED;
+            items.push(match language {
+                Language::English => format!("and {more} more"),
+                Language::French => format!("et {more} autres"),
+                Language::German => format!("und {more} weitere"),
+                Language::Spanish => format!("y {more} más"),
+            });
+        }
+        let (count, list) = (occurrences.len(), items.join(", "));
+        match (language, count) {
+            (Language::English, 1) => format!("You have one event: {list}"),
+            (Language::English, _) => format!("You have {count} events: {list}"),
+            (Language::French, 1) => format!("Tu as un événement : {list}"),
+            (Language::French, _) => format!("Tu as {count} événements : {list}"),
+            (Language::German, 1) => format!("Du hast einen Termin: {list}"),
+            (Language::German, _) => format!("Du hast {count} Termine: {list}"),
+            (Language::Spanish, 1) => format!("Tienes un evento: {list}"),
+            (Language::Spanish, _) => format!("Tienes {count} eventos: {list}"),
+        }
+    }
+
+    /// Removes the event the command names. Naming a day of a recurring event only
+    /// removes that day's occurrence
+    fn remove(&self, request: &Request, context: &Context) -> anyhow::Result<String> {
+        let language = context.language;
+        let now = Local::now();
+        let name = request.slot("event_name").and_then(SlotValue::as_text);
+        let span = span(request);
+        let (from, to) = match (span, name) {
+            (Some(span), _) => span.range(),
+            (None, Some(_)) => (now, now + Duration::days(LOOK_AHEAD)),
+            (None, None) => return Ok(unclear_event(language)),
+        };
+        let occurrences = self.between(from, to, &context.speaker);
+        let matching = match name {
+            Some(name) => named(&occurrences, name),
+            None => occurrences.iter().collect(),
+        };
+        let occurrence = match matching.as_slice() {
+            [] => return Ok(not_found(language)),
+            [first, rest @ ..] if rest.iter().all(|other| other.uid == first.uid) => first,
+            several => {
+                let mut seen = Vec::new();
+                let list = several
+                    .iter()
+                    .filter(|occurrence| {
+                        let new = !seen.contains(&&occurrence.uid);
+                        seen.push(&occurrence.uid);
+                        new
+                    })
+                    .take(MAX_LISTED)
+                    .map(|occurrence| {
+                        format!(
+                            "{} {}",
+                            occurrence.summary,
+                            occurrence_time(language, occurrence)
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                return Ok(match language {
+                    Language::English => format!("Which one? {list}"),
+                    Language::French => format!("Lequel ? {list}"),
+                    Language::German => format!("Welchen? {list}"),
+                    Language::Spanish => format!("¿Cuál? {list}"),
+                });
+            }
+        };
+        if !occurrence.writable {
+            return Ok(match language {
+                Language::English => "That event is in a calendar I can only read",
+                Language::French => "Cet événement est dans un agenda que je peux seulement lire",
+                Language::German => "Dieser Termin steht in einem Kalender, den ich nur lesen kann",
+                Language::Spanish => "Ese evento está en un calendario que solo puedo leer",
+            }
+            .to_owned());
+        }
+        let removal = match (span, occurrence.recurrence) {
+            (Some(_), Some(start)) => Removal::Occurrence(start),
+            _ => Removal::Event,
+        };
+        if !self.calendar.remove(&occurrence.uid, removal)? {
+            return Ok(not_found(language));
+        }
+        let summary = &occurrence.summary;
+        Ok(match (language, removal) {
+            (Language::English, Removal::Event) => format!("Removed {summary}"),
+            (Language::French, Removal::Event) => format!("{summary} supprimé"),
+            (Language::German, Removal::Event) => format!("{summary} gelöscht"),
+            (Language::Spanish, Removal::Event) => format!("He eliminado {summary}"),
+            (language, Removal::Occurrence(_)) => {
+                let when = occurrence_time(language, occurrence);
+                match language {
+                    Language::English => format!("Removed {summary} {when}"),
+                    Language::French => format!("{summary} supprimé {when}"),
+                    Language::German => format!("{summary} {when} gelöscht"),
+                    Language::Spanish => format!("He eliminado {summary} {when}"),
+                }
+            }
+        })
+    }
+}
+
+#[async_trait]
+impl Skill for Agenda {
+    fn name(&self) -> &'static str {
+        "calendar"
+    }
+
+    fn intents(&self) -> &[Intent] {
+        &[
+            Intent::CalendarSet,
+            Intent::CalendarQuery,
+            Intent::CalendarRemove,
+        ]
+    }
+
+    fn required_slots(&self, request: &Request, context: &Context) -> Vec<RequiredSlot> {
+        match request.intent {
+            Intent::CalendarSet => vec![RequiredSlot {
+                name: "event_name",
+                entity: "event_name",
+                question: match context.language {
+                    Language::English => "What should I add?",
+                    Language::French => "Qu'est-ce que je dois ajouter ?",
+                    Language::German => "Was soll ich eintragen?",
+                    Language::Spanish => "¿Qué añado?",
+                }
+                .to_owned(),
+            }],
+            _ => Vec::new(),
+        }
+    }
+
+    async fn handle(&self, request: &Request, context: &Context) -> anyhow::Result<Response> {
+        let text = match request.intent {
+            Intent::CalendarSet => self.set(request, context)?,
+            Intent::CalendarRemove => self.remove(request, context)?,
+            _ => self.query(request, context),
+        };
+        Ok(Response::text(text))
+    }
+}
+
+/// When the command says, combining a day from one slot with a time from another, e.g.
+/// "on friday" and "at 3pm"
+fn span(request: &Request) -> Option<Span> {
+    let spans: Vec<_> = request
+        .slots
+        .iter()
+        .filter(|slot| TIME_SLOTS.contains(&slot.slot_name.as_str()))
+        .filter_map(|slot| match &slot.value {
+            SlotValue::InstantTime(instant) => {
+                let at = instant.datetime().ok()?.with_timezone(&Local);
+                let first = at.naive_local().date();
+                let months = |months: u32| {
+                    let month0 = first.month0() + months;
+                    NaiveDate::from_ymd_opt(first.year() + (month0 / 12) as i32, month0 % 12 + 1, 1)
+                };
+                Some(match instant.grain {
+                    Grain::Hour | Grain::Minute | Grain::Second => Span::At(at),
+                    Grain::Day => Span::Days(first, first + Duration::days(1)),
+                    Grain::Week => Span::Days(first, first + Duration::weeks(1)),
+                    Grain::Month => Span::Days(first, months(1)?),
+                    Grain::Quarter => Span::Days(first, months(3)?),
+                    Grain::Year => Span::Days(first, months(12)?),
+                })
+            }
+            SlotValue::TimeInterval(interval) => {
+                let from = interval.start().and_then(Result::ok)?.with_timezone(&Local);
+                let to = match interval.end().and_then(Result::ok) {
+                    Some(to) => to.with_timezone(&Local),
+                    None => midnight(from.naive_local().date() + Duration::days(1)),
+                };
+                Some(Span::Between(from, to))
+            }
+            _ => None,
+        })
+        .collect();
+    let day = spans.iter().find_map(|span| match span {
+        Span::Days(first, end) if (*end - *first).num_days() == 1 => Some(*first),
+        _ => None,
+    });
+    let at = spans.iter().find_map(|span| match span {
+        Span::At(at) => Some(*at),
+        _ => None,
+    });
+    match (day, at) {
+        (Some(day), Some(at)) => Local
+            .from_local_datetime(&day.and_time(at.naive_local().time()))
+            .earliest()
+            .map(Span::At),
+        _ => spans.first().copied(),
+    }
+}
+
+/// A time for a new event. Recurring events keep their wall clock time when clocks change
+fn at_time(at: DateTime<Local>, recurring: bool) -> Time {
+    if recurring {
+        Time::DateTime(at.naive_local(), Zone::Floating)
+    } else {
+        Time::DateTime(at.naive_utc(), Zone::Utc)
+    }
+}
+
+fn midnight(date: NaiveDate) -> DateTime<Local> {
+    Time::Date(date).local()
+}
+
+/// The occurrences whose summary contains `name` or is part of it, e.g. "dentist" for
+/// "dentist appointment"
+fn named<'a>(occurrences: &'a [Occurrence], name: &str) -> Vec<&'a Occurrence> {
+    let name = name.trim().to_lowercase();
+    occurrences
+        .iter()
+        .filter(|occurrence| {
+            let summary = occurrence.summary.trim().to_lowercase();
+            !summary.is_empty() && (summary.contains(&name) || name.contains(&summary))
+        })
+        .collect()
+}
+
+fn occurrence_time(language: Language, occurrence: &Occurrence) -> String {
+    describe(language, occurrence.start, occurrence.all_day)
+}
+
+/// When an event ends, if it ends on the day it starts, and where it is, e.g. " until
+/// 4:00 PM at the surgery"
+fn details(language: Language, occurrence: &Occurrence) -> String {
+    let mut details = String::new();
+    let same_day = occurrence.end.naive_local().date() == occurrence.start.naive_local().date();
+    if !occurrence.all_day && same_day && occurrence.end > occurrence.start {
+        let end = time(language, &occurrence.end);
+        details.push_str(&match language {
+            Language::English => format!(" until {end}"),
+            Language::French => format!(" jusqu'à {end}"),
+            Language::German => format!(" bis {end}"),
+            Language::Spanish => format!(" hasta las {end}"),
+        });
+    }
+    if let Some(location) = occurrence
+        .location
+        .as_deref()
+        .map(str::trim)
+        .filter(|location| !location.is_empty())
+    {
+        details.push_str(&match language {
+            Language::English => format!(" at {location}"),
+            Language::French => format!(", lieu : {location}"),
+            Language::German => format!(", Ort: {location}"),
+            Language::Spanish => format!(" en {location}"),
+        });
+    }
+    details
+}
+
+/// When an event starts, as it follows the event's name, e.g. "at 3:00 PM on Friday" or
+/// "on Friday" for events that last all day
+fn describe(language: Language, start: DateTime<Local>, all_day: bool) -> String {
+    let date = start.naive_local().date();
+    if all_day {
+        let this_week = (0..7).contains(&(date - Local::now().naive_local().date()).num_days());
+        let day = day(language, date);
+        return match (language, this_week) {
+            (Language::English, _) => format!("on {day}"),
+            (Language::French, true) => day,
+            (Language::French, false) => format!("le {day}"),
+            (Language::German, _) => format!("am {day}"),
+            (Language::Spanish, _) => format!("el {day}"),
+        };
+    }
+    let at = moment(language, start.with_timezone(&Utc), Utc::now());
+    let today = date == Local::now().naive_local().date();
+    match (language, today) {
+        (Language::English, _) => format!("at {at}"),
+        (Language::French, true) => format!("à {at}"),
+        (Language::German, true) => format!("um {at}"),
+        (Language::Spanish, true) => format!("a {at}"),
+        (_, false) => at,
+    }
+}
+
+/// A day of the week within the next week, a date otherwise
+fn day(language: Language, date: NaiveDate) -> String {
+    let days = (date - Local::now().naive_local().date()).num_days();
+    if (0..7).contains(&days) {
+        weekday(language, date.weekday()).to_owned()
+    } else {
+        short_date(language, date)
+    }
+}
+
+fn unclear_event(language: Language) -> String {
+    match language {
+        Language::English => "Sorry, I couldn't tell which event",
+        Language::French => "Désolée, je n'ai pas compris quel événement",
+        Language::German => "Entschuldigung, ich habe nicht verstanden, welcher Termin",
+        Language::Spanish => "Lo siento, no he entendido qué evento",
+    }
+    .to_owned()
+}
+
+fn not_found(language: Language) -> String {
+    match language {
+        Language::English => "I couldn't find that event",
+        Language::French => "Je n'ai pas trouvé cet événement",
+        Language::German => "Diesen Termin habe ich nicht gefunden",
+        Language::Spanish => "No he encontrado ese evento",
+    }
+    .to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use kara_nlu::intents::{Range, Slot, Text};
+
+    use super::*;
+
+    /// A calendar directory of its own for each test, removed when the test ends
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("kara-agenda-{}-{name}", std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn named_query(name: &str) -> Request {
+        Request {
+            intent: Intent::CalendarQuery,
+            input: format!("when is the {name}"),
+            slots: vec![Slot {
+                raw_value: name.to_owned(),
+                value: SlotValue::Custom(Text {
+                    value: name.to_owned(),
+                }),
+                alternatives: Vec::new(),
+                range: Range::default(),
+                entity: "event_name".to_owned(),
+                slot_name: "event_name".to_owned(),
+            }],
+        }
+    }
+
+    fn tomorrow_at(hour: u32) -> Time {
+        let tomorrow = Local::now().naive_local().date() + Duration::days(1);
+        Time::DateTime(tomorrow.and_hms_opt(hour, 0, 0).unwrap(), Zone::Floating)
+    }
+
+    fn occurrence(start: DateTime<Local>, end: DateTime<Local>, all_day: bool) -> Occurrence {
+        Occurrence {
+            uid: "1@test".to_owned(),
+            summary: "Dentist".to_owned(),
+            location: Some(" The surgery ".to_owned()),
+            owner: None,
+            start,
+            end,
+            all_day,
+            recurrence: None,
+            writable: true,
+        }
+    }
+
+    #[test]
+    fn says_when_named_events_end_and_where_they_are() {
+        let dir = TempDir::new("named");
+        let agenda = Agenda::new(Arc::new(Calendar::new(&dir.0, Vec::new())));
+        let mut event = Event::new(
+            "Dentist",
+            tomorrow_at(15),
+            Some(End::For(Duration::minutes(30))),
+        );
+        event.location = Some("the surgery".to_owned());
+        agenda.calendar.add(event).unwrap();
+        let start = tomorrow_at(15).local();
+        let found = agenda.calendar.between(start, start + Duration::days(1));
+        let when = occurrence_time(Language::English, &found[0]);
+        let end = time(Language::English, &(start + Duration::minutes(30)));
+        let context = Context::default();
+        assert_eq!(
+            agenda.query(&named_query("dentist"), &context),
+            format!("Dentist is {when} until {end} at the surgery")
+        );
+        assert_eq!(
+            agenda.query(&named_query("birthday"), &context),
+            not_found(Language::English)
+        );
+    }
+
+    #[test]
+    fn keeps_events_of_each_speaker_apart() {
+        let dir = TempDir::new("owners");
+        let agenda = Agenda::new(Arc::new(Calendar::new(&dir.0, Vec::new())));
+        let mut dentist = Event::new("Dentist", tomorrow_at(15), None);
+        dentist.owner = Some("alice".to_owned());
+        agenda.calendar.add(dentist).unwrap();
+        agenda
+            .calendar
+            .add(Event::new("Party", tomorrow_at(18), None))
+            .unwrap();
+        let from = tomorrow_at(0).local();
+        let summaries = |speaker: Speaker| -> Vec<String> {
+            agenda
+                .between(from, from + Duration::days(1), &speaker)
+                .into_iter()
+                .map(|occurrence| occurrence.summary)
+                .collect()
+        };
+        assert_eq!(
+            summaries(Speaker::Known("alice".to_owned())),
+            ["Dentist", "Party"]
+        );
+        assert_eq!(summaries(Speaker::Known("bob".to_owned())), ["Party"]);
+        assert_eq!(summaries(Speaker::Unknown), ["Party"]);
+    }
+
+    #[test]
+    fn details_of_events() {
+        let start = tomorrow_at(15).local();
+        let end = start + Duration::hours(1);
+        let until = time(Language::French, &end);
+        assert_eq!(
+            details(Language::French, &occurrence(start, end, false)),
+            format!(" jusqu'à {until}, lieu : The surgery")
+        );
+        // Ends are left out of events that last all day or end on another day
+        let midnight = tomorrow_at(0).local();
+        let all_day = occurrence(midnight, midnight + Duration::days(1), true);
+        assert_eq!(details(Language::German, &all_day), ", Ort: The surgery");
+        let mut overnight = occurrence(start, start + Duration::days(1), false);
+        overnight.location = Some(" ".to_owned());
+        assert_eq!(details(Language::English, &overnight), "");
+    }
+}
@@ -0,0 +1,578 @@
+//! Sets, lists and removes the alarms and timers kept by the [`Scheduler`]. Alarms that
+//! are ringing are stopped and snoozed by the front ends, see [`Scheduler::control`].
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Local, Utc, Weekday};
+use kara_events::AlarmKind;
+use kara_nlu::{
+    intents::{Intent, SlotValue},
+    Language,
+};
+
+use super::{
+    datetime::{short_date, time, weekday},
+    Context, Request, RequiredSlot, Response, Skill,
+};
+use crate::scheduler::{Alarm, Repeat, Scheduler, SystemTimeSource, TimeSource, When};
+
+/// Slots that say when an alarm goes off
+const TIME_SLOTS: [&str; 2] = ["time", "timeofday"];
+
+pub struct Alarms<T: TimeSource = SystemTimeSource> {
+    scheduler: Arc<Scheduler<T>>,
+}
+
+impl<T: TimeSource> Alarms<T> {
+    pub fn new(scheduler: Arc<Scheduler<T>>) -> Self {
+        Self { scheduler }
+    }
+
+    fn set(&self, request: &Request, language: Language) -> anyhow::Result<String> {
+        let now = self.scheduler.now();
+        let kind = if is_timer(language, request) {
+            AlarmKind::Timer
+        } else {
+            AlarmKind::Alarm
+        };
+        let due = match due(request, kind, now) {
+            Some(due) => due,
+            None => return Ok(unclear_time(language)),
+        };
+        let label = request
+            .slot("event_name")
+            .and_then(SlotValue::as_text)
+            .map(str::to_owned);
+        let frequency = request
+            .slot("general_frequency")
+            .and_then(SlotValue::as_text)
+            .unwrap_or(&request.input);
+        if let (AlarmKind::Alarm, Some(days)) = (kind, repeat_days(language, frequency)) {
+            let repeat = Repeat {
+                days,
+                time: due.with_timezone(&Local).naive_local().time(),
+            };
+            let at = time(language, &due.with_timezone(&Local));
+            return Ok(
+                match self.scheduler.add(kind, label, When::Every(repeat))? {
+                    Some(_) => match language {
+                        Language::English => format!("Recurring alarm set for {at}"),
+                        Language::French => format!("Alarme récurrente réglée pour {at}"),
+                        Language::German => format!("Wiederkehrender Wecker gestellt für {at}"),
+                        Language::Spanish => format!("Alarma recurrente puesta para las {at}"),
+                    },
+                    None => unclear_time(language),
+                },
+            );
+        }
+        if due <= now {
+            return Ok(match language {
+                Language::English => "That time has already passed",
+                Language::French => "Cette heure est déjà passée",
+                Language::German => "Diese Zeit ist schon vorbei",
+                Language::Spanish => "Esa hora ya ha pasado",
+            }
+            .to_owned());
+        }
+        self.scheduler.add(kind, label, When::At(due))?;
+        Ok(match kind {
+            AlarmKind::Timer => {
+                let span = span(language, due - now);
+                match language {
+                    Language::English => format!("Timer set for {span}"),
+                    Language::French => format!("Minuteur lancé pour {span}"),
+                    Language::German => format!("Timer gestellt auf {span}"),
+                    Language::Spanish => format!("Temporizador puesto para {span}"),
+                }
+            }
+            AlarmKind::Alarm => {
+                let moment = moment(language, due, now);
+                match language {
+                    Language::English => format!("Alarm set for {moment}"),
+                    Language::French => format!("Alarme réglée pour {moment}"),
+                    Language::German => format!("Wecker gestellt für {moment}"),
+                    Language::Spanish => format!("Alarma puesta para {moment}"),
+                }
+            }
+        })
+    }
+
+    fn list(&self, language: Language) -> String {
+        let now = self.scheduler.now();
+        let alarms = self.scheduler.alarms();
+        if alarms.is_empty() {
+            return match language {
+                Language::English => "You have no alarms",
+                Language::French => "Tu n'as aucune alarme",
+                Language::German => "Du hast keine Wecker",
+                Language::Spanish => "No tienes alarmas",
+            }
+            .to_owned();
+        }
+        let list = alarms
+            .iter()
+            .map(|alarm| {
+                let moment = moment(language, alarm.due, now);
+                match &alarm.label {
+                    Some(label) => format!("{moment} ({label})"),
+                    None => moment,
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        match language {
+            Language::English => format!("Your alarms: {list}"),
+            Language::French => format!("Tes alarmes : {list}"),
+            Language::German => format!("Deine Wecker: {list}"),
+            Language::Spanish => format!("Tus alarmas: {list}"),
+        }
+    }
+
+    /// Removes the alarms with the label, time or kind the command names, or every alarm if
+    /// it names none
+    fn remove(&self, request: &Request, language: Language) -> anyhow::Result<String> {
+        let label = request
+            .slot("event_name")
+            .and_then(SlotValue::as_text)
+            .map(str::to_lowercase);
+        let at = due(request, AlarmKind::Alarm, self.scheduler.now())
+            .map(|due| due.with_timezone(&Local).naive_local().time());
+        let timers = is_timer(language, request);
+        let removed = self.scheduler.remove(|alarm: &Alarm| {
+            label.as_ref().map_or(true, |label| {
+                alarm
+                    .label
+                    .as_ref()
+                    .map_or(false, |name| name.to_lowercase() == *label)
+            }) && at.map_or(true, |at| {
+                alarm.due.with_timezone(&Local).naive_local().time() == at
+            }) && (!timers || alarm.kind == AlarmKind::Timer)
+        })?;
+        Ok(match (language, removed.len()) {
+            (Language::English, 0) => "I couldn't find that alarm".to_owned(),
+            (Language::English, 1) => "Alarm removed".to_owned(),
+            (Language::English, n) => format!("{n} alarms removed"),
+            (Language::French, 0) => "Je n'ai pas trouvé cette alarme".to_owned(),
+            (Language::French, 1) => "Alarme supprimée".to_owned(),
+            (Language::French, n) => format!("{n} alarmes supprimées"),
+            (Language::German, 0) => "Diesen Wecker habe ich nicht gefunden".to_owned(),
+            (Language::German, 1) => "Wecker gelöscht".to_owned(),
+            (Language::German, n) => format!("{n} Wecker gelöscht"),
+            (Language::Spanish, 0) => "No he encontrado esa alarma".to_owned(),
+            (Language::Spanish, 1) => "Alarma eliminada".to_owned(),
+            (Language::Spanish, n) => format!("{n} alarmas eliminadas"),
+        })
+    }
+}
+
+#[async_trait]
+impl<T: TimeSource + 'static> Skill for Alarms<T> {
+    fn name(&self) -> &'static str {
+        "alarms"
+    }
+
+    fn intents(&self) -> &[Intent] {
+        &[Intent::AlarmSet, Intent::AlarmQuery, Intent::AlarmRemove]
+    }
+
+    fn required_slots(&self, request: &Request, context: &Context) -> Vec<RequiredSlot> {
+        match request.intent {
+            Intent::AlarmSet => vec![RequiredSlot {
+                name: "time",
+                entity: "snips/time",
+                question: match context.language {
+                    Language::English => "When should it go off?",
+                    Language::French => "Pour quand ?",
+                    Language::German => "Wann soll er klingeln?",
+                    Language::Spanish => "¿Para cuándo?",
+                }
+                .to_owned(),
+            }],
+            _ => Vec::new(),
+        }
+    }
+
+    async fn handle(&self, request: &Request, context: &Context) -> anyhow::Result<Response> {
+        let language = context.language;
+        let text = match request.intent {
+            Intent::AlarmSet => self.set(request, language)?,
+            Intent::AlarmRemove => self.remove(request, language)?,
+            _ => self.list(language),
+        };
+        Ok(Response::text(text))
+    }
+}
+
+/// When the command says the alarm goes off. Timers set "for" a while end when the span
+/// does, alarms set for a span go off when it starts
+fn due(request: &Request, kind: AlarmKind, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    request
+        .slots
+        .iter()
+        .filter(|slot| TIME_SLOTS.contains(&slot.slot_name.as_str()))
+        .find_map(|slot| match &slot.value {
+            SlotValue::InstantTime(instant) => Some(instant.datetime().ok()?.with_timezone(&Utc)),
+            SlotValue::TimeInterval(interval) => {
+                let (first, second) = match kind {
+                    AlarmKind::Timer => (interval.end(), interval.start()),
+                    AlarmKind::Alarm => (interval.start(), interval.end()),
+                };
+                Some(first.or(second)?.ok()?.with_timezone(&Utc))
+            }
+            SlotValue::Duration(duration) => Some(now + duration.to_chrono()),
+            _ => None,
+        })
+}
+
+fn is_timer(language: Language, request: &Request) -> bool {
+    let words: &[&str] = match language {
+        Language::English => &["timer"],
+        Language::French => &["minuteur", "minuterie"],
+        Language::German => &["timer", "kurzzeitwecker"],
+        Language::Spanish => &["temporizador"],
+    };
+    let text = request
+        .slot("alarm_type")
+        .and_then(SlotValue::as_text)
+        .unwrap_or(&request.input)
+        .to_lowercase();
+    words.iter().any(|word| text.contains(word))
+}
+
+/// The days a recurring alarm goes off on, if `text` asks for one, e.g. "every day" or
+/// "on mondays"
+pub(super) fn repeat_days(language: Language, text: &str) -> Option<Vec<Weekday>> {
+    let (every_day, weekdays, weekends, day_phrases): (&[&str], &[&str], &[&str], &[&str]) =
+        match language {
+            Language::English => (
+                &["every day", "everyday", "daily"],
+                &["weekdays", "every weekday", "workdays"],
+                &["weekend", "weekends"],
+                &["every {}", "{}s"],
+            ),
+            Language::French => (
+                &["tous les jours", "chaque jour"],
+                &["en semaine", "jours de semaine"],
+                &["week end", "week ends", "weekend"],
+                &["tous les {}s", "chaque {}"],
+            ),
+            Language::German => (
+                &["jeden tag", "täglich"],
+                &["werktags", "wochentags"],
+                &["wochenende", "wochenenden"],
+                &["jeden {}", "{}s"],
+            ),
+            Language::Spanish => (
+                &["todos los días", "cada día", "diario", "diariamente"],
+                &["entre semana", "días laborables"],
+                &["fin de semana", "fines de semana"],
+                &["todos los {}", "todos los {}s", "cada {}"],
+            ),
+        };
+    let words: Vec<_> = text
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_owned)
+        .collect();
+    let text = format!(" {} ", words.join(" "));
+    let said = |phrase: &str| text.contains(&format!(" {phrase} "));
+    if every_day.iter().any(|phrase| said(phrase)) {
+        return Some(Repeat::EVERY_DAY.to_vec());
+    }
+    if weekdays.iter().any(|phrase| said(phrase)) {
+        return Some(Repeat::EVERY_DAY[..5].to_vec());
+    }
+    if weekends.iter().any(|phrase| said(phrase)) {
+        return Some(Repeat::EVERY_DAY[5..].to_vec());
+    }
+    let days: Vec<_> = Repeat::EVERY_DAY
+        .iter()
+        .copied()
+        .filter(|day| {
+            let name = weekday(language, *day).to_lowercase();
+            day_phrases
+                .iter()
+                .any(|phrase| said(&phrase.replace("{}", &name)))
+        })
+        .collect();
+    if days.is_empty() {
+        None
+    } else {
+        Some(days)
+    }
+}
+
+/// When `due` is, e.g. "7:00 AM" today or "7:00 AM on Tuesday" later in the week
+pub(super) fn moment(language: Language, due: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let due = due.with_timezone(&Local);
+    let (date, today) = (
+        due.naive_local().date(),
+        now.with_timezone(&Local).naive_local().date(),
+    );
+    let at = time(language, &due);
+    let days = (date - today).num_days();
+    if days == 0 {
+        return match language {
+            Language::Spanish => format!("las {at}"),
+            _ => at,
+        };
+    }
+    let day = if (1..7).contains(&days) {
+        weekday(language, date.weekday()).to_owned()
+    } else {
+        short_date(language, date)
+    };
+    let on_date = !(1..7).contains(&days);
+    match (language, on_date) {
+        (Language::English, _) => format!("{at} on {day}"),
+        (Language::French, false) => format!("{day} à {at}"),
+        (Language::French, true) => format!("le {day} à {at}"),
+        (Language::German, false) => format!("{day} um {at}"),
+        (Language::German, true) => format!("am {day} um {at}"),
+        (Language::Spanish, _) => format!("el {day} a las {at}"),
+    }
+}
+
+/// How long `span` is in hours and minutes, or seconds if it is shorter than a minute
+fn span(language: Language, span: chrono::Duration) -> String {
+    let seconds = span.num_seconds().max(1);
+    // Rounded to the nearest minute, as the command took a moment to handle
+    let minutes = (seconds + 30) / 60;
+    let units = |count: i64, unit: (&str, &str)| {
+        let unit = if count == 1 { unit.0 } else { unit.1 };
+        format!("{count} {unit}")
+    };
+    let (hour, minute, second, and) = match language {
+        Language::English => (
+            ("hour", "hours"),
+            ("minute", "minutes"),
+            ("second", "seconds"),
+            "and",
+        ),
+        Language::French => (
+            ("heure", "heures"),
+            ("minute", "minutes"),
+            ("seconde", "secondes"),
+            "et",
+        ),
+        Language::German => (
+            ("Stunde", "Stunden"),
+            ("Minute", "Minuten"),
+            ("Sekunde", "Sekunden"),
+            "und",
+        ),
+        Language::Spanish => (
+            ("hora", "horas"),
+            ("minuto", "minutos"),
+            ("segundo", "segundos"),
+            "y",
+        ),
+    };
+    if seconds < 60 {
+        return units(seconds, second);
+    }
+    match (minutes / 60, minutes % 60) {
+        (0, minutes) => units(minutes, minute),
+        (hours, 0) => units(hours, hour),
+        (hours, minutes) => format!("{} {and} {}", units(hours, hour), units(minutes, minute)),
+    }
+}
+
+pub(super) fn unclear_time(language: Language) -> String {
+    match language {
+        Language::English => "Sorry, I couldn't tell when",
+        Language::French => "Désolée, je n'ai pas compris quand",
+        Language::German => "Entschuldigung, ich habe nicht verstanden, wann",
+        Language::Spanish => "Lo siento, no he entendido cuándo",
+    }
+    .to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, TimeZone};
+    use kara_nlu::intents::{Duration, Grain, InstantTime, Precision, Range, Slot, TimeInterval};
+
+    use super::*;
+
+    fn slot(name: &str, value: SlotValue) -> Slot {
+        Slot {
+            raw_value: String::new(),
+            value,
+            alternatives: Vec::new(),
+            range: Range::default(),
+            entity: "snips/datetime".to_owned(),
+            slot_name: name.to_owned(),
+        }
+    }
+
+    fn set(slots: Vec<Slot>) -> Request {
+        Request {
+            intent: Intent::AlarmSet,
+            input: String::new(),
+            slots,
+        }
+    }
+
+    fn utc(text: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(text)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    /// 1 June 2026 at `hour` on the local clock, plus `days`
+    fn local(days: u32, hour: u32) -> DateTime<Utc> {
+        let date = NaiveDate::from_ymd_opt(2026, 6, 1 + days).unwrap();
+        Local
+            .from_local_datetime(&date.and_hms_opt(hour, 0, 0).unwrap())
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn says_spans() {
+        let english = |seconds| span(Language::English, chrono::Duration::seconds(seconds));
+        assert_eq!(english(0), "1 second");
+        assert_eq!(english(30), "30 seconds");
+        assert_eq!(english(89), "1 minute");
+        assert_eq!(english(90), "2 minutes");
+        assert_eq!(english(3600), "1 hour");
+        assert_eq!(english(5400), "1 hour and 30 minutes");
+        assert_eq!(
+            span(Language::German, chrono::Duration::minutes(125)),
+            "2 Stunden und 5 Minuten"
+        );
+        assert_eq!(
+            span(Language::Spanish, chrono::Duration::seconds(1)),
+            "1 segundo"
+        );
+    }
+
+    #[test]
+    fn reads_repeated_days() {
+        use Weekday::*;
+        let english = |text| repeat_days(Language::English, text);
+        assert_eq!(
+            english("wake me every day at 7"),
+            Some(Repeat::EVERY_DAY.to_vec())
+        );
+        assert_eq!(
+            english("an alarm on weekdays"),
+            Some(vec![Mon, Tue, Wed, Thu, Fri])
+        );
+        assert_eq!(english("at the weekend"), Some(vec![Sat, Sun]));
+        assert_eq!(
+            english("every Monday and every Friday"),
+            Some(vec![Mon, Fri])
+        );
+        assert_eq!(english("on Tuesdays"), Some(vec![Tue]));
+        assert_eq!(english("on Tuesday"), None);
+        assert_eq!(english("tomorrow at 7"), None);
+        assert_eq!(
+            repeat_days(Language::French, "tous les lundis à 7 heures"),
+            Some(vec![Mon])
+        );
+        assert_eq!(
+            repeat_days(Language::German, "jeden Mittwoch"),
+            Some(vec![Wed])
+        );
+        assert_eq!(
+            repeat_days(Language::Spanish, "todos los días"),
+            Some(Repeat::EVERY_DAY.to_vec())
+        );
+    }
+
+    #[test]
+    fn says_moments() {
+        let now = local(0, 6);
+        assert_eq!(moment(Language::English, local(0, 7), now), "7:00 AM");
+        assert_eq!(moment(Language::Spanish, local(0, 7), now), "las 07:00");
+        assert_eq!(
+            moment(Language::English, local(1, 19), now),
+            "7:00 PM on Tuesday"
+        );
+        assert_eq!(
+            moment(Language::German, local(1, 7), now),
+            "Dienstag um 07:00"
+        );
+        assert_eq!(
+            moment(Language::English, local(10, 7), now),
+            "7:00 AM on 11 June 2026"
+        );
+        assert_eq!(
+            moment(Language::French, local(10, 7), now),
+            "le 11 juin 2026 à 07:00"
+        );
+    }
+
+    #[test]
+    fn finds_when_alarms_are_due() {
+        let now = utc("2026-06-01T06:00:00Z");
+        let instant = SlotValue::InstantTime(InstantTime {
+            value: "2026-06-01 07:30:00 +00:00".to_owned(),
+            grain: Grain::Minute,
+            precision: Precision::Exact,
+        });
+        assert_eq!(
+            due(&set(vec![slot("time", instant)]), AlarmKind::Alarm, now),
+            Some(utc("2026-06-01T07:30:00Z"))
+        );
+
+        // Alarms go off when a span starts, timers when it ends
+        let interval = || {
+            SlotValue::TimeInterval(TimeInterval {
+                from: Some("2026-06-01 08:00:00 +02:00".to_owned()),
+                to: Some("2026-06-01 09:00:00 +02:00".to_owned()),
+            })
+        };
+        assert_eq!(
+            due(&set(vec![slot("time", interval())]), AlarmKind::Alarm, now),
+            Some(utc("2026-06-01T06:00:00Z"))
+        );
+        assert_eq!(
+            due(
+                &set(vec![slot("timeofday", interval())]),
+                AlarmKind::Timer,
+                now
+            ),
+            Some(utc("2026-06-01T07:00:00Z"))
+        );
+        let open = SlotValue::TimeInterval(TimeInterval {
+            from: Some("2026-06-01 08:00:00 +00:00".to_owned()),
+            to: None,
+        });
+        assert_eq!(
+            due(&set(vec![slot("time", open)]), AlarmKind::Timer, now),
+            Some(utc("2026-06-01T08:00:00Z"))
+        );
+
+        let duration = SlotValue::Duration(Duration {
+            years: 0,
+            quarters: 0,
+            months: 0,
+            weeks: 0,
+            days: 0,
+            hours: 1,
+            minutes: 30,
+            seconds: 0,
+            precision: Precision::Exact,
+        });
+        assert_eq!(
+            due(&set(vec![slot("time", duration)]), AlarmKind::Timer, now),
+            Some(utc("2026-06-01T07:30:00Z"))
+        );
+
+        // Only time slots count
+        let other = SlotValue::InstantTime(InstantTime {
+            value: "2026-06-02 07:30:00 +00:00".to_owned(),
+            grain: Grain::Minute,
+            precision: Precision::Exact,
+        });
+        assert_eq!(
+            due(&set(vec![slot("date", other)]), AlarmKind::Alarm, now),
+            None
+        );
+        assert_eq!(due(&set(Vec::new()), AlarmKind::Alarm, now), None);
+    }
+}
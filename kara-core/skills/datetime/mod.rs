@@ -0,0 +1,537 @@
+//! The time, date and day of the week, here or in a named place, and conversions between
+//! time zones such as "what's 3pm London in Tokyo". Time zones come from the tz database
+//! compiled into Kara, so nothing is looked up online.
+mod places;
+
+use async_trait::async_trait;
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, Local, NaiveDate, NaiveDateTime, Offset, TimeZone,
+    Timelike, Utc, Weekday,
+};
+use chrono_tz::Tz;
+use kara_nlu::{
+    intents::{Intent, SlotValue},
+    Language,
+};
+
+use super::{Context, Request, Response, Skill};
+
+/// Slots that name a place or a time zone
+const PLACE_SLOTS: [&str; 2] = ["place_name", "time_zone"];
+/// Slots that hold a time or a date
+const TIME_SLOTS: [&str; 3] = ["time", "timeofday", "date"];
+
+pub struct Clock;
+
+#[async_trait]
+impl Skill for Clock {
+    fn name(&self) -> &'static str {
+        "clock"
+    }
+
+    fn intents(&self) -> &[Intent] {
+        &[Intent::DateTimeQuery, Intent::DatetimeConvert]
+    }
+
+    async fn handle(&self, request: &Request, context: &Context) -> anyhow::Result<Response> {
+        let language = context.language;
+        let mut places: Vec<_> = request
+            .slots
+            .iter()
+            .filter(|slot| PLACE_SLOTS.contains(&slot.slot_name.as_str()))
+            .collect();
+        places.sort_by_key(|slot| slot.range.start);
+        let mut zones = Vec::with_capacity(places.len());
+        for slot in places {
+            let place = slot.value.as_text().unwrap_or(&slot.raw_value);
+            match places::time_zone(place) {
+                Some(tz) => zones.push(Zone::Place(places::display_name(place), tz)),
+                None => return Ok(Response::text(unknown_place(language, place))),
+            }
+        }
+        let instant = request
+            .slots
+            .iter()
+            .filter(|slot| TIME_SLOTS.contains(&slot.slot_name.as_str()))
+            .find_map(|slot| match &slot.value {
+                SlotValue::InstantTime(time) => {
+                    Some((slot.slot_name.as_str(), time.datetime().ok()?))
+                }
+                _ => None,
+            });
+
+        let text = match (&request.intent, zones.as_slice()) {
+            (Intent::DatetimeConvert, [target]) => {
+                convert(language, &Zone::Local, target, instant.map(|(_, at)| at))
+            }
+            (Intent::DatetimeConvert, [source, target, ..]) => {
+                convert(language, source, target, instant.map(|(_, at)| at))
+            }
+            _ => {
+                let zone = zones.first().unwrap_or(&Zone::Local);
+                match (Question::of(&request.input), instant) {
+                    (Question::Time, _) => now(language, zone, Question::Time),
+                    (_, Some(("date", at))) => weekday_of(language, at.naive_local().date()),
+                    (question, _) => now(language, zone, question),
+                }
+            }
+        };
+        Ok(Response::text(text))
+    }
+}
+
+/// Where a time is read
+#[derive(Debug, Clone)]
+enum Zone {
+    /// The time zone Kara runs in
+    Local,
+    /// A place and its time zone
+    Place(String, Tz),
+}
+
+impl Zone {
+    fn place(&self) -> Option<&str> {
+        match self {
+            Zone::Local => None,
+            Zone::Place(place, _) => Some(place),
+        }
+    }
+
+    /// The offset from UTC of this zone at `utc`
+    fn offset(&self, utc: &NaiveDateTime) -> FixedOffset {
+        match self {
+            Zone::Local => Local.offset_from_utc_datetime(utc).fix(),
+            Zone::Place(_, tz) => tz.offset_from_utc_datetime(utc).fix(),
+        }
+    }
+
+    fn now(&self) -> DateTime<FixedOffset> {
+        self.at_utc(&Utc::now().naive_utc())
+    }
+
+    /// The time in this zone at `instant`
+    fn at(&self, instant: &DateTime<FixedOffset>) -> DateTime<FixedOffset> {
+        self.at_utc(&instant.naive_utc())
+    }
+
+    fn at_utc(&self, utc: &NaiveDateTime) -> DateTime<FixedOffset> {
+        self.offset(utc).from_utc_datetime(utc)
+    }
+
+    /// `time` on a clock in this zone. The earlier of the two is taken where clocks go back,
+    /// and a time skipped where they go forward is moved on by the change, so 2:30 is 3:30
+    /// when clocks go from 2:00 to 3:00
+    fn localise(&self, time: &NaiveDateTime) -> Option<DateTime<FixedOffset>> {
+        let offset = |time: &NaiveDateTime| match self {
+            Zone::Local => Some(Local.from_local_datetime(time).earliest()?.offset().fix()),
+            Zone::Place(_, tz) => Some(tz.from_local_datetime(time).earliest()?.offset().fix()),
+        };
+        match offset(time) {
+            Some(offset) => offset.from_local_datetime(time).single(),
+            // Read on the clock from before the change, which puts it as far past the change
+            None => {
+                let before = offset(&(*time - Duration::days(1)))?;
+                Some(self.at(&before.from_local_datetime(time).single()?))
+            }
+        }
+    }
+}
+
+/// What a time query asks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Question {
+    Time,
+    Date,
+    Weekday,
+}
+
+impl Question {
+    fn of(input: &str) -> Self {
+        let input = input.to_lowercase();
+        let words: Vec<_> = input
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect();
+        let said = |candidates: &[&str]| words.iter().any(|word| candidates.contains(word));
+        if said(&["time", "heure", "uhr", "uhrzeit", "hora"]) {
+            Question::Time
+        } else if said(&["date", "datum", "fecha"]) {
+            Question::Date
+        } else if said(&["day", "weekday", "jour", "tag", "wochentag", "día", "dia"]) {
+            Question::Weekday
+        } else {
+            Question::Time
+        }
+    }
+}
+
+/// The answer to `question` for the current time in `zone`
+fn now(language: Language, zone: &Zone, question: Question) -> String {
+    let now = zone.now();
+    match question {
+        Question::Time => time_now(language, zone.place(), &now),
+        Question::Date => {
+            let date = full_date(language, now.naive_local().date());
+            match (language, zone.place()) {
+                (Language::English, None) => format!("It's {date}"),
+                (Language::English, Some(place)) => format!("In {place} it's {date}"),
+                (Language::French, None) => format!("Nous sommes le {date}"),
+                (Language::French, Some(place)) => format!("À {place}, nous sommes le {date}"),
+                (Language::German, None) => format!("Heute ist {date}"),
+                (Language::German, Some(place)) => format!("In {place} ist heute {date}"),
+                (Language::Spanish, None) => format!("Hoy es {date}"),
+                (Language::Spanish, Some(place)) => format!("En {place} hoy es {date}"),
+            }
+        }
+        Question::Weekday => {
+            let day = weekday(language, now.weekday());
+            match (language, zone.place()) {
+                (Language::English, None) => format!("It's {day}"),
+                (Language::English, Some(place)) => format!("In {place} it's {day}"),
+                (Language::French, None) => format!("Nous sommes {day}"),
+                (Language::French, Some(place)) => format!("À {place}, nous sommes {day}"),
+                (Language::German, None) => format!("Heute ist {day}"),
+                (Language::German, Some(place)) => format!("In {place} ist heute {day}"),
+                (Language::Spanish, None) => format!("Hoy es {day}"),
+                (Language::Spanish, Some(place)) => format!("En {place} hoy es {day}"),
+            }
+        }
+    }
+}
+
+fn time_now(language: Language, place: Option<&str>, now: &DateTime<FixedOffset>) -> String {
+    let time = time(language, now);
+    match (language, place) {
+        (Language::English, None) => format!("It's {time}"),
+        (Language::English, Some(place)) => format!("It's {time} in {place}"),
+        (Language::French, None) => format!("Il est {time}"),
+        (Language::French, Some(place)) => format!("Il est {time} à {place}"),
+        (Language::German, None) => format!("Es ist {time}"),
+        (Language::German, Some(place)) => format!("In {place} ist es {time}"),
+        (Language::Spanish, None) => format!("Ahora {} {time}", spanish_article(now)),
+        (Language::Spanish, Some(place)) => {
+            format!("En {place} {} {time}", spanish_article(now))
+        }
+    }
+}
+
+/// The day of the week `date` falls on
+fn weekday_of(language: Language, date: NaiveDate) -> String {
+    let day = weekday(language, date.weekday());
+    let date = short_date(language, date);
+    match language {
+        Language::English => format!("{date} is a {day}"),
+        Language::French => format!("Le {date} est un {day}"),
+        Language::German => format!("Der {date} ist ein {day}"),
+        Language::Spanish => format!("El {date} es {day}"),
+    }
+}
+
+/// The time at `target` when it is `at` in `source`, or now if no time was given
+fn convert(
+    language: Language,
+    source: &Zone,
+    target: &Zone,
+    at: Option<DateTime<FixedOffset>>,
+) -> String {
+    let source_time = match at {
+        // The parser reads times on Kara's clock, they are taken as said in `source`
+        Some(at) => source
+            .localise(&at.naive_local())
+            .unwrap_or_else(|| source.now()),
+        None => return time_now(language, target.place(), &target.now()),
+    };
+    let target_time = target.at(&source_time);
+    let mut converted = time(language, &target_time);
+    if target_time.naive_local().date() != source_time.naive_local().date() {
+        converted = format!("{converted} ({})", weekday(language, target_time.weekday()));
+    }
+    let said = time(language, &source_time);
+    let said = match (language, source.place()) {
+        (_, None) => said,
+        (Language::English | Language::German, Some(place)) => format!("{said} in {place}"),
+        (Language::French, Some(place)) => format!("{said} à {place}"),
+        (Language::Spanish, Some(place)) => format!("{said} en {place}"),
+    };
+    let place = target.place().unwrap_or_default();
+    match language {
+        Language::English => format!("{said} is {converted} in {place}"),
+        Language::French => format!("{said}, il est {converted} à {place}"),
+        Language::German => format!("{said} ist es in {place} {converted}"),
+        Language::Spanish => format!(
+            "Cuando {} {said}, en {place} {} {converted}",
+            spanish_article(&source_time),
+            spanish_article(&target_time)
+        ),
+    }
+}
+
+pub(super) fn time<Tz: TimeZone>(language: Language, time: &DateTime<Tz>) -> String
+where
+    Tz::Offset: std::fmt::Display,
+{
+    match language {
+        Language::English => time.format("%-I:%M %p").to_string(),
+        _ => time.format("%H:%M").to_string(),
+    }
+}
+
+/// "son las", or "es la" for one o'clock
+fn spanish_article(time: &DateTime<FixedOffset>) -> &'static str {
+    if time.hour() % 12 == 1 {
+        "es la"
+    } else {
+        "son las"
+    }
+}
+
+pub(super) fn weekday(language: Language, day: Weekday) -> &'static str {
+    let names = match language {
+        Language::English => [
+            "Monday",
+            "Tuesday",
+            "Wednesday",
+            "Thursday",
+            "Friday",
+            "Saturday",
+            "Sunday",
+        ],
+        Language::French => [
+            "lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi", "dimanche",
+        ],
+        Language::German => [
+            "Montag",
+            "Dienstag",
+            "Mittwoch",
+            "Donnerstag",
+            "Freitag",
+            "Samstag",
+            "Sonntag",
+        ],
+        Language::Spanish => [
+            "lunes",
+            "martes",
+            "miércoles",
+            "jueves",
+            "viernes",
+            "sábado",
+            "domingo",
+        ],
+    };
+    names[day.num_days_from_monday() as usize]
+}
+
+fn month(language: Language, date: NaiveDate) -> &'static str {
+    let names = match language {
+        Language::English => [
+            "January",
+            "February",
+            "March",
+            "April",
+            "May",
+            "June",
+            "July",
+            "August",
+            "September",
+            "October",
+            "November",
+            "December",
+        ],
+        Language::French => [
+            "janvier",
+            "février",
+            "mars",
+            "avril",
+            "mai",
+            "juin",
+            "juillet",
+            "août",
+            "septembre",
+            "octobre",
+            "novembre",
+            "décembre",
+        ],
+        Language::German => [
+            "Januar",
+            "Februar",
+            "März",
+            "April",
+            "Mai",
+            "Juni",
+            "Juli",
+            "August",
+            "September",
+            "Oktober",
+            "November",
+            "Dezember",
+        ],
+        Language::Spanish => [
+            "enero",
+            "febrero",
+            "marzo",
+            "abril",
+            "mayo",
+            "junio",
+            "julio",
+            "agosto",
+            "septiembre",
+            "octubre",
+            "noviembre",
+            "diciembre",
+        ],
+    };
+    names[date.month0() as usize]
+}
+
+/// `date` without its weekday, e.g. "25 December 2022"
+pub(super) fn short_date(language: Language, date: NaiveDate) -> String {
+    let (day, month, year) = (date.day(), month(language, date), date.year());
+    match language {
+        Language::English | Language::French => format!("{day} {month} {year}"),
+        Language::German => format!("{day}. {month} {year}"),
+        Language::Spanish => format!("{day} de {month} de {year}"),
+    }
+}
+
+/// `date` with its weekday, e.g. "Sunday 25 December 2022"
+fn full_date(language: Language, date: NaiveDate) -> String {
+    let day = weekday(language, date.weekday());
+    let date = short_date(language, date);
+    match language {
+        Language::German => format!("{day}, {date}"),
+        _ => format!("{day} {date}"),
+    }
+}
+
+fn unknown_place(language: Language, place: &str) -> String {
+    match language {
+        Language::English => format!("Sorry, I don't know the time zone of {place}"),
+        Language::French => format!("Désolée, je ne connais pas le fuseau horaire de {place}"),
+        Language::German => format!("Entschuldigung, ich kenne die Zeitzone von {place} nicht"),
+        Language::Spanish => format!("Lo siento, no conozco la zona horaria de {place}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zone(place: &str) -> Zone {
+        Zone::Place(
+            places::display_name(place),
+            places::time_zone(place).unwrap(),
+        )
+    }
+
+    /// `time` as the parser gives it, on a clock that is never changed
+    fn said(time: &str) -> DateTime<FixedOffset> {
+        let time = NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap();
+        Utc.from_utc_datetime(&time).into()
+    }
+
+    #[test]
+    fn asks_what_was_asked() {
+        assert_eq!(Question::of("what time is it"), Question::Time);
+        assert_eq!(Question::of("What's the date?"), Question::Date);
+        assert_eq!(Question::of("what day is it today"), Question::Weekday);
+        assert_eq!(Question::of("quelle heure est-il"), Question::Time);
+        assert_eq!(Question::of("quel jour sommes-nous"), Question::Weekday);
+        assert_eq!(Question::of("welches Datum haben wir"), Question::Date);
+        assert_eq!(
+            Question::of("welcher Wochentag ist heute"),
+            Question::Weekday
+        );
+        assert_eq!(Question::of("qué día es hoy"), Question::Weekday);
+        // Words are matched whole, "today" is no day
+        assert_eq!(Question::of("what's up today"), Question::Time);
+        assert_eq!(Question::of("dime la fecha y la hora"), Question::Time);
+    }
+
+    #[test]
+    fn finds_time_zones() {
+        assert_eq!(places::time_zone("Tokyo"), Some(Tz::Asia__Tokyo));
+        assert_eq!(places::time_zone(" New York "), Some(Tz::America__New_York));
+        assert_eq!(places::time_zone("the UK"), Some(Tz::Europe__London));
+        assert_eq!(places::time_zone("Londres"), Some(Tz::Europe__London));
+        assert_eq!(places::time_zone("PST"), Some(Tz::America__Los_Angeles));
+        assert_eq!(places::time_zone("europe/paris"), Some(Tz::Europe__Paris));
+        assert_eq!(
+            places::time_zone("buenos aires"),
+            Some(Tz::America__Argentina__Buenos_Aires)
+        );
+        assert_eq!(places::time_zone("atlantis"), None);
+        assert_eq!(places::time_zone(""), None);
+    }
+
+    #[test]
+    fn names_places() {
+        assert_eq!(places::display_name("new york"), "New York");
+        assert_eq!(places::display_name(" pst"), "PST");
+        assert_eq!(places::display_name("pacific time"), "Pacific Time");
+        assert_eq!(places::display_name("münchen"), "München");
+    }
+
+    #[test]
+    fn converts_times_between_places() {
+        let (london, tokyo) = (zone("london"), zone("tokyo"));
+        let at = Some(said("2026-06-15 15:00"));
+        assert_eq!(
+            convert(Language::English, &london, &tokyo, at),
+            "3:00 PM in London is 11:00 PM in Tokyo"
+        );
+        assert_eq!(
+            convert(Language::French, &london, &tokyo, at),
+            "15:00 à London, il est 23:00 à Tokyo"
+        );
+        assert_eq!(
+            convert(Language::German, &london, &tokyo, at),
+            "15:00 in London ist es in Tokyo 23:00"
+        );
+        assert_eq!(
+            convert(
+                Language::Spanish,
+                &tokyo,
+                &london,
+                Some(said("2026-06-15 09:00"))
+            ),
+            "Cuando son las 09:00 en Tokyo, en London es la 01:00"
+        );
+        // The day is given when it differs
+        assert_eq!(
+            convert(
+                Language::English,
+                &zone("new york"),
+                &tokyo,
+                Some(said("2026-01-10 20:00"))
+            ),
+            "8:00 PM in New York is 10:00 AM (Sunday) in Tokyo"
+        );
+        // Where clocks go back the earlier time is taken
+        assert_eq!(
+            convert(
+                Language::English,
+                &london,
+                &tokyo,
+                Some(said("2026-10-25 01:30"))
+            ),
+            "1:30 AM in London is 9:30 AM in Tokyo"
+        );
+    }
+
+    #[test]
+    fn moves_times_skipped_by_the_clocks_forward() {
+        let (paris, tokyo) = (zone("paris"), zone("tokyo"));
+        assert_eq!(
+            convert(
+                Language::English,
+                &paris,
+                &tokyo,
+                Some(said("2026-03-29 02:30"))
+            ),
+            "3:30 AM in Paris is 10:30 AM in Tokyo"
+        );
+        let time = NaiveDateTime::parse_from_str("2026-03-08 02:15", "%Y-%m-%d %H:%M").unwrap();
+        let moved = zone("new york").localise(&time).unwrap();
+        assert_eq!(moved.to_rfc3339(), "2026-03-08T03:15:00-04:00");
+    }
+}
@@ -0,0 +1,166 @@
+//! An offline table from places and time zone abbreviations to time zones. Cities that
+//! name a zone of the tz database, such as Tokyo or New York, are found there, the table
+//! adds other large cities, countries with a single time zone, a few names in French,
+//! German and Spanish, and common abbreviations.
+use chrono_tz::{Tz, TZ_VARIANTS};
+
+#[rustfmt::skip]
+const PLACES: &[(&str, Tz)] = &[
+    // North America
+    ("san francisco", Tz::America__Los_Angeles), ("seattle", Tz::America__Los_Angeles),
+    ("las vegas", Tz::America__Los_Angeles), ("san diego", Tz::America__Los_Angeles),
+    ("portland", Tz::America__Los_Angeles), ("california", Tz::America__Los_Angeles),
+    ("boston", Tz::America__New_York), ("washington", Tz::America__New_York),
+    ("washington dc", Tz::America__New_York), ("miami", Tz::America__New_York),
+    ("atlanta", Tz::America__New_York), ("philadelphia", Tz::America__New_York),
+    ("new york city", Tz::America__New_York), ("nueva york", Tz::America__New_York),
+    ("dallas", Tz::America__Chicago), ("houston", Tz::America__Chicago),
+    ("austin", Tz::America__Chicago), ("minneapolis", Tz::America__Chicago),
+    ("new orleans", Tz::America__Chicago), ("texas", Tz::America__Chicago),
+    ("salt lake city", Tz::America__Denver), ("montreal", Tz::America__Toronto),
+    ("ottawa", Tz::America__Toronto), ("calgary", Tz::America__Edmonton),
+    ("rio de janeiro", Tz::America__Sao_Paulo), ("rio", Tz::America__Sao_Paulo),
+    ("brasilia", Tz::America__Sao_Paulo), ("brazil", Tz::America__Sao_Paulo),
+    ("argentina", Tz::America__Argentina__Buenos_Aires), ("colombia", Tz::America__Bogota),
+    ("peru", Tz::America__Lima), ("chile", Tz::America__Santiago),
+    // Europe
+    ("barcelona", Tz::Europe__Madrid), ("seville", Tz::Europe__Madrid),
+    ("spain", Tz::Europe__Madrid), ("espagne", Tz::Europe__Madrid),
+    ("españa", Tz::Europe__Madrid), ("spanien", Tz::Europe__Madrid),
+    ("milan", Tz::Europe__Rome), ("florence", Tz::Europe__Rome), ("venice", Tz::Europe__Rome),
+    ("naples", Tz::Europe__Rome), ("roma", Tz::Europe__Rome), ("rom", Tz::Europe__Rome),
+    ("italy", Tz::Europe__Rome), ("italie", Tz::Europe__Rome), ("italien", Tz::Europe__Rome),
+    ("italia", Tz::Europe__Rome),
+    ("munich", Tz::Europe__Berlin), ("münchen", Tz::Europe__Berlin),
+    ("hamburg", Tz::Europe__Berlin), ("frankfurt", Tz::Europe__Berlin),
+    ("cologne", Tz::Europe__Berlin), ("köln", Tz::Europe__Berlin),
+    ("germany", Tz::Europe__Berlin), ("allemagne", Tz::Europe__Berlin),
+    ("deutschland", Tz::Europe__Berlin), ("alemania", Tz::Europe__Berlin),
+    ("marseille", Tz::Europe__Paris), ("lyon", Tz::Europe__Paris), ("nice", Tz::Europe__Paris),
+    ("toulouse", Tz::Europe__Paris), ("bordeaux", Tz::Europe__Paris),
+    ("france", Tz::Europe__Paris), ("frankreich", Tz::Europe__Paris),
+    ("francia", Tz::Europe__Paris),
+    ("manchester", Tz::Europe__London), ("birmingham", Tz::Europe__London),
+    ("liverpool", Tz::Europe__London), ("edinburgh", Tz::Europe__London),
+    ("glasgow", Tz::Europe__London), ("londres", Tz::Europe__London),
+    ("england", Tz::Europe__London), ("britain", Tz::Europe__London),
+    ("great britain", Tz::Europe__London), ("united kingdom", Tz::Europe__London),
+    ("uk", Tz::Europe__London), ("scotland", Tz::Europe__London), ("wales", Tz::Europe__London),
+    ("angleterre", Tz::Europe__London), ("inglaterra", Tz::Europe__London),
+    ("ireland", Tz::Europe__Dublin), ("porto", Tz::Europe__Lisbon),
+    ("portugal", Tz::Europe__Lisbon), ("lisbonne", Tz::Europe__Lisbon),
+    ("lissabon", Tz::Europe__Lisbon), ("lisboa", Tz::Europe__Lisbon),
+    ("rotterdam", Tz::Europe__Amsterdam), ("the hague", Tz::Europe__Amsterdam),
+    ("netherlands", Tz::Europe__Amsterdam), ("holland", Tz::Europe__Amsterdam),
+    ("antwerp", Tz::Europe__Brussels), ("belgium", Tz::Europe__Brussels),
+    ("bruxelles", Tz::Europe__Brussels), ("brüssel", Tz::Europe__Brussels),
+    ("bruselas", Tz::Europe__Brussels),
+    ("geneva", Tz::Europe__Zurich), ("genève", Tz::Europe__Zurich), ("genf", Tz::Europe__Zurich),
+    ("ginebra", Tz::Europe__Zurich), ("switzerland", Tz::Europe__Zurich),
+    ("austria", Tz::Europe__Vienna), ("vienne", Tz::Europe__Vienna),
+    ("wien", Tz::Europe__Vienna), ("viena", Tz::Europe__Vienna),
+    ("krakow", Tz::Europe__Warsaw), ("poland", Tz::Europe__Warsaw),
+    ("varsovie", Tz::Europe__Warsaw), ("warschau", Tz::Europe__Warsaw),
+    ("varsovia", Tz::Europe__Warsaw),
+    ("sweden", Tz::Europe__Stockholm), ("norway", Tz::Europe__Oslo),
+    ("denmark", Tz::Europe__Copenhagen), ("finland", Tz::Europe__Helsinki),
+    ("greece", Tz::Europe__Athens), ("turkey", Tz::Europe__Istanbul),
+    ("st petersburg", Tz::Europe__Moscow), ("saint petersburg", Tz::Europe__Moscow),
+    ("russia", Tz::Europe__Moscow), ("moscou", Tz::Europe__Moscow),
+    ("moskau", Tz::Europe__Moscow), ("moscú", Tz::Europe__Moscow),
+    // Asia and the Middle East
+    ("beijing", Tz::Asia__Shanghai), ("peking", Tz::Asia__Shanghai),
+    ("pékin", Tz::Asia__Shanghai), ("pekín", Tz::Asia__Shanghai),
+    ("guangzhou", Tz::Asia__Shanghai), ("shenzhen", Tz::Asia__Shanghai),
+    ("chengdu", Tz::Asia__Shanghai), ("china", Tz::Asia__Shanghai), ("chine", Tz::Asia__Shanghai),
+    ("mumbai", Tz::Asia__Kolkata), ("bombay", Tz::Asia__Kolkata), ("delhi", Tz::Asia__Kolkata),
+    ("new delhi", Tz::Asia__Kolkata), ("bangalore", Tz::Asia__Kolkata),
+    ("bengaluru", Tz::Asia__Kolkata), ("chennai", Tz::Asia__Kolkata),
+    ("hyderabad", Tz::Asia__Kolkata), ("india", Tz::Asia__Kolkata), ("inde", Tz::Asia__Kolkata),
+    ("indien", Tz::Asia__Kolkata),
+    ("osaka", Tz::Asia__Tokyo), ("kyoto", Tz::Asia__Tokyo), ("tokio", Tz::Asia__Tokyo),
+    ("japan", Tz::Asia__Tokyo), ("japon", Tz::Asia__Tokyo), ("japón", Tz::Asia__Tokyo),
+    ("south korea", Tz::Asia__Seoul), ("korea", Tz::Asia__Seoul),
+    ("thailand", Tz::Asia__Bangkok), ("hanoi", Tz::Asia__Bangkok),
+    ("saigon", Tz::Asia__Ho_Chi_Minh), ("ho chi minh city", Tz::Asia__Ho_Chi_Minh),
+    ("vietnam", Tz::Asia__Ho_Chi_Minh), ("philippines", Tz::Asia__Manila),
+    ("abu dhabi", Tz::Asia__Dubai), ("united arab emirates", Tz::Asia__Dubai),
+    ("uae", Tz::Asia__Dubai), ("saudi arabia", Tz::Asia__Riyadh),
+    ("tel aviv", Tz::Asia__Jerusalem), ("israel", Tz::Asia__Jerusalem),
+    ("iran", Tz::Asia__Tehran), ("islamabad", Tz::Asia__Karachi),
+    ("lahore", Tz::Asia__Karachi), ("pakistan", Tz::Asia__Karachi),
+    ("bangladesh", Tz::Asia__Dhaka),
+    // Oceania and Africa
+    ("canberra", Tz::Australia__Sydney), ("wellington", Tz::Pacific__Auckland),
+    ("new zealand", Tz::Pacific__Auckland),
+    ("cape town", Tz::Africa__Johannesburg), ("durban", Tz::Africa__Johannesburg),
+    ("south africa", Tz::Africa__Johannesburg), ("marrakech", Tz::Africa__Casablanca),
+    ("egypt", Tz::Africa__Cairo), ("le caire", Tz::Africa__Cairo), ("kairo", Tz::Africa__Cairo),
+    ("el cairo", Tz::Africa__Cairo), ("nigeria", Tz::Africa__Lagos),
+    ("kenya", Tz::Africa__Nairobi),
+];
+
+/// Time zone abbreviations and names
+#[rustfmt::skip]
+const ABBREVIATIONS: &[(&str, Tz)] = &[
+    ("utc", Tz::UTC), ("gmt", Tz::UTC), ("coordinated universal time", Tz::UTC),
+    ("greenwich mean time", Tz::UTC),
+    ("est", Tz::America__New_York), ("edt", Tz::America__New_York),
+    ("eastern time", Tz::America__New_York), ("cst", Tz::America__Chicago),
+    ("cdt", Tz::America__Chicago), ("central time", Tz::America__Chicago),
+    ("mst", Tz::America__Denver), ("mdt", Tz::America__Denver),
+    ("mountain time", Tz::America__Denver), ("pst", Tz::America__Los_Angeles),
+    ("pdt", Tz::America__Los_Angeles), ("pacific time", Tz::America__Los_Angeles),
+    ("akst", Tz::America__Anchorage), ("hst", Tz::Pacific__Honolulu),
+    ("bst", Tz::Europe__London), ("cet", Tz::Europe__Berlin), ("cest", Tz::Europe__Berlin),
+    ("central european time", Tz::Europe__Berlin), ("eet", Tz::Europe__Athens),
+    ("eest", Tz::Europe__Athens), ("wet", Tz::Europe__Lisbon), ("ist", Tz::Asia__Kolkata),
+    ("jst", Tz::Asia__Tokyo), ("kst", Tz::Asia__Seoul), ("aest", Tz::Australia__Sydney),
+    ("aedt", Tz::Australia__Sydney), ("nzst", Tz::Pacific__Auckland),
+    ("nzdt", Tz::Pacific__Auckland),
+];
+
+/// The time zone of `place`, a city, a country or a time zone abbreviation
+pub fn time_zone(place: &str) -> Option<Tz> {
+    let place = place.trim().to_lowercase();
+    let place = place.strip_prefix("the ").unwrap_or(&place);
+    if let Some((_, zone)) = PLACES
+        .iter()
+        .chain(ABBREVIATIONS)
+        .find(|(name, _)| *name == place)
+    {
+        return Some(*zone);
+    }
+    TZ_VARIANTS.iter().copied().find(|zone| {
+        let name = zone.name().to_lowercase();
+        name == place
+            || name.contains('/')
+                && name
+                    .rsplit('/')
+                    .next()
+                    .map_or(false, |city| city.replace('_', " ") == place)
+    })
+}
+
+/// How to say `place` in a reply: "new york" becomes "New York" and "pst" becomes "PST"
+pub fn display_name(place: &str) -> String {
+    let place = place.trim();
+    let lowercase = place.to_lowercase();
+    if ABBREVIATIONS
+        .iter()
+        .any(|(name, _)| *name == lowercase && !name.contains(' '))
+    {
+        return place.to_uppercase();
+    }
+    place
+        .split_whitespace()
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
@@ -0,0 +1,887 @@
+//! Follow up questions for commands that are missing a slot their skill needs, e.g. "set an
+//! alarm" without a time, and for commands that should be confirmed before they are acted
+//! on. The answer to a question is merged into the pending command, which is dispatched
+//! once it is complete. A question that is not answered in time is dropped.
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use kara_nlu::{
+    context::ConversationContext,
+    intents::{Intent, ParsedIntent, Range, Slot, SlotValue, Text},
+    Language,
+};
+use tracing::debug;
+
+use super::{Context, Dispatcher, RequiredSlot};
+use crate::locale::{self, Response};
+
+/// How many answers may fail to fill a slot before Kara gives up
+const DEFAULT_ATTEMPTS: usize = 3;
+/// How long a question waits for its answer. Anything said later is a new command
+const DEFAULT_ANSWER_WINDOW: Duration = Duration::from_secs(60);
+
+/// What Kara says after a command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Turn {
+    Reply(String),
+    /// Kara asked a question and is waiting for the answer
+    Question(String),
+}
+
+impl Turn {
+    pub fn text(&self) -> &str {
+        match self {
+            Turn::Reply(text) | Turn::Question(text) => text,
+        }
+    }
+
+    /// Whether the microphone should stay open for an answer
+    pub fn expects_answer(&self) -> bool {
+        matches!(self, Turn::Question(_))
+    }
+}
+
+/// Which commands are confirmed before they are acted on, by how confident the language
+/// model was about them. Commands that are hard to undo are confirmed regardless
+#[derive(Debug, Clone, Default)]
+pub struct Confirmation {
+    threshold: f32,
+    intent_thresholds: HashMap<String, f32>,
+}
+
+impl Confirmation {
+    /// Confirms commands recognised with a confidence below `threshold`. 0.0 only confirms
+    /// commands that are hard to undo
+    pub fn new(threshold: f32) -> Self {
+        Self {
+            threshold,
+            intent_thresholds: HashMap::new(),
+        }
+    }
+
+    /// Overrides the threshold for a single intent. 1.0 always confirms it
+    pub fn with_intent_threshold(mut self, intent: impl Into<String>, threshold: f32) -> Self {
+        self.intent_thresholds.insert(intent.into(), threshold);
+        self
+    }
+
+    fn required(&self, intent: &Intent, confidence: f64) -> bool {
+        let threshold = self
+            .intent_thresholds
+            .get(intent.name())
+            .copied()
+            .unwrap_or(self.threshold);
+        // Commands matched by a rule have full confidence and are never confirmed
+        confidence < 1.0 && confidence < f64::from(threshold)
+    }
+}
+
+/// What a question is waiting for
+#[derive(Debug)]
+enum Awaiting {
+    /// Slots still to be asked for, the first one has been asked
+    Slots(Vec<RequiredSlot>),
+    /// A yes or no to carrying out the command, and how it was asked for
+    Confirmation(String),
+}
+
+/// A command waiting for the answer to a question
+#[derive(Debug)]
+struct Pending {
+    request: ParsedIntent,
+    awaiting: Awaiting,
+    /// Answers so far that did not answer the question
+    attempts: usize,
+    /// Commands that were given after this one and wait for it to complete
+    queued: Vec<ParsedIntent>,
+    /// When the question was last asked
+    asked: Instant,
+}
+
+impl Pending {
+    fn question(&self) -> String {
+        match &self.awaiting {
+            Awaiting::Slots(missing) => missing[0].question.clone(),
+            Awaiting::Confirmation(question) => question.clone(),
+        }
+    }
+}
+
+enum Answer {
+    /// The commands to run, the first of which has been confirmed if `confirmed`
+    Complete {
+        commands: Vec<ParsedIntent>,
+        confirmed: bool,
+    },
+    Ask(Pending),
+    Stop(Response),
+}
+
+/// Holds the state of one conversation. Share it between tasks with an `Arc`
+pub struct DialogueManager {
+    dispatcher: Arc<Dispatcher>,
+    pending: Mutex<Option<Pending>>,
+    max_attempts: usize,
+    answer_window: Duration,
+    conversation: Mutex<ConversationContext>,
+    confirmation: Confirmation,
+}
+
+impl DialogueManager {
+    pub fn new(dispatcher: Arc<Dispatcher>) -> Self {
+        Self {
+            dispatcher,
+            pending: Mutex::new(None),
+            max_attempts: DEFAULT_ATTEMPTS,
+            answer_window: DEFAULT_ANSWER_WINDOW,
+            conversation: Mutex::new(ConversationContext::default()),
+            confirmation: Confirmation::default(),
+        }
+    }
+
+    /// Which commands are confirmed before they are dispatched
+    pub fn with_confirmation(mut self, confirmation: Confirmation) -> Self {
+        self.confirmation = confirmation;
+        self
+    }
+
+    /// Questions wait for an answer for `window`
+    pub fn with_answer_window(mut self, window: Duration) -> Self {
+        self.answer_window = window;
+        self
+    }
+
+    /// Follow ups can refer to turns up to `window` ago
+    pub fn with_context_window(self, window: Duration) -> Self {
+        *self.conversation.lock().unwrap() = ConversationContext::new(window);
+        self
+    }
+
+    /// The conversation so far, for parsing follow ups
+    pub fn conversation(&self) -> ConversationContext {
+        self.conversation.lock().unwrap().clone()
+    }
+
+    /// Handles `text`, already parsed as `commands`. If a question is pending, `text` is
+    /// taken as its answer
+    pub async fn respond(&self, text: &str, commands: Vec<ParsedIntent>, context: Context) -> Turn {
+        let pending = self.pending.lock().unwrap().take().filter(|pending| {
+            let waiting = pending.asked.elapsed() < self.answer_window;
+            if !waiting {
+                debug!(?pending, "question expired");
+            }
+            waiting
+        });
+        let (commands, confirmed) = match pending {
+            Some(pending) => match self.answer(pending, text, &commands, &context) {
+                Answer::Complete {
+                    commands,
+                    confirmed,
+                } => (commands, confirmed),
+                Answer::Ask(mut pending) => {
+                    let question = pending.question();
+                    pending.asked = Instant::now();
+                    *self.pending.lock().unwrap() = Some(pending);
+                    return Turn::Question(question);
+                }
+                Answer::Stop(response) => {
+                    return Turn::Reply(locale::response(context.language, response))
+                }
+            },
+            None => (commands, false),
+        };
+        self.run(commands, confirmed, &context).await
+    }
+
+    /// Dispatches `commands` in order until one is missing a slot or has to be confirmed.
+    /// If `confirmed`, the first command is not confirmed again
+    async fn run(&self, commands: Vec<ParsedIntent>, confirmed: bool, context: &Context) -> Turn {
+        let mut replies = Vec::with_capacity(commands.len());
+        let mut commands = commands.into_iter();
+        let mut confirmed = confirmed;
+        while let Some(parsed) = commands.next() {
+            // Nothing is asked about commands the speaker may not give
+            if let Some(intent) = parsed.intent.intent_name.as_ref() {
+                if !self.dispatcher.allows(intent, &context.speaker) {
+                    debug!(%intent, "refusing a sensitive command from an unknown speaker");
+                    replies.push(locale::response(context.language, Response::UnknownSpeaker));
+                    confirmed = false;
+                    continue;
+                }
+            }
+            let missing = self.dispatcher.missing_slots(&parsed, context);
+            let awaiting = if !missing.is_empty() {
+                Some(Awaiting::Slots(missing))
+            } else if confirmed {
+                None
+            } else {
+                self.confirmation_question(&parsed, context.language)
+                    .map(Awaiting::Confirmation)
+            };
+            if let Some(awaiting) = awaiting {
+                let pending = Pending {
+                    request: parsed,
+                    awaiting,
+                    attempts: 0,
+                    queued: commands.collect(),
+                    asked: Instant::now(),
+                };
+                debug!(?pending, "asking a follow up question");
+                replies.push(pending.question());
+                *self.pending.lock().unwrap() = Some(pending);
+                return Turn::Question(replies.join("\n"));
+            }
+            confirmed = false;
+            let context = Context {
+                conversation: self.conversation(),
+                ..context.clone()
+            };
+            let skill = parsed
+                .intent
+                .intent_name
+                .as_ref()
+                .and_then(|intent| self.dispatcher.skill_name(intent));
+            self.conversation.lock().unwrap().record(&parsed, skill);
+            replies.push(self.dispatcher.reply(parsed, context).await);
+        }
+        Turn::Reply(replies.join("\n"))
+    }
+
+    /// The question to ask before `parsed` is dispatched, if it has to be confirmed
+    fn confirmation_question(&self, parsed: &ParsedIntent, language: Language) -> Option<String> {
+        let intent = parsed.intent.intent_name.as_ref()?;
+        if self.dispatcher.is_destructive(intent) {
+            Some(locale::response(language, Response::ConfirmAction))
+        } else if self
+            .confirmation
+            .required(intent, parsed.intent.confidence_score)
+        {
+            let label = intent.name().replace('_', " ");
+            Some(locale::response(language, Response::ConfirmIntent(label)))
+        } else {
+            None
+        }
+    }
+
+    fn answer(
+        &self,
+        pending: Pending,
+        text: &str,
+        commands: &[ParsedIntent],
+        context: &Context,
+    ) -> Answer {
+        let intent = pending.request.intent.intent_name.clone();
+        if locale::is_cancel(context.language, text) {
+            debug!(?intent, "dialogue cancelled");
+            return Answer::Stop(Response::Cancelled);
+        }
+        let mut pending = match pending.awaiting {
+            Awaiting::Slots(_) => self.fill_slots(pending, text, commands),
+            Awaiting::Confirmation(_) => match confirmation(commands, text, context.language) {
+                Some(true) => {
+                    debug!(?intent, "command confirmed");
+                    return complete(pending, true);
+                }
+                Some(false) => {
+                    debug!(?intent, "command declined");
+                    return Answer::Stop(Response::Cancelled);
+                }
+                None => Pending {
+                    attempts: pending.attempts + 1,
+                    ..pending
+                },
+            },
+        };
+        if pending.attempts >= self.max_attempts {
+            debug!(?intent, "dialogue abandoned");
+            return Answer::Stop(Response::GaveUp);
+        }
+        match &mut pending.awaiting {
+            Awaiting::Slots(missing) if missing.is_empty() => {
+                debug!(?intent, "dialogue complete");
+                complete(pending, false)
+            }
+            _ => {
+                debug!(?pending, "asking again");
+                Answer::Ask(pending)
+            }
+        }
+    }
+
+    /// Fills the slot that was asked for, and any later ones, from the answer
+    fn fill_slots(&self, mut pending: Pending, text: &str, commands: &[ParsedIntent]) -> Pending {
+        let intent = pending.request.intent.intent_name.clone();
+        let Pending {
+            request,
+            awaiting,
+            attempts,
+            ..
+        } = &mut pending;
+        let missing = match awaiting {
+            Awaiting::Slots(missing) => missing,
+            Awaiting::Confirmation(_) => return pending,
+        };
+        let slots: Vec<_> = commands.iter().flat_map(|parsed| &parsed.slots).collect();
+        let asked = missing[0].clone();
+        match fill(&asked, &slots).or_else(|| verbatim(&asked, text)) {
+            Some(slot) => {
+                debug!(?intent, slot = asked.name, value = ?slot.value, "slot filled");
+                request.slots.push(slot);
+                missing.remove(0);
+                *attempts = 0;
+                // The answer may have filled later slots too, e.g. "at 7 in the kitchen"
+                missing.retain(|required| match fill(required, &slots) {
+                    Some(slot) => {
+                        request.slots.push(slot);
+                        false
+                    }
+                    None => true,
+                });
+            }
+            None => {
+                *attempts += 1;
+                debug!(
+                    ?intent,
+                    slot = asked.name,
+                    attempts = *attempts,
+                    "answer did not fill the slot"
+                );
+            }
+        }
+        pending
+    }
+}
+
+/// The pending command followed by the commands queued behind it
+fn complete(mut pending: Pending, confirmed: bool) -> Answer {
+    let mut commands = vec![pending.request];
+    commands.append(&mut pending.queued);
+    Answer::Complete {
+        commands,
+        confirmed,
+    }
+}
+
+/// Whether the answer to a confirmation was yes or no, by its intent or failing that its
+/// words
+fn confirmation(commands: &[ParsedIntent], text: &str, language: Language) -> Option<bool> {
+    commands
+        .iter()
+        .find_map(|parsed| match parsed.intent.intent_name.as_ref()? {
+            Intent::GeneralAffirm | Intent::GeneralConfirm => Some(true),
+            Intent::GeneralNegate => Some(false),
+            _ => None,
+        })
+        .or_else(|| locale::yes_or_no(language, text))
+}
+
+/// A slot parsed from the answer that has the entity or name of `required`
+fn fill(required: &RequiredSlot, slots: &[&Slot]) -> Option<Slot> {
+    slots
+        .iter()
+        .find(|slot| slot.entity == required.entity || slot.slot_name == required.name)
+        .map(|slot| Slot {
+            slot_name: required.name.to_owned(),
+            ..(*slot).clone()
+        })
+}
+
+/// Answers for custom entities, such as a contact's name, are taken as they were said
+fn verbatim(required: &RequiredSlot, text: &str) -> Option<Slot> {
+    let text = text.trim();
+    if required.entity.starts_with("snips/") || text.is_empty() {
+        return None;
+    }
+    Some(Slot {
+        raw_value: text.to_owned(),
+        value: SlotValue::Custom(Text {
+            value: text.to_owned(),
+        }),
+        alternatives: Vec::new(),
+        range: Range {
+            start: 0,
+            end: text.chars().count() as i64,
+        },
+        entity: required.entity.to_owned(),
+        slot_name: required.name.to_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use kara_events::Speaker;
+    use kara_nlu::intents::IntentMapper;
+
+    use super::*;
+    use crate::skills::{Fallback, Request, RequiredSlot, Response as SkillResponse, Skill};
+
+    /// Answers every command with the name of its intent
+    struct Echo;
+
+    #[async_trait]
+    impl Skill for Echo {
+        fn name(&self) -> &'static str {
+            "echo"
+        }
+
+        fn intents(&self) -> &[Intent] {
+            &[Intent::ListsRemove, Intent::ListsQuery]
+        }
+
+        async fn handle(&self, request: &Request, _: &Context) -> anyhow::Result<SkillResponse> {
+            Ok(SkillResponse::text(request.intent.name()))
+        }
+    }
+
+    /// Needs a time and a room to set an alarm, and answers with the slots it was given
+    struct Needy;
+
+    #[async_trait]
+    impl Skill for Needy {
+        fn name(&self) -> &'static str {
+            "needy"
+        }
+
+        fn intents(&self) -> &[Intent] {
+            &[Intent::AlarmSet]
+        }
+
+        fn required_slots(&self, _: &Request, _: &Context) -> Vec<RequiredSlot> {
+            vec![
+                RequiredSlot {
+                    name: "time",
+                    entity: "snips/time",
+                    question: "When?".to_owned(),
+                },
+                RequiredSlot {
+                    name: "room",
+                    entity: "room",
+                    question: "Where?".to_owned(),
+                },
+            ]
+        }
+
+        async fn handle(&self, request: &Request, _: &Context) -> anyhow::Result<SkillResponse> {
+            let slots: Vec<_> = request
+                .slots
+                .iter()
+                .map(|slot| format!("{}={}", slot.slot_name, slot.raw_value))
+                .collect();
+            Ok(SkillResponse::text(format!(
+                "{} {}",
+                request.intent.name(),
+                slots.join(" ")
+            )))
+        }
+    }
+
+    /// Looks up whatever it is given
+    struct Lookup;
+
+    #[async_trait]
+    impl Fallback for Lookup {
+        fn name(&self) -> &'static str {
+            "lookup"
+        }
+
+        async fn handle(&self, input: &str, _: &Context) -> anyhow::Result<SkillResponse> {
+            Ok(SkillResponse::text(format!("looked up {input}")))
+        }
+    }
+
+    fn parsed(intent: Intent) -> ParsedIntent {
+        ParsedIntent {
+            input: intent.name().replace('_', " "),
+            intent: IntentMapper {
+                intent_name: Some(intent),
+                confidence_score: 1.0,
+            },
+            ..ParsedIntent::default()
+        }
+    }
+
+    fn slot(name: &str, entity: &str, value: &str) -> Slot {
+        Slot {
+            raw_value: value.to_owned(),
+            value: SlotValue::Custom(Text {
+                value: value.to_owned(),
+            }),
+            alternatives: Vec::new(),
+            range: Range::default(),
+            entity: entity.to_owned(),
+            slot_name: name.to_owned(),
+        }
+    }
+
+    /// A command the language model did not understand
+    fn not_understood(input: &str) -> ParsedIntent {
+        ParsedIntent {
+            input: input.to_owned(),
+            ..ParsedIntent::default()
+        }
+    }
+
+    fn dialogue() -> DialogueManager {
+        let mut dispatcher = Dispatcher::new(Duration::from_secs(5));
+        dispatcher.register(Echo);
+        dispatcher.register(Needy);
+        DialogueManager::new(Arc::new(dispatcher))
+    }
+
+    fn question(text: &str) -> Turn {
+        Turn::Question(text.to_owned())
+    }
+
+    fn reply(text: &str) -> Turn {
+        Turn::Reply(text.to_owned())
+    }
+
+    fn said(response: Response) -> Turn {
+        Turn::Reply(locale::response(Language::English, response))
+    }
+
+    #[tokio::test]
+    async fn asks_for_missing_slots_and_merges_the_answers() {
+        let dialogue = dialogue();
+        let context = Context::default;
+        let mut set = parsed(Intent::AlarmSet);
+        set.slots.push(slot("label", "label", "gym"));
+        let turn = dialogue.respond("set an alarm", vec![set], context()).await;
+        assert_eq!(turn, question("When?"));
+
+        // Slots of any intent the answer was parsed as are merged, later ones too
+        let mut answer = not_understood("at 7 in the kitchen");
+        answer.slots = vec![
+            slot("hour", "snips/time", "at 7"),
+            slot("place", "room", "kitchen"),
+        ];
+        let turn = dialogue
+            .respond("at 7 in the kitchen", vec![answer], context())
+            .await;
+        assert_eq!(turn, reply("alarm_set label=gym time=at 7 room=kitchen"));
+
+        // Answers for custom entities are taken as they were said
+        let mut set = parsed(Intent::AlarmSet);
+        set.slots.push(slot("time", "snips/time", "at 8"));
+        let turn = dialogue
+            .respond("set an alarm at 8", vec![set], context())
+            .await;
+        assert_eq!(turn, question("Where?"));
+        let turn = dialogue.respond("the attic", Vec::new(), context()).await;
+        assert_eq!(turn, reply("alarm_set time=at 8 room=the attic"));
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_answers_that_do_not_fill_the_slot() {
+        let dialogue = dialogue();
+        let turn = dialogue
+            .respond(
+                "set an alarm",
+                vec![parsed(Intent::AlarmSet)],
+                Context::default(),
+            )
+            .await;
+        assert_eq!(turn, question("When?"));
+        for _ in 1..DEFAULT_ATTEMPTS {
+            let turn = dialogue
+                .respond("banana", Vec::new(), Context::default())
+                .await;
+            assert_eq!(turn, question("When?"));
+        }
+        let turn = dialogue
+            .respond("banana", Vec::new(), Context::default())
+            .await;
+        assert_eq!(turn, said(Response::GaveUp));
+        // Nothing is pending any more
+        let turn = dialogue
+            .respond(
+                "lists query",
+                vec![parsed(Intent::ListsQuery)],
+                Context::default(),
+            )
+            .await;
+        assert_eq!(turn, reply("lists_query"));
+    }
+
+    #[tokio::test]
+    async fn cancels_questions() {
+        let dialogue = dialogue();
+        for cancel in ["cancel", "Never mind.", "forget it"] {
+            let turn = dialogue
+                .respond(
+                    "set an alarm",
+                    vec![parsed(Intent::AlarmSet)],
+                    Context::default(),
+                )
+                .await;
+            assert!(turn.expects_answer());
+            let turn = dialogue
+                .respond(cancel, Vec::new(), Context::default())
+                .await;
+            assert_eq!(turn, said(Response::Cancelled));
+        }
+        let spanish = Context {
+            language: Language::Spanish,
+            ..Context::default()
+        };
+        dialogue
+            .respond(
+                "pon una alarma",
+                vec![parsed(Intent::AlarmSet)],
+                spanish.clone(),
+            )
+            .await;
+        let turn = dialogue.respond("olvídalo", Vec::new(), spanish).await;
+        assert_eq!(
+            turn,
+            Turn::Reply(locale::response(Language::Spanish, Response::Cancelled))
+        );
+    }
+
+    #[tokio::test]
+    async fn runs_queued_commands_once_the_dialogue_completes() {
+        let dialogue = dialogue();
+        let turn = dialogue
+            .respond(
+                "read my list, set an alarm and read my list",
+                vec![
+                    parsed(Intent::ListsQuery),
+                    parsed(Intent::AlarmSet),
+                    parsed(Intent::ListsQuery),
+                ],
+                Context::default(),
+            )
+            .await;
+        // Commands before the question are answered with it
+        assert_eq!(turn, question("lists_query\nWhen?"));
+        let mut answer = not_understood("at 7 in the kitchen");
+        answer.slots = vec![
+            slot("time", "snips/time", "at 7"),
+            slot("room", "room", "kitchen"),
+        ];
+        let turn = dialogue
+            .respond("at 7 in the kitchen", vec![answer], Context::default())
+            .await;
+        assert_eq!(turn, reply("alarm_set time=at 7 room=kitchen\nlists_query"));
+    }
+
+    #[tokio::test]
+    async fn takes_late_answers_as_new_commands() {
+        let waiting = dialogue();
+        waiting
+            .respond(
+                "set an alarm",
+                vec![parsed(Intent::AlarmSet)],
+                Context::default(),
+            )
+            .await;
+        let turn = waiting
+            .respond(
+                "lists query",
+                vec![parsed(Intent::ListsQuery)],
+                Context::default(),
+            )
+            .await;
+        assert_eq!(turn, question("When?"));
+
+        let expired = dialogue().with_answer_window(Duration::ZERO);
+        expired
+            .respond(
+                "set an alarm",
+                vec![parsed(Intent::AlarmSet)],
+                Context::default(),
+            )
+            .await;
+        let turn = expired
+            .respond(
+                "lists query",
+                vec![parsed(Intent::ListsQuery)],
+                Context::default(),
+            )
+            .await;
+        assert_eq!(turn, reply("lists_query"));
+    }
+
+    #[test]
+    fn confirms_commands_below_the_threshold() {
+        let confirmation = Confirmation::new(0.5);
+        assert!(confirmation.required(&Intent::AlarmSet, 0.4));
+        assert!(!confirmation.required(&Intent::AlarmSet, 0.5));
+        assert!(!confirmation.required(&Intent::AlarmSet, 0.9));
+
+        let confirmation = confirmation.with_intent_threshold("lists_query", 1.0);
+        assert!(confirmation.required(&Intent::ListsQuery, 0.99));
+        assert!(confirmation.required(&Intent::AlarmSet, 0.4));
+        assert!(!confirmation.required(&Intent::AlarmSet, 0.6));
+        // Commands matched by a rule are never confirmed
+        assert!(!confirmation.required(&Intent::ListsQuery, 1.0));
+
+        let confirmation = Confirmation::default();
+        assert!(!confirmation.required(&Intent::ListsQuery, 0.0));
+        assert!(!confirmation.required(&Intent::ListsQuery, 0.1));
+    }
+
+    fn unsure(intent: Intent, confidence: f64) -> ParsedIntent {
+        let mut parsed = parsed(intent);
+        parsed.intent.confidence_score = confidence;
+        parsed
+    }
+
+    #[tokio::test]
+    async fn confirms_destructive_and_unsure_commands() {
+        let dialogue = dialogue().with_confirmation(Confirmation::new(0.5));
+        let remove = || vec![parsed(Intent::ListsRemove)];
+        let confirm_action = question(&locale::response(
+            Language::English,
+            Response::ConfirmAction,
+        ));
+
+        // Hard to undo, even when recognised with full confidence
+        let turn = dialogue
+            .respond("remove the list", remove(), Context::default())
+            .await;
+        assert_eq!(turn, confirm_action);
+        let turn = dialogue
+            .respond("yes", Vec::new(), Context::default())
+            .await;
+        assert_eq!(turn, reply("lists_remove"));
+
+        dialogue
+            .respond("remove the list", remove(), Context::default())
+            .await;
+        let turn = dialogue
+            .respond("No, thanks.", Vec::new(), Context::default())
+            .await;
+        assert_eq!(turn, said(Response::Cancelled));
+
+        let turn = dialogue
+            .respond(
+                "read my list",
+                vec![unsure(Intent::ListsQuery, 0.4)],
+                Context::default(),
+            )
+            .await;
+        assert_eq!(
+            turn,
+            question(&locale::response(
+                Language::English,
+                Response::ConfirmIntent("lists query".to_owned())
+            ))
+        );
+        // An answer recognised as agreeing counts as yes whatever its words
+        let turn = dialogue
+            .respond(
+                "go ahead",
+                vec![parsed(Intent::GeneralAffirm)],
+                Context::default(),
+            )
+            .await;
+        assert_eq!(turn, reply("lists_query"));
+
+        let turn = dialogue
+            .respond(
+                "read my list",
+                vec![unsure(Intent::ListsQuery, 0.6)],
+                Context::default(),
+            )
+            .await;
+        assert_eq!(turn, reply("lists_query"));
+    }
+
+    #[tokio::test]
+    async fn asks_again_after_an_unrelated_reply() {
+        let dialogue = dialogue();
+        let remove = vec![parsed(Intent::ListsRemove)];
+        let turn = dialogue
+            .respond("remove the list", remove, Context::default())
+            .await;
+        let asked = turn.clone();
+        assert!(turn.expects_answer());
+        let turn = dialogue
+            .respond("banana", Vec::new(), Context::default())
+            .await;
+        assert_eq!(turn, asked);
+        let turn = dialogue
+            .respond(
+                "nope",
+                vec![parsed(Intent::GeneralNegate)],
+                Context::default(),
+            )
+            .await;
+        assert_eq!(turn, said(Response::Cancelled));
+    }
+
+    #[tokio::test]
+    async fn hands_commands_not_understood_to_the_fallback_if_set() {
+        let input = "what is a quokka";
+        let turn = dialogue()
+            .respond(input, vec![not_understood(input)], Context::default())
+            .await;
+        assert_eq!(turn, said(Response::NotUnderstood));
+
+        let mut dispatcher = Dispatcher::new(Duration::from_secs(5));
+        dispatcher.register(Echo);
+        dispatcher.set_fallback(Lookup);
+        let dialogue = DialogueManager::new(Arc::new(dispatcher));
+        let turn = dialogue
+            .respond(input, vec![not_understood(input)], Context::default())
+            .await;
+        assert_eq!(turn, reply("looked up what is a quokka"));
+        // Commands that were understood still go to their skill
+        let turn = dialogue
+            .respond(
+                "read my list",
+                vec![parsed(Intent::ListsQuery)],
+                Context::default(),
+            )
+            .await;
+        assert_eq!(turn, reply("lists_query"));
+    }
+
+    fn restricted() -> DialogueManager {
+        let mut dispatcher = Dispatcher::new(Duration::from_secs(5));
+        dispatcher.register(Echo);
+        dispatcher.restrict_to_known_speakers();
+        DialogueManager::new(Arc::new(dispatcher))
+    }
+
+    #[tokio::test]
+    async fn does_not_ask_unknown_speakers_to_confirm() {
+        let dialogue = restricted();
+        let turn = dialogue
+            .respond(
+                "remove the list",
+                vec![parsed(Intent::ListsRemove), parsed(Intent::ListsQuery)],
+                Context::default(),
+            )
+            .await;
+        assert_eq!(
+            turn,
+            Turn::Reply(format!(
+                "{}\nlists_query",
+                locale::response(Language::English, Response::UnknownSpeaker)
+            ))
+        );
+
+        let alice = Context {
+            speaker: Speaker::Known("alice".to_owned()),
+            ..Context::default()
+        };
+        let turn = dialogue
+            .respond("remove the list", vec![parsed(Intent::ListsRemove)], alice)
+            .await;
+        assert!(turn.expects_answer());
+        // Someone else cannot confirm it
+        let turn = dialogue
+            .respond("yes", Vec::new(), Context::default())
+            .await;
+        assert_eq!(
+            turn.text(),
+            locale::response(Language::English, Response::UnknownSpeaker)
+        );
+    }
+}
@@ -0,0 +1,52 @@
+use async_trait::async_trait;
+use kara_events::Speaker;
+use kara_nlu::{intents::Intent, Language};
+
+use super::{Context, Request, Response, Skill};
+
+/// Small talk
+pub struct General;
+
+#[async_trait]
+impl Skill for General {
+    fn name(&self) -> &'static str {
+        "general"
+    }
+
+    fn intents(&self) -> &[Intent] {
+        &[Intent::GeneralGreet, Intent::GeneralPraise]
+    }
+
+    async fn handle(&self, request: &Request, context: &Context) -> anyhow::Result<Response> {
+        let text = match (&request.intent, &context.speaker) {
+            (Intent::GeneralGreet, Speaker::Known(name)) => {
+                greet(context.language, Some(name.as_str()))
+            }
+            (Intent::GeneralGreet, Speaker::Unknown) => greet(context.language, None),
+            _ => thank(context.language).to_owned(),
+        };
+        Ok(Response::text(text))
+    }
+}
+
+fn greet(language: Language, name: Option<&str>) -> String {
+    match (language, name) {
+        (Language::English, Some(name)) => format!("Hello, {name}!"),
+        (Language::English, None) => "Hello!".to_owned(),
+        (Language::French, Some(name)) => format!("Bonjour, {name} !"),
+        (Language::French, None) => "Bonjour !".to_owned(),
+        (Language::German, Some(name)) => format!("Hallo, {name}!"),
+        (Language::German, None) => "Hallo!".to_owned(),
+        (Language::Spanish, Some(name)) => format!("¡Hola, {name}!"),
+        (Language::Spanish, None) => "¡Hola!".to_owned(),
+    }
+}
+
+fn thank(language: Language) -> &'static str {
+    match language {
+        Language::English => "Thank you!",
+        Language::French => "Merci !",
+        Language::German => "Danke!",
+        Language::Spanish => "¡Gracias!",
+    }
+}
@@ -0,0 +1,1047 @@
+//! Creates, reads out and changes the named lists in the [`ListStore`], e.g. "add milk
+//! and eggs to my shopping list", "what's on my todo list" or "cross bread off my list".
+use async_trait::async_trait;
+use kara_nlu::{
+    intents::{Intent, SlotValue},
+    Language,
+};
+
+use super::{Context, Request, RequiredSlot, Response, Skill};
+use crate::lists::{self, Added, ListStore};
+
+/// The most items read out at once
+const MAX_READ: usize = 10;
+/// How alike a list name that was said must be to an existing list's to be taken for it
+const SAME_LIST: f64 = 0.95;
+/// Slots that never hold an item, whichever intent they were found for
+const NOT_ITEMS: [&str; 8] = [
+    "list_name",
+    "date",
+    "time",
+    "timeofday",
+    "general_frequency",
+    "person",
+    "relation",
+    "place_name",
+];
+
+/// The words commands about lists are made of in a language
+struct Vocabulary {
+    /// A list, or for German the end of compounds such as "Einkaufsliste"
+    list: &'static str,
+    lists: &'static str,
+    add: &'static [&'static str],
+    create: &'static [&'static str],
+    remove: &'static [&'static str],
+    /// Words that mean an item is done rather than to be removed
+    done: &'static [&'static str],
+    /// Words that mean every item is to be removed rather than the list
+    clear: &'static [&'static str],
+    /// Words that mean the list itself is to be removed
+    delete: &'static [&'static str],
+    /// Words between an item and the list it is added to or removed from
+    prepositions: &'static [&'static str],
+    /// Words before the name of a list, e.g. "my" or "de"
+    determiners: &'static [&'static str],
+    /// Words that come before the name of a list that is being created
+    called: &'static [&'static str],
+    and: &'static str,
+    /// The list things are added to when none is named
+    default_list: &'static str,
+}
+
+fn vocabulary(language: Language) -> Vocabulary {
+    match language {
+        Language::English => Vocabulary {
+            list: "list",
+            lists: "lists",
+            add: &["add", "put", "write", "include", "append"],
+            create: &["create", "make", "start", "new"],
+            remove: &[
+                "remove", "delete", "take", "erase", "cross", "tick", "check", "mark", "clear",
+                "empty",
+            ],
+            done: &[
+                "cross",
+                "tick",
+                "check",
+                "done",
+                "complete",
+                "completed",
+                "finished",
+            ],
+            clear: &["clear", "empty", "everything", "all"],
+            delete: &["delete"],
+            prepositions: &["to", "on", "onto", "in", "into", "from", "off", "as", "of"],
+            determiners: &[
+                "my", "the", "a", "an", "our", "your", "new", "some", "this", "that", "to", "on",
+                "from", "off", "in", "of", "what's", "whats", "what", "is", "read",
+            ],
+            called: &["called", "named"],
+            and: "and",
+            default_list: "shopping",
+        },
+        Language::French => Vocabulary {
+            list: "liste",
+            lists: "listes",
+            add: &[
+                "ajoute", "ajouter", "mets", "mettre", "rajoute", "note", "inscris",
+            ],
+            create: &["crée", "créer", "cree", "creer", "nouvelle", "commence"],
+            remove: &[
+                "enlève",
+                "enleve",
+                "enlever",
+                "supprime",
+                "supprimer",
+                "retire",
+                "retirer",
+                "efface",
+                "raye",
+                "rayer",
+                "coche",
+                "cocher",
+                "vide",
+                "vider",
+            ],
+            done: &[
+                "raye",
+                "rayer",
+                "coche",
+                "cocher",
+                "fait",
+                "faite",
+                "terminé",
+                "terminée",
+            ],
+            clear: &["vide", "vider", "tout"],
+            delete: &["supprime", "supprimer"],
+            prepositions: &["à", "a", "au", "sur", "dans", "de", "du", "des", "comme"],
+            determiners: &[
+                "ma", "mon", "la", "le", "les", "une", "un", "notre", "ta", "nouvelle", "de",
+                "des", "du", "d'", "à", "a", "sur", "dans",
+            ],
+            called: &["appelée", "appelee", "nommée", "nommee"],
+            and: "et",
+            default_list: "courses",
+        },
+        Language::German => Vocabulary {
+            list: "liste",
+            lists: "listen",
+            add: &[
+                "füge",
+                "fuege",
+                "hinzufügen",
+                "setz",
+                "setze",
+                "schreib",
+                "schreibe",
+                "pack",
+            ],
+            create: &["erstelle", "erstellen", "neue", "lege", "leg", "mach"],
+            remove: &[
+                "lösche",
+                "loesche",
+                "löschen",
+                "entferne",
+                "entfernen",
+                "nimm",
+                "streich",
+                "streiche",
+                "hake",
+                "hak",
+                "leere",
+                "leeren",
+            ],
+            done: &["hake", "hak", "abhaken", "erledigt", "streich", "streiche"],
+            clear: &["leere", "leeren", "alles"],
+            delete: &["lösche", "loesche", "löschen"],
+            prepositions: &["zu", "zur", "zum", "auf", "in", "von", "vom", "aus", "als"],
+            determiners: &[
+                "meine", "meiner", "die", "der", "eine", "einer", "unsere", "deine", "neue", "zur",
+                "zu", "auf", "von", "aus", "in",
+            ],
+            called: &["namens", "genannt", "heißt", "heisst"],
+            and: "und",
+            default_list: "einkaufsliste",
+        },
+        Language::Spanish => Vocabulary {
+            list: "lista",
+            lists: "listas",
+            add: &[
+                "añade", "añadir", "agrega", "agregar", "pon", "poner", "apunta", "incluye",
+            ],
+            create: &["crea", "crear", "nueva", "empieza", "haz"],
+            remove: &[
+                "quita", "quitar", "elimina", "eliminar", "borra", "borrar", "tacha", "tachar",
+                "marca", "vacía", "vacia", "vaciar",
+            ],
+            done: &[
+                "tacha",
+                "tachar",
+                "marca",
+                "hecho",
+                "hecha",
+                "completado",
+                "completada",
+            ],
+            clear: &["vacía", "vacia", "vaciar", "todo"],
+            delete: &["borra", "borrar", "elimina", "eliminar"],
+            prepositions: &["a", "al", "en", "de", "del", "como"],
+            determiners: &[
+                "mi", "la", "el", "una", "un", "nuestra", "tu", "nueva", "de", "del", "a", "al",
+                "en", "los", "las",
+            ],
+            called: &["llamada", "llamado", "llame"],
+            and: "y",
+            default_list: "compra",
+        },
+    }
+}
+
+/// Articles dropped from the start of items, e.g. "some" in "some milk"
+fn articles(language: Language) -> &'static [&'static str] {
+    match language {
+        Language::English => &["a", "an", "the", "some", "more"],
+        Language::French => &[
+            "du", "de", "la", "le", "les", "des", "un", "une", "l'", "d'",
+        ],
+        Language::German => &["die", "der", "das", "den", "ein", "eine", "einen", "etwas"],
+        Language::Spanish => &[
+            "el", "la", "los", "las", "un", "una", "unos", "unas", "algo",
+        ],
+    }
+}
+
+pub struct Lists {
+    store: ListStore,
+}
+
+impl Lists {
+    pub fn new(store: ListStore) -> Self {
+        Self { store }
+    }
+
+    /// The list a command is about: the one it names, or one that is named nearly the
+    /// same. `None` if it names none
+    fn named(&self, request: &Request, language: Language) -> anyhow::Result<Option<String>> {
+        let said = request
+            .slot("list_name")
+            .and_then(SlotValue::as_text)
+            .and_then(|name| {
+                let vocabulary = vocabulary(language);
+                let name_words = words(name);
+                // "shopping list", or only "list"
+                if name_words.iter().any(|word| word.contains(vocabulary.list)) {
+                    list_name(language, name)
+                } else {
+                    strip(&name_words, vocabulary.determiners)
+                }
+            })
+            .or_else(|| list_name(language, &request.input));
+        let said = match said {
+            Some(said) => said,
+            None => return Ok(None),
+        };
+        let existing = self
+            .store
+            .names()?
+            .into_iter()
+            .map(|name| (lists::similarity(&name, &said), name))
+            .filter(|(similarity, _)| *similarity >= SAME_LIST)
+            .max_by(|(a, _), (b, _)| a.total_cmp(b))
+            .map(|(_, name)| name);
+        Ok(Some(existing.unwrap_or(said)))
+    }
+
+    fn create_or_add(&self, request: &Request, language: Language) -> anyhow::Result<String> {
+        let named = self.named(request, language)?;
+        let items = items(request, language, Action::Add);
+        if items.is_empty() {
+            let name = match named {
+                Some(name) => name,
+                None => return Ok(unclear_list(language)),
+            };
+            let list = list_phrase(language, &name);
+            return Ok(if self.store.create(&name)? {
+                match language {
+                    Language::English => format!("I've created {list}"),
+                    Language::French => format!("J'ai créé {list}"),
+                    Language::German => format!("Ich habe {list} angelegt"),
+                    Language::Spanish => format!("He creado {list}"),
+                }
+            } else {
+                match language {
+                    Language::English => format!("You already have {list}"),
+                    Language::French => format!("Tu as déjà {list}"),
+                    Language::German => format!("Du hast schon {list}"),
+                    Language::Spanish => format!("Ya tienes {list}"),
+                }
+            });
+        }
+        let name = match named {
+            Some(name) => name,
+            // With only one list, that is the list
+            None => match self.store.names()?.as_slice() {
+                [only] => only.clone(),
+                _ => vocabulary(language).default_list.to_owned(),
+            },
+        };
+        let (mut added, mut already) = (Vec::new(), Vec::new());
+        for item in items {
+            match self.store.add(&name, &item)? {
+                Added::Added { .. } => added.push(item),
+                Added::AlreadyThere(existing) => already.push(existing.text),
+            }
+        }
+        let list = list_phrase(language, &name);
+        let mut replies = Vec::new();
+        if !added.is_empty() {
+            let added = and_list(language, &added);
+            replies.push(match language {
+                Language::English => format!("I've added {added} to {list}"),
+                Language::French => format!("J'ai ajouté {added} à {list}"),
+                Language::German => format!("Ich habe {added} auf {list} gesetzt"),
+                Language::Spanish => format!("He añadido {added} a {list}"),
+            });
+        }
+        if !already.is_empty() {
+            let several = already.len() > 1;
+            let already = and_list(language, &already);
+            replies.push(match (language, several) {
+                (Language::English, false) => format!("{already} is already on {list}"),
+                (Language::English, true) => format!("{already} are already on {list}"),
+                (Language::French, false) => format!("{already} est déjà sur {list}"),
+                (Language::French, true) => format!("{already} sont déjà sur {list}"),
+                (Language::German, false) => {
+                    format!("{already} steht schon auf {}", dative(&list))
+                }
+                (Language::German, true) => {
+                    format!("{already} stehen schon auf {}", dative(&list))
+                }
+                (Language::Spanish, false) => format!("{already} ya está en {list}"),
+                (Language::Spanish, true) => format!("{already} ya están en {list}"),
+            });
+        }
+        Ok(replies.join(". "))
+    }
+
+    /// Reads a list out, or says which lists there are if the command names none
+    fn query(&self, request: &Request, language: Language) -> anyhow::Result<String> {
+        // "what lists do I have"
+        if words(&request.input).contains(&vocabulary(language).lists.to_owned()) {
+            return self.overview(language);
+        }
+        let name = match self.named(request, language)? {
+            Some(name) => name,
+            None => match self.store.names()?.as_slice() {
+                [only] => only.clone(),
+                _ => return self.overview(language),
+            },
+        };
+        let list = match self.store.list(&name)? {
+            Some(list) => list,
+            None => return Ok(no_such_list(language, &name)),
+        };
+        let phrase = list_phrase(language, &list.name);
+        let open: Vec<_> = list.open_items().map(|item| item.text.clone()).collect();
+        let done = list.items.len() - open.len();
+        if open.is_empty() {
+            return Ok(match (language, done) {
+                (Language::English, 0) => format!("{} is empty", capitalise(&phrase)),
+                (Language::English, _) => format!("Everything on {phrase} is done"),
+                (Language::French, 0) => format!("{} est vide", capitalise(&phrase)),
+                (Language::French, _) => format!("Tout est fait sur {phrase}"),
+                (Language::German, 0) => format!("{} ist leer", capitalise(&phrase)),
+                (Language::German, _) => format!("{}: alles erledigt", capitalise(&phrase)),
+                (Language::Spanish, 0) => format!("{} está vacía", capitalise(&phrase)),
+                (Language::Spanish, _) => format!("Todo lo de {phrase} está hecho"),
+            });
+        }
+        let mut read: Vec<_> = open.iter().take(MAX_READ).cloned().collect();
+        if open.len() > MAX_READ {
+            let more = open.len() - MAX_READ;
+            read.push(match language {
+                Language::English => format!("{more} more"),
+                Language::French => format!("{more} autres"),
+                Language::German => format!("{more} weitere"),
+                Language::Spanish => format!("{more} más"),
+            });
+        }
+        let (count, items) = (open.len(), and_list(language, &read));
+        let phrase = capitalise(&phrase);
+        let mut reply = match (language, count) {
+            (Language::English, 1) => format!("{phrase} has one thing on it: {items}"),
+            (Language::English, _) => format!("{phrase} has {count} things on it: {items}"),
+            (Language::French, 1) => format!("{phrase} contient une chose : {items}"),
+            (Language::French, _) => format!("{phrase} contient {count} choses : {items}"),
+            (Language::German, 1) => format!("{phrase} hat einen Eintrag: {items}"),
+            (Language::German, _) => format!("{phrase} hat {count} Einträge: {items}"),
+            (Language::Spanish, 1) => format!("{phrase} tiene una cosa: {items}"),
+            (Language::Spanish, _) => format!("{phrase} tiene {count} cosas: {items}"),
+        };
+        if done > 0 {
+            reply.push_str(&match (language, done) {
+                (Language::English, _) => format!(", and {done} ticked off"),
+                (Language::French, 1) => ", plus une cochée".to_owned(),
+                (Language::French, _) => format!(", plus {done} cochées"),
+                (Language::German, _) => format!(", dazu {done} abgehakt"),
+                (Language::Spanish, 1) => ", y una tachada".to_owned(),
+                (Language::Spanish, _) => format!(", y {done} tachadas"),
+            });
+        }
+        Ok(reply)
+    }
+
+    fn overview(&self, language: Language) -> anyhow::Result<String> {
+        let names = self.store.names()?;
+        let count = names.len();
+        let names = and_list(language, &names);
+        Ok(match (language, count) {
+            (Language::English, 0) => "You don't have any lists".to_owned(),
+            (Language::English, 1) => format!("You have one list: {names}"),
+            (Language::English, _) => format!("You have {count} lists: {names}"),
+            (Language::French, 0) => "Tu n'as aucune liste".to_owned(),
+            (Language::French, 1) => format!("Tu as une liste : {names}"),
+            (Language::French, _) => format!("Tu as {count} listes : {names}"),
+            (Language::German, 0) => "Du hast keine Listen".to_owned(),
+            (Language::German, 1) => format!("Du hast eine Liste: {names}"),
+            (Language::German, _) => format!("Du hast {count} Listen: {names}"),
+            (Language::Spanish, 0) => "No tienes listas".to_owned(),
+            (Language::Spanish, 1) => format!("Tienes una lista: {names}"),
+            (Language::Spanish, _) => format!("Tienes {count} listas: {names}"),
+        })
+    }
+
+    /// Removes items, or ticks them off if the command says they are done. Without an
+    /// item the list is emptied or deleted if the command says so
+    fn remove(&self, request: &Request, language: Language) -> anyhow::Result<String> {
+        let vocabulary = vocabulary(language);
+        let words = words(&request.input);
+        let said = |options: &[&str]| words.iter().any(|word| options.contains(&word.as_str()));
+        let done = said(vocabulary.done);
+        let named = self.named(request, language)?;
+        let items = removed_items(request, language);
+        if items.is_empty() {
+            if !said(vocabulary.clear) && !said(vocabulary.delete) {
+                return Ok(what_to_remove(language));
+            }
+            let name = match named {
+                Some(name) => name,
+                None => return Ok(unclear_list(language)),
+            };
+            let list = list_phrase(language, &name);
+            if said(vocabulary.clear) {
+                if self.store.list(&name)?.is_none() {
+                    return Ok(no_such_list(language, &name));
+                }
+                self.store.clear(&name, false)?;
+                return Ok(match language {
+                    Language::English => format!("I've emptied {list}"),
+                    Language::French => format!("J'ai vidé {list}"),
+                    Language::German => format!("Ich habe {list} geleert"),
+                    Language::Spanish => format!("He vaciado {list}"),
+                });
+            }
+            return Ok(if self.store.delete(&name)? {
+                match language {
+                    Language::English => format!("I've deleted {list}"),
+                    Language::French => format!("J'ai supprimé {list}"),
+                    Language::German => format!("Ich habe {list} gelöscht"),
+                    Language::Spanish => format!("He borrado {list}"),
+                }
+            } else {
+                no_such_list(language, &name)
+            });
+        }
+        let (mut changed, mut missing) = (Vec::new(), Vec::new());
+        let mut in_list = named.clone();
+        for item in items {
+            // Without a list, the item is looked for in all of them
+            let name = match &named {
+                Some(name) => Some(name.clone()),
+                None => self.containing(&item)?,
+            };
+            let found = match &name {
+                Some(name) if done => self.store.set_done(name, &item, true)?,
+                Some(name) => self.store.remove(name, &item)?,
+                None => None,
+            };
+            match found {
+                Some(found) => {
+                    changed.push(found.text);
+                    in_list = in_list.or(name);
+                }
+                None => missing.push(item),
+            }
+        }
+        let list = match &in_list {
+            Some(name) => list_phrase(language, name),
+            None => match language {
+                Language::English => "your lists",
+                Language::French => "tes listes",
+                Language::German => "deinen Listen",
+                Language::Spanish => "tus listas",
+            }
+            .to_owned(),
+        };
+        let mut replies = Vec::new();
+        if !changed.is_empty() {
+            let changed = and_list(language, &changed);
+            replies.push(match (language, done) {
+                (Language::English, false) => format!("I've removed {changed} from {list}"),
+                (Language::English, true) => format!("I've ticked off {changed} on {list}"),
+                (Language::French, false) => format!("J'ai retiré {changed} de {list}"),
+                (Language::French, true) => format!("J'ai coché {changed} sur {list}"),
+                (Language::German, false) => {
+                    format!("Ich habe {changed} von {} entfernt", dative(&list))
+                }
+                (Language::German, true) => {
+                    format!("Ich habe {changed} auf {} abgehakt", dative(&list))
+                }
+                (Language::Spanish, false) => format!("He quitado {changed} de {list}"),
+                (Language::Spanish, true) => format!("He tachado {changed} de {list}"),
+            });
+        }
+        if !missing.is_empty() {
+            let missing = and_list(language, &missing);
+            replies.push(match language {
+                Language::English => format!("I couldn't find {missing} on {list}"),
+                Language::French => format!("Je n'ai pas trouvé {missing} sur {list}"),
+                Language::German => {
+                    format!("Ich habe {missing} nicht auf {} gefunden", dative(&list))
+                }
+                Language::Spanish => format!("No he encontrado {missing} en {list}"),
+            });
+        }
+        Ok(replies.join(". "))
+    }
+
+    /// The list with the item most like `item`
+    fn containing(&self, item: &str) -> anyhow::Result<Option<String>> {
+        Ok(self
+            .store
+            .lists()?
+            .into_iter()
+            .filter_map(|list| {
+                let found = lists::find(&list.items, item)?;
+                Some((lists::similarity(&found.text, item), list.name))
+            })
+            .max_by(|(a, _), (b, _)| a.total_cmp(b))
+            .map(|(_, name)| name))
+    }
+}
+
+#[async_trait]
+impl Skill for Lists {
+    fn name(&self) -> &'static str {
+        "lists"
+    }
+
+    fn intents(&self) -> &[Intent] {
+        &[
+            Intent::ListsCreateOrAdd,
+            Intent::ListsQuery,
+            Intent::ListsRemove,
+        ]
+    }
+
+    /// Removing without an item only empties or deletes a list when the command says so,
+    /// otherwise the item is asked for
+    fn required_slots(&self, request: &Request, context: &Context) -> Vec<RequiredSlot> {
+        let language = context.language;
+        let words = words(&request.input);
+        let vocabulary = vocabulary(language);
+        let says_what = words.iter().any(|word| {
+            vocabulary.clear.contains(&word.as_str()) || vocabulary.delete.contains(&word.as_str())
+        });
+        if request.intent != Intent::ListsRemove
+            || says_what
+            || !removed_items(request, language).is_empty()
+        {
+            return Vec::new();
+        }
+        vec![RequiredSlot {
+            name: "item",
+            entity: "item",
+            question: what_to_remove(language),
+        }]
+    }
+
+    async fn handle(&self, request: &Request, context: &Context) -> anyhow::Result<Response> {
+        let language = context.language;
+        // Each speaker has lists of their own as well as the shared ones
+        let lists = Lists::new(self.store.for_speaker(&context.speaker));
+        let text = match request.intent {
+            Intent::ListsCreateOrAdd => lists.create_or_add(request, language)?,
+            Intent::ListsRemove => lists.remove(request, language)?,
+            _ => lists.query(request, language)?,
+        };
+        Ok(Response::text(text))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Add,
+    Remove,
+}
+
+/// The items a command adds or removes, e.g. "milk" and "eggs" in "add milk and eggs to
+/// my shopping list". They are read from the text around the verb and the list, or from
+/// the slots the language model found if the text does not have that shape
+fn items(request: &Request, language: Language, action: Action) -> Vec<String> {
+    let vocabulary = vocabulary(language);
+    let said = tokens(&request.input);
+    let lower: Vec<_> = said.iter().map(|token| token.to_lowercase()).collect();
+    let verbs = match action {
+        Action::Add => vocabulary.add,
+        Action::Remove => vocabulary.remove,
+    };
+    let verb = lower.iter().position(|word| verbs.contains(&word.as_str()));
+    let create = lower
+        .iter()
+        .any(|word| vocabulary.create.contains(&word.as_str()));
+    let from_verb = verb.and_then(|verb| {
+        // "cross off milk", "hake milch ab"
+        let mut start = verb + 1;
+        while lower
+            .get(start)
+            .map_or(false, |word| ["off", "ab"].contains(&word.as_str()))
+        {
+            start += 1;
+        }
+        let end = lower
+            .iter()
+            .position(|word| word.contains(vocabulary.list))
+            .filter(|list| *list > start)
+            .map(|list| {
+                (start..list)
+                    .rev()
+                    .find(|i| vocabulary.prepositions.contains(&lower[*i].as_str()))
+                    .unwrap_or(start)
+            })
+            .or_else(|| {
+                // "mark milk as done", "remove milk"
+                lower[start..]
+                    .iter()
+                    .position(|word| vocabulary.prepositions.contains(&word.as_str()))
+                    .map(|i| start + i)
+            })
+            .unwrap_or(said.len());
+        (end > start).then(|| said[start..end].join(" "))
+    });
+    let text = match (from_verb, verb) {
+        (Some(text), _) => Some(text),
+        // "create a todo list"
+        (None, _) if create => None,
+        // The answer to "what should I remove?"
+        (None, Some(_)) => request
+            .slot("item")
+            .and_then(SlotValue::as_text)
+            .map(str::to_owned),
+        (None, None) => request
+            .slots
+            .iter()
+            .filter(|slot| !NOT_ITEMS.contains(&slot.slot_name.as_str()))
+            .find_map(|slot| slot.value.as_text())
+            .map(str::to_owned),
+    };
+    let text = match text {
+        Some(text) => text,
+        None => return Vec::new(),
+    };
+    let and = format!(" {} ", vocabulary.and);
+    text.split(',')
+        .flat_map(|part| part.split(and.as_str()))
+        .filter_map(|item| strip(&tokens(item), articles(language)))
+        .collect()
+}
+
+/// The items a command removes, leaving out words such as "everything" in "clear
+/// everything from my list"
+fn removed_items(request: &Request, language: Language) -> Vec<String> {
+    let vocabulary = vocabulary(language);
+    items(request, language, Action::Remove)
+        .into_iter()
+        .filter(|item| !vocabulary.clear.contains(&item.to_lowercase().as_str()))
+        .collect()
+}
+
+/// The name of the list `text` mentions, e.g. "shopping" in "what's on my shopping list"
+fn list_name(language: Language, text: &str) -> Option<String> {
+    let vocabulary = vocabulary(language);
+    let said = words(text);
+    let list = said
+        .iter()
+        .position(|word| word.contains(vocabulary.list))?;
+    if let Some(called) = said
+        .iter()
+        .position(|word| vocabulary.called.contains(&word.as_str()))
+    {
+        return strip(&said[called + 1..], vocabulary.called);
+    }
+    match language {
+        // "my shopping list"
+        Language::English => {
+            let first = said[..list]
+                .iter()
+                .rposition(|word| vocabulary.determiners.contains(&word.as_str()))
+                .map_or(0, |i| i + 1);
+            strip(&said[first..list], vocabulary.determiners)
+        }
+        // "Einkaufsliste", or "Liste Arbeit"
+        Language::German if said[list] != vocabulary.list => Some(said[list].clone()),
+        // "liste de courses", "lista de la compra"
+        _ => {
+            let rest = &said[list + 1..];
+            let end = rest
+                .iter()
+                .position(|word| !vocabulary.determiners.contains(&word.as_str()))
+                .map(|first| {
+                    first
+                        + rest[first..]
+                            .iter()
+                            .position(|word| vocabulary.prepositions.contains(&word.as_str()))
+                            .unwrap_or(rest.len() - first)
+                })
+                .unwrap_or(0);
+            strip(&rest[..end], vocabulary.determiners)
+        }
+    }
+}
+
+/// `words` joined, without the `dropped` words they start with
+fn strip(words: &[String], dropped: &[&str]) -> Option<String> {
+    let first = words
+        .iter()
+        .position(|word| !dropped.contains(&word.to_lowercase().as_str()))?;
+    let mut rest: Vec<_> = words[first..].to_vec();
+    // "l'huile"
+    if let Some(elided) = dropped
+        .iter()
+        .filter(|prefix| prefix.ends_with('\''))
+        .find_map(|prefix| rest[0].strip_prefix(prefix))
+    {
+        rest[0] = elided.to_owned();
+    }
+    let text = rest.join(" ");
+    let text = text.trim_matches(|c: char| !c.is_alphanumeric());
+    if text.is_empty() {
+        None
+    } else {
+        Some(text.to_owned())
+    }
+}
+
+/// The words of `text` as they were said, without the punctuation around them. Commas
+/// are kept as words of their own, as they separate items
+fn tokens(text: &str) -> Vec<String> {
+    text.replace(',', " , ")
+        .split_whitespace()
+        .map(|word| match word {
+            "," => word,
+            _ => word.trim_matches(|c: char| !c.is_alphanumeric() && c != '\''),
+        })
+        .filter(|word| !word.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+fn words(text: &str) -> Vec<String> {
+    tokens(&text.to_lowercase())
+}
+
+/// How to refer to a list in a reply, e.g. "your shopping list"
+fn list_phrase(language: Language, name: &str) -> String {
+    match language {
+        Language::English => format!("your {name} list"),
+        Language::French if name.starts_with(['a', 'e', 'i', 'o', 'u', 'h', 'é']) => {
+            format!("ta liste d'{name}")
+        }
+        Language::French => format!("ta liste de {name}"),
+        Language::German if name.ends_with("liste") => format!("deine {}", capitalise(name)),
+        Language::German => format!("deine Liste „{name}“"),
+        Language::Spanish => format!("tu lista de {name}"),
+    }
+}
+
+/// A list phrase in German after "auf" or "von", e.g. "deiner Einkaufsliste"
+fn dative(list: &str) -> String {
+    list.replacen("deine ", "deiner ", 1)
+}
+
+fn capitalise(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// "milk, eggs and bread"
+fn and_list(language: Language, items: &[String]) -> String {
+    match items {
+        [] => String::new(),
+        [only] => only.clone(),
+        [rest @ .., last] => format!("{} {} {last}", rest.join(", "), vocabulary(language).and),
+    }
+}
+
+fn no_such_list(language: Language, name: &str) -> String {
+    let list = list_phrase(language, name);
+    match language {
+        Language::English => format!("You don't have {}", list.replacen("your", "a", 1)),
+        Language::French => format!("Tu n'as pas de {}", list.replacen("ta ", "", 1)),
+        Language::German => format!("Du hast keine {}", list.replacen("deine ", "", 1)),
+        Language::Spanish => format!("No tienes {}", list.replacen("tu", "una", 1)),
+    }
+}
+
+fn what_to_remove(language: Language) -> String {
+    match language {
+        Language::English => "What should I remove?",
+        Language::French => "Qu'est-ce que je dois enlever ?",
+        Language::German => "Was soll ich entfernen?",
+        Language::Spanish => "¿Qué quito?",
+    }
+    .to_owned()
+}
+
+fn unclear_list(language: Language) -> String {
+    match language {
+        Language::English => "Sorry, I couldn't tell which list",
+        Language::French => "Désolée, je n'ai pas compris quelle liste",
+        Language::German => "Entschuldigung, ich habe nicht verstanden, welche Liste",
+        Language::Spanish => "Lo siento, no he entendido qué lista",
+    }
+    .to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use kara_nlu::intents::{Range, Slot, Text};
+
+    use super::*;
+
+    /// A list database of its own for each test, removed when the test ends
+    struct TempDb(PathBuf);
+
+    impl TempDb {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "kara-lists-skill-{}-{name}.sqlite",
+                std::process::id()
+            ));
+            let _ = std::fs::remove_file(&path);
+            Self(path)
+        }
+
+        fn lists(&self) -> Lists {
+            Lists::new(ListStore::open(&self.0).unwrap())
+        }
+    }
+
+    impl Drop for TempDb {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn request(intent: Intent, input: &str) -> Request {
+        Request {
+            intent,
+            input: input.to_owned(),
+            slots: Vec::new(),
+        }
+    }
+
+    /// `request` with the answer to "what should I remove?"
+    fn answered(mut request: Request, item: &str) -> Request {
+        request.slots.push(Slot {
+            raw_value: item.to_owned(),
+            value: SlotValue::Custom(Text {
+                value: item.to_owned(),
+            }),
+            alternatives: Vec::new(),
+            range: Range::default(),
+            entity: "item".to_owned(),
+            slot_name: "item".to_owned(),
+        });
+        request
+    }
+
+    fn strings(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn reads_items() {
+        let add = |language, input| {
+            items(
+                &request(Intent::ListsCreateOrAdd, input),
+                language,
+                Action::Add,
+            )
+        };
+        let remove = |language, input| {
+            items(
+                &request(Intent::ListsRemove, input),
+                language,
+                Action::Remove,
+            )
+        };
+        assert_eq!(
+            add(
+                Language::English,
+                "add milk, eggs and some bread to my shopping list"
+            ),
+            ["milk", "eggs", "bread"]
+        );
+        assert_eq!(
+            add(Language::English, "put oat milk on the list"),
+            ["oat milk"]
+        );
+        assert_eq!(
+            add(Language::German, "füge Milch zur Einkaufsliste hinzu"),
+            ["Milch"]
+        );
+        assert_eq!(
+            add(
+                Language::French,
+                "ajoute de l'huile et du pain à ma liste de courses"
+            ),
+            ["huile", "pain"]
+        );
+        assert_eq!(remove(Language::English, "cross off milk"), ["milk"]);
+        assert_eq!(
+            remove(Language::English, "remove the eggs from my shopping list"),
+            ["eggs"]
+        );
+        assert!(remove(Language::English, "remove my shopping list").is_empty());
+        assert!(add(Language::English, "create a todo list").is_empty());
+        // The answer to a question is read from its slot
+        assert_eq!(
+            items(
+                &answered(
+                    request(Intent::ListsRemove, "remove my shopping list"),
+                    "milk and eggs"
+                ),
+                Language::English,
+                Action::Remove
+            ),
+            ["milk", "eggs"]
+        );
+    }
+
+    #[test]
+    fn reads_list_names() {
+        let name = |language, text| list_name(language, text);
+        assert_eq!(
+            name(Language::English, "what's on my shopping list"),
+            Some("shopping".to_owned())
+        );
+        assert_eq!(
+            name(Language::English, "create a list called weekend jobs"),
+            Some("weekend jobs".to_owned())
+        );
+        assert_eq!(
+            name(
+                Language::French,
+                "qu'est-ce qu'il y a sur ma liste de courses"
+            ),
+            Some("courses".to_owned())
+        );
+        assert_eq!(
+            name(Language::German, "was steht auf der einkaufsliste"),
+            Some("einkaufsliste".to_owned())
+        );
+        assert_eq!(
+            name(Language::Spanish, "añade pan a la lista de la compra"),
+            Some("compra".to_owned())
+        );
+        assert_eq!(name(Language::English, "what's on my list"), None);
+        assert_eq!(name(Language::English, "add milk"), None);
+    }
+
+    #[test]
+    fn strips_leading_words() {
+        let english = articles(Language::English);
+        assert_eq!(
+            strip(&strings(&["Some", "more", "milk"]), english),
+            Some("milk".to_owned())
+        );
+        assert_eq!(strip(&strings(&["the", "a"]), english), None);
+        assert_eq!(
+            strip(
+                &strings(&["l'huile", "d'olive"]),
+                articles(Language::French)
+            ),
+            Some("huile d'olive".to_owned())
+        );
+    }
+
+    #[test]
+    fn asks_what_to_remove() {
+        let db = TempDb::new("ask");
+        let lists = db.lists();
+        lists.store.add("shopping", "milk").unwrap();
+        let context = Context::default();
+        let required = |input| {
+            lists
+                .required_slots(&request(Intent::ListsRemove, input), &context)
+                .into_iter()
+                .map(|slot| slot.name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(required("remove my shopping list"), ["item"]);
+        assert!(required("remove milk from my shopping list").is_empty());
+        assert!(required("clear my shopping list").is_empty());
+        assert!(required("delete my shopping list").is_empty());
+
+        // Without the dialogue manager, the list is kept
+        let remove = request(Intent::ListsRemove, "remove my shopping list");
+        assert_eq!(
+            lists.remove(&remove, Language::English).unwrap(),
+            "What should I remove?"
+        );
+        assert_eq!(
+            lists
+                .remove(&answered(remove, "milk"), Language::English)
+                .unwrap(),
+            "I've removed milk from your shopping list"
+        );
+        let delete = request(Intent::ListsRemove, "delete my shopping list");
+        assert_eq!(
+            lists.remove(&delete, Language::English).unwrap(),
+            "I've deleted your shopping list"
+        );
+    }
+
+    #[test]
+    fn names_the_item_it_removes() {
+        let db = TempDb::new("remove");
+        let lists = db.lists();
+        lists.store.add("shopping", "beef").unwrap();
+        lists.store.add("shopping", "oat milk").unwrap();
+        let remove = |input| {
+            lists
+                .remove(&request(Intent::ListsRemove, input), Language::English)
+                .unwrap()
+        };
+        assert_eq!(
+            remove("remove beer from my shopping list"),
+            "I couldn't find beer on your shopping list"
+        );
+        assert_eq!(
+            remove("remove milk from my shopping list"),
+            "I've removed oat milk from your shopping list"
+        );
+        assert_eq!(
+            remove("tick off beef"),
+            "I've ticked off beef on your shopping list"
+        );
+    }
+}
@@ -0,0 +1,712 @@
+//! Reads arithmetic that was said rather than typed, e.g. "what's 15% of 240" or "open
+//! bracket 2 plus 3 close bracket times 4". Numbers are written in digits, or spelled out
+//! in French, German and Spanish, see [`spelled`].
+//!
+//! Words before and after the expression, such as "what's the", are ignored. The
+//! expression itself may only be made of numbers and of the phrases in [`phrases`]:
+//!
+//! - `+ - × ÷` and their names, with the usual precedence
+//! - "to the power of", "squared", "cubed", "square root of", "cube root of", "factorial"
+//! - "percent", which is relative after "plus", "minus" and "off": "50 plus 10%" is 55
+//! - "of" for multiplication, as in "half of 90", and fractions: "2 thirds", "3 and a half"
+//! - "open bracket" and "close bracket". Brackets still open at the end are closed
+//! - "divide 10 by 2", "subtract 3 from 10" and the like
+use kara_nlu::Language;
+
+use super::{number::Number, spelled, Error};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token {
+    Number(Number),
+    /// "half", "thirds": on its own, or multiplying the number it follows
+    Fraction(Number),
+    /// "and", which adds, or joins a fraction to a whole number
+    And,
+    Operator(Operator),
+    /// Minus, which also negates
+    Minus,
+    Postfix(Postfix),
+    Root(u32),
+    /// "factorial of"
+    Factorial,
+    Open,
+    Close,
+    /// A word that is not part of arithmetic
+    Word,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Power,
+    /// "10% off 50", or "5 off 50"
+    Off,
+    /// "3 from 10", after "subtract"
+    From,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Postfix {
+    Squared,
+    Cubed,
+    Percent,
+    Factorial,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Number(Number),
+    /// A percentage, e.g. 15% is 0.15 but adds 15% of what it is added to
+    Percent(Box<Expression>),
+    Negate(Box<Expression>),
+    Root(u32, Box<Expression>),
+    Factorial(Box<Expression>),
+    Binary(Operator, Box<Expression>, Box<Expression>),
+}
+
+impl Expression {
+    fn binary(operator: Operator, left: Expression, right: Expression) -> Self {
+        Expression::Binary(operator, Box::new(left), Box::new(right))
+    }
+
+    pub fn evaluate(&self) -> Result<Number, Error> {
+        match self {
+            Expression::Number(number) => Ok(*number),
+            Expression::Percent(percent) => percent.evaluate()?.divide(Number::integer(100)),
+            Expression::Negate(value) => Ok(value.evaluate()?.negate()),
+            Expression::Root(n, value) => value.evaluate()?.root(*n),
+            Expression::Factorial(value) => value.evaluate()?.factorial(),
+            Expression::Binary(operator, left, right) => {
+                let (a, b) = (left.evaluate()?, right.evaluate()?);
+                let relative = matches!(**right, Expression::Percent(_));
+                match operator {
+                    Operator::Add if relative => a.add(a.multiply(b)?),
+                    Operator::Add => a.add(b),
+                    Operator::Subtract if relative => a.subtract(a.multiply(b)?),
+                    Operator::Subtract => a.subtract(b),
+                    Operator::Multiply => a.multiply(b),
+                    Operator::Divide => a.divide(b),
+                    Operator::Power => a.power(b),
+                    Operator::Off if matches!(**left, Expression::Percent(_)) => {
+                        b.subtract(b.multiply(a)?)
+                    }
+                    Operator::Off | Operator::From => b.subtract(a),
+                }
+            }
+        }
+    }
+}
+
+/// The arithmetic in `text`, if there is any
+pub fn parse(language: Language, text: &str) -> Result<Expression, Error> {
+    let mut tokens = tokenise(language, text);
+    // Words around the expression, such as "what's the", are not part of it
+    let start = tokens.iter().position(|token| *token != Token::Word);
+    let end = tokens.iter().rposition(|token| *token != Token::Word);
+    let (start, end) = match (start, end) {
+        (Some(start), Some(end)) => (start, end),
+        _ => return Err(Error::NotUnderstood),
+    };
+    tokens.truncate(end + 1);
+    tokens.drain(..start);
+    if !tokens
+        .iter()
+        .any(|token| matches!(token, Token::Number(_) | Token::Fraction(_)))
+    {
+        return Err(Error::NotUnderstood);
+    }
+    let mut parser = Parser { tokens, next: 0 };
+    let expression = parser.expression()?;
+    if parser.next < parser.tokens.len() {
+        return Err(Error::NotUnderstood);
+    }
+    Ok(expression)
+}
+
+/// Splits `text` into numbers, the phrases of arithmetic and other words
+fn tokenise(language: Language, text: &str) -> Vec<Token> {
+    let lowercase: Vec<_> = text.to_lowercase().chars().collect();
+    let spaced: String = lowercase
+        .iter()
+        .enumerate()
+        .flat_map(|(i, &c)| match c {
+            // Hyphens join the words of a number, as in "vingt-trois"
+            '-' if i > 0
+                && lowercase[i - 1].is_alphabetic()
+                && lowercase.get(i + 1).map_or(false, |c| c.is_alphabetic()) =>
+            {
+                vec![' ']
+            }
+            '(' | ')' | '+' | '-' | '*' | '/' | '^' | '%' | '×' | '÷' | '√' => {
+                vec![' ', c, ' ']
+            }
+            '?' | '!' | '=' => vec![' '],
+            c => vec![c],
+        })
+        .collect();
+    let words: Vec<_> = spaced
+        .split_whitespace()
+        .map(|word| word.trim_end_matches(['.', ',']))
+        .filter(|word| !word.is_empty())
+        .collect();
+    let verb = words
+        .iter()
+        .find_map(|word| verbs(language).iter().find(|(verb, _)| verb == word))
+        .map(|(_, connectors)| *connectors)
+        .unwrap_or(&[]);
+    let phrases = phrases(language);
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < words.len() {
+        if let Some(number) = number(language, words[i]) {
+            tokens.push(Token::Number(number));
+            i += 1;
+            continue;
+        }
+        if let Some((number, read)) = spelled::read(language, &words[i..]) {
+            tokens.push(Token::Number(number));
+            i += read;
+            continue;
+        }
+        // The longest phrase wins, e.g. "por ciento" over "por", then what the verb
+        // makes of it
+        let matched = phrases
+            .iter()
+            .chain(verb.iter())
+            .filter(|(phrase, _)| {
+                let phrase: Vec<_> = phrase.split(' ').collect();
+                words[i..].starts_with(&phrase)
+            })
+            .max_by_key(|(phrase, _)| phrase.split(' ').count());
+        match matched {
+            Some((phrase, token)) => {
+                tokens.push(*token);
+                i += phrase.split(' ').count();
+            }
+            None => {
+                tokens.push(Token::Word);
+                i += 1;
+            }
+        }
+    }
+    tokens
+}
+
+/// A number written in digits. Decimal commas and the separators between thousands are
+/// read as the language writes them
+fn number(language: Language, word: &str) -> Option<Number> {
+    let word = word.trim_start_matches(['$', '€', '£']);
+    let (thousands, decimal) = match language {
+        Language::English => (',', '.'),
+        Language::French | Language::German | Language::Spanish => ('.', ','),
+    };
+    let groups: Vec<_> = word.split(thousands).collect();
+    let grouped = groups.len() > 1
+        && groups[0].len() <= 3
+        && groups[1..].iter().all(|group| {
+            group
+                .split(decimal)
+                .next()
+                .map_or(false, |digits| digits.len() == 3)
+        });
+    let word = if grouped {
+        word.replace(thousands, "")
+    } else {
+        word.to_owned()
+    };
+    Number::parse(&word.replace(decimal, "."))
+}
+
+/// Verbs that start a sum, with what the words that join its numbers mean, e.g. "divide 10
+/// by 2"
+type Verb = (&'static str, &'static [(&'static str, Token)]);
+
+fn verbs(language: Language) -> &'static [Verb] {
+    const ADD: Token = Token::Operator(Operator::Add);
+    const MULTIPLY: Token = Token::Operator(Operator::Multiply);
+    const DIVIDE: Token = Token::Operator(Operator::Divide);
+    const FROM: Token = Token::Operator(Operator::From);
+    match language {
+        Language::English => &[
+            ("add", &[("to", ADD)]),
+            ("multiply", &[("by", MULTIPLY)]),
+            ("divide", &[("by", DIVIDE), ("into", DIVIDE)]),
+            ("subtract", &[("from", FROM)]),
+            ("take", &[("from", FROM)]),
+        ],
+        Language::French => &[
+            ("ajoute", &[("à", ADD), ("a", ADD)]),
+            ("multiplie", &[("par", MULTIPLY)]),
+            ("divise", &[("par", DIVIDE)]),
+            ("soustrais", &[("de", FROM), ("à", FROM)]),
+            ("retire", &[("de", FROM), ("à", FROM)]),
+        ],
+        Language::German => &[
+            ("addiere", &[("zu", ADD)]),
+            ("multipliziere", &[("mit", MULTIPLY)]),
+            ("teile", &[]),
+            ("subtrahiere", &[("von", FROM)]),
+            ("ziehe", &[("von", FROM)]),
+        ],
+        Language::Spanish => &[
+            ("suma", &[("a", ADD)]),
+            ("multiplica", &[("por", MULTIPLY)]),
+            ("divide", &[("entre", DIVIDE), ("por", DIVIDE)]),
+            ("resta", &[("de", FROM), ("a", FROM)]),
+        ],
+    }
+}
+
+/// The phrases of arithmetic in a language
+fn phrases(language: Language) -> Vec<(&'static str, Token)> {
+    use self::Operator::*;
+    let half = Token::Fraction(Number::Exact(1, 2));
+    let third = Token::Fraction(Number::Exact(1, 3));
+    let quarter = Token::Fraction(Number::Exact(1, 4));
+    let mut phrases = vec![
+        ("+", Token::Operator(Add)),
+        ("-", Token::Minus),
+        ("*", Token::Operator(Multiply)),
+        ("×", Token::Operator(Multiply)),
+        ("/", Token::Operator(Divide)),
+        ("÷", Token::Operator(Divide)),
+        ("^", Token::Operator(Power)),
+        ("%", Token::Postfix(Postfix::Percent)),
+        ("√", Token::Root(2)),
+        ("(", Token::Open),
+        (")", Token::Close),
+    ];
+    phrases.extend(match language {
+        Language::English => vec![
+            ("plus", Token::Operator(Add)),
+            ("and", Token::And),
+            ("minus", Token::Minus),
+            ("negative", Token::Minus),
+            ("take away", Token::Operator(Subtract)),
+            ("less", Token::Operator(Subtract)),
+            ("times", Token::Operator(Multiply)),
+            ("multiplied by", Token::Operator(Multiply)),
+            ("x", Token::Operator(Multiply)),
+            ("by", Token::Operator(Multiply)),
+            ("of", Token::Operator(Multiply)),
+            ("divided by", Token::Operator(Divide)),
+            ("over", Token::Operator(Divide)),
+            ("off", Token::Operator(Off)),
+            ("to the power of", Token::Operator(Power)),
+            ("to the power", Token::Operator(Power)),
+            ("raised to", Token::Operator(Power)),
+            ("raised to the power of", Token::Operator(Power)),
+            ("squared", Token::Postfix(Postfix::Squared)),
+            ("cubed", Token::Postfix(Postfix::Cubed)),
+            ("percent", Token::Postfix(Postfix::Percent)),
+            ("per cent", Token::Postfix(Postfix::Percent)),
+            ("factorial", Token::Postfix(Postfix::Factorial)),
+            ("factorial of", Token::Factorial),
+            ("square root of", Token::Root(2)),
+            ("root of", Token::Root(2)),
+            ("sqrt", Token::Root(2)),
+            ("cube root of", Token::Root(3)),
+            ("open bracket", Token::Open),
+            ("open brackets", Token::Open),
+            ("open parenthesis", Token::Open),
+            ("open paren", Token::Open),
+            ("left bracket", Token::Open),
+            ("close bracket", Token::Close),
+            ("close brackets", Token::Close),
+            ("close parenthesis", Token::Close),
+            ("close paren", Token::Close),
+            ("right bracket", Token::Close),
+            ("half", half),
+            ("a half", half),
+            ("halves", half),
+            ("third", third),
+            ("a third", third),
+            ("thirds", third),
+            ("quarter", quarter),
+            ("a quarter", quarter),
+            ("quarters", quarter),
+        ],
+        Language::French => vec![
+            ("plus", Token::Operator(Add)),
+            ("et", Token::And),
+            ("moins", Token::Minus),
+            ("fois", Token::Operator(Multiply)),
+            ("multiplié par", Token::Operator(Multiply)),
+            ("par", Token::Operator(Multiply)),
+            ("de", Token::Operator(Multiply)),
+            ("des", Token::Operator(Multiply)),
+            ("du", Token::Operator(Multiply)),
+            ("divisé par", Token::Operator(Divide)),
+            ("sur", Token::Operator(Divide)),
+            ("puissance", Token::Operator(Power)),
+            ("à la puissance", Token::Operator(Power)),
+            ("exposant", Token::Operator(Power)),
+            ("au carré", Token::Postfix(Postfix::Squared)),
+            ("au cube", Token::Postfix(Postfix::Cubed)),
+            ("pour cent", Token::Postfix(Postfix::Percent)),
+            ("pourcent", Token::Postfix(Postfix::Percent)),
+            ("factorielle", Token::Postfix(Postfix::Factorial)),
+            ("factorielle de", Token::Factorial),
+            ("racine carrée de", Token::Root(2)),
+            ("racine de", Token::Root(2)),
+            ("racine cubique de", Token::Root(3)),
+            ("ouvre la parenthèse", Token::Open),
+            ("ouvrez la parenthèse", Token::Open),
+            ("ouvrir la parenthèse", Token::Open),
+            ("parenthèse ouvrante", Token::Open),
+            ("ferme la parenthèse", Token::Close),
+            ("fermez la parenthèse", Token::Close),
+            ("fermer la parenthèse", Token::Close),
+            ("parenthèse fermante", Token::Close),
+            ("demi", half),
+            ("demie", half),
+            ("la moitié", half),
+            ("moitié", half),
+            ("tiers", third),
+            ("un tiers", third),
+            ("quart", quarter),
+            ("un quart", quarter),
+            ("quarts", quarter),
+        ],
+        Language::German => vec![
+            ("plus", Token::Operator(Add)),
+            ("und", Token::And),
+            ("minus", Token::Minus),
+            ("weniger", Token::Operator(Subtract)),
+            ("mal", Token::Operator(Multiply)),
+            ("multipliziert mit", Token::Operator(Multiply)),
+            ("von", Token::Operator(Multiply)),
+            ("geteilt durch", Token::Operator(Divide)),
+            ("durch", Token::Operator(Divide)),
+            ("hoch", Token::Operator(Power)),
+            ("zum quadrat", Token::Postfix(Postfix::Squared)),
+            ("quadrat", Token::Postfix(Postfix::Squared)),
+            ("hoch drei", Token::Postfix(Postfix::Cubed)),
+            ("prozent", Token::Postfix(Postfix::Percent)),
+            ("fakultät", Token::Postfix(Postfix::Factorial)),
+            ("fakultät von", Token::Factorial),
+            ("wurzel aus", Token::Root(2)),
+            ("wurzel von", Token::Root(2)),
+            ("quadratwurzel aus", Token::Root(2)),
+            ("quadratwurzel von", Token::Root(2)),
+            ("kubikwurzel aus", Token::Root(3)),
+            ("kubikwurzel von", Token::Root(3)),
+            ("klammer auf", Token::Open),
+            ("klammer zu", Token::Close),
+            ("halb", half),
+            ("einhalb", half),
+            ("ein halb", half),
+            ("die hälfte", half),
+            ("hälfte", half),
+            ("drittel", third),
+            ("ein drittel", third),
+            ("viertel", quarter),
+            ("ein viertel", quarter),
+        ],
+        Language::Spanish => vec![
+            ("más", Token::Operator(Add)),
+            ("mas", Token::Operator(Add)),
+            ("y", Token::And),
+            ("menos", Token::Minus),
+            ("por", Token::Operator(Multiply)),
+            ("multiplicado por", Token::Operator(Multiply)),
+            ("de", Token::Operator(Multiply)),
+            ("del", Token::Operator(Multiply)),
+            ("dividido por", Token::Operator(Divide)),
+            ("dividido entre", Token::Operator(Divide)),
+            ("entre", Token::Operator(Divide)),
+            ("elevado a", Token::Operator(Power)),
+            ("elevado a la", Token::Operator(Power)),
+            ("al cuadrado", Token::Postfix(Postfix::Squared)),
+            ("al cubo", Token::Postfix(Postfix::Cubed)),
+            ("por ciento", Token::Postfix(Postfix::Percent)),
+            ("porciento", Token::Postfix(Postfix::Percent)),
+            ("factorial", Token::Postfix(Postfix::Factorial)),
+            ("factorial de", Token::Factorial),
+            ("raíz cuadrada de", Token::Root(2)),
+            ("raiz cuadrada de", Token::Root(2)),
+            ("raíz de", Token::Root(2)),
+            ("raiz de", Token::Root(2)),
+            ("raíz cúbica de", Token::Root(3)),
+            ("raiz cubica de", Token::Root(3)),
+            ("abre paréntesis", Token::Open),
+            ("abrir paréntesis", Token::Open),
+            ("abre el paréntesis", Token::Open),
+            ("cierra paréntesis", Token::Close),
+            ("cerrar paréntesis", Token::Close),
+            ("cierra el paréntesis", Token::Close),
+            ("medio", half),
+            ("media", half),
+            ("la mitad", half),
+            ("mitad", half),
+            ("tercio", third),
+            ("un tercio", third),
+            ("tercios", third),
+            ("cuarto", quarter),
+            ("un cuarto", quarter),
+            ("cuartos", quarter),
+        ],
+    });
+    phrases
+}
+
+/// Recursive descent, from the operators that bind least to those that bind most
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<Token> {
+        self.tokens.get(self.next).copied()
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.peek();
+        self.next += 1;
+        token
+    }
+
+    /// Sums and differences
+    fn expression(&mut self) -> Result<Expression, Error> {
+        let mut left = self.term()?;
+        loop {
+            let operator = match self.peek() {
+                Some(Token::Operator(
+                    operator @ (Operator::Add | Operator::Subtract | Operator::From),
+                )) => operator,
+                Some(Token::And) => Operator::Add,
+                Some(Token::Minus) => Operator::Subtract,
+                _ => return Ok(left),
+            };
+            self.next += 1;
+            let right = self.term()?;
+            left = match operator {
+                Operator::From => Expression::binary(Operator::Subtract, right, left),
+                operator => Expression::binary(operator, left, right),
+            };
+        }
+    }
+
+    /// Products and quotients
+    fn term(&mut self) -> Result<Expression, Error> {
+        let mut left = self.unary()?;
+        while let Some(Token::Operator(
+            operator @ (Operator::Multiply | Operator::Divide | Operator::Off),
+        )) = self.peek()
+        {
+            self.next += 1;
+            let right = self.unary()?;
+            left = Expression::binary(operator, left, right);
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expression, Error> {
+        match self.peek() {
+            Some(Token::Minus) => {
+                self.next += 1;
+                Ok(Expression::Negate(Box::new(self.unary()?)))
+            }
+            Some(Token::Root(n)) => {
+                self.next += 1;
+                Ok(Expression::Root(n, Box::new(self.unary()?)))
+            }
+            Some(Token::Factorial) => {
+                self.next += 1;
+                Ok(Expression::Factorial(Box::new(self.unary()?)))
+            }
+            _ => self.power(),
+        }
+    }
+
+    /// Powers, which bind to the right: 2^3^2 is 2^9
+    fn power(&mut self) -> Result<Expression, Error> {
+        let base = self.postfix()?;
+        if self.peek() == Some(Token::Operator(Operator::Power)) {
+            self.next += 1;
+            let exponent = self.unary()?;
+            return Ok(Expression::binary(Operator::Power, base, exponent));
+        }
+        Ok(base)
+    }
+
+    fn postfix(&mut self) -> Result<Expression, Error> {
+        let mut value = self.primary()?;
+        loop {
+            value = match (self.peek(), self.tokens.get(self.next + 1).copied()) {
+                (Some(Token::Postfix(postfix)), _) => {
+                    self.next += 1;
+                    match postfix {
+                        Postfix::Squared => Expression::binary(
+                            Operator::Power,
+                            value,
+                            Expression::Number(Number::integer(2)),
+                        ),
+                        Postfix::Cubed => Expression::binary(
+                            Operator::Power,
+                            value,
+                            Expression::Number(Number::integer(3)),
+                        ),
+                        Postfix::Percent => Expression::Percent(Box::new(value)),
+                        Postfix::Factorial => Expression::Factorial(Box::new(value)),
+                    }
+                }
+                // "2 thirds"
+                (Some(Token::Fraction(fraction)), _) => {
+                    self.next += 1;
+                    Expression::binary(Operator::Multiply, value, Expression::Number(fraction))
+                }
+                // "3 and a half", which is one number rather than a sum
+                (Some(Token::And), Some(Token::Fraction(fraction))) => {
+                    self.next += 2;
+                    Expression::binary(Operator::Add, value, Expression::Number(fraction))
+                }
+                _ => return Ok(value),
+            };
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expression, Error> {
+        match self.advance() {
+            Some(Token::Number(number) | Token::Fraction(number)) => Ok(Expression::Number(number)),
+            Some(Token::Open) => {
+                let inner = self.expression()?;
+                // A bracket that is still open at the end is closed there
+                match self.advance() {
+                    Some(Token::Close) | None => Ok(inner),
+                    Some(_) => Err(Error::NotUnderstood),
+                }
+            }
+            _ => Err(Error::NotUnderstood),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(language: Language, text: &str) -> Result<Number, Error> {
+        parse(language, text)?.evaluate()
+    }
+
+    fn english(text: &str) -> Result<Number, Error> {
+        result(Language::English, text)
+    }
+
+    #[test]
+    fn follows_precedence() {
+        assert_eq!(english("what's 2 plus 3 times 4"), Ok(Number::integer(14)));
+        assert_eq!(
+            english("open bracket 2 plus 3 close bracket times 4"),
+            Ok(Number::integer(20))
+        );
+        assert_eq!(english("(2 + 3 times 4"), Ok(Number::integer(14)));
+        assert_eq!(english("10 minus 4 minus 3"), Ok(Number::integer(3)));
+        assert_eq!(english("2 ^ 3 ^ 2"), Ok(Number::integer(512)));
+        assert_eq!(english("2 times 3 squared"), Ok(Number::integer(18)));
+        assert_eq!(
+            english("square root of 144 times 3"),
+            Ok(Number::integer(36))
+        );
+    }
+
+    #[test]
+    fn negates() {
+        assert_eq!(english("minus 5 plus 2"), Ok(Number::integer(-3)));
+        assert_eq!(english("minus 2 squared"), Ok(Number::integer(-4)));
+        assert_eq!(english("3 times -2"), Ok(Number::integer(-6)));
+        assert_eq!(english("negative 4 minus minus 1"), Ok(Number::integer(-3)));
+    }
+
+    #[test]
+    fn reads_percentages() {
+        assert_eq!(english("what's 15% of 240"), Ok(Number::integer(36)));
+        assert_eq!(english("10 percent of 50"), Ok(Number::integer(5)));
+        // Relative after plus, minus and off
+        assert_eq!(english("50 plus 10%"), Ok(Number::integer(55)));
+        assert_eq!(english("50 minus 10%"), Ok(Number::integer(45)));
+        assert_eq!(english("20% off 80"), Ok(Number::integer(64)));
+        assert_eq!(english("5 off 80"), Ok(Number::integer(75)));
+        assert_eq!(english("50%"), Ok(Number::Exact(1, 2)));
+    }
+
+    #[test]
+    fn keeps_fractions_exact() {
+        assert_eq!(english("1 divided by 3 times 3"), Ok(Number::integer(1)));
+        assert_eq!(english("1 over 3"), Ok(Number::Exact(1, 3)));
+        assert_eq!(english("2 thirds of 90"), Ok(Number::integer(60)));
+        assert_eq!(english("3 and a half times 2"), Ok(Number::integer(7)));
+        assert_eq!(english("half of 9"), Ok(Number::Exact(9, 2)));
+        assert_eq!(english("0.1 plus 0.2"), Ok(Number::Exact(3, 10)));
+    }
+
+    #[test]
+    fn reads_verbs() {
+        assert_eq!(english("divide 10 by 4"), Ok(Number::Exact(5, 2)));
+        assert_eq!(english("subtract 3 from 10"), Ok(Number::integer(7)));
+        assert_eq!(english("add 2 to 5"), Ok(Number::integer(7)));
+        assert_eq!(english("multiply 6 by 7"), Ok(Number::integer(42)));
+    }
+
+    #[test]
+    fn reads_numbers_as_the_language_writes_them() {
+        assert_eq!(
+            english("12,500 plus 1.5"),
+            Number::parse("12501.5").ok_or(Error::NotUnderstood)
+        );
+        assert_eq!(
+            result(Language::German, "12.500 plus 1,5"),
+            Number::parse("12501.5").ok_or(Error::NotUnderstood)
+        );
+    }
+
+    #[test]
+    fn reads_spelled_numbers() {
+        assert_eq!(
+            result(Language::French, "combien font vingt-trois fois deux"),
+            Ok(Number::integer(46))
+        );
+        assert_eq!(
+            result(Language::French, "deux et demi fois quatre"),
+            Ok(Number::integer(10))
+        );
+        assert_eq!(
+            result(Language::German, "was ist ein drittel von neunundneunzig"),
+            Ok(Number::integer(33))
+        );
+        assert_eq!(
+            result(Language::German, "zwei und drei"),
+            Ok(Number::integer(5))
+        );
+        assert_eq!(
+            result(Language::Spanish, "cuánto es treinta y dos entre cuatro"),
+            Ok(Number::integer(8))
+        );
+        assert_eq!(
+            result(Language::Spanish, "el quince por ciento de doscientos"),
+            Ok(Number::integer(30))
+        );
+    }
+
+    #[test]
+    fn fails_without_an_answer() {
+        assert_eq!(english("5 divided by 0"), Err(Error::DivisionByZero));
+        assert_eq!(english("1 over 2 minus 0.5"), Ok(Number::integer(0)));
+        assert_eq!(
+            english("1 divided by (2 minus 2)"),
+            Err(Error::DivisionByZero)
+        );
+        assert_eq!(english("square root of -4"), Err(Error::NotReal));
+        assert_eq!(english("half factorial"), Err(Error::NotWhole));
+        assert_eq!(
+            english("what's the weather like"),
+            Err(Error::NotUnderstood)
+        );
+        assert_eq!(english("2 plus plus"), Err(Error::NotUnderstood));
+    }
+}
@@ -0,0 +1,326 @@
+//! Sums that are asked for out loud, such as "what's 15% of 240" or "square root of 144
+//! times 3", see [`expression`]. Answers are exact where they can be: "1 divided by 3" is
+//! a third rather than 0.3333.
+mod expression;
+mod number;
+mod spelled;
+
+use async_trait::async_trait;
+use kara_nlu::{intents::Intent, Language};
+
+use self::number::Number;
+use super::{Context, Request, Response, Skill};
+
+/// Decimal places said of a result that is exact but not whole, e.g. 0.125
+const MAX_EXACT_PLACES: u32 = 6;
+/// Decimal places said of a result that is not exact
+const APPROXIMATE_PLACES: usize = 4;
+/// Results at least this large, or smaller than [`SMALLEST`], are said as powers of 10
+const LARGEST: f64 = 1e15;
+const SMALLEST: f64 = 1e-4;
+
+/// Why a sum has no answer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    DivisionByZero,
+    /// E.g. the square root of -1
+    NotReal,
+    TooLarge,
+    /// A factorial of a fraction or of a negative number
+    NotWhole,
+    NotUnderstood,
+}
+
+pub struct Maths;
+
+#[async_trait]
+impl Skill for Maths {
+    fn name(&self) -> &'static str {
+        "maths"
+    }
+
+    fn intents(&self) -> &[Intent] {
+        &[Intent::QAMaths]
+    }
+
+    async fn handle(&self, request: &Request, context: &Context) -> anyhow::Result<Response> {
+        let language = context.language;
+        let result = expression::parse(language, &request.input)
+            .and_then(|expression| expression.evaluate());
+        let text = match result {
+            Ok(number) => answer(language, number),
+            Err(error) => failure(language, error).to_owned(),
+        };
+        Ok(Response::text(text))
+    }
+}
+
+fn answer(language: Language, number: Number) -> String {
+    let (value, exact) = say(language, number);
+    match (language, exact) {
+        (Language::English, true) => format!("That's {value}"),
+        (Language::English, false) => format!("That's about {value}"),
+        (Language::French, true) => format!("Ça fait {value}"),
+        (Language::French, false) => format!("Ça fait environ {value}"),
+        (Language::German, true) => format!("Das ergibt {value}"),
+        (Language::German, false) => format!("Das ergibt ungefähr {value}"),
+        (Language::Spanish, true) => format!("El resultado es {value}"),
+        (Language::Spanish, false) => format!("El resultado es aproximadamente {value}"),
+    }
+}
+
+fn failure(language: Language, error: Error) -> &'static str {
+    match (error, language) {
+        (Error::DivisionByZero, Language::English) => "You can't divide by zero",
+        (Error::DivisionByZero, Language::French) => "On ne peut pas diviser par zéro",
+        (Error::DivisionByZero, Language::German) => "Man kann nicht durch null teilen",
+        (Error::DivisionByZero, Language::Spanish) => "No se puede dividir entre cero",
+        (Error::NotReal, Language::English) => "The answer isn't a real number",
+        (Error::NotReal, Language::French) => "Le résultat n'est pas un nombre réel",
+        (Error::NotReal, Language::German) => "Das Ergebnis ist keine reelle Zahl",
+        (Error::NotReal, Language::Spanish) => "El resultado no es un número real",
+        (Error::TooLarge, Language::English) => "The answer is too large for me",
+        (Error::TooLarge, Language::French) => "Le résultat est trop grand pour moi",
+        (Error::TooLarge, Language::German) => "Das Ergebnis ist mir zu groß",
+        (Error::TooLarge, Language::Spanish) => "El resultado es demasiado grande para mí",
+        (Error::NotWhole, Language::English) => {
+            "Only whole numbers that aren't negative have a factorial"
+        }
+        (Error::NotWhole, Language::French) => {
+            "Seuls les nombres entiers positifs ont une factorielle"
+        }
+        (Error::NotWhole, Language::German) => "Nur natürliche Zahlen haben eine Fakultät",
+        (Error::NotWhole, Language::Spanish) => {
+            "Solo los números enteros no negativos tienen factorial"
+        }
+        (Error::NotUnderstood, Language::English) => "Sorry, I couldn't work out that sum",
+        (Error::NotUnderstood, Language::French) => "Désolée, je n'ai pas compris ce calcul",
+        (Error::NotUnderstood, Language::German) => {
+            "Entschuldigung, ich habe die Rechnung nicht verstanden"
+        }
+        (Error::NotUnderstood, Language::Spanish) => "Lo siento, no he entendido la operación",
+    }
+}
+
+/// How to say `number`, and whether that is exactly it
+fn say(language: Language, number: Number) -> (String, bool) {
+    let value = number.to_f64();
+    if value != 0.0 && (value.abs() >= LARGEST || value.abs() < SMALLEST) {
+        return scientific(language, number);
+    }
+    match number {
+        Number::Exact(numerator, 1) => (group(language, &numerator.to_string()), true),
+        Number::Exact(numerator, denominator) => {
+            if let Some(places) =
+                (1..=MAX_EXACT_PLACES).find(|places| 10i128.pow(*places) % denominator == 0)
+            {
+                return (decimal(language, value, places as usize), true);
+            }
+            match thirds(language, numerator, denominator) {
+                Some(thirds) => (thirds, true),
+                None => (decimal(language, value, APPROXIMATE_PLACES), false),
+            }
+        }
+        Number::Approximate(value) if value.fract() == 0.0 => {
+            (group(language, &format!("{value:.0}")), true)
+        }
+        Number::Approximate(value) => (decimal(language, value, APPROXIMATE_PLACES), false),
+    }
+}
+
+/// Thirds as words, e.g. "33 and a third", as 33.3333 is not what anyone would say
+fn thirds(language: Language, numerator: i128, denominator: i128) -> Option<String> {
+    if denominator != 3 {
+        return None;
+    }
+    let (whole, thirds) = (numerator / 3, numerator.abs() % 3);
+    let fraction = match (language, thirds) {
+        (Language::English, 1) => "a third",
+        (Language::English, _) => "2 thirds",
+        (Language::French, 1) => "un tiers",
+        (Language::French, _) => "2 tiers",
+        (Language::German, 1) => "ein Drittel",
+        (Language::German, _) => "2 Drittel",
+        (Language::Spanish, 1) => "un tercio",
+        (Language::Spanish, _) => "2 tercios",
+    };
+    if whole == 0 && numerator < 0 {
+        let minus = match language {
+            Language::English | Language::German => "minus",
+            Language::French => "moins",
+            Language::Spanish => "menos",
+        };
+        return Some(format!("{minus} {fraction}"));
+    }
+    if whole == 0 {
+        return Some(fraction.to_owned());
+    }
+    let and = match language {
+        Language::English => "and",
+        Language::French => "et",
+        Language::German => "und",
+        Language::Spanish => "y",
+    };
+    let whole = group(language, &whole.to_string());
+    Some(format!("{whole} {and} {fraction}"))
+}
+
+/// `value` with at most `places` decimal places, leaving out zeros at the end
+fn decimal(language: Language, value: f64, places: usize) -> String {
+    let digits = format!("{value:.places$}");
+    let digits = digits.trim_end_matches('0').trim_end_matches('.');
+    let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    // Rounding can leave "-0"
+    let whole = match whole {
+        "-0" if fraction.is_empty() => "0",
+        whole => whole,
+    };
+    let whole = group(language, whole);
+    if fraction.is_empty() {
+        return whole;
+    }
+    let mark = match language {
+        Language::English => '.',
+        _ => ',',
+    };
+    format!("{whole}{mark}{fraction}")
+}
+
+/// Separates the thousands of whole numbers from 10000 up, e.g. "12,500"
+fn group(language: Language, digits: &str) -> String {
+    let (sign, digits) = match digits.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", digits),
+    };
+    if digits.len() < 5 {
+        return format!("{sign}{digits}");
+    }
+    let separator = match language {
+        Language::English => ',',
+        Language::French => ' ',
+        Language::German | Language::Spanish => '.',
+    };
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            grouped.push(separator);
+        }
+        grouped.push(digit);
+    }
+    format!("{sign}{grouped}")
+}
+
+/// E.g. "1.5 times 10 to the power of 20", and whether that is exactly `number`
+fn scientific(language: Language, number: Number) -> (String, bool) {
+    let value = number.to_f64();
+    let exponent = value.abs().log10().floor() as i32;
+    let mut mantissa = value / 10f64.powi(exponent);
+    let mut exponent = exponent;
+    // 9.9996 rounds to 10
+    if (mantissa.abs() * 1000.0).round() >= 10_000.0 {
+        mantissa /= 10.0;
+        exponent += 1;
+    }
+    // Whether the 4 digits said are all there is
+    let exact = Number::integer(10)
+        .power(Number::integer(i128::from(3 - exponent)))
+        .and_then(|scale| number.multiply(scale))
+        .map_or(false, |scaled| matches!(scaled, Number::Exact(_, 1)));
+    let mantissa = decimal(language, mantissa, 3);
+    let text = match language {
+        Language::English => format!("{mantissa} times 10 to the power of {exponent}"),
+        Language::French => format!("{mantissa} fois 10 puissance {exponent}"),
+        Language::German => format!("{mantissa} mal 10 hoch {exponent}"),
+        Language::Spanish => format!("{mantissa} por 10 elevado a {exponent}"),
+    };
+    (text, exact)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn says_numbers() {
+        let english = |number| say(Language::English, number);
+        assert_eq!(english(Number::integer(42)), ("42".to_owned(), true));
+        assert_eq!(english(Number::Exact(1, 8)), ("0.125".to_owned(), true));
+        assert_eq!(english(Number::Exact(-5, 2)), ("-2.5".to_owned(), true));
+        assert_eq!(english(Number::Exact(1, 7)), ("0.1429".to_owned(), false));
+        assert_eq!(
+            english(Number::Approximate(2f64.sqrt())),
+            ("1.4142".to_owned(), false)
+        );
+        assert_eq!(english(Number::Approximate(3.0)), ("3".to_owned(), true));
+        assert_eq!(english(Number::Exact(0, 1)), ("0".to_owned(), true));
+        assert_eq!(
+            say(Language::French, Number::Exact(5, 4)),
+            ("1,25".to_owned(), true)
+        );
+    }
+
+    #[test]
+    fn says_thirds_as_words() {
+        assert_eq!(
+            say(Language::English, Number::Exact(100, 3)),
+            ("33 and a third".to_owned(), true)
+        );
+        assert_eq!(thirds(Language::English, 2, 3), Some("2 thirds".to_owned()));
+        assert_eq!(
+            thirds(Language::French, -1, 3),
+            Some("moins un tiers".to_owned())
+        );
+        assert_eq!(
+            thirds(Language::German, 37000, 3),
+            Some("12.333 und ein Drittel".to_owned())
+        );
+        assert_eq!(
+            thirds(Language::Spanish, -5, 3),
+            Some("-1 y 2 tercios".to_owned())
+        );
+        assert_eq!(thirds(Language::English, 1, 9), None);
+    }
+
+    #[test]
+    fn groups_thousands() {
+        assert_eq!(group(Language::English, "1234"), "1234");
+        assert_eq!(group(Language::English, "12500"), "12,500");
+        assert_eq!(group(Language::English, "-1234567"), "-1,234,567");
+        assert_eq!(group(Language::French, "12500"), "12 500");
+        assert_eq!(group(Language::German, "100000"), "100.000");
+        assert_eq!(group(Language::Spanish, "12500"), "12.500");
+    }
+
+    #[test]
+    fn says_large_and_small_numbers_as_powers_of_10() {
+        assert_eq!(
+            say(Language::English, Number::integer(2 * 10i128.pow(20))),
+            ("2 times 10 to the power of 20".to_owned(), true)
+        );
+        assert_eq!(
+            scientific(Language::English, Number::integer(123_456_789_000_000_000)),
+            ("1.235 times 10 to the power of 17".to_owned(), false)
+        );
+        assert_eq!(
+            say(Language::German, Number::Exact(15, 1_000_000)),
+            ("1,5 mal 10 hoch -5".to_owned(), true)
+        );
+        // 9.9996 rounds up to 10
+        assert_eq!(
+            scientific(Language::English, Number::Approximate(9.9996e20)),
+            ("1 times 10 to the power of 21".to_owned(), false)
+        );
+    }
+
+    #[test]
+    fn answers_in_the_language() {
+        assert_eq!(
+            answer(Language::English, Number::Exact(1, 3)),
+            "That's a third"
+        );
+        assert_eq!(
+            answer(Language::Spanish, Number::Approximate(2f64.sqrt())),
+            "El resultado es aproximadamente 1,4142"
+        );
+    }
+}
@@ -0,0 +1,296 @@
+//! Numbers that are kept as exact fractions for as long as the arithmetic allows, so that
+//! "1 divided by 3 times 3" is 1 and not 0.9999999. Results that have no exact fraction,
+//! such as the square root of 2, or that do not fit, are approximated.
+use std::cmp::Ordering;
+
+use super::Error;
+
+/// The largest factorial that is worked out, as larger ones are too large to say
+const MAX_FACTORIAL: i128 = 170;
+/// The largest exponent applied exactly, as larger ones overflow anyway
+const MAX_EXACT_EXPONENT: i128 = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Number {
+    /// A numerator and a denominator, in lowest terms with a positive denominator
+    Exact(i128, i128),
+    Approximate(f64),
+}
+
+impl Number {
+    pub fn integer(value: i128) -> Self {
+        Number::Exact(value, 1)
+    }
+
+    pub fn ratio(numerator: i128, denominator: i128) -> Result<Self, Error> {
+        if denominator == 0 {
+            return Err(Error::DivisionByZero);
+        }
+        let divisor = gcd(numerator, denominator);
+        let sign = if denominator < 0 { -1 } else { 1 };
+        Ok(Number::Exact(
+            sign * numerator / divisor,
+            sign * denominator / divisor,
+        ))
+    }
+
+    /// A number written with digits and an optional decimal point, e.g. "12.25"
+    pub fn parse(digits: &str) -> Option<Self> {
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        if whole.is_empty() && fraction.is_empty()
+            || !whole
+                .chars()
+                .chain(fraction.chars())
+                .all(|c| c.is_ascii_digit())
+        {
+            return None;
+        }
+        let exact = u32::try_from(fraction.len())
+            .ok()
+            .and_then(|places| 10i128.checked_pow(places))
+            .and_then(|denominator| {
+                let numerator = format!("{whole}{fraction}").parse().ok()?;
+                Number::ratio(numerator, denominator).ok()
+            });
+        exact.or_else(|| {
+            format!("{whole}.{fraction}0")
+                .parse()
+                .ok()
+                .map(Number::Approximate)
+        })
+    }
+
+    pub fn to_f64(self) -> f64 {
+        match self {
+            Number::Exact(numerator, denominator) => numerator as f64 / denominator as f64,
+            Number::Approximate(value) => value,
+        }
+    }
+
+    pub fn is_zero(self) -> bool {
+        match self {
+            Number::Exact(numerator, _) => numerator == 0,
+            Number::Approximate(value) => value == 0.0,
+        }
+    }
+
+    /// Applies `exact` to two exact numbers, or `approximate` if either is not exact or
+    /// the exact result does not fit
+    fn combine(
+        self,
+        other: Number,
+        exact: impl Fn(i128, i128, i128, i128) -> Option<(i128, i128)>,
+        approximate: impl Fn(f64, f64) -> f64,
+    ) -> Result<Number, Error> {
+        if let (Number::Exact(a, b), Number::Exact(c, d)) = (self, other) {
+            if let Some((numerator, denominator)) = exact(a, b, c, d) {
+                return Number::ratio(numerator, denominator);
+            }
+        }
+        Number::Approximate(approximate(self.to_f64(), other.to_f64())).checked()
+    }
+
+    pub fn add(self, other: Number) -> Result<Number, Error> {
+        self.combine(
+            other,
+            |a, b, c, d| {
+                Some((
+                    a.checked_mul(d)?.checked_add(c.checked_mul(b)?)?,
+                    b.checked_mul(d)?,
+                ))
+            },
+            |x, y| x + y,
+        )
+    }
+
+    pub fn subtract(self, other: Number) -> Result<Number, Error> {
+        self.add(other.negate())
+    }
+
+    pub fn multiply(self, other: Number) -> Result<Number, Error> {
+        self.combine(
+            other,
+            |a, b, c, d| Some((a.checked_mul(c)?, b.checked_mul(d)?)),
+            |x, y| x * y,
+        )
+    }
+
+    pub fn divide(self, other: Number) -> Result<Number, Error> {
+        if other.is_zero() {
+            return Err(Error::DivisionByZero);
+        }
+        self.combine(
+            other,
+            |a, b, c, d| Some((a.checked_mul(d)?, b.checked_mul(c)?)),
+            |x, y| x / y,
+        )
+    }
+
+    pub fn negate(self) -> Number {
+        match self {
+            Number::Exact(numerator, denominator) => Number::Exact(-numerator, denominator),
+            Number::Approximate(value) => Number::Approximate(-value),
+        }
+    }
+
+    pub fn power(self, exponent: Number) -> Result<Number, Error> {
+        match exponent {
+            Number::Exact(exponent, 1) if exponent.abs() <= MAX_EXACT_EXPONENT => {
+                if let Number::Exact(numerator, denominator) = self {
+                    let power = u32::try_from(exponent.abs()).expect("exponent is small");
+                    if let (Some(numerator), Some(denominator)) =
+                        (numerator.checked_pow(power), denominator.checked_pow(power))
+                    {
+                        return match exponent.cmp(&0) {
+                            Ordering::Less => Number::ratio(denominator, numerator),
+                            _ => Number::ratio(numerator, denominator),
+                        };
+                    }
+                }
+            }
+            // Square and cube roots, e.g. "16 to the power of 0.5"
+            Number::Exact(1, root @ (2 | 3)) => return self.root(root as u32),
+            _ => {}
+        }
+        if self.is_zero() && exponent.to_f64() < 0.0 {
+            return Err(Error::DivisionByZero);
+        }
+        Number::Approximate(self.to_f64().powf(exponent.to_f64())).checked()
+    }
+
+    /// The `n`th root, e.g. the square root for 2
+    pub fn root(self, n: u32) -> Result<Number, Error> {
+        if n % 2 == 0 && self.to_f64() < 0.0 {
+            return Err(Error::NotReal);
+        }
+        if let Number::Exact(numerator, denominator) = self {
+            if let (Some(numerator), Some(denominator)) =
+                (exact_root(numerator, n), exact_root(denominator, n))
+            {
+                return Number::ratio(numerator, denominator);
+            }
+        }
+        let value = self.to_f64();
+        let root = match n {
+            2 => value.sqrt(),
+            3 => value.cbrt(),
+            _ => value.signum() * value.abs().powf(1.0 / n as f64),
+        };
+        Number::Approximate(root).checked()
+    }
+
+    pub fn factorial(self) -> Result<Number, Error> {
+        let n = match self {
+            Number::Exact(n, 1) if n >= 0 => n,
+            _ => return Err(Error::NotWhole),
+        };
+        if n > MAX_FACTORIAL {
+            return Err(Error::TooLarge);
+        }
+        (2..=n).try_fold(Number::integer(1), |product, factor| {
+            product.multiply(Number::integer(factor))
+        })
+    }
+
+    /// Errors for results that are not numbers
+    fn checked(self) -> Result<Number, Error> {
+        match self {
+            Number::Approximate(value) if value.is_nan() => Err(Error::NotReal),
+            Number::Approximate(value) if value.is_infinite() => Err(Error::TooLarge),
+            number => Ok(number),
+        }
+    }
+}
+
+fn gcd(a: i128, b: i128) -> i128 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a.max(1)
+}
+
+/// The whole `n`th root of `value`, if it has one
+fn exact_root(value: i128, n: u32) -> Option<i128> {
+    let guess = (value.abs() as f64).powf(1.0 / n as f64).round() as i128;
+    let root = (guess.saturating_sub(1)..=guess.saturating_add(1))
+        .find(|root| root.checked_pow(n) == Some(value.abs()))?;
+    Some(if value < 0 { -root } else { root })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_ratios_in_lowest_terms() {
+        assert_eq!(Number::ratio(6, -8), Ok(Number::Exact(-3, 4)));
+        assert_eq!(Number::ratio(0, 5), Ok(Number::Exact(0, 1)));
+        assert_eq!(Number::ratio(1, 0), Err(Error::DivisionByZero));
+    }
+
+    #[test]
+    fn parses_digits() {
+        assert_eq!(Number::parse("12.25"), Some(Number::Exact(49, 4)));
+        assert_eq!(Number::parse(".5"), Some(Number::Exact(1, 2)));
+        assert_eq!(Number::parse("7"), Some(Number::integer(7)));
+        assert_eq!(Number::parse("1.2.3"), None);
+        assert_eq!(Number::parse("."), None);
+        assert_eq!(Number::parse("twelve"), None);
+    }
+
+    #[test]
+    fn works_out_exactly() {
+        let third = Number::Exact(1, 3);
+        assert_eq!(
+            third.add(third).and_then(|sum| sum.add(third)),
+            Ok(Number::integer(1))
+        );
+        assert_eq!(Number::integer(2).subtract(third), Ok(Number::Exact(5, 3)));
+        assert_eq!(third.divide(Number::Exact(2, 3)), Ok(Number::Exact(1, 2)));
+        assert_eq!(
+            Number::Exact(2, 3).power(Number::integer(-2)),
+            Ok(Number::Exact(9, 4))
+        );
+        assert_eq!(
+            Number::integer(16).power(Number::Exact(1, 2)),
+            Ok(Number::integer(4))
+        );
+        assert_eq!(Number::Exact(-8, 27).root(3), Ok(Number::Exact(-2, 3)));
+        assert_eq!(Number::integer(5).factorial(), Ok(Number::integer(120)));
+    }
+
+    #[test]
+    fn approximates_what_is_not_exact() {
+        assert!(matches!(
+            Number::integer(2).root(2),
+            Ok(Number::Approximate(root)) if (root - 2f64.sqrt()).abs() < 1e-12
+        ));
+        // Overflowing i128 falls back to floating point
+        let large = Number::integer(i128::MAX);
+        assert!(matches!(
+            large.multiply(Number::integer(2)),
+            Ok(Number::Approximate(_))
+        ));
+    }
+
+    #[test]
+    fn fails_without_an_answer() {
+        assert_eq!(
+            Number::integer(1).divide(Number::integer(0)),
+            Err(Error::DivisionByZero)
+        );
+        assert_eq!(
+            Number::integer(0).power(Number::Approximate(-0.5)),
+            Err(Error::DivisionByZero)
+        );
+        assert_eq!(Number::integer(-4).root(2), Err(Error::NotReal));
+        assert_eq!(Number::Exact(1, 2).factorial(), Err(Error::NotWhole));
+        assert_eq!(Number::integer(-1).factorial(), Err(Error::NotWhole));
+        assert_eq!(Number::integer(171).factorial(), Err(Error::TooLarge));
+        assert_eq!(
+            Number::integer(10).power(Number::integer(400)),
+            Err(Error::TooLarge)
+        );
+    }
+}
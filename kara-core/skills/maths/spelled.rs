@@ -0,0 +1,473 @@
+//! Numbers that are spelled out, e.g. "deux cent cinquante", "dreiundzwanzig" or "treinta y
+//! dos coma cinco". Only English transcripts are normalised to digits before they reach
+//! the skill, so French, German and Spanish numbers are read here. German writes its
+//! numbers as one word, which is split into the words it is made of first.
+use kara_nlu::Language;
+
+use super::number::Number;
+
+/// What a word of a spelled number does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Part {
+    /// Adds to the group being read, e.g. "vingt" in "vingt trois"
+    Value(i128),
+    /// Multiplies the group being read, e.g. "cent" in "deux cent"
+    Hundred,
+    /// Ends a group: "mille", "Millionen"
+    Scale(i128),
+    /// "et", "und" and "y" inside a number, e.g. "vingt et un"
+    Join,
+    /// The decimal comma
+    Point,
+}
+
+/// The spelled number that `words` start with, and how many words it took
+pub fn read(language: Language, words: &[&str]) -> Option<(Number, usize)> {
+    let (whole, mut read) = whole(language, words)?;
+    if !words
+        .get(read)
+        .map_or(false, |word| is(language, word, Part::Point))
+    {
+        return Some((Number::integer(whole), read));
+    }
+    // Zeros after the comma are said one by one, e.g. "trois virgule zéro cinq"
+    let zeros = words[read + 1..]
+        .iter()
+        .take_while(|word| is(language, word, Part::Value(0)))
+        .count();
+    let fraction = whole_after(language, &words[read + 1 + zeros..]);
+    let fraction = match fraction {
+        Some((fraction, more)) if fraction > 0 => {
+            read += 1 + zeros + more;
+            format!("{}{fraction}", "0".repeat(zeros))
+        }
+        // "zéro virgule zéro" is still a number
+        _ if zeros > 0 => {
+            read += 1 + zeros;
+            "0".to_owned()
+        }
+        _ => return Some((Number::integer(whole), read)),
+    };
+    let number = Number::parse(&format!("{whole}.{fraction}"))?;
+    Some((number, read))
+}
+
+/// The whole number after a decimal comma, which may not start with zero
+fn whole_after(language: Language, words: &[&str]) -> Option<(i128, usize)> {
+    whole(language, words).filter(|(number, _)| *number != 0)
+}
+
+/// The whole number that `words` start with. Words are taken for as long as they make a
+/// number together, so "deux trois" is 2 and then 3
+fn whole(language: Language, words: &[&str]) -> Option<(i128, usize)> {
+    let mut reader = Reader::default();
+    let mut read = None;
+    for (i, word) in words.iter().enumerate() {
+        let parts = match parts(language, word) {
+            Some(parts) => parts,
+            None => break,
+        };
+        let mut next = reader.clone();
+        if !parts.iter().all(|part| next.take(language, *part)) {
+            break;
+        }
+        reader = next;
+        if !reader.joining {
+            read = Some((reader.value(), i + 1));
+        }
+    }
+    read
+}
+
+/// A number read so far, split like "deux mille | trois cent quatre"
+#[derive(Debug, Clone, Default)]
+struct Reader {
+    /// What the scales read so far add up to
+    total: i128,
+    /// The group after the last scale
+    group: i128,
+    /// The last value added to the group, which the next one must be smaller than
+    last: Option<i128>,
+    /// The last scale, which the next one must be smaller than
+    scale: Option<i128>,
+    /// After "et", "und" or "y", which must be followed by a value
+    joining: bool,
+    /// Whether anything has been read, as zero must be the whole number
+    started: bool,
+}
+
+impl Reader {
+    fn value(&self) -> i128 {
+        self.total + self.group
+    }
+
+    /// Reads `part`, or returns false if it can't come next
+    fn take(&mut self, language: Language, part: Part) -> bool {
+        if self.last == Some(0) {
+            return false;
+        }
+        let taken = match part {
+            Part::Value(0) if !self.started => {
+                self.last = Some(0);
+                true
+            }
+            Part::Value(0) => false,
+            Part::Value(value) if self.joining => self.join(language, value),
+            // "quatre-vingts" is 4 times 20
+            Part::Value(20) if language == Language::French && self.last == Some(4) => {
+                self.group += 76;
+                self.last = Some(80);
+                true
+            }
+            Part::Value(value) => {
+                let fits = self.last.map_or(true, |last| value < last);
+                if fits {
+                    self.group += value;
+                    self.last = Some(value);
+                }
+                fits
+            }
+            Part::Hundred if !self.joining && self.group < 100 => {
+                self.group = self.group.max(1) * 100;
+                self.last = Some(100);
+                true
+            }
+            Part::Scale(scale) if !self.joining && self.scale.map_or(true, |last| scale < last) => {
+                self.total += self.group.max(1) * scale;
+                self.group = 0;
+                self.last = None;
+                self.scale = Some(scale);
+                true
+            }
+            Part::Join if !self.joining => {
+                let joins = match language {
+                    Language::English => false,
+                    // "soixante et onze"
+                    Language::French => matches!(self.last, Some(20 | 30 | 40 | 50 | 60)),
+                    // "drei und zwanzig"
+                    Language::German => matches!(self.last, Some(1..=9)),
+                    // "treinta y dos"
+                    Language::Spanish => {
+                        matches!(self.last, Some(30 | 40 | 50 | 60 | 70 | 80 | 90))
+                    }
+                };
+                self.joining = joins;
+                joins
+            }
+            _ => false,
+        };
+        self.started |= taken;
+        taken
+    }
+
+    /// Reads `value` after "et", "und" or "y"
+    fn join(&mut self, language: Language, value: i128) -> bool {
+        let joins = match language {
+            Language::English => false,
+            Language::French => matches!(value, 1 | 11),
+            Language::German => matches!(value, 20 | 30 | 40 | 50 | 60 | 70 | 80 | 90),
+            Language::Spanish => matches!(value, 1..=9),
+        };
+        if joins {
+            self.group += value;
+            // Nothing is added to "dreiundzwanzig"
+            self.last = Some(if language == Language::German {
+                1
+            } else {
+                value
+            });
+            self.joining = false;
+        }
+        joins
+    }
+}
+
+/// Whether `word` is only `part`
+fn is(language: Language, word: &str, part: Part) -> bool {
+    parts(language, word).as_deref() == Some(&[part][..])
+}
+
+/// What `word` means in a number, if it is part of one. German words are split into the
+/// words they are made of, e.g. "zweihundertfünfzig" into "zwei", "hundert", "fünfzig"
+fn parts(language: Language, word: &str) -> Option<Vec<Part>> {
+    let words = words(language);
+    if language != Language::German {
+        return words
+            .iter()
+            .find(|(spelled, _)| *spelled == word)
+            .map(|(_, part)| vec![*part]);
+    }
+    let mut parts = Vec::new();
+    let mut rest = word;
+    while !rest.is_empty() {
+        // The longest word wins, e.g. "sechzehn" over "sechs"
+        let (spelled, part) = words
+            .iter()
+            .filter(|(spelled, _)| rest.starts_with(spelled))
+            .max_by_key(|(spelled, _)| spelled.len())?;
+        parts.push(*part);
+        rest = &rest[spelled.len()..];
+    }
+    Some(parts)
+}
+
+/// The words of spelled numbers in a language
+fn words(language: Language) -> &'static [(&'static str, Part)] {
+    use self::Part::*;
+    match language {
+        Language::English => &[],
+        Language::French => &[
+            ("zéro", Value(0)),
+            ("un", Value(1)),
+            ("une", Value(1)),
+            ("deux", Value(2)),
+            ("trois", Value(3)),
+            ("quatre", Value(4)),
+            ("cinq", Value(5)),
+            ("six", Value(6)),
+            ("sept", Value(7)),
+            ("huit", Value(8)),
+            ("neuf", Value(9)),
+            ("dix", Value(10)),
+            ("onze", Value(11)),
+            ("douze", Value(12)),
+            ("treize", Value(13)),
+            ("quatorze", Value(14)),
+            ("quinze", Value(15)),
+            ("seize", Value(16)),
+            ("vingt", Value(20)),
+            ("vingts", Value(20)),
+            ("trente", Value(30)),
+            ("quarante", Value(40)),
+            ("cinquante", Value(50)),
+            ("soixante", Value(60)),
+            ("septante", Value(70)),
+            ("huitante", Value(80)),
+            ("octante", Value(80)),
+            ("nonante", Value(90)),
+            ("cent", Hundred),
+            ("cents", Hundred),
+            ("mille", Scale(1_000)),
+            ("million", Scale(1_000_000)),
+            ("millions", Scale(1_000_000)),
+            ("milliard", Scale(1_000_000_000)),
+            ("milliards", Scale(1_000_000_000)),
+            ("et", Join),
+            ("virgule", Point),
+        ],
+        Language::German => &[
+            ("null", Value(0)),
+            ("ein", Value(1)),
+            ("eins", Value(1)),
+            ("eine", Value(1)),
+            ("zwei", Value(2)),
+            ("zwo", Value(2)),
+            ("drei", Value(3)),
+            ("vier", Value(4)),
+            ("fünf", Value(5)),
+            ("sechs", Value(6)),
+            ("sieben", Value(7)),
+            ("acht", Value(8)),
+            ("neun", Value(9)),
+            ("zehn", Value(10)),
+            ("elf", Value(11)),
+            ("zwölf", Value(12)),
+            ("dreizehn", Value(13)),
+            ("vierzehn", Value(14)),
+            ("fünfzehn", Value(15)),
+            ("sechzehn", Value(16)),
+            ("siebzehn", Value(17)),
+            ("achtzehn", Value(18)),
+            ("neunzehn", Value(19)),
+            ("zwanzig", Value(20)),
+            ("dreißig", Value(30)),
+            ("dreissig", Value(30)),
+            ("vierzig", Value(40)),
+            ("fünfzig", Value(50)),
+            ("sechzig", Value(60)),
+            ("siebzig", Value(70)),
+            ("achtzig", Value(80)),
+            ("neunzig", Value(90)),
+            ("hundert", Hundred),
+            ("tausend", Scale(1_000)),
+            ("million", Scale(1_000_000)),
+            ("millionen", Scale(1_000_000)),
+            ("milliarde", Scale(1_000_000_000)),
+            ("milliarden", Scale(1_000_000_000)),
+            ("und", Join),
+            ("komma", Point),
+        ],
+        Language::Spanish => &[
+            ("cero", Value(0)),
+            ("un", Value(1)),
+            ("uno", Value(1)),
+            ("una", Value(1)),
+            ("dos", Value(2)),
+            ("tres", Value(3)),
+            ("cuatro", Value(4)),
+            ("cinco", Value(5)),
+            ("seis", Value(6)),
+            ("siete", Value(7)),
+            ("ocho", Value(8)),
+            ("nueve", Value(9)),
+            ("diez", Value(10)),
+            ("once", Value(11)),
+            ("doce", Value(12)),
+            ("trece", Value(13)),
+            ("catorce", Value(14)),
+            ("quince", Value(15)),
+            ("dieciséis", Value(16)),
+            ("dieciseis", Value(16)),
+            ("diecisiete", Value(17)),
+            ("dieciocho", Value(18)),
+            ("diecinueve", Value(19)),
+            ("veinte", Value(20)),
+            ("veintiuno", Value(21)),
+            ("veintiún", Value(21)),
+            ("veintiun", Value(21)),
+            ("veintiuna", Value(21)),
+            ("veintidós", Value(22)),
+            ("veintidos", Value(22)),
+            ("veintitrés", Value(23)),
+            ("veintitres", Value(23)),
+            ("veinticuatro", Value(24)),
+            ("veinticinco", Value(25)),
+            ("veintiséis", Value(26)),
+            ("veintiseis", Value(26)),
+            ("veintisiete", Value(27)),
+            ("veintiocho", Value(28)),
+            ("veintinueve", Value(29)),
+            ("treinta", Value(30)),
+            ("cuarenta", Value(40)),
+            ("cincuenta", Value(50)),
+            ("sesenta", Value(60)),
+            ("setenta", Value(70)),
+            ("ochenta", Value(80)),
+            ("noventa", Value(90)),
+            ("cien", Value(100)),
+            ("ciento", Value(100)),
+            ("doscientos", Value(200)),
+            ("doscientas", Value(200)),
+            ("trescientos", Value(300)),
+            ("trescientas", Value(300)),
+            ("cuatrocientos", Value(400)),
+            ("cuatrocientas", Value(400)),
+            ("quinientos", Value(500)),
+            ("quinientas", Value(500)),
+            ("seiscientos", Value(600)),
+            ("seiscientas", Value(600)),
+            ("setecientos", Value(700)),
+            ("setecientas", Value(700)),
+            ("ochocientos", Value(800)),
+            ("ochocientas", Value(800)),
+            ("novecientos", Value(900)),
+            ("novecientas", Value(900)),
+            ("mil", Scale(1_000)),
+            ("millón", Scale(1_000_000)),
+            ("millon", Scale(1_000_000)),
+            ("millones", Scale(1_000_000)),
+            ("y", Join),
+            ("coma", Point),
+            ("punto", Point),
+        ],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spelled(language: Language, text: &str) -> Option<(Number, usize)> {
+        let words: Vec<_> = text.split(' ').collect();
+        read(language, &words)
+    }
+
+    fn number(language: Language, text: &str) -> Option<Number> {
+        spelled(language, text).map(|(number, _)| number)
+    }
+
+    #[test]
+    fn reads_french_numbers() {
+        let french = |text| number(Language::French, text);
+        assert_eq!(french("zéro"), Some(Number::integer(0)));
+        assert_eq!(french("vingt et un"), Some(Number::integer(21)));
+        assert_eq!(french("soixante et onze"), Some(Number::integer(71)));
+        assert_eq!(french("soixante dix sept"), Some(Number::integer(77)));
+        assert_eq!(french("quatre vingts"), Some(Number::integer(80)));
+        assert_eq!(french("quatre vingt dix neuf"), Some(Number::integer(99)));
+        assert_eq!(french("deux cent cinquante"), Some(Number::integer(250)));
+        assert_eq!(french("deux mille vingt six"), Some(Number::integer(2026)));
+        assert_eq!(
+            french("trois millions deux cent mille"),
+            Some(Number::integer(3_200_000))
+        );
+        assert_eq!(french("trois virgule cinq"), Number::parse("3.5"));
+        assert_eq!(french("un virgule zéro cinq"), Number::parse("1.05"));
+        assert_eq!(french("plus"), None);
+    }
+
+    #[test]
+    fn reads_german_numbers() {
+        let german = |text| number(Language::German, text);
+        assert_eq!(german("dreiundzwanzig"), Some(Number::integer(23)));
+        assert_eq!(german("drei und zwanzig"), Some(Number::integer(23)));
+        assert_eq!(german("siebzehn"), Some(Number::integer(17)));
+        assert_eq!(
+            german("zweihundertsechsundfünfzig"),
+            Some(Number::integer(256))
+        );
+        assert_eq!(
+            german("zweitausendsechsundzwanzig"),
+            Some(Number::integer(2026))
+        );
+        assert_eq!(
+            german("eine million zweihunderttausend"),
+            Some(Number::integer(1_200_000))
+        );
+        assert_eq!(german("null komma fünf"), Number::parse("0.5"));
+        // Fractions are not numbers, but phrases of arithmetic
+        assert_eq!(german("viertel"), None);
+        assert_eq!(german("einhalb"), None);
+    }
+
+    #[test]
+    fn reads_spanish_numbers() {
+        let spanish = |text| number(Language::Spanish, text);
+        assert_eq!(spanish("treinta y dos"), Some(Number::integer(32)));
+        assert_eq!(spanish("veintitrés"), Some(Number::integer(23)));
+        assert_eq!(spanish("ciento veinte"), Some(Number::integer(120)));
+        assert_eq!(spanish("dos mil quinientos"), Some(Number::integer(2500)));
+        assert_eq!(spanish("dos millones"), Some(Number::integer(2_000_000)));
+        assert_eq!(spanish("doce coma veinticinco"), Number::parse("12.25"));
+    }
+
+    #[test]
+    fn stops_where_the_number_ends() {
+        // "et", "und" and "y" that do not join a number are left to the sum
+        assert_eq!(
+            spelled(Language::French, "deux et demi"),
+            Some((Number::integer(2), 1))
+        );
+        assert_eq!(
+            spelled(Language::Spanish, "dos y dos"),
+            Some((Number::integer(2), 1))
+        );
+        assert_eq!(
+            spelled(Language::Spanish, "treinta y"),
+            Some((Number::integer(30), 1))
+        );
+        assert_eq!(
+            spelled(Language::French, "deux trois"),
+            Some((Number::integer(2), 1))
+        );
+        assert_eq!(
+            spelled(Language::French, "mille mille"),
+            Some((Number::integer(1000), 1))
+        );
+        assert_eq!(
+            spelled(Language::German, "zwei komma"),
+            Some((Number::integer(2), 1))
+        );
+        assert_eq!(spelled(Language::English, "two"), None);
+    }
+}
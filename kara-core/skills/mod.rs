@@ -0,0 +1,568 @@
+//! Skills act on the intents Kara recognises. Each [`Skill`] claims a set of intents and
+//! the [`Dispatcher`] routes every parsed command to the skill that claimed its intent.
+mod agenda;
+mod alarms;
+mod datetime;
+mod dialogue;
+mod general;
+mod lists;
+mod maths;
+mod search;
+mod setup;
+
+use std::{collections::HashMap, fmt::Display, future::Future, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use kara_events::Speaker;
+use kara_nlu::{
+    context::ConversationContext,
+    intents::{Intent, ParsedIntent, Slot, SlotValue},
+    Language,
+};
+use tracing::{debug, warn};
+
+use crate::locale;
+
+pub use agenda::Agenda;
+pub use alarms::Alarms;
+pub use dialogue::{Confirmation, DialogueManager, Turn};
+pub use lists::Lists;
+pub use setup::Skills;
+
+/// How long a skill may take to respond before it is abandoned
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[async_trait]
+pub trait Skill: Send + Sync {
+    fn name(&self) -> &'static str;
+    /// The intents this skill handles
+    fn intents(&self) -> &[Intent];
+    /// Slots `request` cannot be handled without. The dialogue manager asks for them before
+    /// the request reaches [`Skill::handle`]
+    fn required_slots(&self, _request: &Request, _context: &Context) -> Vec<RequiredSlot> {
+        Vec::new()
+    }
+    /// Whether `intent` does something that is hard to undo. Such commands are always
+    /// confirmed before they reach [`Skill::handle`]
+    fn is_destructive(&self, intent: &Intent) -> bool {
+        matches!(
+            intent,
+            Intent::AlarmRemove | Intent::CalendarRemove | Intent::EmailSend | Intent::ListsRemove
+        )
+    }
+    /// Whether `intent` changes or gives away someone's data. When speakers are identified,
+    /// such commands are only carried out for enrolled speakers
+    fn is_sensitive(&self, intent: &Intent) -> bool {
+        self.is_destructive(intent)
+            || matches!(
+                intent,
+                Intent::CalendarSet | Intent::ListsCreateOrAdd | Intent::EmailAddContact
+            )
+    }
+    async fn handle(&self, request: &Request, context: &Context) -> anyhow::Result<Response>;
+}
+
+/// Handles commands that were not understood, or not with enough confidence, e.g. by
+/// looking them up on the web
+#[async_trait]
+pub trait Fallback: Send + Sync {
+    fn name(&self) -> &'static str;
+    /// `input` is the text of the command
+    async fn handle(&self, input: &str, context: &Context) -> anyhow::Result<Response>;
+}
+
+/// A slot a skill needs, and how to ask for it
+#[derive(Debug, Clone)]
+pub struct RequiredSlot {
+    pub name: &'static str,
+    /// Entity of the slot, e.g. "snips/datetime". Answers to questions for custom entities
+    /// are taken as they were said
+    pub entity: &'static str,
+    pub question: String,
+}
+
+/// A command that was understood
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub intent: Intent,
+    /// The text the intent was parsed from
+    pub input: String,
+    pub slots: Vec<Slot>,
+}
+
+impl Request {
+    /// The value of the first slot named `name`
+    pub fn slot(&self, name: &str) -> Option<&SlotValue> {
+        self.slots
+            .iter()
+            .find(|slot| slot.slot_name == name)
+            .map(|slot| &slot.value)
+    }
+}
+
+/// Who is asking and how to answer them
+#[derive(Debug, Clone, Default)]
+pub struct Context {
+    pub speaker: Speaker,
+    pub language: Language,
+    /// Earlier turns of the conversation
+    pub conversation: ConversationContext,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub text: String,
+}
+
+impl Response {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self { text: text.into() }
+    }
+}
+
+#[derive(Debug)]
+pub enum DispatchError {
+    /// The command did not match any intent
+    NotUnderstood,
+    /// No skill has claimed the intent
+    Unhandled(Intent),
+    /// The intent is sensitive and the speaker was not recognised
+    UnknownSpeaker(Intent),
+    TimedOut {
+        skill: &'static str,
+        after: Duration,
+    },
+    Failed {
+        skill: &'static str,
+        error: anyhow::Error,
+    },
+    Panicked {
+        skill: &'static str,
+    },
+}
+
+impl Display for DispatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DispatchError::NotUnderstood => write!(f, "command was not understood"),
+            DispatchError::Unhandled(intent) => write!(f, "no skill handles {intent}"),
+            DispatchError::UnknownSpeaker(intent) => {
+                write!(f, "{intent} is only carried out for enrolled speakers")
+            }
+            DispatchError::TimedOut { skill, after } => {
+                write!(f, "{skill} did not respond within {after:?}")
+            }
+            DispatchError::Failed { skill, error } => write!(f, "{skill} failed: {error}"),
+            DispatchError::Panicked { skill } => write!(f, "{skill} panicked"),
+        }
+    }
+}
+
+impl std::error::Error for DispatchError {}
+
+/// Routes parsed commands to skills. Share it between front ends with an `Arc`
+pub struct Dispatcher {
+    skills: HashMap<Intent, Arc<dyn Skill>>,
+    fallback: Option<Arc<dyn Fallback>>,
+    timeout: Duration,
+    /// Sensitive commands are only dispatched for enrolled speakers
+    known_speakers_only: bool,
+}
+
+impl Default for Dispatcher {
+    /// A dispatcher with Kara's builtin skills
+    fn default() -> Self {
+        let mut dispatcher = Self::new(DEFAULT_TIMEOUT);
+        dispatcher.register(general::General);
+        dispatcher.register(datetime::Clock);
+        dispatcher.register(maths::Maths);
+        dispatcher
+    }
+}
+
+impl Dispatcher {
+    /// An empty dispatcher that abandons skills after `timeout`
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            skills: HashMap::new(),
+            fallback: None,
+            timeout,
+            known_speakers_only: false,
+        }
+    }
+
+    /// Refuses sensitive commands, see [`Skill::is_sensitive`], from speakers who were not
+    /// recognised
+    pub fn restrict_to_known_speakers(&mut self) {
+        self.known_speakers_only = true;
+    }
+
+    /// Registers `skill` for its intents. An intent that was already claimed moves to the
+    /// new skill
+    pub fn register(&mut self, skill: impl Skill + 'static) {
+        let skill: Arc<dyn Skill> = Arc::new(skill);
+        for intent in skill.intents() {
+            if let Some(previous) = self.skills.insert(intent.clone(), Arc::clone(&skill)) {
+                warn!(
+                    %intent,
+                    previous = previous.name(),
+                    skill = skill.name(),
+                    "intent claimed by more than one skill"
+                );
+            }
+        }
+    }
+
+    /// Hands commands that were not understood to `fallback` instead of apologising
+    pub fn set_fallback(&mut self, fallback: impl Fallback + 'static) {
+        self.fallback = Some(Arc::new(fallback));
+    }
+
+    /// The intents in `intents` that no skill has claimed
+    pub fn unhandled<'a>(&self, intents: &'a [Intent]) -> Vec<&'a Intent> {
+        intents
+            .iter()
+            .filter(|intent| !self.skills.contains_key(intent))
+            .collect()
+    }
+
+    /// Name of the skill that handles `intent`
+    pub fn skill_name(&self, intent: &Intent) -> Option<&'static str> {
+        self.skills.get(intent).map(|skill| skill.name())
+    }
+
+    /// Whether the skill that handles `intent` considers it hard to undo
+    pub fn is_destructive(&self, intent: &Intent) -> bool {
+        self.skills
+            .get(intent)
+            .map_or(false, |skill| skill.is_destructive(intent))
+    }
+
+    /// Whether `speaker` may give commands with `intent`
+    pub fn allows(&self, intent: &Intent, speaker: &Speaker) -> bool {
+        !self.known_speakers_only
+            || speaker.is_known()
+            || !self
+                .skills
+                .get(intent)
+                .map_or(false, |skill| skill.is_sensitive(intent))
+    }
+
+    /// The required slots of the skill that handles `parsed` that `parsed` has no value for
+    pub fn missing_slots(&self, parsed: &ParsedIntent, context: &Context) -> Vec<RequiredSlot> {
+        let required = match parsed.intent.intent_name.as_ref().and_then(|intent| {
+            let skill = self.skills.get(intent)?;
+            let request = Request {
+                intent: intent.clone(),
+                input: parsed.input.clone(),
+                slots: parsed.slots.clone(),
+            };
+            Some(skill.required_slots(&request, context))
+        }) {
+            Some(required) => required,
+            None => return Vec::new(),
+        };
+        required
+            .into_iter()
+            .filter(|required| {
+                !parsed
+                    .slots
+                    .iter()
+                    .any(|slot| slot.slot_name == required.name)
+            })
+            .collect()
+    }
+
+    /// Dispatches `parsed` and returns the reply. A command that fails is answered with an
+    /// apology in the context's language
+    pub async fn reply(&self, parsed: ParsedIntent, context: Context) -> String {
+        let language = context.language;
+        match self.dispatch(parsed, context).await {
+            Ok(response) => response.text,
+            Err(e) => {
+                warn!("{}", e);
+                locale::response(language, locale::Response::from(&e))
+            }
+        }
+    }
+
+    pub async fn dispatch(
+        &self,
+        parsed: ParsedIntent,
+        context: Context,
+    ) -> Result<Response, DispatchError> {
+        let intent = match parsed.intent.intent_name {
+            Some(intent) => intent,
+            None => {
+                let fallback = self.fallback.clone().ok_or(DispatchError::NotUnderstood)?;
+                let name = fallback.name();
+                debug!(skill = name, "handing command to the fallback");
+                let input = parsed.input;
+                return self
+                    .run(name, async move { fallback.handle(&input, &context).await })
+                    .await;
+            }
+        };
+        let skill = self
+            .skills
+            .get(&intent)
+            .cloned()
+            .ok_or_else(|| DispatchError::Unhandled(intent.clone()))?;
+        if !self.allows(&intent, &context.speaker) {
+            return Err(DispatchError::UnknownSpeaker(intent));
+        }
+        let name = skill.name();
+        debug!(%intent, skill = name, "dispatching command");
+        let request = Request {
+            intent,
+            input: parsed.input,
+            slots: parsed.slots,
+        };
+        self.run(name, async move { skill.handle(&request, &context).await })
+            .await
+    }
+
+    /// Runs a skill's handler on its own task so that a panic does not take the caller down
+    async fn run(
+        &self,
+        name: &'static str,
+        handler: impl Future<Output = anyhow::Result<Response>> + Send + 'static,
+    ) -> Result<Response, DispatchError> {
+        let mut task = tokio::spawn(handler);
+        match tokio::time::timeout(self.timeout, &mut task).await {
+            Ok(Ok(Ok(response))) => Ok(response),
+            Ok(Ok(Err(error))) => Err(DispatchError::Failed { skill: name, error }),
+            Ok(Err(_)) => Err(DispatchError::Panicked { skill: name }),
+            Err(_) => {
+                task.abort();
+                Err(DispatchError::TimedOut {
+                    skill: name,
+                    after: self.timeout,
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kara_nlu::intents::IntentMapper;
+
+    use super::*;
+
+    /// Answers every command with the name of its intent
+    struct Echo;
+
+    #[async_trait]
+    impl Skill for Echo {
+        fn name(&self) -> &'static str {
+            "echo"
+        }
+
+        fn intents(&self) -> &[Intent] {
+            &[Intent::ListsCreateOrAdd, Intent::ListsQuery]
+        }
+
+        async fn handle(&self, request: &Request, _: &Context) -> anyhow::Result<Response> {
+            Ok(Response::text(request.intent.name()))
+        }
+    }
+
+    /// Never answers alarm commands properly: setting one hangs, asking about them panics and
+    /// removing one fails
+    struct Moody;
+
+    #[async_trait]
+    impl Skill for Moody {
+        fn name(&self) -> &'static str {
+            "moody"
+        }
+
+        fn intents(&self) -> &[Intent] {
+            &[Intent::AlarmSet, Intent::AlarmQuery, Intent::AlarmRemove]
+        }
+
+        async fn handle(&self, request: &Request, _: &Context) -> anyhow::Result<Response> {
+            match request.intent {
+                Intent::AlarmSet => {
+                    tokio::time::sleep(Duration::from_secs(3600)).await;
+                    Ok(Response::text("too late"))
+                }
+                Intent::AlarmQuery => panic!("no alarms to query"),
+                _ => anyhow::bail!("no alarm to remove"),
+            }
+        }
+    }
+
+    /// Looks up whatever it is given
+    struct Lookup;
+
+    #[async_trait]
+    impl Fallback for Lookup {
+        fn name(&self) -> &'static str {
+            "lookup"
+        }
+
+        async fn handle(&self, input: &str, _: &Context) -> anyhow::Result<Response> {
+            Ok(Response::text(format!("looked up {input}")))
+        }
+    }
+
+    fn dispatcher() -> Dispatcher {
+        let mut dispatcher = Dispatcher::new(Duration::from_millis(50));
+        dispatcher.register(Echo);
+        dispatcher.register(Moody);
+        dispatcher
+    }
+
+    fn not_understood(input: &str) -> ParsedIntent {
+        ParsedIntent {
+            input: input.to_owned(),
+            ..ParsedIntent::default()
+        }
+    }
+
+    fn parsed(intent: Intent) -> ParsedIntent {
+        ParsedIntent {
+            input: intent.name().replace('_', " "),
+            intent: IntentMapper {
+                intent_name: Some(intent),
+                confidence_score: 1.0,
+            },
+            ..ParsedIntent::default()
+        }
+    }
+
+    fn speaking(speaker: Speaker) -> Context {
+        Context {
+            speaker,
+            ..Context::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn refuses_sensitive_commands_from_unknown_speakers() {
+        let mut dispatcher = Dispatcher::new(DEFAULT_TIMEOUT);
+        dispatcher.register(Echo);
+        let alice = Speaker::Known("alice".to_owned());
+        assert!(dispatcher.allows(&Intent::ListsCreateOrAdd, &Speaker::Unknown));
+
+        dispatcher.restrict_to_known_speakers();
+        assert!(!dispatcher.allows(&Intent::ListsCreateOrAdd, &Speaker::Unknown));
+        assert!(dispatcher.allows(&Intent::ListsCreateOrAdd, &alice));
+        assert!(dispatcher.allows(&Intent::ListsQuery, &Speaker::Unknown));
+        assert!(matches!(
+            dispatcher
+                .dispatch(parsed(Intent::ListsCreateOrAdd), speaking(Speaker::Unknown))
+                .await,
+            Err(DispatchError::UnknownSpeaker(Intent::ListsCreateOrAdd))
+        ));
+        let response = dispatcher
+            .dispatch(parsed(Intent::ListsCreateOrAdd), speaking(alice))
+            .await
+            .unwrap();
+        assert_eq!(response.text, "lists_createoradd");
+    }
+
+    #[tokio::test]
+    async fn abandons_skills_that_take_too_long() {
+        let dispatcher = dispatcher();
+        let result = dispatcher
+            .dispatch(parsed(Intent::AlarmSet), Context::default())
+            .await;
+        assert!(matches!(
+            result,
+            Err(DispatchError::TimedOut { skill: "moody", after }) if after == Duration::from_millis(50)
+        ));
+        assert_eq!(
+            dispatcher
+                .reply(parsed(Intent::AlarmSet), Context::default())
+                .await,
+            locale::response(Language::English, locale::Response::Failed)
+        );
+    }
+
+    #[tokio::test]
+    async fn survives_skills_that_fail_or_panic() {
+        let dispatcher = dispatcher();
+        assert!(matches!(
+            dispatcher
+                .dispatch(parsed(Intent::AlarmQuery), Context::default())
+                .await,
+            Err(DispatchError::Panicked { skill: "moody" })
+        ));
+        match dispatcher
+            .dispatch(parsed(Intent::AlarmRemove), Context::default())
+            .await
+        {
+            Err(DispatchError::Failed { skill, error }) => {
+                assert_eq!(skill, "moody");
+                assert_eq!(error.to_string(), "no alarm to remove");
+            }
+            result => panic!("expected a failure, got {result:?}"),
+        }
+        let french = Context {
+            language: Language::French,
+            ..Context::default()
+        };
+        assert_eq!(
+            dispatcher.reply(parsed(Intent::AlarmQuery), french).await,
+            locale::response(Language::French, locale::Response::Failed)
+        );
+        // Other skills still answer
+        assert_eq!(
+            dispatcher
+                .reply(parsed(Intent::ListsQuery), Context::default())
+                .await,
+            "lists_query"
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_unhandled_intents() {
+        let dispatcher = dispatcher();
+        assert!(matches!(
+            dispatcher
+                .dispatch(parsed(Intent::EmailSend), Context::default())
+                .await,
+            Err(DispatchError::Unhandled(Intent::EmailSend))
+        ));
+        assert_eq!(
+            dispatcher.unhandled(&[Intent::ListsQuery, Intent::EmailSend, Intent::AlarmSet]),
+            [&Intent::EmailSend]
+        );
+        assert_eq!(dispatcher.skill_name(&Intent::AlarmSet), Some("moody"));
+        assert_eq!(dispatcher.skill_name(&Intent::EmailSend), None);
+    }
+
+    #[tokio::test]
+    async fn hands_commands_not_understood_to_the_fallback() {
+        let mut dispatcher = dispatcher();
+        assert!(matches!(
+            dispatcher
+                .dispatch(not_understood("what is a quokka"), Context::default())
+                .await,
+            Err(DispatchError::NotUnderstood)
+        ));
+        assert_eq!(
+            dispatcher
+                .reply(not_understood("what is a quokka"), Context::default())
+                .await,
+            locale::response(Language::English, locale::Response::NotUnderstood)
+        );
+
+        dispatcher.set_fallback(Lookup);
+        let response = dispatcher
+            .dispatch(not_understood("what is a quokka"), Context::default())
+            .await
+            .unwrap();
+        assert_eq!(response.text, "looked up what is a quokka");
+        // Commands that were understood do not reach it, even if no skill handles them
+        assert!(matches!(
+            dispatcher
+                .dispatch(parsed(Intent::EmailSend), Context::default())
+                .await,
+            Err(DispatchError::Unhandled(_))
+        ));
+    }
+}
@@ -0,0 +1,102 @@
+//! Looks up commands that were not understood with the DuckDuckGo Instant Answer API, e.g.
+//! "who wrote pride and prejudice". Only commands that no skill could take are sent
+use async_trait::async_trait;
+use kara_nlu::Language;
+use reqwest::Client;
+use serde_json::Value;
+
+use super::{Context, Fallback, Response};
+use crate::locale;
+
+const API_URL: &str = "https://api.duckduckgo.com/";
+
+#[derive(Default)]
+pub struct WebSearch {
+    client: Client,
+}
+
+#[async_trait]
+impl Fallback for WebSearch {
+    fn name(&self) -> &'static str {
+        "web search"
+    }
+
+    async fn handle(&self, input: &str, context: &Context) -> anyhow::Result<Response> {
+        let body = self
+            .client
+            .get(API_URL)
+            .query(&[
+                ("q", input),
+                ("format", "json"),
+                ("no_html", "1"),
+                ("skip_disambig", "1"),
+                ("kl", region(context.language)),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let text = answer(&serde_json::from_str(&body)?)
+            .unwrap_or_else(|| locale::response(context.language, locale::Response::NotUnderstood));
+        Ok(Response::text(text))
+    }
+}
+
+/// The region results are given for, which sets their language
+fn region(language: Language) -> &'static str {
+    match language {
+        Language::English => "us-en",
+        Language::French => "fr-fr",
+        Language::German => "de-de",
+        Language::Spanish => "es-es",
+    }
+}
+
+/// The first sentence of the most direct answer in `result`: a computed answer, then the
+/// summary of an article, then a definition
+fn answer(result: &Value) -> Option<String> {
+    ["Answer", "AbstractText", "Definition"]
+        .iter()
+        .filter_map(|field| result.get(field)?.as_str())
+        .map(str::trim)
+        .find(|text| !text.is_empty())
+        .map(|text| match text.find(". ") {
+            Some(end) => text[..=end].to_owned(),
+            None => text.to_owned(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefers_direct_answers() {
+        let result = serde_json::json!({
+            "Answer": "42",
+            "AbstractText": "The answer to everything. It comes from a novel.",
+            "Definition": "",
+        });
+        assert_eq!(answer(&result).as_deref(), Some("42"));
+    }
+
+    #[test]
+    fn says_the_first_sentence() {
+        let result = serde_json::json!({
+            "Answer": "",
+            "AbstractText": "Pride and Prejudice is an 1813 novel by Jane Austen. It follows Elizabeth Bennet.",
+        });
+        assert_eq!(
+            answer(&result).as_deref(),
+            Some("Pride and Prejudice is an 1813 novel by Jane Austen.")
+        );
+    }
+
+    #[test]
+    fn nothing_found() {
+        // Answers that are not text, such as calculator widgets, are skipped
+        let result = serde_json::json!({"Answer": {"from": "calculator"}, "AbstractText": " "});
+        assert_eq!(answer(&result), None);
+    }
+}
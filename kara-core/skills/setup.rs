@@ -0,0 +1,75 @@
+//! Wires up the skills that need configuration or run in the background, the same way for
+//! every interface
+use std::sync::Arc;
+
+use kara_events::Ringing;
+use tracing::error;
+
+use super::{search::WebSearch, Agenda, Alarms, DialogueManager, Dispatcher, Lists};
+use crate::{
+    calendar::{Calendar, CalendarSync},
+    config::state::ParsedConfig,
+    lists::ListStore,
+    scheduler::Scheduler,
+};
+
+/// Kara's skills, set up from the config
+pub struct Skills {
+    pub dispatcher: Arc<Dispatcher>,
+    pub dialogue: DialogueManager,
+    /// `None` if the alarms could not be loaded
+    pub scheduler: Option<Arc<Scheduler>>,
+}
+
+impl Skills {
+    /// Registers the builtin skills and those that depend on the config. Calendar sync and
+    /// the alarm scheduler are started on the current runtime, `on_ring` is called with
+    /// every alarm that goes off. Skills that cannot be set up are left out
+    pub fn configured(
+        config: &ParsedConfig,
+        on_ring: impl FnMut(Ringing) + Send + 'static,
+    ) -> Self {
+        let mut dispatcher = Dispatcher::default();
+        if config.nlu.speakers.enabled {
+            dispatcher.restrict_to_known_speakers();
+        }
+        if config.nlu.intents.web_fallback {
+            dispatcher.set_fallback(WebSearch::default());
+        }
+        let calendar = Arc::new(Calendar::configured(&config.calendar));
+        dispatcher.register(Agenda::new(Arc::clone(&calendar)));
+        if let Some(caldav) = &config.calendar.caldav {
+            match CalendarSync::configured(calendar, caldav) {
+                Ok(sync) => {
+                    tokio::spawn(Arc::new(sync).run());
+                }
+                Err(e) => error!("could not set up calendar sync: {}", e),
+            }
+        }
+        match ListStore::configured() {
+            Ok(store) => dispatcher.register(Lists::new(store)),
+            Err(e) => error!("could not open lists, lists will not work: {}", e),
+        }
+        let scheduler = match Scheduler::configured(&config.alarms) {
+            Ok(scheduler) => {
+                let scheduler = Arc::new(scheduler);
+                dispatcher.register(Alarms::new(Arc::clone(&scheduler)));
+                tokio::spawn(Arc::clone(&scheduler).run(on_ring));
+                Some(scheduler)
+            }
+            Err(e) => {
+                error!("could not load alarms, alarms will not work: {}", e);
+                None
+            }
+        };
+        let dispatcher = Arc::new(dispatcher);
+        let dialogue = DialogueManager::new(Arc::clone(&dispatcher))
+            .with_context_window(config.nlu.intents.context_window)
+            .with_confirmation(config.nlu.intents.confirmation());
+        Self {
+            dispatcher,
+            dialogue,
+            scheduler,
+        }
+    }
+}
@@ -1,6 +1,139 @@
+use std::{fmt::Display, time::Duration};
+
 pub enum KaraEvents {
     WakeUp(bool),
     SpeechFeed(String),
-    ProcessCommand(String),
+    ProcessCommand(Utterance),
     IsBusy(bool),
+    Progress(Progress),
+    /// A skill's reply to a command
+    Response(String),
+    /// Models for a new language have been loaded. Carries the ISO 639-1 language code
+    LanguageChanged(String),
+    /// The language model should be loaded again, e.g. because it was retrained
+    ReloadLanguageModel,
+    /// An alarm or timer went off and should ring until it is stopped or snoozed
+    AlarmRinging(Ringing),
+    /// Every ringing alarm was stopped or snoozed
+    AlarmStopped,
+}
+
+/// An alarm or timer that went off
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ringing {
+    pub id: u64,
+    pub kind: AlarmKind,
+    /// What the alarm or timer was set for, e.g. "pasta"
+    pub label: Option<String>,
+    /// Whether it was due while Kara was not running
+    pub missed: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlarmKind {
+    Alarm,
+    Timer,
+}
+
+/// A final transcription and who said it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Utterance {
+    pub text: String,
+    pub speaker: Speaker,
+}
+
+/// The enrolled speaker an utterance was attributed to
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Speaker {
+    Known(String),
+    #[default]
+    Unknown,
+}
+
+impl Speaker {
+    /// The speaker's user id, or "unknown"
+    pub fn id(&self) -> &str {
+        match self {
+            Speaker::Known(id) => id,
+            Speaker::Unknown => "unknown",
+        }
+    }
+
+    pub fn is_known(&self) -> bool {
+        matches!(self, Speaker::Known(_))
+    }
+}
+
+/// A snapshot of a long running task such as downloading or loading a model
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Progress {
+    pub phase: ProgressPhase,
+    /// Bytes processed so far in this phase
+    pub bytes: u64,
+    /// Total bytes for this phase, if they are known
+    pub total: Option<u64>,
+    /// Estimated time left for this phase, if it can be calculated
+    pub eta: Option<Duration>,
+}
+
+impl Progress {
+    pub fn new(phase: ProgressPhase) -> Self {
+        Self {
+            phase,
+            bytes: 0,
+            total: None,
+            eta: None,
+        }
+    }
+
+    /// Progress as a fraction in the range 0.0 <= val <= 1.0
+    pub fn fraction(&self) -> Option<f32> {
+        match self.total {
+            Some(total) if total > 0 => Some((self.bytes as f64 / total as f64).min(1.0) as f32),
+            _ => None,
+        }
+    }
+}
+
+/// What is being prepared. Models are loaded at the same time and their progress is
+/// shown separately
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ProgressTask {
+    SpeechModel,
+    LanguageModel,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressPhase {
+    Download,
+    Extract,
+    LoadSpeechModel,
+    LoadLanguageModel,
+    /// The task has no more work in progress
+    Finished(ProgressTask),
+}
+
+impl ProgressPhase {
+    pub fn task(&self) -> ProgressTask {
+        match self {
+            ProgressPhase::Download | ProgressPhase::Extract | ProgressPhase::LoadSpeechModel => {
+                ProgressTask::SpeechModel
+            }
+            ProgressPhase::LoadLanguageModel => ProgressTask::LanguageModel,
+            ProgressPhase::Finished(task) => *task,
+        }
+    }
+}
+
+impl Display for ProgressPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let phase = match self {
+            ProgressPhase::Download => "Downloading speech model",
+            ProgressPhase::Extract => "Extracting speech model",
+            ProgressPhase::LoadSpeechModel => "Loading speech model",
+            ProgressPhase::LoadLanguageModel => "Loading language model",
+            ProgressPhase::Finished(_) => "Ready",
+        };
+        f.write_str(phase)
+    }
 }
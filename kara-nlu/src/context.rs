@@ -0,0 +1,249 @@
+//! What was said earlier in a conversation, so that follow ups such as "what about
+//! tomorrow?" or "add milk too" can be understood.
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use crate::intents::{Intent, ParsedIntent, Slot, SlotValue};
+
+/// How long a turn is remembered by default
+pub const DEFAULT_WINDOW: Duration = Duration::from_secs(30);
+
+/// The last command that was acted on
+#[derive(Debug, Clone)]
+pub struct PreviousTurn {
+    pub intent: Intent,
+    pub slots: Vec<Slot>,
+    /// Name of the skill that handled the command
+    pub skill: Option<String>,
+    at: Instant,
+}
+
+impl PreviousTurn {
+    /// Time since the turn was recorded
+    pub fn elapsed(&self) -> Duration {
+        self.at.elapsed()
+    }
+}
+
+/// Turns of a conversation that are still recent enough to refer to
+#[derive(Debug, Clone)]
+pub struct ConversationContext {
+    window: Duration,
+    previous: Option<PreviousTurn>,
+    /// The last value mentioned for each entity
+    entities: HashMap<String, (SlotValue, Instant)>,
+}
+
+impl Default for ConversationContext {
+    fn default() -> Self {
+        Self::new(DEFAULT_WINDOW)
+    }
+}
+
+impl ConversationContext {
+    /// Remembers turns for `window`. A zero window disables carry over
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            previous: None,
+            entities: HashMap::new(),
+        }
+    }
+
+    /// Records a command that was acted on
+    pub fn record(&mut self, parsed: &ParsedIntent, skill: Option<&str>) {
+        let intent = match &parsed.intent.intent_name {
+            Some(intent) => intent.clone(),
+            None => return,
+        };
+        let now = Instant::now();
+        for slot in &parsed.slots {
+            self.entities
+                .insert(slot.entity.clone(), (slot.value.clone(), now));
+        }
+        self.previous = Some(PreviousTurn {
+            intent,
+            slots: parsed.slots.clone(),
+            skill: skill.map(ToOwned::to_owned),
+            at: now,
+        });
+    }
+
+    /// The previous turn, if it is within the window
+    pub fn previous(&self) -> Option<&PreviousTurn> {
+        self.previous
+            .as_ref()
+            .filter(|previous| previous.elapsed() < self.window)
+    }
+
+    /// The last value mentioned for `entity` within the window, e.g. the city of an earlier
+    /// weather query
+    pub fn entity(&self, entity: &str) -> Option<&SlotValue> {
+        self.entities
+            .get(entity)
+            .filter(|(_, at)| at.elapsed() < self.window)
+            .map(|(value, _)| value)
+    }
+
+    pub fn clear(&mut self) {
+        self.previous = None;
+        self.entities.clear();
+    }
+
+    /// Fills the slots `parsed` is missing from the previous turn, if both are the same
+    /// intent. "Add milk too" after "add eggs to the shopping list" keeps the list name
+    pub fn carry_over(&self, parsed: &mut ParsedIntent) {
+        let previous = match self.previous() {
+            Some(previous) if parsed.intent.intent_name.as_ref() == Some(&previous.intent) => {
+                previous
+            }
+            _ => return,
+        };
+        let inherited: Vec<_> = previous
+            .slots
+            .iter()
+            .filter(|slot| {
+                !parsed
+                    .slots
+                    .iter()
+                    .any(|current| current.slot_name == slot.slot_name)
+            })
+            .cloned()
+            .collect();
+        parsed.slots.extend(inherited);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::intents::{IntentMapper, Range, Text};
+
+    fn slot(name: &str, entity: &str, value: &str) -> Slot {
+        Slot {
+            raw_value: value.to_owned(),
+            value: SlotValue::Custom(Text {
+                value: value.to_owned(),
+            }),
+            alternatives: Vec::new(),
+            range: Range::default(),
+            entity: entity.to_owned(),
+            slot_name: name.to_owned(),
+        }
+    }
+
+    fn parsed(intent: Option<Intent>, slots: Vec<Slot>) -> ParsedIntent {
+        ParsedIntent {
+            intent: IntentMapper {
+                intent_name: intent,
+                confidence_score: 1.0,
+            },
+            slots,
+            ..ParsedIntent::default()
+        }
+    }
+
+    fn names(parsed: &ParsedIntent) -> Vec<(&str, &str)> {
+        parsed
+            .slots
+            .iter()
+            .map(|slot| (slot.slot_name.as_str(), slot.raw_value.as_str()))
+            .collect()
+    }
+
+    /// "Add eggs to the shopping list" was acted on
+    fn after_adding_eggs(window: Duration) -> ConversationContext {
+        let mut context = ConversationContext::new(window);
+        context.record(
+            &parsed(
+                Some(Intent::ListsCreateOrAdd),
+                vec![
+                    slot("item", "item", "eggs"),
+                    slot("list_name", "list", "shopping"),
+                ],
+            ),
+            Some("lists"),
+        );
+        context
+    }
+
+    #[test]
+    fn carries_slots_over_within_the_window() {
+        let context = after_adding_eggs(DEFAULT_WINDOW);
+        let previous = context.previous().unwrap();
+        assert_eq!(previous.intent, Intent::ListsCreateOrAdd);
+        assert_eq!(previous.skill.as_deref(), Some("lists"));
+
+        // "Add milk too" keeps the list but not the item
+        let mut follow_up = parsed(
+            Some(Intent::ListsCreateOrAdd),
+            vec![slot("item", "item", "milk")],
+        );
+        context.carry_over(&mut follow_up);
+        assert_eq!(
+            names(&follow_up),
+            [("item", "milk"), ("list_name", "shopping")]
+        );
+        assert_eq!(
+            context.entity("list"),
+            Some(&SlotValue::Custom(Text {
+                value: "shopping".to_owned()
+            }))
+        );
+        assert_eq!(context.entity("room"), None);
+    }
+
+    #[test]
+    fn forgets_turns_after_the_window() {
+        let context = after_adding_eggs(Duration::from_millis(20));
+        thread::sleep(Duration::from_millis(40));
+        assert!(context.previous().is_none());
+        assert_eq!(context.entity("list"), None);
+        let mut follow_up = parsed(
+            Some(Intent::ListsCreateOrAdd),
+            vec![slot("item", "item", "milk")],
+        );
+        context.carry_over(&mut follow_up);
+        assert_eq!(names(&follow_up), [("item", "milk")]);
+
+        // A zero window disables carry over
+        let context = after_adding_eggs(Duration::ZERO);
+        let mut follow_up = parsed(Some(Intent::ListsCreateOrAdd), Vec::new());
+        context.carry_over(&mut follow_up);
+        assert!(follow_up.slots.is_empty());
+
+        let mut context = after_adding_eggs(DEFAULT_WINDOW);
+        context.clear();
+        assert!(context.previous().is_none());
+        assert_eq!(context.entity("list"), None);
+    }
+
+    #[test]
+    fn carries_slots_over_to_the_same_intent_only() {
+        let mut context = after_adding_eggs(DEFAULT_WINDOW);
+        let mut query = parsed(Some(Intent::ListsQuery), Vec::new());
+        context.carry_over(&mut query);
+        assert!(query.slots.is_empty());
+
+        let mut unknown = parsed(None, Vec::new());
+        context.carry_over(&mut unknown);
+        assert!(unknown.slots.is_empty());
+
+        // Commands that were not understood are not remembered
+        context.record(&parsed(None, vec![slot("item", "item", "jam")]), None);
+        assert_eq!(
+            context.previous().map(|previous| &previous.intent),
+            Some(&Intent::ListsCreateOrAdd)
+        );
+        let mut follow_up = parsed(Some(Intent::ListsCreateOrAdd), Vec::new());
+        context.carry_over(&mut follow_up);
+        assert_eq!(
+            names(&follow_up),
+            [("item", "eggs"), ("list_name", "shopping")]
+        );
+    }
+}
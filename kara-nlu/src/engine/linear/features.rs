@@ -0,0 +1,264 @@
+//! Words, known entity values and the TF-IDF vectors the classifier works on
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::intents::{Number, Percentage, SlotValue, Text};
+
+/// A lowercased word and the characters it spans
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Token {
+    pub text: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Splits `text` into words. Decimal numbers such as "2.5" are kept together
+pub(super) fn tokenise(text: &str) -> Vec<Token> {
+    let chars: Vec<_> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut current: Option<Token> = None;
+    for (index, &c) in chars.iter().enumerate() {
+        let decimal_point = matches!(c, '.' | ',')
+            && current.as_ref().map_or(false, |token| {
+                token.text.chars().all(|c| c.is_ascii_digit())
+            })
+            && chars.get(index + 1).map_or(false, char::is_ascii_digit);
+        if c.is_alphanumeric() || c == '\'' || decimal_point {
+            let token = current.get_or_insert_with(|| Token {
+                text: String::new(),
+                start: index,
+                end: index,
+            });
+            token.text.extend(c.to_lowercase());
+            token.end = index + 1;
+        } else if let Some(token) = current.take() {
+            tokens.push(token);
+        }
+    }
+    tokens.extend(current);
+    tokens
+}
+
+fn number(token: &Token) -> Option<f64> {
+    token.text.replace(',', ".").parse().ok()
+}
+
+/// Entity values found in a command
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct EntityMatch {
+    pub entity: String,
+    /// Indices of the matched tokens
+    pub tokens: std::ops::Range<usize>,
+    pub value: SlotValue,
+}
+
+/// The values of each entity seen in training, matched as whole words. Numbers and
+/// percentages are recognised without having been seen
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub(super) struct Gazetteer {
+    /// Entity names to their values, keyed by their lowercased words
+    values: BTreeMap<String, BTreeMap<String, String>>,
+    /// The builtin entities that were labelled in training, e.g. "snips/number"
+    builtins: BTreeSet<String>,
+}
+
+impl Gazetteer {
+    pub fn add(&mut self, entity: &str, value: &str) {
+        if entity.starts_with("snips/") {
+            self.builtins.insert(entity.to_owned());
+            return;
+        }
+        let key = key(&tokenise(value));
+        if !key.is_empty() {
+            self.values
+                .entry(entity.to_owned())
+                .or_default()
+                .entry(key)
+                .or_insert_with(|| value.trim().to_owned());
+        }
+    }
+
+    /// The most words any value has
+    fn longest(&self) -> usize {
+        self.values
+            .values()
+            .flat_map(BTreeMap::keys)
+            .map(|key| key.split(' ').count())
+            .max()
+            .unwrap_or(1)
+    }
+
+    /// Values of the entities `allowed` accepts in `tokens`, left to right. Where values
+    /// overlap the longest wins
+    pub fn find(&self, tokens: &[Token], allowed: impl Fn(&str) -> bool) -> Vec<EntityMatch> {
+        let longest = self.longest().max(2);
+        let mut matches = Vec::new();
+        let mut start = 0;
+        while start < tokens.len() {
+            match self.longest_at(tokens, start, longest, &allowed) {
+                Some(found) => {
+                    start = found.tokens.end;
+                    matches.push(found);
+                }
+                None => start += 1,
+            }
+        }
+        matches
+    }
+
+    fn longest_at(
+        &self,
+        tokens: &[Token],
+        start: usize,
+        longest: usize,
+        allowed: &impl Fn(&str) -> bool,
+    ) -> Option<EntityMatch> {
+        let end = tokens.len().min(start + longest);
+        for end in (start + 1..=end).rev() {
+            let key = key(&tokens[start..end]);
+            let found = self
+                .values
+                .iter()
+                .filter(|(entity, _)| allowed(entity))
+                .find_map(|(entity, values)| Some((entity, values.get(&key)?)));
+            if let Some((entity, value)) = found {
+                return Some(EntityMatch {
+                    entity: entity.clone(),
+                    tokens: start..end,
+                    value: SlotValue::Custom(Text {
+                        value: value.clone(),
+                    }),
+                });
+            }
+        }
+        let value = number(&tokens[start])?;
+        let builtin = |entity: &str| self.builtins.contains(entity) && allowed(entity);
+        let percent = tokens
+            .get(start + 1)
+            .map_or(false, |token| token.text == "percent");
+        if percent && builtin("snips/percentage") {
+            Some(EntityMatch {
+                entity: "snips/percentage".to_owned(),
+                tokens: start..start + 2,
+                value: SlotValue::Percentage(Percentage { value }),
+            })
+        } else if builtin("snips/number") {
+            Some(EntityMatch {
+                entity: "snips/number".to_owned(),
+                tokens: start..start + 1,
+                value: SlotValue::Number(Number { value }),
+            })
+        } else {
+            None
+        }
+    }
+}
+
+fn key(tokens: &[Token]) -> String {
+    tokens
+        .iter()
+        .map(|token| token.text.as_str())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The words and word pairs of a command. Entity values are replaced by their entity so
+/// that "play jazz" and "play some blues" share features
+pub(super) fn terms(tokens: &[Token], matches: &[EntityMatch]) -> Vec<String> {
+    let mut words = words(tokens, matches);
+    let pairs: Vec<_> = words
+        .windows(2)
+        .map(|pair| format!("{} {}", pair[0], pair[1]))
+        .collect();
+    words.extend(pairs);
+    words
+}
+
+/// The words of a command, with each entity value as one `{entity}` word
+pub(super) fn words(tokens: &[Token], matches: &[EntityMatch]) -> Vec<String> {
+    let mut words = Vec::with_capacity(tokens.len());
+    let mut matches = matches.iter().peekable();
+    let mut index = 0;
+    while index < tokens.len() {
+        match matches.next_if(|found| found.tokens.start == index) {
+            Some(found) => {
+                words.push(format!("{{{}}}", found.entity));
+                index = found.tokens.end;
+            }
+            None => {
+                words.push(tokens[index].text.clone());
+                index += 1;
+            }
+        }
+    }
+    words
+}
+
+/// The terms seen in training and how rare they are
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub(super) struct Vocabulary {
+    /// Terms to their column in the weights
+    columns: HashMap<String, usize>,
+    /// Inverse document frequency by column
+    idf: Vec<f32>,
+}
+
+impl Vocabulary {
+    pub fn new<'a>(documents: impl IntoIterator<Item = &'a [String]>) -> Self {
+        let mut frequencies: BTreeMap<&str, usize> = BTreeMap::new();
+        let mut count = 0;
+        for document in documents {
+            count += 1;
+            let unique: BTreeSet<_> = document.iter().map(String::as_str).collect();
+            for term in unique {
+                *frequencies.entry(term).or_default() += 1;
+            }
+        }
+        let mut vocabulary = Self::default();
+        for (column, (term, frequency)) in frequencies.into_iter().enumerate() {
+            vocabulary.columns.insert(term.to_owned(), column);
+            let idf = ((1 + count) as f32 / (1 + frequency) as f32).ln() + 1.0;
+            vocabulary.idf.push(idf);
+        }
+        vocabulary
+    }
+
+    pub fn len(&self) -> usize {
+        self.idf.len()
+    }
+
+    /// The share of `words` that were seen in training, 0 if there are none
+    pub fn coverage(&self, words: &[String]) -> f64 {
+        if words.is_empty() {
+            return 0.0;
+        }
+        let known = words
+            .iter()
+            .filter(|word| self.columns.contains_key(*word))
+            .count();
+        known as f64 / words.len() as f64
+    }
+
+    /// The L2 normalised TF-IDF vector of `terms` as column and value pairs. Terms not
+    /// seen in training are ignored
+    pub fn vector(&self, terms: &[String]) -> Vec<(usize, f32)> {
+        let mut counts: BTreeMap<usize, f32> = BTreeMap::new();
+        for column in terms.iter().filter_map(|term| self.columns.get(term)) {
+            *counts.entry(*column).or_default() += 1.0;
+        }
+        let mut vector: Vec<_> = counts
+            .into_iter()
+            .map(|(column, count)| (column, count * self.idf[column]))
+            .collect();
+        let norm = vector
+            .iter()
+            .map(|(_, value)| value * value)
+            .sum::<f32>()
+            .sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|(_, value)| *value /= norm);
+        }
+        vector
+    }
+}
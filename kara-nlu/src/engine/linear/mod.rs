@@ -0,0 +1,395 @@
+//! A pure Rust engine: a linear classifier over the TF-IDF vectors of a command's words and
+//! word pairs, and a gazetteer that fills slots.
+//!
+//! The gazetteer is a lookup of the entity values labelled in training, matched as whole
+//! words with the longest value winning. It does not generalise: a value that was never
+//! labelled, such as a new song title, is not found. Numbers and percentages are read
+//! from the text instead. Slots of other builtin entities, such as dates and times, are
+//! not filled, use a snips model for those.
+//!
+//! Commands that are mostly words the model was not trained on are not understood rather
+//! than forced into the closest intent, see [`MIN_COVERAGE`].
+mod features;
+mod train;
+
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use self::features::{Gazetteer, Token, Vocabulary};
+use super::NluEngine;
+use crate::{
+    intents::{Intent, IntentAlternative, IntentMapper, ParsedIntent, Range, Slot},
+    Language, NluError, ParseOptions,
+};
+
+pub(super) const MODEL_FILE: &str = "kara_engine.json";
+/// The share of a command's words that must have been seen in training for it to be
+/// classified. Below it the command is taken to be about something the model does not know
+const MIN_COVERAGE: f64 = 0.5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SlotDefinition {
+    name: String,
+    entity: String,
+    /// Words seen right before the slot's values, to tell slots of the same entity apart
+    preceding: BTreeSet<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Model {
+    language_code: String,
+    intents: Vec<String>,
+    /// Slots by intent name
+    slots: BTreeMap<String, Vec<SlotDefinition>>,
+    gazetteer: Gazetteer,
+    vocabulary: Vocabulary,
+    /// A row of weights for each intent, with a column for each term of the vocabulary
+    weights: Vec<Vec<f32>>,
+    bias: Vec<f32>,
+}
+
+pub struct LinearEngine {
+    model: Model,
+    intents: Vec<Intent>,
+    language: Option<Language>,
+}
+
+impl LinearEngine {
+    fn new(model: Model) -> Self {
+        Self {
+            intents: model
+                .intents
+                .iter()
+                .map(|name| Intent::from(name.as_str()))
+                .collect(),
+            language: Language::from_code(&model.language_code),
+            model,
+        }
+    }
+
+    pub fn load(model_path: impl AsRef<Path>) -> Result<Self, NluError> {
+        let path = model_path.as_ref().join(MODEL_FILE);
+        let file = std::fs::File::open(&path)
+            .map_err(|e| NluError::Load(format!("{}: {e}", path.display())))?;
+        let model = serde_json::from_reader(std::io::BufReader::new(file))?;
+        Ok(Self::new(model))
+    }
+
+    /// Writes the model to `model_path`, creating the directory if needed
+    pub fn save(&self, model_path: impl AsRef<Path>) -> std::io::Result<()> {
+        let model_path = model_path.as_ref();
+        std::fs::create_dir_all(model_path)?;
+        std::fs::write(
+            model_path.join(MODEL_FILE),
+            serde_json::to_string(&self.model)?,
+        )
+    }
+
+    /// The probability of each allowed intent, most likely first
+    fn classify(&self, tokens: &[Token], options: &ParseOptions) -> Vec<(usize, f64)> {
+        let matches = self.model.gazetteer.find(tokens, |_| true);
+        let words = features::words(tokens, &matches);
+        if self.model.vocabulary.coverage(&words) < MIN_COVERAGE {
+            return Vec::new();
+        }
+        let vector = self
+            .model
+            .vocabulary
+            .vector(&features::terms(tokens, &matches));
+        let scores: Vec<_> = (0..self.model.intents.len())
+            .filter(|&intent| options.allows(Some(&self.model.intents[intent])))
+            .map(|intent| (intent, self.score(intent, &vector)))
+            .collect();
+        let mut probabilities = softmax(&scores);
+        probabilities.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+        probabilities
+    }
+
+    fn score(&self, intent: usize, vector: &[(usize, f32)]) -> f64 {
+        let weights = &self.model.weights[intent];
+        let score: f32 = vector
+            .iter()
+            .map(|(column, value)| weights[*column] * value)
+            .sum();
+        f64::from(score + self.model.bias[intent])
+    }
+
+    /// The slots of `intent` in `text`
+    fn slots(&self, intent: usize, text: &str, tokens: &[Token]) -> Vec<Slot> {
+        let definitions = match self.model.slots.get(&self.model.intents[intent]) {
+            Some(definitions) => definitions,
+            None => return Vec::new(),
+        };
+        let chars: Vec<_> = text.chars().collect();
+        let mut filled: Vec<&str> = Vec::new();
+        self.model
+            .gazetteer
+            .find(tokens, |entity| {
+                definitions
+                    .iter()
+                    .any(|definition| definition.entity == entity)
+            })
+            .into_iter()
+            .filter_map(|found| {
+                let preceding = found
+                    .tokens
+                    .start
+                    .checked_sub(1)
+                    .map(|index| tokens[index].text.as_str());
+                let candidates: Vec<_> = definitions
+                    .iter()
+                    .filter(|definition| definition.entity == found.entity)
+                    .collect();
+                let definition = candidates
+                    .iter()
+                    .find(|definition| {
+                        preceding.map_or(false, |word| definition.preceding.contains(word))
+                    })
+                    .or_else(|| {
+                        candidates
+                            .iter()
+                            .find(|definition| !filled.contains(&definition.name.as_str()))
+                    })
+                    .or_else(|| candidates.first())?;
+                filled.push(&definition.name);
+                let start = tokens[found.tokens.start].start;
+                let end = tokens[found.tokens.end - 1].end;
+                Some(Slot {
+                    raw_value: chars[start..end].iter().collect(),
+                    value: found.value,
+                    alternatives: Vec::new(),
+                    range: Range {
+                        start: start as i64,
+                        end: end as i64,
+                    },
+                    entity: found.entity,
+                    slot_name: definition.name.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+impl NluEngine for LinearEngine {
+    fn parse(&self, text: &str, options: &ParseOptions) -> Result<ParsedIntent, NluError> {
+        let tokens = features::tokenise(text);
+        let mut ranked = self.classify(&tokens, options).into_iter();
+        let mut parsed = ParsedIntent {
+            input: text.to_owned(),
+            ..ParsedIntent::default()
+        };
+        if let Some((intent, confidence_score)) = ranked.next() {
+            parsed.intent = IntentMapper {
+                intent_name: Some(self.intents[intent].clone()),
+                confidence_score,
+            };
+            parsed.slots = self.slots(intent, text, &tokens);
+        }
+        parsed.alternatives = ranked
+            .take(options.alternatives)
+            .map(|(intent, confidence_score)| IntentAlternative {
+                intent: IntentMapper {
+                    intent_name: Some(self.intents[intent].clone()),
+                    confidence_score,
+                },
+                slots: self.slots(intent, text, &tokens),
+            })
+            .collect();
+        Ok(parsed)
+    }
+
+    fn intents(&self) -> &[Intent] {
+        &self.intents
+    }
+
+    fn language(&self) -> Option<Language> {
+        self.language
+    }
+}
+
+fn softmax(scores: &[(usize, f64)]) -> Vec<(usize, f64)> {
+    let max = scores
+        .iter()
+        .map(|(_, score)| *score)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let exponents: Vec<_> = scores
+        .iter()
+        .map(|(intent, score)| (*intent, (score - max).exp()))
+        .collect();
+    let total: f64 = exponents.iter().map(|(_, exponent)| exponent).sum();
+    exponents
+        .into_iter()
+        .map(|(intent, exponent)| (intent, exponent / total))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        evaluation::{LabelledSlot, LabelledUtterance},
+        intents::SlotValue,
+    };
+
+    /// An utterance of `intent`. `[value](slot:entity)` marks a slot
+    fn utterance(intent: Intent, text: &str) -> LabelledUtterance {
+        let mut plain = String::new();
+        let mut slots = Vec::new();
+        let mut rest = text;
+        while let Some(open) = rest.find('[') {
+            let close = rest.find("](").unwrap();
+            let end = rest.find(')').unwrap();
+            let value = &rest[open + 1..close];
+            let (name, entity) = rest[close + 2..end].split_once(':').unwrap();
+            plain.push_str(&rest[..open]);
+            plain.push_str(value);
+            slots.push(LabelledSlot {
+                name: name.to_owned(),
+                entity: entity.to_owned(),
+                value: value.to_owned(),
+            });
+            rest = &rest[end + 1..];
+        }
+        plain.push_str(rest);
+        LabelledUtterance {
+            text: plain,
+            intent,
+            slots,
+        }
+    }
+
+    fn dataset() -> Vec<LabelledUtterance> {
+        vec![
+            utterance(Intent::AlarmSet, "set an alarm"),
+            utterance(Intent::AlarmSet, "wake me up tomorrow"),
+            utterance(Intent::AlarmSet, "set an alarm for [7](hour:snips/number)"),
+            utterance(Intent::AlarmQuery, "what alarms do i have"),
+            utterance(Intent::AlarmQuery, "which alarms are set"),
+            utterance(Intent::AlarmQuery, "show me my alarms"),
+            utterance(Intent::PlayMusic, "play [jazz](genre:genre)"),
+            utterance(Intent::PlayMusic, "play some [rock and roll](genre:genre)"),
+            utterance(
+                Intent::PlayMusic,
+                "play [jazz](genre:genre) in the [kitchen](room:room)",
+            ),
+            utterance(Intent::PlayMusic, "put on [blues](genre:genre)"),
+        ]
+    }
+
+    fn engine() -> LinearEngine {
+        LinearEngine::train(&dataset(), Language::English).unwrap()
+    }
+
+    fn parse(engine: &LinearEngine, text: &str) -> ParsedIntent {
+        engine.parse(text, &ParseOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn classifies_commands() {
+        let engine = engine();
+        assert_eq!(engine.intents().len(), 3);
+        assert_eq!(engine.language(), Some(Language::English));
+        for (text, intent) in [
+            ("set an alarm for tomorrow", Intent::AlarmSet),
+            ("what alarms are set", Intent::AlarmQuery),
+            ("play some blues", Intent::PlayMusic),
+        ] {
+            let parsed = parse(&engine, text);
+            assert_eq!(parsed.intent.intent_name, Some(intent), "{text}");
+            assert!(parsed.intent.confidence_score > 0.5, "{text}");
+        }
+    }
+
+    #[test]
+    fn fills_slots_from_the_gazetteer() {
+        let parsed = parse(&engine(), "play rock and roll in the kitchen");
+        let slots: Vec<_> = parsed
+            .slots
+            .iter()
+            .map(|slot| {
+                (
+                    slot.slot_name.as_str(),
+                    slot.raw_value.as_str(),
+                    slot.range.start,
+                    slot.range.end,
+                )
+            })
+            .collect();
+        assert_eq!(
+            slots,
+            [
+                ("genre", "rock and roll", 5, 18),
+                ("room", "kitchen", 26, 33)
+            ]
+        );
+        // Values that were never labelled are not found
+        assert!(parse(&engine(), "play some reggae").slots.is_empty());
+    }
+
+    #[test]
+    fn reads_numbers() {
+        let parsed = parse(&engine(), "set an alarm for 6");
+        assert_eq!(parsed.intent.intent_name, Some(Intent::AlarmSet));
+        assert_eq!(parsed.slots.len(), 1);
+        assert_eq!(parsed.slots[0].slot_name, "hour");
+        assert_eq!(parsed.slots[0].value.as_number(), Some(6.0));
+        assert!(matches!(parsed.slots[0].value, SlotValue::Number(_)));
+    }
+
+    #[test]
+    fn rejects_unknown_commands() {
+        let engine = engine();
+        for text in [
+            "how tall is the eiffel tower",
+            "translate good morning into german",
+            "",
+        ] {
+            assert_eq!(parse(&engine, text).intent.intent_name, None, "{text}");
+        }
+    }
+
+    #[test]
+    fn respects_options() {
+        let options = ParseOptions::default()
+            .with_blacklist(["play_music"])
+            .with_alternatives(1, 0);
+        let parsed = engine()
+            .parse("set an alarm for tomorrow", &options)
+            .unwrap();
+        assert_eq!(parsed.intent.intent_name, Some(Intent::AlarmSet));
+        assert_eq!(parsed.alternatives.len(), 1);
+        assert_eq!(
+            parsed.alternatives[0].intent.intent_name,
+            Some(Intent::AlarmQuery)
+        );
+    }
+
+    #[test]
+    fn training_is_repeatable() {
+        let (first, second) = (engine(), engine());
+        assert_eq!(first.model.weights, second.model.weights);
+        assert_eq!(first.model.bias, second.model.bias);
+        assert!(LinearEngine::train(&[], Language::English).is_err());
+    }
+
+    #[test]
+    fn saved_models_load() {
+        let dir = std::env::temp_dir().join(format!("kara-linear-{}", std::process::id()));
+        let engine = engine();
+        engine.save(&dir).unwrap();
+        let loaded = LinearEngine::load(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        for text in [
+            "play jazz in the kitchen",
+            "show me my alarms",
+            "good morning",
+        ] {
+            assert_eq!(parse(&loaded, text), parse(&engine, text), "{text}");
+        }
+    }
+}
@@ -0,0 +1,155 @@
+//! Training from labelled utterances, with multinomial logistic regression fitted by
+//! stochastic gradient descent. Training is deterministic, the same dataset always gives the
+//! same model
+use std::collections::BTreeMap;
+
+use super::{
+    features::{self, Gazetteer, Vocabulary},
+    softmax, LinearEngine, Model, SlotDefinition,
+};
+use crate::{evaluation::LabelledUtterance, Language, NluError};
+
+/// Passes over the dataset
+const EPOCHS: usize = 30;
+const LEARNING_RATE: f32 = 0.5;
+/// How much the learning rate shrinks after each pass
+const DECAY: f32 = 0.1;
+
+impl LinearEngine {
+    /// Trains an engine on `dataset`. The values slots can take are the ones labelled in it
+    pub fn train(dataset: &[LabelledUtterance], language: Language) -> Result<Self, NluError> {
+        if dataset.is_empty() {
+            return Err(NluError::Dataset("no labelled utterances".to_owned()));
+        }
+        let mut intents: Vec<_> = dataset
+            .iter()
+            .map(|utterance| utterance.intent.name().to_owned())
+            .collect();
+        intents.sort();
+        intents.dedup();
+
+        let mut gazetteer = Gazetteer::default();
+        let mut slots: BTreeMap<String, Vec<SlotDefinition>> = BTreeMap::new();
+        for utterance in dataset {
+            let tokens = features::tokenise(&utterance.text);
+            let definitions = slots.entry(utterance.intent.name().to_owned()).or_default();
+            for slot in &utterance.slots {
+                gazetteer.add(&slot.entity, &slot.value);
+                let index = match definitions
+                    .iter()
+                    .position(|definition| definition.name == slot.name)
+                {
+                    Some(index) => index,
+                    None => {
+                        definitions.push(SlotDefinition {
+                            name: slot.name.clone(),
+                            entity: slot.entity.clone(),
+                            preceding: Default::default(),
+                        });
+                        definitions.len() - 1
+                    }
+                };
+                let value = features::tokenise(&slot.value);
+                let start = (0..tokens.len()).find(|&start| {
+                    tokens[start..]
+                        .iter()
+                        .map(|token| &token.text)
+                        .take(value.len())
+                        .eq(value.iter().map(|token| &token.text))
+                });
+                let preceding = start
+                    .and_then(|start| start.checked_sub(1))
+                    .map(|index| tokens[index].text.clone());
+                definitions[index].preceding.extend(preceding);
+            }
+        }
+        slots.retain(|_, definitions| !definitions.is_empty());
+
+        let documents: Vec<_> = dataset
+            .iter()
+            .map(|utterance| {
+                let tokens = features::tokenise(&utterance.text);
+                let matches = gazetteer.find(&tokens, |_| true);
+                features::terms(&tokens, &matches)
+            })
+            .collect();
+        let vocabulary = Vocabulary::new(documents.iter().map(Vec::as_slice));
+        let vectors: Vec<_> = documents
+            .iter()
+            .map(|terms| vocabulary.vector(terms))
+            .collect();
+        let labels: Vec<_> = dataset
+            .iter()
+            .map(|utterance| {
+                intents
+                    .binary_search_by(|intent| intent.as_str().cmp(utterance.intent.name()))
+                    .expect("every intent of the dataset is listed")
+            })
+            .collect();
+
+        let mut weights = vec![vec![0.0; vocabulary.len()]; intents.len()];
+        let mut bias = vec![0.0; intents.len()];
+        let mut order: Vec<_> = (0..dataset.len()).collect();
+        let mut random = Random::default();
+        for epoch in 0..EPOCHS {
+            random.shuffle(&mut order);
+            let rate = LEARNING_RATE / (1.0 + DECAY * epoch as f32);
+            for &example in &order {
+                let vector = &vectors[example];
+                let scores: Vec<_> = (0..intents.len())
+                    .map(|intent| {
+                        let score: f32 = vector
+                            .iter()
+                            .map(|(column, value)| weights[intent][*column] * value)
+                            .sum();
+                        (intent, f64::from(score + bias[intent]))
+                    })
+                    .collect();
+                for (intent, probability) in softmax(&scores) {
+                    let target = if intent == labels[example] { 1.0 } else { 0.0 };
+                    let gradient = rate * (probability as f32 - target);
+                    bias[intent] -= gradient;
+                    for (column, value) in vector {
+                        weights[intent][*column] -= gradient * value;
+                    }
+                }
+            }
+        }
+
+        Ok(Self::new(Model {
+            language_code: language.code().to_owned(),
+            intents,
+            slots,
+            gazetteer,
+            vocabulary,
+            weights,
+            bias,
+        }))
+    }
+}
+
+/// A xorshift generator with a fixed seed, so that training is repeatable
+struct Random(u64);
+
+impl Default for Random {
+    fn default() -> Self {
+        Self(0x2545_f491_4f6c_dd1d)
+    }
+}
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Fisher-Yates
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for index in (1..items.len()).rev() {
+            let other = (self.next() % (index as u64 + 1)) as usize;
+            items.swap(index, other);
+        }
+    }
+}
@@ -0,0 +1,49 @@
+//! Engines that classify commands and fill their slots. A model directory holds either a
+//! snips model, trained with the snips Python toolchain, or a [`LinearEngine`] model,
+//! trained with `kara nlu train`.
+mod linear;
+mod snips;
+
+use std::path::Path;
+
+use crate::{
+    intents::{Intent, ParsedIntent},
+    Language, NluError, ParseOptions,
+};
+pub use linear::LinearEngine;
+pub use snips::SnipsEngine;
+pub(crate) use snips::{EngineMetadata, MODEL_FILE as SNIPS_MODEL_FILE};
+
+pub trait NluEngine: Send {
+    /// Parses `text`, considering only the intents `options` allows and returning up to
+    /// as many alternatives as it asks for. Confidence thresholds are applied by the caller
+    fn parse(&self, text: &str, options: &ParseOptions) -> Result<ParsedIntent, NluError>;
+
+    /// The intents the model was trained on
+    fn intents(&self) -> &[Intent];
+
+    /// The language the model was trained for, if Kara supports it
+    fn language(&self) -> Option<Language>;
+}
+
+/// Whether `model_path` holds a model any engine can load
+pub fn is_model(model_path: impl AsRef<Path>) -> bool {
+    let model_path = model_path.as_ref();
+    model_path.join(linear::MODEL_FILE).exists() || model_path.join(snips::MODEL_FILE).exists()
+}
+
+/// Whether the model [`load`] would pick in `model_path` is a snips model
+pub(crate) fn is_snips_model(model_path: &Path) -> bool {
+    !model_path.join(linear::MODEL_FILE).exists() && model_path.join(snips::MODEL_FILE).exists()
+}
+
+/// Loads the model in `model_path` with the engine it was trained for. A model trained with
+/// `kara nlu train` is preferred if the directory also has a snips model
+pub fn load(model_path: impl AsRef<Path>) -> Result<Box<dyn NluEngine>, NluError> {
+    let model_path = model_path.as_ref();
+    if model_path.join(linear::MODEL_FILE).exists() {
+        Ok(Box::new(LinearEngine::load(model_path)?))
+    } else {
+        Ok(Box::new(SnipsEngine::load(model_path)?))
+    }
+}
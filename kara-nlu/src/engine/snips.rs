@@ -0,0 +1,203 @@
+//! Models trained with the snips Python toolchain
+use std::{collections::HashMap, path::Path};
+
+use serde::Deserialize;
+use snips_nlu_lib::{ontology, SnipsNluEngine};
+
+use super::NluEngine;
+use crate::{
+    intents::{
+        AmountOfMoney, Duration, Grain, InstantTime, Intent, IntentAlternative, IntentMapper,
+        Number, Ordinal, ParsedIntent, Percentage, Precision, Range, Slot, SlotValue, Temperature,
+        TemperatureUnit, Text, TimeInterval,
+    },
+    Language, NluError, ParseOptions,
+};
+
+pub(crate) const MODEL_FILE: &str = "nlu_engine.json";
+
+/// The part of a trained model's `nlu_engine.json` that describes what it was trained on
+#[derive(Deserialize)]
+pub(crate) struct EngineMetadata {
+    pub(crate) dataset_metadata: DatasetMetadata,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct DatasetMetadata {
+    /// Slot names to entity names for every intent in the model
+    slot_name_mappings: HashMap<Intent, HashMap<String, String>>,
+    /// Custom entities by name
+    pub(crate) entities: HashMap<String, serde_json::Value>,
+    language_code: String,
+}
+
+impl EngineMetadata {
+    pub(crate) fn read(model_path: &Path) -> Result<Self, NluError> {
+        let path = model_path.join(MODEL_FILE);
+        let file = std::fs::File::open(&path)
+            .map_err(|e| NluError::Load(format!("{}: {e}", path.display())))?;
+        Ok(serde_json::from_reader(std::io::BufReader::new(file))?)
+    }
+}
+
+pub struct SnipsEngine {
+    model: SnipsNluEngine,
+    intents: Vec<Intent>,
+    language: Option<Language>,
+}
+
+impl SnipsEngine {
+    pub fn load(model_path: impl AsRef<Path>) -> Result<Self, NluError> {
+        let model_path = model_path.as_ref();
+        let metadata = EngineMetadata::read(model_path)?.dataset_metadata;
+        let mut intents: Vec<_> = metadata.slot_name_mappings.into_keys().collect();
+        intents.sort_by(|a, b| a.name().cmp(b.name()));
+        let model =
+            SnipsNluEngine::from_path(model_path).map_err(|e| NluError::Load(e.to_string()))?;
+        Ok(Self {
+            model,
+            intents,
+            language: Language::from_code(&metadata.language_code),
+        })
+    }
+}
+
+impl NluEngine for SnipsEngine {
+    fn parse(&self, text: &str, options: &ParseOptions) -> Result<ParsedIntent, NluError> {
+        let result = self
+            .model
+            .parse_with_alternatives(
+                text,
+                options.whitelist(),
+                options.blacklist(),
+                options.alternatives,
+                options.slot_alternatives,
+            )
+            .map_err(|e| NluError::Engine(e.to_string()))?;
+        Ok(ParsedIntent {
+            input: result.input,
+            intent: intent(result.intent),
+            slots: result.slots.into_iter().map(slot).collect(),
+            alternatives: result
+                .alternatives
+                .into_iter()
+                .map(|alternative| IntentAlternative {
+                    intent: intent(alternative.intent),
+                    slots: alternative.slots.into_iter().map(slot).collect(),
+                })
+                .collect(),
+        })
+    }
+
+    fn intents(&self) -> &[Intent] {
+        &self.intents
+    }
+
+    fn language(&self) -> Option<Language> {
+        self.language
+    }
+}
+
+fn intent(result: ontology::IntentClassifierResult) -> IntentMapper {
+    IntentMapper {
+        intent_name: result.intent_name.as_deref().map(Intent::from_name),
+        confidence_score: f64::from(result.confidence_score),
+    }
+}
+
+fn slot(slot: ontology::Slot) -> Slot {
+    Slot {
+        raw_value: slot.raw_value,
+        value: slot_value(slot.value),
+        alternatives: slot.alternatives.into_iter().map(slot_value).collect(),
+        range: Range {
+            start: slot.range.start as i64,
+            end: slot.range.end as i64,
+        },
+        entity: slot.entity,
+        slot_name: slot.slot_name,
+    }
+}
+
+fn slot_value(value: ontology::SlotValue) -> SlotValue {
+    use ontology::SlotValue as Snips;
+    let text = |text: ontology::StringValue| Text { value: text.value };
+    match value {
+        Snips::Custom(value) => SlotValue::Custom(text(value)),
+        Snips::Number(number) => SlotValue::Number(Number {
+            value: number.value,
+        }),
+        Snips::Ordinal(ordinal) => SlotValue::Ordinal(Ordinal {
+            value: ordinal.value,
+        }),
+        Snips::Percentage(percentage) => SlotValue::Percentage(Percentage {
+            value: percentage.value,
+        }),
+        Snips::InstantTime(instant) => SlotValue::InstantTime(InstantTime {
+            value: instant.value,
+            grain: grain(instant.grain),
+            precision: precision(instant.precision),
+        }),
+        Snips::TimeInterval(interval) => SlotValue::TimeInterval(TimeInterval {
+            from: interval.from,
+            to: interval.to,
+        }),
+        Snips::AmountOfMoney(money) => SlotValue::AmountOfMoney(AmountOfMoney {
+            value: f64::from(money.value),
+            precision: precision(money.precision),
+            unit: money.unit,
+        }),
+        Snips::Temperature(temperature) => SlotValue::Temperature(Temperature {
+            value: f64::from(temperature.value),
+            unit: temperature.unit.as_deref().and_then(temperature_unit),
+        }),
+        Snips::Duration(duration) => SlotValue::Duration(Duration {
+            years: duration.years,
+            quarters: duration.quarters,
+            months: duration.months,
+            weeks: duration.weeks,
+            days: duration.days,
+            hours: duration.hours,
+            minutes: duration.minutes,
+            seconds: duration.seconds,
+            precision: precision(duration.precision),
+        }),
+        Snips::MusicAlbum(value) => SlotValue::MusicAlbum(text(value)),
+        Snips::MusicArtist(value) => SlotValue::MusicArtist(text(value)),
+        Snips::MusicTrack(value) => SlotValue::MusicTrack(text(value)),
+        Snips::City(value) => SlotValue::City(text(value)),
+        Snips::Country(value) => SlotValue::Country(text(value)),
+        Snips::Region(value) => SlotValue::Region(text(value)),
+    }
+}
+
+fn grain(grain: ontology::Grain) -> Grain {
+    match grain {
+        ontology::Grain::Year => Grain::Year,
+        ontology::Grain::Quarter => Grain::Quarter,
+        ontology::Grain::Month => Grain::Month,
+        ontology::Grain::Week => Grain::Week,
+        ontology::Grain::Day => Grain::Day,
+        ontology::Grain::Hour => Grain::Hour,
+        ontology::Grain::Minute => Grain::Minute,
+        ontology::Grain::Second => Grain::Second,
+    }
+}
+
+fn precision(precision: ontology::Precision) -> Precision {
+    match precision {
+        ontology::Precision::Approximate => Precision::Approximate,
+        ontology::Precision::Exact => Precision::Exact,
+    }
+}
+
+/// Units snips does not know of are dropped, as if no scale was said
+fn temperature_unit(unit: &str) -> Option<TemperatureUnit> {
+    match unit {
+        "celsius" => Some(TemperatureUnit::Celsius),
+        "fahrenheit" => Some(TemperatureUnit::Fahrenheit),
+        "kelvin" => Some(TemperatureUnit::Kelvin),
+        "degree" => Some(TemperatureUnit::Degree),
+        _ => None,
+    }
+}
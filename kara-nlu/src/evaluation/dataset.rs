@@ -0,0 +1,329 @@
+//! Labelled utterances in the snips dataset formats. JSON datasets look like
+//!
+//! ```json
+//! {"intents": {"iot_hue_lightoff": {"utterances": [
+//!     {"data": [{"text": "turn off the lights in the "},
+//!               {"text": "kitchen", "entity": "house_place", "slot_name": "house_place"}]}
+//! ]}}}
+//! ```
+//!
+//! and YAML datasets, one document per intent, like
+//!
+//! ```yaml
+//! type: intent
+//! name: iot_hue_lightoff
+//! utterances:
+//!   - turn off the lights in the [house_place](kitchen)
+//!   - lights off in the [room:house_place](bedroom)
+//! ```
+use std::{collections::HashMap, path::Path};
+
+use serde::Deserialize;
+
+use crate::{intents::Intent, NluError};
+
+/// An utterance and what it should be parsed as
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabelledUtterance {
+    pub text: String,
+    pub intent: Intent,
+    pub slots: Vec<LabelledSlot>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabelledSlot {
+    pub name: String,
+    /// The entity the slot takes values of, the slot name if the dataset does not say
+    pub entity: String,
+    /// The value as it is written in the utterance
+    pub value: String,
+}
+
+#[derive(Deserialize)]
+struct JsonDataset {
+    intents: HashMap<Intent, JsonIntent>,
+}
+
+#[derive(Deserialize)]
+struct JsonIntent {
+    utterances: Vec<JsonUtterance>,
+}
+
+#[derive(Deserialize)]
+struct JsonUtterance {
+    data: Vec<JsonChunk>,
+}
+
+#[derive(Deserialize)]
+struct JsonChunk {
+    text: String,
+    entity: Option<String>,
+    slot_name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct YamlDocument {
+    #[serde(rename = "type")]
+    kind: String,
+    name: Option<Intent>,
+    #[serde(default)]
+    utterances: Vec<String>,
+}
+
+/// Reads a JSON dataset, a YAML dataset or a directory of YAML datasets
+pub fn load(path: impl AsRef<Path>) -> Result<Vec<LabelledUtterance>, NluError> {
+    let path = path.as_ref();
+    let io = |e: std::io::Error| NluError::Dataset(format!("{}: {e}", path.display()));
+    if path.is_dir() {
+        let mut files = std::fs::read_dir(path)
+            .and_then(|entries| {
+                entries
+                    .map(|entry| entry.map(|entry| entry.path()))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .map_err(io)?;
+        files.retain(|file| {
+            matches!(
+                file.extension().and_then(|ext| ext.to_str()),
+                Some("yaml" | "yml")
+            )
+        });
+        files.sort();
+        let mut utterances = Vec::new();
+        for file in files {
+            utterances.extend(load(file)?);
+        }
+        return Ok(utterances);
+    }
+    let contents = std::fs::read_to_string(path).map_err(io)?;
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => from_json(&contents),
+        _ => from_yaml(&contents),
+    }
+}
+
+pub fn from_json(contents: &str) -> Result<Vec<LabelledUtterance>, NluError> {
+    let dataset: JsonDataset = serde_json::from_str(contents)?;
+    let mut utterances: Vec<_> = dataset
+        .intents
+        .into_iter()
+        .flat_map(|(intent, definition)| {
+            definition.utterances.into_iter().map(move |utterance| {
+                let text = utterance
+                    .data
+                    .iter()
+                    .map(|chunk| chunk.text.as_str())
+                    .collect();
+                let slots = utterance
+                    .data
+                    .into_iter()
+                    .filter_map(|chunk| {
+                        let name = chunk.slot_name?;
+                        Some(LabelledSlot {
+                            entity: chunk.entity.unwrap_or_else(|| name.clone()),
+                            name,
+                            value: chunk.text,
+                        })
+                    })
+                    .collect();
+                LabelledUtterance {
+                    text,
+                    intent: intent.clone(),
+                    slots,
+                }
+            })
+        })
+        .collect();
+    // Intents are read from a map, sort them so that reports are stable
+    utterances.sort_by(|a, b| (a.intent.name(), &a.text).cmp(&(b.intent.name(), &b.text)));
+    Ok(utterances)
+}
+
+pub fn from_yaml(contents: &str) -> Result<Vec<LabelledUtterance>, NluError> {
+    let mut utterances = Vec::new();
+    for document in serde_yaml::Deserializer::from_str(contents) {
+        let document =
+            YamlDocument::deserialize(document).map_err(|e| NluError::Dataset(e.to_string()))?;
+        let intent = match (document.kind.as_str(), document.name) {
+            ("intent", Some(intent)) => intent,
+            // Entity definitions do not label anything
+            _ => continue,
+        };
+        for utterance in &document.utterances {
+            let (text, slots) = parse_annotated(utterance)
+                .ok_or_else(|| NluError::Dataset(format!("malformed utterance: {utterance}")))?;
+            utterances.push(LabelledUtterance {
+                text,
+                intent: intent.clone(),
+                slots,
+            });
+        }
+    }
+    Ok(utterances)
+}
+
+/// Splits "lights off in the [room:house_place](kitchen)" into its text and slots
+fn parse_annotated(utterance: &str) -> Option<(String, Vec<LabelledSlot>)> {
+    let mut text = String::new();
+    let mut slots = Vec::new();
+    let mut rest = utterance;
+    while let Some(open) = rest.find('[') {
+        text.push_str(&rest[..open]);
+        let close = open + rest[open..].find("](")?;
+        let end = close + rest[close..].find(')')?;
+        let slot = &rest[open + 1..close];
+        let value = &rest[close + 2..end];
+        let (name, entity) = match slot.split_once(':') {
+            Some((name, entity)) => (name.trim(), entity.trim()),
+            None => (slot.trim(), slot.trim()),
+        };
+        text.push_str(value);
+        slots.push(LabelledSlot {
+            name: name.to_owned(),
+            entity: entity.to_owned(),
+            value: value.to_owned(),
+        });
+        rest = &rest[end + 1..];
+    }
+    text.push_str(rest);
+    Some((text, slots))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn slot(name: &str, entity: &str, value: &str) -> LabelledSlot {
+        LabelledSlot {
+            name: name.to_owned(),
+            entity: entity.to_owned(),
+            value: value.to_owned(),
+        }
+    }
+
+    #[test]
+    fn parses_annotated_utterances() {
+        assert_eq!(
+            parse_annotated("turn off the lights in the [house_place](kitchen)"),
+            Some((
+                "turn off the lights in the kitchen".to_owned(),
+                vec![slot("house_place", "house_place", "kitchen")]
+            ))
+        );
+        assert_eq!(
+            parse_annotated("lights off in the [room: house_place](bedroom) at [time](10pm)"),
+            Some((
+                "lights off in the bedroom at 10pm".to_owned(),
+                vec![
+                    slot("room", "house_place", "bedroom"),
+                    slot("time", "time", "10pm")
+                ]
+            ))
+        );
+        assert_eq!(
+            parse_annotated("good morning"),
+            Some(("good morning".to_owned(), Vec::new()))
+        );
+        assert_eq!(parse_annotated("lights off in the [room(bedroom)"), None);
+        assert_eq!(parse_annotated("lights off in the [room](bedroom"), None);
+    }
+
+    const YAML: &str = "type: intent
+name: iot_hue_lightoff
+utterances:
+  - turn off the lights in the [house_place](kitchen)
+  - lights off
+---
+type: entity
+name: house_place
+values:
+  - kitchen
+---
+type: intent
+name: alarm_set
+utterances:
+  - wake me up at [time](7am)
+";
+
+    const JSON: &str = r#"{"intents": {
+        "lists_query": {"utterances": [{"data": [{"text": "read my list"}]}]},
+        "iot_hue_lightoff": {"utterances": [
+            {"data": [{"text": "turn off the lights in the "},
+                      {"text": "kitchen", "entity": "house_place", "slot_name": "room"}]},
+            {"data": [{"text": "lights off in the "}, {"text": "hall", "slot_name": "room"}]}
+        ]}
+    }}"#;
+
+    #[test]
+    fn reads_yaml_datasets() {
+        let utterances = from_yaml(YAML).unwrap();
+        let read: Vec<_> = utterances
+            .iter()
+            .map(|utterance| (utterance.intent.name(), utterance.text.as_str()))
+            .collect();
+        assert_eq!(
+            read,
+            [
+                ("iot_hue_lightoff", "turn off the lights in the kitchen"),
+                ("iot_hue_lightoff", "lights off"),
+                ("alarm_set", "wake me up at 7am"),
+            ]
+        );
+        assert_eq!(utterances[2].slots, [slot("time", "time", "7am")]);
+
+        let malformed = "type: intent\nname: alarm_set\nutterances:\n  - at [time(7am)\n";
+        assert!(matches!(from_yaml(malformed), Err(NluError::Dataset(_))));
+        assert!(from_yaml("type: [intent").is_err());
+    }
+
+    #[test]
+    fn reads_json_datasets() {
+        let utterances = from_json(JSON).unwrap();
+        // Sorted by intent and text, whatever the order of the file
+        assert_eq!(
+            utterances,
+            [
+                LabelledUtterance {
+                    text: "lights off in the hall".to_owned(),
+                    intent: Intent::from_name("iot_hue_lightoff"),
+                    slots: vec![slot("room", "room", "hall")],
+                },
+                LabelledUtterance {
+                    text: "turn off the lights in the kitchen".to_owned(),
+                    intent: Intent::from_name("iot_hue_lightoff"),
+                    slots: vec![slot("room", "house_place", "kitchen")],
+                },
+                LabelledUtterance {
+                    text: "read my list".to_owned(),
+                    intent: Intent::ListsQuery,
+                    slots: Vec::new(),
+                },
+            ]
+        );
+        assert!(from_json(r#"{"intents": []}"#).is_err());
+    }
+
+    #[test]
+    fn loads_files_and_directories() {
+        let dir = std::env::temp_dir().join(format!("kara-dataset-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (first, second) = YAML.split_at(YAML.rfind("---").unwrap());
+        fs::write(dir.join("b.yml"), second.trim_start_matches("---\n")).unwrap();
+        fs::write(dir.join("a.yaml"), first).unwrap();
+        fs::write(dir.join("notes.txt"), "not a dataset").unwrap();
+        let json = dir.join("dataset.json");
+        fs::write(&json, JSON).unwrap();
+
+        let from_dir = load(&dir);
+        let from_file = load(&json);
+        let missing = load(dir.join("missing.yaml"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        // Directories are read file by file in order, JSON files in them are left out
+        assert_eq!(from_dir.unwrap(), from_yaml(YAML).unwrap());
+        assert_eq!(from_file.unwrap(), from_json(JSON).unwrap());
+        assert!(matches!(missing, Err(NluError::Dataset(_))));
+    }
+}
@@ -0,0 +1,343 @@
+//! Measures how well a model understands a labelled dataset, so that model versions can
+//! be compared.
+mod dataset;
+
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet},
+};
+
+use serde::Serialize;
+
+use crate::{intents::ParsedIntent, NLUParser, NluError};
+pub use dataset::{from_json, from_yaml, load, LabelledSlot, LabelledUtterance};
+
+/// The label used for utterances the model did not understand
+pub const NOT_UNDERSTOOD: &str = "null";
+
+/// How many of the most confused intent pairs a report lists
+const WORST_CONFUSIONS: usize = 10;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Metrics {
+    pub precision: f64,
+    pub recall: f64,
+    pub f1: f64,
+    /// Number of labelled examples
+    pub support: usize,
+}
+
+impl Metrics {
+    fn new(true_positives: usize, false_positives: usize, false_negatives: usize) -> Self {
+        let ratio = |num: usize, den: usize| {
+            if den == 0 {
+                0.0
+            } else {
+                num as f64 / den as f64
+            }
+        };
+        let precision = ratio(true_positives, true_positives + false_positives);
+        let recall = ratio(true_positives, true_positives + false_negatives);
+        let f1 = if precision + recall == 0.0 {
+            0.0
+        } else {
+            2.0 * precision * recall / (precision + recall)
+        };
+        Self {
+            precision,
+            recall,
+            f1,
+            support: true_positives + false_negatives,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Confusion {
+    pub expected: String,
+    pub predicted: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Report {
+    pub utterances: usize,
+    /// Share of utterances whose intent was right
+    pub intent_accuracy: f64,
+    /// Share of utterances whose intent was right and whose slots all matched exactly
+    pub exact_match: f64,
+    pub intents: BTreeMap<String, Metrics>,
+    /// Metrics over every slot name and value pair
+    pub slots: Metrics,
+    /// Expected intent, then predicted intent, then how often it happened
+    pub confusion: BTreeMap<String, BTreeMap<String, usize>>,
+    /// The most frequent mistakes, worst first
+    pub worst_confusions: Vec<Confusion>,
+}
+
+/// Parses every utterance with `parser` and scores the results
+pub fn evaluate(parser: &NLUParser, dataset: &[LabelledUtterance]) -> Result<Report, NluError> {
+    let predictions = dataset
+        .iter()
+        .map(|utterance| parser.parse_text(&utterance.text))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Report::new(dataset.iter().zip(&predictions)))
+}
+
+impl Report {
+    pub fn new<'a>(
+        results: impl IntoIterator<Item = (&'a LabelledUtterance, &'a ParsedIntent)>,
+    ) -> Self {
+        let mut confusion: BTreeMap<String, BTreeMap<String, usize>> = BTreeMap::new();
+        let (mut utterances, mut correct, mut exact) = (0, 0, 0);
+        let (mut slot_tp, mut slot_fp, mut slot_fn) = (0, 0, 0);
+        for (expected, predicted) in results {
+            utterances += 1;
+            let expected_intent = expected.intent.name().to_owned();
+            let predicted_intent = predicted
+                .intent
+                .intent_name
+                .as_ref()
+                .map_or(NOT_UNDERSTOOD, |intent| intent.name())
+                .to_owned();
+            let intent_correct = expected_intent == predicted_intent;
+            *confusion
+                .entry(expected_intent)
+                .or_default()
+                .entry(predicted_intent)
+                .or_default() += 1;
+
+            let expected_slots: BTreeSet<_> = expected
+                .slots
+                .iter()
+                .map(|slot| (slot.name.clone(), normalise(&slot.value)))
+                .collect();
+            let predicted_slots: BTreeSet<_> = predicted
+                .slots
+                .iter()
+                .map(|slot| (slot.slot_name.clone(), normalise(&slot.raw_value)))
+                .collect();
+            let matched = expected_slots.intersection(&predicted_slots).count();
+            slot_tp += matched;
+            slot_fp += predicted_slots.len() - matched;
+            slot_fn += expected_slots.len() - matched;
+
+            if intent_correct {
+                correct += 1;
+                if expected_slots == predicted_slots {
+                    exact += 1;
+                }
+            }
+        }
+
+        let labels: BTreeSet<_> = confusion
+            .iter()
+            .flat_map(|(expected, row)| std::iter::once(expected).chain(row.keys()))
+            .filter(|label| *label != NOT_UNDERSTOOD)
+            .cloned()
+            .collect();
+        let count = |expected: &str, predicted: &str| {
+            confusion
+                .get(expected)
+                .and_then(|row| row.get(predicted))
+                .copied()
+                .unwrap_or_default()
+        };
+        let intents = labels
+            .into_iter()
+            .map(|label| {
+                let true_positives = count(&label, &label);
+                let false_positives = confusion
+                    .iter()
+                    .filter(|(expected, _)| **expected != label)
+                    .map(|(expected, _)| count(expected, &label))
+                    .sum();
+                let false_negatives = confusion
+                    .get(&label)
+                    .map_or(0, |row| row.values().sum::<usize>())
+                    - true_positives;
+                let metrics = Metrics::new(true_positives, false_positives, false_negatives);
+                (label, metrics)
+            })
+            .collect();
+
+        let mut worst_confusions: Vec<_> = confusion
+            .iter()
+            .flat_map(|(expected, row)| {
+                row.iter()
+                    .filter(move |(predicted, _)| *predicted != expected)
+                    .map(move |(predicted, count)| Confusion {
+                        expected: expected.clone(),
+                        predicted: predicted.clone(),
+                        count: *count,
+                    })
+            })
+            .collect();
+        worst_confusions.sort_by_key(|confusion| Reverse(confusion.count));
+        worst_confusions.truncate(WORST_CONFUSIONS);
+
+        let share = |count: usize| {
+            if utterances == 0 {
+                0.0
+            } else {
+                count as f64 / utterances as f64
+            }
+        };
+        Self {
+            utterances,
+            intent_accuracy: share(correct),
+            exact_match: share(exact),
+            intents,
+            slots: Metrics::new(slot_tp, slot_fp, slot_fn),
+            confusion,
+            worst_confusions,
+        }
+    }
+}
+
+fn normalise(value: &str) -> String {
+    value.trim().to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intents::{Intent, IntentMapper, Range, Slot, SlotValue, Text};
+
+    fn labelled(intent: Intent, slots: &[(&str, &str)]) -> LabelledUtterance {
+        LabelledUtterance {
+            text: String::new(),
+            intent,
+            slots: slots
+                .iter()
+                .map(|(name, value)| LabelledSlot {
+                    name: (*name).to_owned(),
+                    entity: (*name).to_owned(),
+                    value: (*value).to_owned(),
+                })
+                .collect(),
+        }
+    }
+
+    fn predicted(intent: Option<Intent>, slots: &[(&str, &str)]) -> ParsedIntent {
+        ParsedIntent {
+            intent: IntentMapper {
+                intent_name: intent,
+                confidence_score: 1.0,
+            },
+            slots: slots
+                .iter()
+                .map(|(name, value)| Slot {
+                    raw_value: (*value).to_owned(),
+                    value: SlotValue::Custom(Text {
+                        value: (*value).to_owned(),
+                    }),
+                    alternatives: Vec::new(),
+                    range: Range::default(),
+                    entity: (*name).to_owned(),
+                    slot_name: (*name).to_owned(),
+                })
+                .collect(),
+            ..ParsedIntent::default()
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+    }
+
+    fn report() -> Report {
+        let results = [
+            (
+                labelled(Intent::AlarmSet, &[("time", "7am")]),
+                predicted(Some(Intent::AlarmSet), &[("time", " 7AM")]),
+            ),
+            (
+                labelled(Intent::AlarmSet, &[("time", "8am")]),
+                predicted(Some(Intent::AlarmSet), &[("time", "9am")]),
+            ),
+            (
+                labelled(Intent::AlarmQuery, &[]),
+                predicted(Some(Intent::AlarmSet), &[]),
+            ),
+            (labelled(Intent::ListsQuery, &[]), predicted(None, &[])),
+            (labelled(Intent::ListsQuery, &[]), predicted(None, &[])),
+            (
+                labelled(Intent::ListsQuery, &[]),
+                predicted(Some(Intent::ListsRemove), &[]),
+            ),
+        ];
+        Report::new(
+            results
+                .iter()
+                .map(|(expected, predicted)| (expected, predicted)),
+        )
+    }
+
+    #[test]
+    fn scores_intents() {
+        let report = report();
+        assert_eq!(report.utterances, 6);
+        assert_close(report.intent_accuracy, 2.0 / 6.0);
+        assert_close(report.exact_match, 1.0 / 6.0);
+        // Not understanding is no intent of its own, it only counts against recall
+        assert_eq!(
+            report.intents.keys().collect::<Vec<_>>(),
+            ["alarm_query", "alarm_set", "lists_query", "lists_remove"]
+        );
+
+        let alarm_set = &report.intents["alarm_set"];
+        assert_close(alarm_set.precision, 2.0 / 3.0);
+        assert_close(alarm_set.recall, 1.0);
+        assert_close(alarm_set.f1, 0.8);
+        assert_eq!(alarm_set.support, 2);
+        let lists_query = &report.intents["lists_query"];
+        assert_eq!((lists_query.precision, lists_query.recall), (0.0, 0.0));
+        assert_eq!(lists_query.support, 3);
+        // Never expected, so nothing can be recalled
+        assert_eq!(report.intents["lists_remove"], Metrics::default());
+        assert_eq!(report.intents["alarm_query"].support, 1);
+    }
+
+    #[test]
+    fn scores_slots() {
+        let slots = report().slots;
+        assert_close(slots.precision, 0.5);
+        assert_close(slots.recall, 0.5);
+        assert_close(slots.f1, 0.5);
+        assert_eq!(slots.support, 2);
+    }
+
+    #[test]
+    fn counts_confusions() {
+        let report = report();
+        assert_eq!(report.confusion["alarm_set"]["alarm_set"], 2);
+        assert_eq!(report.confusion["alarm_query"]["alarm_set"], 1);
+        assert_eq!(report.confusion["lists_query"][NOT_UNDERSTOOD], 2);
+        assert_eq!(report.confusion["lists_query"]["lists_remove"], 1);
+        let worst: Vec<_> = report
+            .worst_confusions
+            .iter()
+            .map(|confusion| {
+                (
+                    confusion.expected.as_str(),
+                    confusion.predicted.as_str(),
+                    confusion.count,
+                )
+            })
+            .collect();
+        assert_eq!(
+            worst,
+            [
+                ("lists_query", NOT_UNDERSTOOD, 2),
+                ("alarm_query", "alarm_set", 1),
+                ("lists_query", "lists_remove", 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_nothing_for_an_empty_dataset() {
+        assert_eq!(Report::new([]), Report::default());
+    }
+}
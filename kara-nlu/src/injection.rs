@@ -0,0 +1,276 @@
+//! Values added to a trained model's custom entities, such as the names of contacts,
+//! playlists and rooms. Injected values are kept in a file and injected into every model
+//! when it is loaded, so they survive restarts and language changes.
+//!
+//! Models are often shared or installed read-only, so the values are injected into a copy
+//! of the model in the user's data directory, see [`InjectedEntities::inject_copy`].
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use serde::{Deserialize, Serialize};
+use snips_nlu_lib::NluInjector;
+
+use crate::{
+    engine::{self, EngineMetadata},
+    Language, NluError,
+};
+
+/// Short names for the entities users are most likely to extend
+const ALIASES: [(&str, &str); 4] = [
+    ("contact", "person"),
+    ("playlist", "playlist_name"),
+    ("device", "device_type"),
+    ("room", "house_place"),
+];
+
+/// The model's name for `entity`, e.g. "playlist_name" for "playlist"
+pub fn entity_name(entity: &str) -> &str {
+    ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == entity)
+        .map_or(entity, |(_, name)| name)
+}
+
+/// The custom entities of the model in `model_path`. Only snips models can have values
+/// injected, other models learn their values from the dataset they are trained on
+pub fn model_entities(model_path: impl AsRef<Path>) -> Result<Vec<String>, NluError> {
+    let model_path = model_path.as_ref();
+    if model_path.exists() && !engine::is_snips_model(model_path) {
+        return Err(NluError::Injection(format!(
+            "{} is not a snips model, add the values to its dataset and train it again",
+            model_path.display()
+        )));
+    }
+    let mut entities: Vec<_> = EngineMetadata::read(model_path)?
+        .dataset_metadata
+        .entities
+        .into_keys()
+        .collect();
+    entities.sort();
+    Ok(entities)
+}
+
+/// Injected values by entity
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InjectedEntities {
+    entities: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl InjectedEntities {
+    /// Reads the values injected so far. A missing file has no values
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => Ok(serde_json::from_str(&contents)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    /// Returns false if `value` was already injected
+    pub fn add(&mut self, entity: &str, value: &str) -> bool {
+        self.entities
+            .entry(entity.to_owned())
+            .or_default()
+            .insert(value.trim().to_owned())
+    }
+
+    /// Returns false if `value` was not injected
+    pub fn remove(&mut self, entity: &str, value: &str) -> bool {
+        let removed = self
+            .entities
+            .get_mut(entity)
+            .map_or(false, |values| values.remove(value.trim()));
+        self.entities.retain(|_, values| !values.is_empty());
+        removed
+    }
+
+    pub fn values(&self, entity: &str) -> impl Iterator<Item = &str> {
+        self.entities
+            .get(entity)
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+    }
+
+    /// Entities with injected values
+    pub fn entities(&self) -> impl Iterator<Item = &str> {
+        self.entities.keys().map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Injects the values into a copy of the model in `model_path` at `copy_path`, which is
+    /// loaded instead of the model. The model is copied the first time and again whenever
+    /// it changes, and is never changed itself
+    pub fn inject_copy(
+        &self,
+        model_path: impl AsRef<Path>,
+        copy_path: impl AsRef<Path>,
+    ) -> Result<(), NluError> {
+        let (model_path, copy_path) = (model_path.as_ref(), copy_path.as_ref());
+        // Non snips models are rejected before anything is copied
+        model_entities(model_path)?;
+        let injection_error = |e: io::Error| NluError::Injection(e.to_string());
+        let source = source(model_path).map_err(injection_error)?;
+        let source_path = copy_path.with_extension("source");
+        let copied = fs::read_to_string(&source_path).map_or(false, |copied| copied == source)
+            && engine::is_model(copy_path);
+        if !copied {
+            copy_model(model_path, copy_path).map_err(injection_error)?;
+            fs::write(&source_path, source).map_err(injection_error)?;
+        }
+        self.inject(copy_path)
+    }
+
+    /// Injects the values into the model in `model_path`. The model's own values are
+    /// restored first so that removed values are dropped
+    fn inject(&self, model_path: &Path) -> Result<(), NluError> {
+        let known = model_entities(model_path)?;
+        self.entities
+            .iter()
+            .filter(|(entity, _)| known.contains(entity))
+            .flat_map(|(entity, values)| values.iter().map(move |value| (entity, value)))
+            .fold(
+                NluInjector::new(model_path).from_vanilla(true),
+                |injector, (entity, value)| injector.add_value(entity.clone(), value.clone()),
+            )
+            .inject()
+            .map_err(|e| NluError::Injection(e.to_string()))
+    }
+}
+
+/// Which model a copy was made from and when it last changed, to tell when to copy it again
+fn source(model_path: &Path) -> io::Result<String> {
+    let modified = fs::metadata(model_path.join(engine::SNIPS_MODEL_FILE))?
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    Ok(format!(
+        "{}\n{}",
+        model_path.canonicalize()?.display(),
+        modified.as_nanos()
+    ))
+}
+
+/// Replaces the model in `to` with a copy of the one in `from`. It is copied next to it
+/// first so that a failed copy never replaces a working one
+fn copy_model(from: &Path, to: &Path) -> io::Result<()> {
+    let temporary = to.with_extension("tmp");
+    if temporary.exists() {
+        fs::remove_dir_all(&temporary)?;
+    }
+    copy_dir(from, &temporary)?;
+    if to.exists() {
+        fs::remove_dir_all(to)?;
+    }
+    fs::rename(temporary, to)
+}
+
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Where the copy of the `language` model with the injected values is kept
+pub fn injected_model_path(language: Language) -> PathBuf {
+    let mut path = dirs::data_dir().expect("could not find data dir");
+    path.push("kara");
+    path.push("injected");
+    path.push(language.code());
+    path
+}
+
+pub fn default_entity_path() -> PathBuf {
+    let mut path = dirs::data_dir().expect("could not find data dir");
+    path.push("kara");
+    path.push("entities.json");
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_model(dir: &Path, entities: &str) {
+        fs::create_dir_all(dir.join("slot_filler")).unwrap();
+        fs::write(
+            dir.join(engine::SNIPS_MODEL_FILE),
+            format!(
+                r#"{{"dataset_metadata": {{"slot_name_mappings": {{}}, "entities": {{{entities}}}, "language_code": "en"}}}}"#
+            ),
+        )
+        .unwrap();
+        fs::write(dir.join("slot_filler/model.json"), "{}").unwrap();
+    }
+
+    #[test]
+    fn aliases() {
+        assert_eq!(entity_name("room"), "house_place");
+        assert_eq!(entity_name("house_place"), "house_place");
+    }
+
+    #[test]
+    fn adds_and_removes_values() {
+        let mut injected = InjectedEntities::default();
+        assert!(injected.add("house_place", " attic "));
+        assert!(!injected.add("house_place", "attic"));
+        assert_eq!(
+            injected.values("house_place").collect::<Vec<_>>(),
+            ["attic"]
+        );
+        assert!(!injected.remove("house_place", "cellar"));
+        assert!(injected.remove("house_place", "attic"));
+        assert!(injected.is_empty());
+    }
+
+    #[test]
+    fn injects_into_a_copy() {
+        let dir = std::env::temp_dir().join(format!("kara-injection-{}", std::process::id()));
+        let (model, copy) = (dir.join("shared/en"), dir.join("user/en"));
+        write_model(&model, r#""house_place": {}"#);
+        let mut injected = InjectedEntities::default();
+        injected.add("house_place", "attic");
+
+        injected.inject_copy(&model, &copy).unwrap();
+        assert!(engine::is_model(&copy));
+        assert!(copy.join("slot_filler/model.json").exists());
+        assert!(copy.with_extension("source").exists());
+        let entries = |path: &Path| fs::read_dir(path).unwrap().count();
+        assert_eq!(entries(&model), 2);
+
+        // A copy of an older model is replaced
+        write_model(&model, r#""house_place": {}, "playlist_name": {}"#);
+        fs::write(copy.join("stale.json"), "{}").unwrap();
+        injected.inject_copy(&model, &copy).unwrap();
+        assert!(!copy.join("stale.json").exists());
+        assert_eq!(
+            model_entities(&copy).unwrap(),
+            ["house_place", "playlist_name"]
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
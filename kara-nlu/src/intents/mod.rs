@@ -1,4 +1,13 @@
-use serde::Deserialize;
+mod value;
+
+use std::fmt::Display;
+
+use serde::{Deserialize, Deserializer};
+
+pub use value::{
+    AmountOfMoney, Duration, Grain, InstantTime, Number, Ordinal, Percentage, Precision, SlotValue,
+    Temperature, TemperatureUnit, Text, TimeInterval,
+};
 
 #[derive(Default, Debug, Clone, PartialEq, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -6,7 +15,15 @@ pub struct ParsedIntent {
     pub input: String,
     pub intent: IntentMapper,
     pub slots: Vec<Slot>,
-    pub alternatives: Vec<Value>,
+    /// The next most likely intents, best first
+    pub alternatives: Vec<IntentAlternative>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntentAlternative {
+    pub intent: IntentMapper,
+    pub slots: Vec<Slot>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Deserialize)]
@@ -16,24 +33,17 @@ pub struct IntentMapper {
     pub confidence_score: f64,
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Eq, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Slot {
     pub raw_value: String,
-    pub value: Value,
-    pub alternatives: Vec<Value>,
+    pub value: SlotValue,
+    pub alternatives: Vec<SlotValue>,
     pub range: Range,
     pub entity: String,
     pub slot_name: String,
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Eq, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Value {
-    pub kind: String,
-    pub value: String,
-}
-
 #[derive(Default, Debug, Clone, PartialEq, Eq, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Range {
@@ -41,142 +51,125 @@ pub struct Range {
     pub end: i64,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
-pub enum Intent {
-    #[serde(rename = "lists_createoradd")]
-    ListsCreateOrAdd,
-    #[serde(rename = "iot_cleaning")]
-    IotCleaning,
-    #[serde(rename = "iot_wemo_off")]
-    IotWemoOff,
-    #[serde(rename = "iot_hue_lightdim")]
-    IotHueLightdim,
-    #[serde(rename = "general_joke")]
-    GeneralJoke,
-    #[serde(rename = "play_music")]
-    PlayMusic,
-    #[serde(rename = "email_query")]
-    EmailQuery,
-    #[serde(rename = "lists_query")]
-    ListsQuery,
-    #[serde(rename = "general_commandstop")]
-    GeneralCommandstop,
-    #[serde(rename = "audio_volume_other")]
-    AudioVolumeOther,
-    #[serde(rename = "weather_query")]
-    WeatherQuery,
-    #[serde(rename = "datetime_query")]
-    DateTimeQuery,
-    #[serde(rename = "transport_taxi")]
-    TransportTaxi,
-    #[serde(rename = "general_confirm")]
-    GeneralConfirm,
-    #[serde(rename = "general_praise")]
-    GeneralPraise,
-    #[serde(rename = "audio_volume_mute")]
-    AudioVolumeMute,
-    #[serde(rename = "iot_hue_lightoff")]
-    IotHueLightoff,
-    #[serde(rename = "music_likeness")]
-    MusicLikeness,
-    #[serde(rename = "recommendation_locations")]
-    RecommendationLocations,
-    #[serde(rename = "cooking_query")]
-    CookingQuery,
-    #[serde(rename = "general_dontcare")]
-    GeneralDontCare,
-    #[serde(rename = "audio_volume_up")]
-    AudioVolumeUp,
-    #[serde(rename = "play_radio")]
-    PlayRadio,
-    #[serde(rename = "qa_definition")]
-    QADefinition,
-    #[serde(rename = "lists_remove")]
-    ListsRemove,
-    #[serde(rename = "calendar_remove")]
-    CalendarRemove,
-    #[serde(rename = "audio_volume_down")]
-    AudioVolumeDown,
-    #[serde(rename = "qa_factoid")]
-    QAFactoid,
-    #[serde(rename = "transport_traffic")]
-    TransportTraffic,
-    #[serde(rename = "iot_wemo_on")]
-    IotWemoOn,
-    #[serde(rename = "iot_coffee")]
-    IotCoffee,
-    #[serde(rename = "music_settings")]
-    MusicSettings,
-    #[serde(rename = "datetime_convert")]
-    DatetimeConvert,
-    #[serde(rename = "social_query")]
-    SocialQuery,
-    #[serde(rename = "calendar_query")]
-    CalendarQuery,
-    #[serde(rename = "cooking_recipe")]
-    CookingRecipe,
-    #[serde(rename = "transport_ticket")]
-    TransportTicket,
-    #[serde(rename = "iot_hue_lighton")]
-    IotHueLightOn,
-    #[serde(rename = "music_dislikeness")]
-    MusicDislikeness,
-    #[serde(rename = "general_repeat")]
-    GeneralRepeat,
-    #[serde(rename = "transport_query")]
-    TransportQuery,
-    #[serde(rename = "music_query")]
-    MusicQuery,
-    #[serde(rename = "general_quirky")]
-    GeneralQuirky,
-    #[serde(rename = "qa_currency")]
-    QACurrency,
-    #[serde(rename = "iot_hue_lightchange")]
-    IotHueLightChange,
-    #[serde(rename = "iot_hue_lightup")]
-    IotHueLightUp,
-    #[serde(rename = "email_querycontact")]
-    EmailQueryContact,
-    #[serde(rename = "email_addcontact")]
-    EmailAddContact,
-    #[serde(rename = "general_explain")]
-    GeneralExplain,
-    #[serde(rename = "recommendation_events")]
-    RecommendationEvents,
-    #[serde(rename = "alarm_set")]
-    AlarmSet,
-    #[serde(rename = "play_game")]
-    PlayGame,
-    #[serde(rename = "play_podcasts")]
-    PlayPodcasts,
-    #[serde(rename = "takeaway_query")]
-    TakeawayQuery,
-    #[serde(rename = "general_greet")]
-    GeneralGreet,
-    #[serde(rename = "calendar_set")]
-    CalendarSet,
-    #[serde(rename = "alarm_query")]
-    AlarmQuery,
-    #[serde(rename = "qa_maths")]
-    QAMaths,
-    #[serde(rename = "takeaway_order")]
-    TakeawayOrder,
-    #[serde(rename = "email_sendemail")]
-    EmailSend,
-    #[serde(rename = "general_negate")]
-    GeneralNegate,
-    #[serde(rename = "alarm_remove")]
-    AlarmRemove,
-    #[serde(rename = "news_query")]
-    NewsQuery,
-    #[serde(rename = "recommendation_movies")]
-    RecommendationMovies,
-    #[serde(rename = "social_post")]
-    SocialPost,
-    #[serde(rename = "play_audiobook")]
-    PlayAudiobook,
-    #[serde(rename = "general_affirm")]
-    GeneralAffirm,
-    #[serde(rename = "qa_stock")]
-    QAStock,
+/// Generates [`Intent`] from the intents of the bundled model and the names they have in it
+macro_rules! intents {
+    ($($variant:ident => $name:literal,)*) => {
+        /// An intent name. The intents of the bundled model have their own variants, any other
+        /// intent a model was trained on is [`Intent::Custom`]
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        pub enum Intent {
+            $($variant,)*
+            Custom(String),
+        }
+
+        impl Intent {
+            /// The name of the intent in the model, e.g. "general_greet"
+            pub fn name(&self) -> &str {
+                match self {
+                    $(Intent::$variant => $name,)*
+                    Intent::Custom(name) => name,
+                }
+            }
+
+            pub fn from_name(name: &str) -> Self {
+                match name {
+                    $($name => Intent::$variant,)*
+                    name => Intent::Custom(name.to_owned()),
+                }
+            }
+        }
+    };
+}
+
+intents! {
+    ListsCreateOrAdd => "lists_createoradd",
+    IotCleaning => "iot_cleaning",
+    IotWemoOff => "iot_wemo_off",
+    IotHueLightdim => "iot_hue_lightdim",
+    GeneralJoke => "general_joke",
+    PlayMusic => "play_music",
+    EmailQuery => "email_query",
+    ListsQuery => "lists_query",
+    GeneralCommandstop => "general_commandstop",
+    AudioVolumeOther => "audio_volume_other",
+    WeatherQuery => "weather_query",
+    DateTimeQuery => "datetime_query",
+    TransportTaxi => "transport_taxi",
+    GeneralConfirm => "general_confirm",
+    GeneralPraise => "general_praise",
+    AudioVolumeMute => "audio_volume_mute",
+    IotHueLightoff => "iot_hue_lightoff",
+    MusicLikeness => "music_likeness",
+    RecommendationLocations => "recommendation_locations",
+    CookingQuery => "cooking_query",
+    GeneralDontCare => "general_dontcare",
+    AudioVolumeUp => "audio_volume_up",
+    PlayRadio => "play_radio",
+    QADefinition => "qa_definition",
+    ListsRemove => "lists_remove",
+    CalendarRemove => "calendar_remove",
+    AudioVolumeDown => "audio_volume_down",
+    QAFactoid => "qa_factoid",
+    TransportTraffic => "transport_traffic",
+    IotWemoOn => "iot_wemo_on",
+    IotCoffee => "iot_coffee",
+    MusicSettings => "music_settings",
+    DatetimeConvert => "datetime_convert",
+    SocialQuery => "social_query",
+    CalendarQuery => "calendar_query",
+    CookingRecipe => "cooking_recipe",
+    TransportTicket => "transport_ticket",
+    IotHueLightOn => "iot_hue_lighton",
+    MusicDislikeness => "music_dislikeness",
+    GeneralRepeat => "general_repeat",
+    TransportQuery => "transport_query",
+    MusicQuery => "music_query",
+    GeneralQuirky => "general_quirky",
+    QACurrency => "qa_currency",
+    IotHueLightChange => "iot_hue_lightchange",
+    IotHueLightUp => "iot_hue_lightup",
+    EmailQueryContact => "email_querycontact",
+    EmailAddContact => "email_addcontact",
+    GeneralExplain => "general_explain",
+    RecommendationEvents => "recommendation_events",
+    AlarmSet => "alarm_set",
+    PlayGame => "play_game",
+    PlayPodcasts => "play_podcasts",
+    TakeawayQuery => "takeaway_query",
+    GeneralGreet => "general_greet",
+    CalendarSet => "calendar_set",
+    AlarmQuery => "alarm_query",
+    QAMaths => "qa_maths",
+    TakeawayOrder => "takeaway_order",
+    EmailSend => "email_sendemail",
+    GeneralNegate => "general_negate",
+    AlarmRemove => "alarm_remove",
+    NewsQuery => "news_query",
+    RecommendationMovies => "recommendation_movies",
+    SocialPost => "social_post",
+    PlayAudiobook => "play_audiobook",
+    GeneralAffirm => "general_affirm",
+    QAStock => "qa_stock",
+}
+
+impl Display for Intent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl From<&str> for Intent {
+    fn from(name: &str) -> Self {
+        Intent::from_name(name)
+    }
+}
+
+impl<'de> Deserialize<'de> for Intent {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let name = String::deserialize(deserializer)?;
+        Ok(Intent::from_name(&name))
+    }
 }
@@ -0,0 +1,382 @@
+//! Slot values as they are produced by the snips builtin entity parsers, e.g.
+//!
+//! ```json
+//! {"kind": "InstantTime", "value": "2022-08-01 19:00:00 +02:00", "grain": "Hour", "precision": "Exact"}
+//! {"kind": "Temperature", "value": 23.0, "unit": "celsius"}
+//! {"kind": "Custom", "value": "kitchen"}
+//! ```
+use chrono::{DateTime, FixedOffset};
+use serde::Deserialize;
+
+/// The format snips uses for instants
+const INSTANT_FORMAT: &str = "%Y-%m-%d %H:%M:%S %:z";
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "kind")]
+pub enum SlotValue {
+    Custom(Text),
+    Number(Number),
+    Ordinal(Ordinal),
+    Percentage(Percentage),
+    InstantTime(InstantTime),
+    TimeInterval(TimeInterval),
+    AmountOfMoney(AmountOfMoney),
+    Temperature(Temperature),
+    Duration(Duration),
+    MusicAlbum(Text),
+    MusicArtist(Text),
+    MusicTrack(Text),
+    City(Text),
+    Country(Text),
+    Region(Text),
+    /// A kind of entity that is not known of here. It is kept so that the rest of the
+    /// parse can still be used
+    #[serde(other)]
+    Unknown,
+}
+
+impl SlotValue {
+    /// The value of a custom or text entity such as a music track or a city
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            SlotValue::Custom(text)
+            | SlotValue::MusicAlbum(text)
+            | SlotValue::MusicArtist(text)
+            | SlotValue::MusicTrack(text)
+            | SlotValue::City(text)
+            | SlotValue::Country(text)
+            | SlotValue::Region(text) => Some(&text.value),
+            _ => None,
+        }
+    }
+
+    /// The value of a number, ordinal or percentage
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            SlotValue::Number(number) => Some(number.value),
+            SlotValue::Ordinal(ordinal) => Some(ordinal.value as f64),
+            SlotValue::Percentage(percentage) => Some(percentage.value),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Text {
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct Number {
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct Ordinal {
+    pub value: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct Percentage {
+    /// Percentage points, e.g. 25.0 for 25%
+    pub value: f64,
+}
+
+impl Percentage {
+    /// The percentage as a fraction, e.g. 0.25 for 25%
+    pub fn fraction(&self) -> f64 {
+        self.value / 100.0
+    }
+}
+
+/// How much of an instant was actually said, e.g. "tomorrow" has a grain of `Day`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+pub enum Grain {
+    Year,
+    Quarter,
+    Month,
+    Week,
+    Day,
+    Hour,
+    Minute,
+    Second,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum Precision {
+    Approximate,
+    Exact,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct InstantTime {
+    pub value: String,
+    pub grain: Grain,
+    pub precision: Precision,
+}
+
+impl InstantTime {
+    pub fn datetime(&self) -> Result<DateTime<FixedOffset>, chrono::ParseError> {
+        parse_instant(&self.value)
+    }
+}
+
+/// A span of time. Open ended intervals such as "after 5pm" only have one bound
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TimeInterval {
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+impl TimeInterval {
+    pub fn start(&self) -> Option<Result<DateTime<FixedOffset>, chrono::ParseError>> {
+        self.from.as_deref().map(parse_instant)
+    }
+
+    pub fn end(&self) -> Option<Result<DateTime<FixedOffset>, chrono::ParseError>> {
+        self.to.as_deref().map(parse_instant)
+    }
+}
+
+fn parse_instant(value: &str) -> Result<DateTime<FixedOffset>, chrono::ParseError> {
+    DateTime::parse_from_str(value, INSTANT_FORMAT)
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AmountOfMoney {
+    pub value: f64,
+    pub precision: Precision,
+    /// A currency symbol or ISO 4217 code, e.g. "$" or "EUR"
+    pub unit: Option<String>,
+}
+
+impl AmountOfMoney {
+    /// The ISO 4217 code of the currency, if one was given
+    pub fn currency(&self) -> Option<String> {
+        let unit = self.unit.as_deref()?.trim();
+        let code = match unit {
+            "$" => "USD",
+            "€" => "EUR",
+            "£" => "GBP",
+            "¥" => "JPY",
+            "₹" => "INR",
+            "₽" => "RUB",
+            "cent" | "cents" => "USD",
+            unit => return Some(unit.to_uppercase()),
+        };
+        Some(code.to_owned())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TemperatureUnit {
+    Celsius,
+    Fahrenheit,
+    Kelvin,
+    /// "degrees" without a scale
+    Degree,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Temperature {
+    pub value: f64,
+    pub unit: Option<TemperatureUnit>,
+}
+
+impl Temperature {
+    /// The temperature in degrees Celsius. Temperatures without a scale are assumed to be
+    /// in `default`
+    pub fn celsius(&self, default: TemperatureUnit) -> f64 {
+        match self.unit.unwrap_or(default) {
+            TemperatureUnit::Celsius | TemperatureUnit::Degree => self.value,
+            TemperatureUnit::Fahrenheit => (self.value - 32.0) * 5.0 / 9.0,
+            TemperatureUnit::Kelvin => self.value - 273.15,
+        }
+    }
+
+    pub fn fahrenheit(&self, default: TemperatureUnit) -> f64 {
+        self.celsius(default) * 9.0 / 5.0 + 32.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Duration {
+    pub years: i64,
+    pub quarters: i64,
+    pub months: i64,
+    pub weeks: i64,
+    pub days: i64,
+    pub hours: i64,
+    pub minutes: i64,
+    pub seconds: i64,
+    pub precision: Precision,
+}
+
+impl Duration {
+    /// The duration as a fixed length. Months are taken to be 30 days, quarters 91 days
+    /// and years 365 days
+    pub fn to_chrono(&self) -> chrono::Duration {
+        chrono::Duration::days(self.years * 365 + self.quarters * 91 + self.months * 30)
+            + chrono::Duration::weeks(self.weeks)
+            + chrono::Duration::days(self.days)
+            + chrono::Duration::hours(self.hours)
+            + chrono::Duration::minutes(self.minutes)
+            + chrono::Duration::seconds(self.seconds)
+    }
+
+    /// `None` for negative durations
+    pub fn to_std(&self) -> Option<std::time::Duration> {
+        self.to_chrono().to_std().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(json: &str) -> SlotValue {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn instant_time() {
+        let instant = match value(
+            r#"{"kind": "InstantTime", "value": "2022-08-01 19:00:00 +02:00", "grain": "Hour", "precision": "Exact"}"#,
+        ) {
+            SlotValue::InstantTime(instant) => instant,
+            other => panic!("{other:?}"),
+        };
+        assert_eq!(instant.grain, Grain::Hour);
+        assert_eq!(instant.precision, Precision::Exact);
+        assert_eq!(
+            instant.datetime().unwrap().to_rfc3339(),
+            "2022-08-01T19:00:00+02:00"
+        );
+    }
+
+    #[test]
+    fn time_interval() {
+        let interval = match value(
+            r#"{"kind": "TimeInterval", "from": "2022-08-01 17:00:00 +02:00", "to": null}"#,
+        ) {
+            SlotValue::TimeInterval(interval) => interval,
+            other => panic!("{other:?}"),
+        };
+        assert_eq!(
+            interval.start().unwrap().unwrap().to_rfc3339(),
+            "2022-08-01T17:00:00+02:00"
+        );
+        assert!(interval.end().is_none());
+    }
+
+    #[test]
+    fn duration() {
+        let duration = match value(
+            r#"{"kind": "Duration", "years": 0, "quarters": 0, "months": 0, "weeks": 0, "days": 1, "hours": 2, "minutes": 30, "seconds": 0, "precision": "Exact"}"#,
+        ) {
+            SlotValue::Duration(duration) => duration,
+            other => panic!("{other:?}"),
+        };
+        assert_eq!(
+            duration.to_std(),
+            Some(std::time::Duration::from_secs(26 * 3600 + 30 * 60))
+        );
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(
+            value(r#"{"kind": "Number", "value": 42.5}"#).as_number(),
+            Some(42.5)
+        );
+        assert_eq!(
+            value(r#"{"kind": "Ordinal", "value": 3}"#).as_number(),
+            Some(3.0)
+        );
+        let percentage = value(r#"{"kind": "Percentage", "value": 25.0}"#);
+        assert_eq!(percentage.as_number(), Some(25.0));
+        match percentage {
+            SlotValue::Percentage(percentage) => assert_eq!(percentage.fraction(), 0.25),
+            other => panic!("{other:?}"),
+        }
+    }
+
+    #[test]
+    fn amount_of_money() {
+        let money = match value(
+            r#"{"kind": "AmountOfMoney", "value": 10.05, "precision": "Approximate", "unit": "€"}"#,
+        ) {
+            SlotValue::AmountOfMoney(money) => money,
+            other => panic!("{other:?}"),
+        };
+        assert_eq!(money.value, 10.05);
+        assert_eq!(money.precision, Precision::Approximate);
+        assert_eq!(money.currency().as_deref(), Some("EUR"));
+    }
+
+    #[test]
+    fn temperature() {
+        let temperature =
+            match value(r#"{"kind": "Temperature", "value": 212.0, "unit": "fahrenheit"}"#) {
+                SlotValue::Temperature(temperature) => temperature,
+                other => panic!("{other:?}"),
+            };
+        assert_eq!(temperature.unit, Some(TemperatureUnit::Fahrenheit));
+        assert_eq!(temperature.celsius(TemperatureUnit::Celsius), 100.0);
+        let temperature = match value(r#"{"kind": "Temperature", "value": 20.0, "unit": null}"#) {
+            SlotValue::Temperature(temperature) => temperature,
+            other => panic!("{other:?}"),
+        };
+        assert_eq!(temperature.celsius(TemperatureUnit::Celsius), 20.0);
+    }
+
+    #[test]
+    fn text() {
+        assert_eq!(
+            value(r#"{"kind": "Custom", "value": "kitchen"}"#).as_text(),
+            Some("kitchen")
+        );
+        for kind in [
+            "MusicAlbum",
+            "MusicArtist",
+            "MusicTrack",
+            "City",
+            "Country",
+            "Region",
+        ] {
+            let json = format!(r#"{{"kind": "{kind}", "value": "Paris"}}"#);
+            assert_eq!(value(&json).as_text(), Some("Paris"), "{kind}");
+        }
+    }
+
+    #[test]
+    fn unknown_kind() {
+        assert_eq!(
+            value(r#"{"kind": "Hologram", "value": "anything", "depth": 3}"#),
+            SlotValue::Unknown
+        );
+    }
+
+    #[test]
+    fn unknown_kind_keeps_the_parse() {
+        let parsed: crate::intents::ParsedIntent = serde_json::from_str(
+            r#"{
+                "input": "play it on the hologram",
+                "intent": {"intentName": "custom_play", "confidenceScore": 0.9},
+                "slots": [{
+                    "rawValue": "hologram",
+                    "value": {"kind": "Hologram", "value": "hologram"},
+                    "alternatives": [],
+                    "range": {"start": 15, "end": 23},
+                    "entity": "snips/hologram",
+                    "slotName": "device"
+                }],
+                "alternatives": []
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(parsed.slots[0].value, SlotValue::Unknown);
+        assert_eq!(parsed.slots[0].slot_name, "device");
+    }
+}
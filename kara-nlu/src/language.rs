@@ -0,0 +1,74 @@
+use serde::Deserialize;
+
+/// Languages Kara can understand
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    #[default]
+    English,
+    French,
+    German,
+    Spanish,
+}
+
+impl Language {
+    pub const ALL: [Language; 4] = [
+        Language::English,
+        Language::French,
+        Language::German,
+        Language::Spanish,
+    ];
+
+    /// ISO 639-1 language code
+    pub fn code(&self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::French => "fr",
+            Language::German => "de",
+            Language::Spanish => "es",
+        }
+    }
+
+    /// The language's name for itself
+    pub fn name(&self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::French => "Français",
+            Language::German => "Deutsch",
+            Language::Spanish => "Español",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        let code = code.trim().to_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|language| language.code() == code)
+    }
+
+    /// Reads the language from the system locale, e.g. `LANG=fr_FR.UTF-8`
+    pub fn from_locale() -> Option<Self> {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|locale| !locale.is_empty())
+            .and_then(|locale| {
+                let code = locale.split(|c| c == '_' || c == '.' || c == '-').next()?;
+                Self::from_code(code)
+            })
+    }
+
+    /// Matches a spoken language name in any of the supported languages, e.g. "french",
+    /// "français" or "französisch"
+    pub fn from_name(name: &str) -> Option<Self> {
+        let language = match name.trim().to_lowercase().as_str() {
+            "english" | "anglais" | "englisch" | "inglés" | "ingles" => Language::English,
+            "french" | "français" | "francais" | "französisch" | "franzosisch" | "francés"
+            | "frances" => Language::French,
+            "german" | "allemand" | "deutsch" | "alemán" | "aleman" => Language::German,
+            "spanish" | "espagnol" | "spanisch" | "español" | "espanol" => Language::Spanish,
+            _ => return None,
+        };
+        Some(language)
+    }
+}
@@ -1,24 +1,183 @@
+pub mod context;
+pub mod engine;
+pub mod evaluation;
+pub mod injection;
 pub mod intents;
-use snips_nlu_lib::SnipsNluEngine;
+mod language;
+pub mod normaliser;
+mod options;
+pub mod punctuation;
+pub mod rules;
+pub mod segmentation;
+use std::{fmt::Display, path::Path};
 
-use crate::intents::ParsedIntent;
+use crate::{
+    context::ConversationContext,
+    engine::NluEngine,
+    intents::{Intent, IntentMapper, ParsedIntent},
+    rules::RuleSet,
+    segmentation::Segmenter,
+};
+pub use language::Language;
+pub use options::ParseOptions;
+
+#[derive(Debug)]
+pub enum NluError {
+    /// The model could not be loaded
+    Load(String),
+    /// The engine could not parse the input
+    Engine(String),
+    /// The engine returned a result that does not match [`ParsedIntent`]
+    Decode(serde_json::Error),
+    /// Entity values could not be injected into the model
+    Injection(String),
+    /// A labelled dataset could not be read
+    Dataset(String),
+}
+
+impl Display for NluError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NluError::Load(e) => write!(f, "failed to load language model: {e}"),
+            NluError::Engine(e) => write!(f, "failed to parse input: {e}"),
+            NluError::Decode(e) => write!(f, "failed to read parse result: {e}"),
+            NluError::Injection(e) => write!(f, "failed to inject entity values: {e}"),
+            NluError::Dataset(e) => write!(f, "failed to read dataset: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for NluError {}
+
+impl From<serde_json::Error> for NluError {
+    fn from(e: serde_json::Error) -> Self {
+        NluError::Decode(e)
+    }
+}
 
 pub struct NLUParser {
-    model: SnipsNluEngine,
+    engine: Box<dyn NluEngine>,
+    options: ParseOptions,
+    rules: RuleSet,
 }
 
 impl NLUParser {
-    pub fn new(model_path: impl AsRef<str>) -> Self {
+    /// Loads the model in `model_path` with the engine it was trained for
+    pub fn new(model_path: impl AsRef<Path>) -> Result<Self, NluError> {
+        Ok(Self::from_engine(engine::load(model_path)?))
+    }
+
+    pub fn from_engine(engine: Box<dyn NluEngine>) -> Self {
         Self {
-            model: SnipsNluEngine::from_path(model_path.as_ref()).unwrap(),
+            engine,
+            options: ParseOptions::default(),
+            rules: RuleSet::default(),
         }
     }
 
-    pub fn parse_text(&self, text: impl AsRef<str>) {
-        let result = &self.model.parse(text.as_ref(), None, None).unwrap();
-        let result_json = serde_json::to_string_pretty(&result).unwrap();
-        let result: ParsedIntent = serde_json::from_str(&result_json).unwrap();
-        println!("{}", result_json);
-        println!("{:#?}", result.intent)
+    /// Rules that are tried before the trained model
+    pub fn with_rules(mut self, rules: RuleSet) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// The intents the loaded model was trained on
+    pub fn intents(&self) -> &[Intent] {
+        self.engine.intents()
+    }
+
+    /// Sets the options used by [`NLUParser::parse_text`]
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn parse_text(&self, text: impl AsRef<str>) -> Result<ParsedIntent, NluError> {
+        self.parse_text_with(text, &self.options)
+    }
+
+    /// Like [`NLUParser::parse_commands`], but follow ups are read in the light of the
+    /// previous turn. A command that is not understood on its own, such as "what about
+    /// tomorrow?", is parsed again as the previous intent, and slots that are missing are
+    /// carried over from the previous turn
+    pub fn parse_in_context(
+        &self,
+        text: impl AsRef<str>,
+        context: &ConversationContext,
+    ) -> Result<Vec<ParsedIntent>, NluError> {
+        let mut commands = self.parse_commands(text)?;
+        let previous = match context.previous() {
+            Some(previous) => previous,
+            None => return Ok(commands),
+        };
+        for parsed in &mut commands {
+            if parsed.intent.intent_name.is_none() {
+                let options = self
+                    .options
+                    .clone()
+                    .with_whitelist([previous.intent.name()]);
+                let follow_up = self.parse_text_with(&parsed.input, &options)?;
+                // Without a slot of its own there is nothing to follow up with
+                if follow_up.intent.intent_name.is_some() && !follow_up.slots.is_empty() {
+                    *parsed = follow_up;
+                }
+            }
+            context.carry_over(parsed);
+        }
+        Ok(commands)
+    }
+
+    /// Parses `text` as one or more commands. Compound commands such as "turn off the
+    /// lights and play some jazz" are split on conjunctions and each part is parsed on its
+    /// own, in the order they were given
+    pub fn parse_commands(&self, text: impl AsRef<str>) -> Result<Vec<ParsedIntent>, NluError> {
+        let text = text.as_ref();
+        let whole = self.parse_text(text)?;
+        let language = match self.engine.language() {
+            Some(language) => language,
+            None => return Ok(vec![whole]),
+        };
+        let parts = Segmenter::new(language).split(text, &whole.slots);
+        if parts.len() < 2 {
+            return Ok(vec![whole]);
+        }
+        let parsed = parts
+            .into_iter()
+            .map(|part| self.parse_text(part))
+            .collect::<Result<Vec<_>, _>>()?;
+        // "salt and pepper" is one command, only split if every part is understood
+        if parsed.iter().all(|part| part.intent.intent_name.is_some()) {
+            Ok(parsed)
+        } else {
+            Ok(vec![whole])
+        }
+    }
+
+    pub fn parse_text_with(
+        &self,
+        text: impl AsRef<str>,
+        options: &ParseOptions,
+    ) -> Result<ParsedIntent, NluError> {
+        if let Some(parsed) = self.rules.parse(text.as_ref()) {
+            let intent = parsed.intent.intent_name.as_ref().map(Intent::name);
+            if options.allows(intent) && options.accepts(intent, 1.0) {
+                return Ok(parsed);
+            }
+        }
+        let mut result = self.engine.parse(text.as_ref(), options)?;
+        let accepts = |intent: &IntentMapper| {
+            options.accepts(
+                intent.intent_name.as_ref().map(Intent::name),
+                intent.confidence_score as f32,
+            )
+        };
+        if !accepts(&result.intent) {
+            result.intent.intent_name = None;
+            result.slots.clear();
+        }
+        result
+            .alternatives
+            .retain(|alternative| accepts(&alternative.intent));
+        Ok(result)
     }
 }
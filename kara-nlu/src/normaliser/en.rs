@@ -0,0 +1,716 @@
+use super::Grammar;
+
+pub struct English;
+
+type Rule = fn(&[&str], usize) -> Option<(String, usize)>;
+
+impl Grammar for English {
+    fn normalise(&self, tokens: &[&str]) -> Vec<String> {
+        // Order matters, the more specific rules need to see a number before it is rewritten
+        const RULES: [Rule; 6] = [time, date, money, measurement, ordinal, number];
+        let mut output = Vec::with_capacity(tokens.len());
+        let mut i = 0;
+        while i < tokens.len() {
+            match RULES.iter().find_map(|rule| rule(tokens, i)) {
+                Some((text, consumed)) => {
+                    output.push(text);
+                    i += consumed;
+                }
+                None => {
+                    output.push(tokens[i].to_owned());
+                    i += 1;
+                }
+            }
+        }
+        output
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Last {
+    Nothing,
+    Unit,
+    Teen,
+    Tens,
+    Hundred,
+    Scale,
+    And,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Number {
+    integer: u64,
+    fraction: Option<String>,
+    negative: bool,
+    /// Whether "hundred", "thousand" etc. were spoken
+    scaled: bool,
+    /// Number of tokens the number spans
+    len: usize,
+}
+
+impl Number {
+    fn is_whole(&self) -> bool {
+        self.fraction.is_none() && !self.negative
+    }
+
+    fn written(&self) -> String {
+        let sign = if self.negative { "-" } else { "" };
+        match &self.fraction {
+            Some(fraction) => format!("{sign}{}.{fraction}", self.integer),
+            None => format!("{sign}{}", self.integer),
+        }
+    }
+}
+
+fn unit(word: &str) -> Option<u64> {
+    let value = match word {
+        "zero" => 0,
+        "one" => 1,
+        "two" => 2,
+        "three" => 3,
+        "four" => 4,
+        "five" => 5,
+        "six" => 6,
+        "seven" => 7,
+        "eight" => 8,
+        "nine" => 9,
+        "ten" => 10,
+        "eleven" => 11,
+        "twelve" => 12,
+        "thirteen" => 13,
+        "fourteen" => 14,
+        "fifteen" => 15,
+        "sixteen" => 16,
+        "seventeen" => 17,
+        "eighteen" => 18,
+        "nineteen" => 19,
+        _ => return None,
+    };
+    Some(value)
+}
+
+fn tens(word: &str) -> Option<u64> {
+    let value = match word {
+        "twenty" => 20,
+        "thirty" => 30,
+        "forty" => 40,
+        "fifty" => 50,
+        "sixty" => 60,
+        "seventy" => 70,
+        "eighty" => 80,
+        "ninety" => 90,
+        _ => return None,
+    };
+    Some(value)
+}
+
+fn scale(word: &str) -> Option<u64> {
+    let value = match word {
+        "thousand" => 1_000,
+        "million" => 1_000_000,
+        "billion" => 1_000_000_000,
+        "trillion" => 1_000_000_000_000,
+        _ => return None,
+    };
+    Some(value)
+}
+
+fn ordinal_word(word: &str) -> Option<u64> {
+    let value = match word {
+        "first" => 1,
+        "second" => 2,
+        "third" => 3,
+        "fourth" => 4,
+        "fifth" => 5,
+        "sixth" => 6,
+        "seventh" => 7,
+        "eighth" => 8,
+        "ninth" => 9,
+        "tenth" => 10,
+        "eleventh" => 11,
+        "twelfth" => 12,
+        "thirteenth" => 13,
+        "fourteenth" => 14,
+        "fifteenth" => 15,
+        "sixteenth" => 16,
+        "seventeenth" => 17,
+        "eighteenth" => 18,
+        "nineteenth" => 19,
+        "twentieth" => 20,
+        "thirtieth" => 30,
+        "fortieth" => 40,
+        "fiftieth" => 50,
+        "sixtieth" => 60,
+        "seventieth" => 70,
+        "eightieth" => 80,
+        "ninetieth" => 90,
+        "hundredth" => 100,
+        "thousandth" => 1_000,
+        "millionth" => 1_000_000,
+        _ => return None,
+    };
+    Some(value)
+}
+
+fn digit(word: &str) -> Option<u64> {
+    match word {
+        "oh" => Some(0),
+        word => unit(word).filter(|value| *value < 10),
+    }
+}
+
+fn is_number_word(word: &str) -> bool {
+    unit(word).is_some() || tens(word).is_some() || scale(word).is_some() || word == "hundred"
+}
+
+fn month(word: &str) -> Option<&'static str> {
+    let month = match word {
+        "january" => "january",
+        "february" => "february",
+        "march" => "march",
+        "april" => "april",
+        "may" => "may",
+        "june" => "june",
+        "july" => "july",
+        "august" => "august",
+        "september" => "september",
+        "october" => "october",
+        "november" => "november",
+        "december" => "december",
+        _ => return None,
+    };
+    Some(month)
+}
+
+fn ordinal_suffix(value: u64) -> &'static str {
+    match (value % 10, value % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    }
+}
+
+/// Parses a spoken cardinal number such as "two hundred and forty point five"
+fn cardinal(tokens: &[&str], start: usize) -> Option<Number> {
+    let mut i = start;
+    let follows_number = start > 0 && is_number_word(tokens[start - 1]);
+    let negative = matches!(tokens.get(i), Some(&"minus") | Some(&"negative"))
+        && !follows_number
+        && matches!(tokens.get(i + 1), Some(word) if is_number_word(word));
+    if negative {
+        i += 1;
+    }
+    let mut total = 0;
+    let mut current = 0;
+    let mut last = Last::Nothing;
+    let mut scaled = false;
+    while let Some(word) = tokens.get(i) {
+        let next = tokens.get(i + 1).copied().unwrap_or_default();
+        if *word == "a" && last == Last::Nothing && (next == "hundred" || scale(next).is_some()) {
+            current = 1;
+            last = Last::Unit;
+        } else if let Some(value) = unit(word) {
+            match last {
+                Last::Unit | Last::Teen => break,
+                Last::Tens if !(1..10).contains(&value) => break,
+                _ => {}
+            }
+            current += value;
+            last = if value < 10 { Last::Unit } else { Last::Teen };
+        } else if let Some(value) = tens(word) {
+            if !matches!(
+                last,
+                Last::Nothing | Last::Hundred | Last::Scale | Last::And
+            ) {
+                break;
+            }
+            current += value;
+            last = Last::Tens;
+        } else if *word == "hundred" {
+            if current == 0 || current >= 100 || last == Last::Hundred {
+                break;
+            }
+            current *= 100;
+            scaled = true;
+            last = Last::Hundred;
+        } else if let Some(value) = scale(word) {
+            if current == 0 || last == Last::Scale {
+                break;
+            }
+            total += current * value;
+            current = 0;
+            scaled = true;
+            last = Last::Scale;
+        } else if *word == "and"
+            && matches!(last, Last::Hundred | Last::Scale)
+            && (unit(next).is_some() || tens(next).is_some())
+        {
+            last = Last::And;
+        } else {
+            break;
+        }
+        i += 1;
+    }
+    if last == Last::Nothing {
+        return None;
+    }
+    let integer = total + current;
+
+    let mut fraction = None;
+    if tokens.get(i) == Some(&"point") {
+        let digits: String = tokens[i + 1..]
+            .iter()
+            .map_while(|word| digit(word))
+            .map(|value| value.to_string())
+            .collect();
+        if !digits.is_empty() {
+            i += 1 + digits.len();
+            fraction = Some(digits);
+        }
+    }
+
+    Some(Number {
+        integer,
+        fraction,
+        negative,
+        scaled,
+        len: i - start,
+    })
+}
+
+/// A cardinal number that may be a spoken year such as "nineteen eighty four" or
+/// "twenty oh five"
+fn cardinal_or_year(tokens: &[&str], start: usize) -> Option<Number> {
+    let mut number = cardinal(tokens, start)?;
+    if !number.is_whole() || number.scaled || !(15..=20).contains(&number.integer) {
+        return Some(number);
+    }
+    let next = start + number.len;
+    if tokens.get(next) == Some(&"oh") {
+        if let Some(value) = tokens.get(next + 1).and_then(|word| digit(word)) {
+            if value > 0 {
+                number.integer = number.integer * 100 + value;
+                number.len += 2;
+            }
+        }
+    } else if let Some(second) = cardinal(tokens, next) {
+        if second.is_whole() && !second.scaled && (10..100).contains(&second.integer) {
+            number.integer = number.integer * 100 + second.integer;
+            number.len += second.len;
+        }
+    }
+    Some(number)
+}
+
+fn number(tokens: &[&str], i: usize) -> Option<(String, usize)> {
+    let number = cardinal_or_year(tokens, i)?;
+    // "the one with the red cover" is not a number
+    let determiner = i > 0
+        && matches!(
+            tokens[i - 1],
+            "the" | "this" | "that" | "which" | "any" | "every" | "each" | "no"
+        );
+    if number.len == 1 && tokens[i] == "one" && determiner {
+        return None;
+    }
+    Some((number.written(), number.len))
+}
+
+/// "twenty third", "one hundred and first", and "the second" but not "wait a second"
+fn ordinal_value(tokens: &[&str], i: usize) -> Option<(u64, usize)> {
+    if let Some(value) = tokens.get(i).and_then(|word| ordinal_word(word)) {
+        return Some((value, 1));
+    }
+    let prefix = cardinal(tokens, i).filter(Number::is_whole)?;
+    let mut j = i + prefix.len;
+    if tokens.get(j) == Some(&"and") {
+        j += 1;
+    }
+    let value = tokens.get(j).and_then(|word| ordinal_word(word))?;
+    let combined = match value {
+        1..=99 if prefix.integer % 100 == 0 || (prefix.integer % 10 == 0 && value < 10) => {
+            prefix.integer + value
+        }
+        100.. => prefix.integer * value,
+        _ => return None,
+    };
+    Some((combined, j + 1 - i))
+}
+
+fn ordinal(tokens: &[&str], i: usize) -> Option<(String, usize)> {
+    let (value, len) = ordinal_value(tokens, i)?;
+    if len == 1 {
+        let after_the = i > 0 && tokens[i - 1] == "the";
+        let before_month = tokens.get(i + 1) == Some(&"of")
+            && tokens.get(i + 2).and_then(|word| month(word)).is_some();
+        if !after_the && !before_month {
+            return None;
+        }
+    }
+    Some((format!("{value}{}", ordinal_suffix(value)), len))
+}
+
+/// "march third twenty twenty two", "march the third" or "may nineteen eighty four"
+fn date(tokens: &[&str], i: usize) -> Option<(String, usize)> {
+    let month = month(tokens[i])?;
+    let mut j = i + 1;
+    if let Some(year) = cardinal_or_year(tokens, j).filter(|year| year.integer >= 1000) {
+        return Some((format!("{month} {}", year.written()), 1 + year.len));
+    }
+    if tokens.get(j) == Some(&"the") {
+        j += 1;
+    }
+    let day = match ordinal_value(tokens, j) {
+        Some((value, len)) => {
+            j += len;
+            format!("{value}{}", ordinal_suffix(value))
+        }
+        None => {
+            let number = cardinal(tokens, j)?;
+            j += number.len;
+            number.written()
+        }
+    };
+    match day.trim_end_matches(char::is_alphabetic).parse::<u64>() {
+        Ok(1..=31) => {}
+        _ => return None,
+    }
+    let mut written = format!("{month} {day}");
+    if let Some(year) = cardinal_or_year(tokens, j).filter(|year| year.integer >= 1000) {
+        written.push_str(&format!(" {}", year.written()));
+        j += year.len;
+    }
+    Some((written, j - i))
+}
+
+fn meridiem(tokens: &[&str], i: usize) -> Option<(&'static str, usize)> {
+    match (tokens.get(i).copied(), tokens.get(i + 1).copied()) {
+        (Some("a"), Some("m")) => Some(("am", 2)),
+        (Some("p"), Some("m")) => Some(("pm", 2)),
+        (Some("am" | "a.m." | "a.m"), _) => Some(("am", 1)),
+        (Some("pm" | "p.m." | "p.m"), _) => Some(("pm", 1)),
+        _ => None,
+    }
+}
+
+fn hour(tokens: &[&str], i: usize) -> Option<(u64, usize)> {
+    cardinal(tokens, i)
+        .filter(|number| number.is_whole() && !number.scaled && (1..=12).contains(&number.integer))
+        .map(|number| (number.integer, number.len))
+}
+
+fn format_time(hour: u64, minutes: Option<u64>, tokens: &[&str], end: usize) -> (String, usize) {
+    let mut written = match minutes {
+        Some(minutes) => format!("{hour}:{minutes:02}"),
+        None => hour.to_string(),
+    };
+    let mut end = end;
+    if let Some((meridiem, len)) = meridiem(tokens, end) {
+        written.push_str(&format!(" {meridiem}"));
+        end += len;
+    }
+    (written, end)
+}
+
+/// "seven thirty a m", "half past six", "ten o'clock" and "at eleven forty five"
+fn time(tokens: &[&str], i: usize) -> Option<(String, usize)> {
+    if let Some((hour, minutes, end)) = relative_time(tokens, i) {
+        let (written, end) = format_time(hour, Some(minutes), tokens, end);
+        return Some((written, end - i));
+    }
+
+    let (hour, len) = hour(tokens, i)?;
+    let mut j = i + len;
+    let mut minutes = None;
+    let mut explicit = false;
+    if tokens.get(j) == Some(&"o'clock") {
+        minutes = Some(0);
+        explicit = true;
+        j += 1;
+    } else if tokens.get(j) == Some(&"oh") {
+        if let Some(value) = tokens.get(j + 1).and_then(|word| digit(word)) {
+            minutes = Some(value);
+            j += 2;
+        }
+    } else if let Some(number) = cardinal(tokens, j)
+        .filter(|number| number.is_whole() && !number.scaled && (10..60).contains(&number.integer))
+    {
+        minutes = Some(number.integer);
+        j += number.len;
+    }
+
+    let after_preposition = i > 0
+        && matches!(
+            tokens[i - 1],
+            "at" | "for" | "until" | "till" | "by" | "from" | "around"
+        );
+    let has_meridiem = meridiem(tokens, j).is_some();
+    if !(explicit || has_meridiem || (after_preposition && minutes.is_some())) {
+        return None;
+    }
+    let (written, end) = format_time(hour, minutes, tokens, j);
+    Some((written, end - i))
+}
+
+/// "quarter past seven", "ten to six". "ten to six" is only a time where a time is
+/// expected, as "from ten to six" and "count from five to ten" are ranges
+fn relative_time(tokens: &[&str], i: usize) -> Option<(u64, u64, usize)> {
+    let previous = i.checked_sub(1).map(|previous| tokens[previous]);
+    let (minutes, mut j) = match tokens.get(i).copied() {
+        Some("half") => (30, i + 1),
+        Some("quarter") => (15, i + 1),
+        _ => {
+            let number = cardinal(tokens, i)
+                .filter(|number| number.is_whole() && (1..=30).contains(&number.integer))?;
+            let mut j = i + number.len;
+            if matches!(tokens.get(j), Some(&"minutes") | Some(&"minute")) {
+                j += 1;
+            } else if number.integer % 5 != 0 {
+                // "count from one to ten" is not a time
+                return None;
+            }
+            (number.integer, j)
+        }
+    };
+    let past = match tokens.get(j).copied() {
+        Some("past") => true,
+        Some("to") if minutes != 30 => false,
+        _ => return None,
+    };
+    j += 1;
+    let (hour, len) = hour(tokens, j)?;
+    j += len;
+    let time_context = matches!(
+        previous,
+        Some("at" | "for" | "until" | "till" | "by" | "around" | "about" | "before" | "after")
+            | Some("is" | "it's" | "its")
+    ) || meridiem(tokens, j).is_some();
+    if !past && (matches!(previous, Some("from" | "between")) || !time_context) {
+        return None;
+    }
+    if past {
+        Some((hour, minutes, j))
+    } else {
+        let hour = if hour == 1 { 12 } else { hour - 1 };
+        Some((hour, 60 - minutes, j))
+    }
+}
+
+fn currency(tokens: &[&str], i: usize) -> Option<(&'static str, usize)> {
+    let symbol = match (tokens.get(i).copied()?, tokens.get(i + 1).copied()) {
+        ("pounds" | "pound", Some("sterling")) => return Some(("£", 2)),
+        ("dollars" | "dollar" | "bucks" | "buck", _) => "$",
+        ("euros" | "euro", _) => "€",
+        ("quid", _) => "£",
+        ("yen", _) => "¥",
+        ("rupees" | "rupee", _) => "₹",
+        _ => return None,
+    };
+    Some((symbol, 1))
+}
+
+/// "five dollars and fifty cents"
+fn money(tokens: &[&str], i: usize) -> Option<(String, usize)> {
+    let amount = cardinal(tokens, i)?;
+    let mut j = i + amount.len;
+    let (symbol, len) = currency(tokens, j)?;
+    j += len;
+    let mut written = format!("{symbol}{}", amount.written());
+    let and = usize::from(tokens.get(j) == Some(&"and"));
+    if let Some(cents) = cardinal(tokens, j + and)
+        .filter(|cents| cents.is_whole() && cents.integer < 100 && amount.fraction.is_none())
+    {
+        let unit = j + and + cents.len;
+        if matches!(
+            tokens.get(unit),
+            Some(&"cents") | Some(&"cent") | Some(&"pence") | Some(&"p")
+        ) {
+            written.push_str(&format!(".{:02}", cents.integer));
+            j = unit + 1;
+        }
+    }
+    Some((written, j - i))
+}
+
+const UNITS: &[(&[&str], &str)] = &[
+    (&["degrees", "celsius"], "°C"),
+    (&["degrees", "centigrade"], "°C"),
+    (&["degrees", "fahrenheit"], "°F"),
+    (&["degree", "celsius"], "°C"),
+    (&["degree", "fahrenheit"], "°F"),
+    (&["kilometers", "per", "hour"], "km/h"),
+    (&["kilometres", "per", "hour"], "km/h"),
+    (&["miles", "per", "hour"], "mph"),
+    (&["per", "cent"], "%"),
+    (&["percent"], "%"),
+    (&["degrees"], "°"),
+    (&["degree"], "°"),
+    (&["kilometers"], "km"),
+    (&["kilometres"], "km"),
+    (&["kilometer"], "km"),
+    (&["kilometre"], "km"),
+    (&["centimeters"], "cm"),
+    (&["centimetres"], "cm"),
+    (&["centimeter"], "cm"),
+    (&["centimetre"], "cm"),
+    (&["millimeters"], "mm"),
+    (&["millimetres"], "mm"),
+    (&["millimeter"], "mm"),
+    (&["millimetre"], "mm"),
+    (&["meters"], "m"),
+    (&["metres"], "m"),
+    (&["meter"], "m"),
+    (&["metre"], "m"),
+    (&["miles"], "mi"),
+    (&["mile"], "mi"),
+    (&["feet"], "ft"),
+    (&["foot"], "ft"),
+    (&["inches"], "in"),
+    (&["inch"], "in"),
+    (&["yards"], "yd"),
+    (&["yard"], "yd"),
+    (&["kilograms"], "kg"),
+    (&["kilogram"], "kg"),
+    (&["kilos"], "kg"),
+    (&["kilo"], "kg"),
+    (&["milligrams"], "mg"),
+    (&["milligram"], "mg"),
+    (&["grams"], "g"),
+    (&["gram"], "g"),
+    // without "sterling", pounds are a weight
+    (&["pounds"], "lb"),
+    (&["pound"], "lb"),
+    (&["ounces"], "oz"),
+    (&["ounce"], "oz"),
+    (&["milliliters"], "ml"),
+    (&["millilitres"], "ml"),
+    (&["milliliter"], "ml"),
+    (&["millilitre"], "ml"),
+    (&["liters"], "l"),
+    (&["litres"], "l"),
+    (&["liter"], "l"),
+    (&["litre"], "l"),
+    (&["gallons"], "gal"),
+    (&["gallon"], "gal"),
+];
+
+/// "twenty degrees celsius", "fifteen percent", "five kilometers"
+fn measurement(tokens: &[&str], i: usize) -> Option<(String, usize)> {
+    let amount = cardinal(tokens, i)?;
+    let j = i + amount.len;
+    let rest = &tokens[j..];
+    let (words, symbol) = UNITS.iter().find(|(words, _)| rest.starts_with(words))?;
+    let written = match *symbol {
+        "%" | "°" => format!("{}{symbol}", amount.written()),
+        _ => format!("{} {symbol}", amount.written()),
+    };
+    Some((written, j + words.len() - i))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalise(text: &str) -> String {
+        let tokens: Vec<_> = text.split_whitespace().collect();
+        English.normalise(&tokens).join(" ")
+    }
+
+    #[test]
+    fn cardinals() {
+        assert_eq!(
+            normalise("what is twenty five times four"),
+            "what is 25 times 4"
+        );
+        assert_eq!(normalise("two hundred and forty"), "240");
+        assert_eq!(
+            normalise("one thousand two hundred and thirty four"),
+            "1234"
+        );
+        assert_eq!(normalise("a hundred people"), "100 people");
+        assert_eq!(normalise("one million"), "1000000");
+        assert_eq!(normalise("one two three"), "1 2 3");
+    }
+
+    #[test]
+    fn decimals_and_signs() {
+        assert_eq!(normalise("three point one four"), "3.14");
+        assert_eq!(normalise("minus five plus two"), "-5 plus 2");
+        // Between two numbers "minus" is subtraction
+        assert_eq!(normalise("ten minus three"), "10 minus 3");
+    }
+
+    #[test]
+    fn years() {
+        assert_eq!(normalise("twenty oh five"), "2005");
+        assert_eq!(normalise("may nineteen eighty four"), "may 1984");
+    }
+
+    #[test]
+    fn ordinals() {
+        assert_eq!(normalise("the twenty third of march"), "the 23rd of march");
+        assert_eq!(normalise("wait a second"), "wait a second");
+        assert_eq!(
+            normalise("the one with the red cover"),
+            "the one with the red cover"
+        );
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(normalise("march third twenty twenty two"), "march 3rd 2022");
+    }
+
+    #[test]
+    fn clock_times() {
+        assert_eq!(
+            normalise("set an alarm for seven thirty a m"),
+            "set an alarm for 7:30 am"
+        );
+        assert_eq!(
+            normalise("wake me up at six forty five"),
+            "wake me up at 6:45"
+        );
+        assert_eq!(normalise("meet at ten o'clock"), "meet at 10:00");
+        assert_eq!(
+            normalise("set an alarm for seven oh five pm"),
+            "set an alarm for 7:05 pm"
+        );
+    }
+
+    #[test]
+    fn relative_times() {
+        assert_eq!(normalise("quarter past seven"), "7:15");
+        assert_eq!(normalise("half past six"), "6:30");
+        assert_eq!(normalise("it's ten to six"), "it's 5:50");
+        assert_eq!(normalise("wake me at ten to six"), "wake me at 5:50");
+        assert_eq!(normalise("at twenty to eight"), "at 7:40");
+    }
+
+    #[test]
+    fn ranges_are_not_times() {
+        assert_eq!(normalise("from ten to five"), "from 10 to 5");
+        assert_eq!(normalise("between ten to five"), "between 10 to 5");
+        assert_eq!(normalise("count from five to ten"), "count from 5 to 10");
+        assert_eq!(normalise("count from one to ten"), "count from 1 to 10");
+        assert_eq!(normalise("ten to six"), "10 to 6");
+    }
+
+    #[test]
+    fn money() {
+        assert_eq!(normalise("five dollars and fifty cents"), "$5.50");
+        assert_eq!(normalise("twenty pounds sterling"), "£20");
+    }
+
+    #[test]
+    fn measurements() {
+        assert_eq!(normalise("twenty degrees celsius"), "20 °C");
+        assert_eq!(
+            normalise("fifteen percent of two hundred and forty"),
+            "15% of 240"
+        );
+        assert_eq!(normalise("five kilometers"), "5 km");
+    }
+}
@@ -0,0 +1,59 @@
+//! Inverse text normalisation.
+//!
+//! Speech to text engines spell everything out: "set an alarm for seven thirty a m". The
+//! builtin entity parsers and skills such as `qa_maths` cope much better with the written
+//! form: "set an alarm for 7:30 am". Each language has a rule based [`Grammar`] that
+//! rewrites numbers, ordinals, times, dates, currencies and units.
+//!
+//! Only English has a grammar so far. French, German and Spanish transcripts are passed
+//! on as they are, so their numbers stay spelled out and skills that need them, such as
+//! `qa_maths`, read them themselves.
+mod en;
+
+use crate::Language;
+
+/// A transcript before and after normalisation
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct NormalisedText {
+    /// The transcript as it was produced by the speech to text engine
+    pub raw: String,
+    /// The transcript with spoken forms rewritten in their written form
+    pub normalised: String,
+}
+
+/// Rewrites the spoken form of a lowercase, whitespace separated transcript
+pub trait Grammar: Send + Sync {
+    fn normalise(&self, tokens: &[&str]) -> Vec<String>;
+}
+
+/// Leaves transcripts as they are for languages that do not have a grammar yet: French,
+/// German and Spanish
+struct Verbatim;
+
+impl Grammar for Verbatim {
+    fn normalise(&self, tokens: &[&str]) -> Vec<String> {
+        tokens.iter().map(|token| token.to_string()).collect()
+    }
+}
+
+pub struct Normaliser {
+    grammar: Box<dyn Grammar>,
+}
+
+impl Normaliser {
+    pub fn new(language: Language) -> Self {
+        let grammar: Box<dyn Grammar> = match language {
+            Language::English => Box::new(en::English),
+            Language::French | Language::German | Language::Spanish => Box::new(Verbatim),
+        };
+        Self { grammar }
+    }
+
+    pub fn normalise(&self, text: impl AsRef<str>) -> NormalisedText {
+        let raw = text.as_ref().to_owned();
+        let lowercase = raw.to_lowercase();
+        let tokens: Vec<_> = lowercase.split_whitespace().collect();
+        let normalised = self.grammar.normalise(&tokens).join(" ");
+        NormalisedText { raw, normalised }
+    }
+}
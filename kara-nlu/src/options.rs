@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+
+/// Options for parsing a command. Intents are referred to by their names in the trained
+/// model, e.g. "alarm_set"
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    whitelist: Option<Vec<String>>,
+    blacklist: Option<Vec<String>>,
+    pub(crate) alternatives: usize,
+    pub(crate) slot_alternatives: usize,
+    threshold: f32,
+    intent_thresholds: HashMap<String, f32>,
+}
+
+impl ParseOptions {
+    /// Only consider these intents
+    pub fn with_whitelist(mut self, intents: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.whitelist = Some(intents.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Never consider these intents
+    pub fn with_blacklist(mut self, intents: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.blacklist = Some(intents.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Return up to `intents` alternative intents and `slots` alternative values per slot
+    pub fn with_alternatives(mut self, intents: usize, slots: usize) -> Self {
+        self.alternatives = intents;
+        self.slot_alternatives = slots;
+        self
+    }
+
+    /// Intents with a lower confidence score, in the range 0.0 <= val <= 1.0, are
+    /// treated as not understood
+    pub fn with_threshold(mut self, threshold: f32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Overrides the threshold for a single intent
+    pub fn with_intent_threshold(mut self, intent: impl Into<String>, threshold: f32) -> Self {
+        self.intent_thresholds.insert(intent.into(), threshold);
+        self
+    }
+
+    /// Whether `intent` was recognised with enough confidence to be acted on
+    pub fn accepts(&self, intent: Option<&str>, confidence: f32) -> bool {
+        match intent {
+            Some(intent) => {
+                confidence
+                    >= self
+                        .intent_thresholds
+                        .get(intent)
+                        .copied()
+                        .unwrap_or(self.threshold)
+            }
+            None => true,
+        }
+    }
+
+    /// Whether `intent` passes the whitelist and blacklist
+    pub(crate) fn allows(&self, intent: Option<&str>) -> bool {
+        let listed = |list: &Option<Vec<String>>| {
+            list.as_ref()
+                .map(|intents| intents.iter().any(|name| Some(name.as_str()) == intent))
+        };
+        listed(&self.whitelist).unwrap_or(true) && !listed(&self.blacklist).unwrap_or(false)
+    }
+
+    pub(crate) fn whitelist(&self) -> Option<Vec<&str>> {
+        self.whitelist
+            .as_ref()
+            .map(|intents| intents.iter().map(String::as_str).collect())
+    }
+
+    pub(crate) fn blacklist(&self) -> Option<Vec<&str>> {
+        self.blacklist
+            .as_ref()
+            .map(|intents| intents.iter().map(String::as_str).collect())
+    }
+}
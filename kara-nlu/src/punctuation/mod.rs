@@ -0,0 +1,344 @@
+//! Punctuation and truecasing restoration.
+//!
+//! Transcripts come out of the speech to text engine in lowercase without any
+//! punctuation. This is fine for intent classification but looks unfinished when shown to
+//! a user or written to disk. The [`Restorer`] uses a small n-gram model to insert commas
+//! and sentence breaks, end sentences with the right mark and restore the casing of
+//! known words.
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
+
+use serde::Deserialize;
+
+use crate::Language;
+
+/// Scores lower than this do not insert any punctuation
+const THRESHOLD: f32 = 1.0;
+
+/// Where a restored transcript is going
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Output {
+    Gui,
+    History,
+    /// Text dictated to Kara
+    Dictation,
+}
+
+/// The outputs that should have punctuation and casing restored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct Outputs {
+    pub gui: bool,
+    pub history: bool,
+    pub dictation: bool,
+}
+
+impl Default for Outputs {
+    fn default() -> Self {
+        Self {
+            gui: true,
+            history: true,
+            dictation: true,
+        }
+    }
+}
+
+impl Outputs {
+    pub fn enabled(&self, output: Output) -> bool {
+        match output {
+            Output::Gui => self.gui,
+            Output::History => self.history,
+            Output::Dictation => self.dictation,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ModelError {
+    pub line: usize,
+    pub reason: &'static str,
+}
+
+impl Display for ModelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "punctuation model line {}: {}", self.line, self.reason)
+    }
+}
+
+impl std::error::Error for ModelError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mark {
+    Comma,
+    Period,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Left {
+    Any,
+    Word(String),
+    /// The first word of a sentence
+    Initial(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Right {
+    Any,
+    Word(String),
+}
+
+pub struct Restorer {
+    casing: HashMap<String, String>,
+    questions: HashSet<String>,
+    gaps: HashMap<(Left, Right), Vec<(Mark, f32)>>,
+    outputs: Outputs,
+}
+
+impl Restorer {
+    /// Creates a restorer with the bundled model for `language`
+    pub fn new(language: Language) -> Self {
+        let model = match language {
+            Language::English => include_str!("../../../kara-assets/punctuation/en.tsv"),
+            Language::French => include_str!("../../../kara-assets/punctuation/fr.tsv"),
+            Language::German => include_str!("../../../kara-assets/punctuation/de.tsv"),
+            Language::Spanish => include_str!("../../../kara-assets/punctuation/es.tsv"),
+        };
+        Self::from_model(model).expect("bundled punctuation model is valid")
+    }
+
+    /// Parses a model in the format described in `kara-assets/punctuation/en.tsv`
+    pub fn from_model(model: &str) -> Result<Self, ModelError> {
+        let mut casing = HashMap::new();
+        let mut questions = HashSet::new();
+        let mut gaps: HashMap<_, Vec<_>> = HashMap::new();
+        for (index, line) in model.lines().enumerate() {
+            let error = |reason| ModelError {
+                line: index + 1,
+                reason,
+            };
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<_> = line.split('\t').collect();
+            match fields[..] {
+                ["case", word, cased, ..] => {
+                    casing.insert(word.to_lowercase(), cased.to_owned());
+                }
+                ["question", word, ..] => {
+                    questions.insert(word.to_lowercase());
+                }
+                ["gap", left, right, mark, weight] => {
+                    let left = match left {
+                        "*" => Left::Any,
+                        left => match left.strip_prefix('^') {
+                            Some(word) => Left::Initial(word.to_owned()),
+                            None => Left::Word(left.to_owned()),
+                        },
+                    };
+                    let right = match right {
+                        "*" => Right::Any,
+                        right => Right::Word(right.to_owned()),
+                    };
+                    let mark = match mark {
+                        "," => Mark::Comma,
+                        "." => Mark::Period,
+                        _ => return Err(error("unknown punctuation mark")),
+                    };
+                    let weight = weight.parse().map_err(|_| error("invalid weight"))?;
+                    gaps.entry((left, right)).or_default().push((mark, weight));
+                }
+                _ => return Err(error("unknown entry")),
+            }
+        }
+        Ok(Self {
+            casing,
+            questions,
+            gaps,
+            outputs: Outputs::default(),
+        })
+    }
+
+    pub fn with_outputs(mut self, outputs: Outputs) -> Self {
+        self.outputs = outputs;
+        self
+    }
+
+    /// Restores `text` if restoration is enabled for `output`
+    pub fn restore_for(&self, output: Output, text: &str) -> String {
+        if self.outputs.enabled(output) {
+            self.restore(text)
+        } else {
+            text.to_owned()
+        }
+    }
+
+    pub fn restore(&self, text: &str) -> String {
+        let words: Vec<_> = text.split_whitespace().collect();
+        let mut restored = String::with_capacity(text.len() + 8);
+        let mut sentence_start = 0;
+        for (i, word) in words.iter().enumerate() {
+            let initial = i == sentence_start;
+            if i > 0 {
+                restored.push(' ');
+            }
+            restored.push_str(&self.case(word, initial));
+            match words.get(i + 1) {
+                Some(next) => match self.gap(word, initial, next) {
+                    Some(Mark::Comma) => restored.push(','),
+                    Some(Mark::Period) => {
+                        restored.push(self.sentence_end(words[sentence_start]));
+                        sentence_start = i + 1;
+                    }
+                    None => {}
+                },
+                None => {
+                    if !word.ends_with(&['.', '?', '!'][..]) {
+                        restored.push(self.sentence_end(words[sentence_start]));
+                    }
+                }
+            }
+        }
+        restored
+    }
+
+    fn case(&self, word: &str, initial: bool) -> String {
+        let lowercase = word.to_lowercase();
+        match self.casing.get(&lowercase) {
+            Some(cased) => cased.to_owned(),
+            None if initial => {
+                let mut chars = word.chars();
+                match chars.next() {
+                    Some(first) => first.to_uppercase().chain(chars).collect(),
+                    None => String::new(),
+                }
+            }
+            None => word.to_owned(),
+        }
+    }
+
+    fn sentence_end(&self, first_word: &str) -> char {
+        if self.questions.contains(&first_word.to_lowercase()) {
+            '?'
+        } else {
+            '.'
+        }
+    }
+
+    fn gap(&self, left: &str, initial: bool, right: &str) -> Option<Mark> {
+        let left = left.to_lowercase();
+        let right = right.to_lowercase();
+        let mut lefts = vec![Left::Any, Left::Word(left.clone())];
+        if initial {
+            lefts.push(Left::Initial(left));
+        }
+        let rights = [Right::Any, Right::Word(right)];
+        let (mut comma, mut period) = (0.0, 0.0);
+        for left in lefts {
+            for right in &rights {
+                for (mark, weight) in self
+                    .gaps
+                    .get(&(left.clone(), right.clone()))
+                    .into_iter()
+                    .flatten()
+                {
+                    match mark {
+                        Mark::Comma => comma += weight,
+                        Mark::Period => period += weight,
+                    }
+                }
+            }
+        }
+        if period >= THRESHOLD && period >= comma {
+            Some(Mark::Period)
+        } else if comma >= THRESHOLD {
+            Some(Mark::Comma)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODEL: &str = "case\ti\tI\n\
+                         case\tparis\tParis\n\
+                         question\twhat\n\
+                         gap\t^yes\t*\t,\t1.0\n\
+                         gap\t^yes\tthanks\t,\t-0.5\n\
+                         gap\tnow\t*\t.\t1.0\n\
+                         gap\tnow\tplease\t.\t-1.0\n\
+                         gap\tthen\t*\t,\t0.6\n";
+
+    fn restorer() -> Restorer {
+        Restorer::from_model(MODEL).unwrap()
+    }
+
+    #[test]
+    fn restores_casing() {
+        assert_eq!(restorer().restore("i flew to paris"), "I flew to Paris.");
+        assert_eq!(restorer().restore("lights on"), "Lights on.");
+    }
+
+    #[test]
+    fn ends_sentences() {
+        assert_eq!(restorer().restore("what time is it"), "What time is it?");
+        assert_eq!(restorer().restore("it is late."), "It is late.");
+        assert_eq!(
+            restorer().restore("leave now what is the time"),
+            "Leave now. What is the time?"
+        );
+    }
+
+    #[test]
+    fn punctuates_at_the_threshold() {
+        assert_eq!(restorer().restore("yes i do"), "Yes, I do.");
+        // The more specific entry brings the score below the threshold
+        assert_eq!(restorer().restore("yes thanks"), "Yes thanks.");
+        assert_eq!(restorer().restore("now please go"), "Now please go.");
+        assert_eq!(restorer().restore("then we go"), "Then we go.");
+        // Only the first word of a sentence matches a '^' entry
+        assert_eq!(
+            restorer().restore("i said yes you know"),
+            "I said yes you know."
+        );
+    }
+
+    #[test]
+    fn restores_only_enabled_outputs() {
+        let restorer = restorer().with_outputs(Outputs {
+            gui: true,
+            history: false,
+            dictation: true,
+        });
+        assert_eq!(restorer.restore_for(Output::Gui, "i agree"), "I agree.");
+        assert_eq!(restorer.restore_for(Output::History, "i agree"), "i agree");
+        assert_eq!(
+            restorer.restore_for(Output::Dictation, "i agree"),
+            "I agree."
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_models() {
+        let error = Restorer::from_model("case\ti\tI\ngap\ta\tb\t;\t1.0")
+            .err()
+            .unwrap();
+        assert_eq!((error.line, error.reason), (2, "unknown punctuation mark"));
+        assert!(Restorer::from_model("gap\ta\tb\t,\theavy").is_err());
+        assert!(Restorer::from_model("nonsense").is_err());
+    }
+
+    #[test]
+    fn bundled_models_are_valid() {
+        for language in Language::ALL {
+            Restorer::new(language);
+        }
+        assert_eq!(
+            Restorer::new(Language::English).restore("yes i will"),
+            "Yes, I will."
+        );
+    }
+}
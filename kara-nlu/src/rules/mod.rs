@@ -0,0 +1,500 @@
+//! Deterministic commands that are matched before the trained model. Rules are read from
+//! YAML files, e.g.
+//!
+//! ```yaml
+//! language: en
+//! rules:
+//!   - intent: iot_hue_lightoff
+//!     patterns:
+//!       - "(turn|switch) off the lights"
+//!       - "lights off [in the {room}]"
+//!     slots:
+//!       room:
+//!         entity: house_place
+//!         values: [kitchen, bedroom, living room]
+//!   - intent: audio_volume_up
+//!     patterns: ["volume up [by {amount}]"]
+//!     slots:
+//!       amount: snips/number
+//!   - intent: alarm_set
+//!     patterns: ["wake me at {time}"]
+//!     slots:
+//!       time: snips/datetime
+//! ```
+//!
+//! Patterns are matched against the whole command, ignoring case. `(a|b)` is a choice,
+//! `[a]` is optional and `{name}` is a slot. Slots that are not declared match any text.
+//! `snips/number`, `snips/percentage`, `snips/datetime` and `snips/duration` slots only
+//! match text of their kind and have typed values, the datetime of a time of day being the
+//! next time it comes round.
+mod template;
+mod value;
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use regex::Regex;
+use serde::Deserialize;
+
+use chrono::Local;
+
+use crate::intents::{
+    Intent, IntentMapper, Number, ParsedIntent, Percentage, Range, Slot, SlotValue, Text,
+};
+
+#[derive(Debug)]
+pub enum RuleError {
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    Yaml {
+        path: PathBuf,
+        source: serde_yaml::Error,
+    },
+    /// A pattern could not be compiled
+    Pattern { pattern: String, reason: String },
+}
+
+impl Display for RuleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuleError::Io { path, source } => write!(f, "{}: {source}", path.display()),
+            RuleError::Yaml { path, source } => write!(f, "{}: {source}", path.display()),
+            RuleError::Pattern { pattern, reason } => {
+                write!(f, "invalid pattern \"{pattern}\": {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RuleError {}
+
+#[derive(Deserialize)]
+struct RuleFile {
+    /// ISO 639-1 code of the language the rules are written in. Rules without a language
+    /// apply to every language
+    language: Option<String>,
+    rules: Vec<RuleDefinition>,
+}
+
+#[derive(Deserialize)]
+struct RuleDefinition {
+    intent: Intent,
+    patterns: Vec<String>,
+    #[serde(default)]
+    slots: HashMap<String, SlotDefinition>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum SlotDefinition {
+    Entity(String),
+    Values {
+        entity: String,
+        #[serde(default)]
+        values: Vec<String>,
+    },
+}
+
+impl SlotDefinition {
+    fn entity(&self) -> &str {
+        match self {
+            SlotDefinition::Entity(entity) | SlotDefinition::Values { entity, .. } => entity,
+        }
+    }
+
+    fn values(&self) -> &[String] {
+        match self {
+            SlotDefinition::Entity(_) => &[],
+            SlotDefinition::Values { values, .. } => values,
+        }
+    }
+}
+
+/// What a slot placeholder matches and how its text becomes a [`SlotValue`]
+#[derive(Debug, Clone)]
+struct SlotKind {
+    name: String,
+    entity: String,
+}
+
+impl SlotKind {
+    fn value(&self, raw: &str) -> Option<SlotValue> {
+        let number = || {
+            raw.trim_end_matches(|c: char| !c.is_ascii_digit())
+                .replace(',', ".")
+                .parse()
+                .ok()
+        };
+        let value = match self.entity.as_str() {
+            "snips/number" => SlotValue::Number(Number { value: number()? }),
+            "snips/percentage" => SlotValue::Percentage(Percentage { value: number()? }),
+            "snips/datetime" => SlotValue::InstantTime(value::instant(raw, &Local::now())?),
+            "snips/duration" => SlotValue::Duration(value::duration(raw)?),
+            _ => SlotValue::Custom(Text {
+                value: raw.to_owned(),
+            }),
+        };
+        Some(value)
+    }
+}
+
+#[derive(Debug)]
+struct Pattern {
+    regex: Regex,
+    /// The slot captured by each group, group `s0` is `slots[0]`
+    slots: Vec<SlotKind>,
+}
+
+#[derive(Debug)]
+struct Rule {
+    intent: Intent,
+    patterns: Vec<Pattern>,
+}
+
+/// Rules in the order they are tried
+#[derive(Debug, Default)]
+pub struct RuleSet {
+    rules: Vec<Rule>,
+}
+
+impl RuleSet {
+    /// Loads every `.yaml` and `.yml` file in `dir` that applies to `language`, in file
+    /// name order. A missing directory has no rules
+    pub fn load_dir(dir: impl AsRef<Path>, language: &str) -> Result<Self, RuleError> {
+        let dir = dir.as_ref();
+        let io_error = |source| RuleError::Io {
+            path: dir.to_owned(),
+            source,
+        };
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(io_error(e)),
+        };
+        let mut paths = entries
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(io_error)?;
+        paths.retain(|path| {
+            matches!(
+                path.extension().and_then(|ext| ext.to_str()),
+                Some("yaml" | "yml")
+            )
+        });
+        paths.sort();
+
+        let mut rules = Self::default();
+        for path in paths {
+            let contents = std::fs::read_to_string(&path).map_err(|source| RuleError::Io {
+                path: path.clone(),
+                source,
+            })?;
+            let file: RuleFile =
+                serde_yaml::from_str(&contents).map_err(|source| RuleError::Yaml {
+                    path: path.clone(),
+                    source,
+                })?;
+            if file
+                .language
+                .map_or(true, |code| code.eq_ignore_ascii_case(language))
+            {
+                for definition in file.rules {
+                    rules.rules.push(Rule::compile(definition)?);
+                }
+            }
+        }
+        Ok(rules)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// The first rule that matches `input`, as a parse with full confidence
+    pub fn parse(&self, input: &str) -> Option<ParsedIntent> {
+        self.rules.iter().find_map(|rule| rule.parse(input))
+    }
+}
+
+impl Rule {
+    fn compile(definition: RuleDefinition) -> Result<Self, RuleError> {
+        let patterns = definition
+            .patterns
+            .iter()
+            .map(|pattern| template::compile(pattern, &definition.slots))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            intent: definition.intent,
+            patterns,
+        })
+    }
+
+    fn parse(&self, input: &str) -> Option<ParsedIntent> {
+        self.patterns.iter().find_map(|pattern| {
+            let captures = pattern.regex.captures(input)?;
+            let slots = pattern
+                .slots
+                .iter()
+                .enumerate()
+                .filter_map(|(index, kind)| {
+                    let capture = captures.name(&format!("s{index}"))?;
+                    // snips ranges count characters, not bytes
+                    let start = input[..capture.start()].chars().count();
+                    Some(Slot {
+                        raw_value: capture.as_str().to_owned(),
+                        value: kind.value(capture.as_str())?,
+                        alternatives: Vec::new(),
+                        range: Range {
+                            start: start as i64,
+                            end: (start + capture.as_str().chars().count()) as i64,
+                        },
+                        entity: kind.entity.clone(),
+                        slot_name: kind.name.clone(),
+                    })
+                })
+                .collect();
+            Some(ParsedIntent {
+                input: input.to_owned(),
+                intent: IntentMapper {
+                    intent_name: Some(self.intent.clone()),
+                    confidence_score: 1.0,
+                },
+                slots,
+                alternatives: Vec::new(),
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(yaml: &str) -> RuleSet {
+        let file: RuleFile = serde_yaml::from_str(yaml).unwrap();
+        RuleSet {
+            rules: file
+                .rules
+                .into_iter()
+                .map(|definition| Rule::compile(definition).unwrap())
+                .collect(),
+        }
+    }
+
+    const TYPED: &str = r#"
+rules:
+  - intent: alarm_set
+    patterns: ["wake me at {time}"]
+    slots:
+      time: snips/datetime
+  - intent: alarm_set
+    patterns: ["set a timer for {length}"]
+    slots:
+      length: snips/duration
+"#;
+
+    #[test]
+    fn datetime_slots() {
+        let parsed = rules(TYPED).parse("wake me at 7:30 am").unwrap();
+        assert_eq!(parsed.slots.len(), 1);
+        assert_eq!(parsed.slots[0].raw_value, "7:30 am");
+        match &parsed.slots[0].value {
+            SlotValue::InstantTime(instant) => {
+                let at = instant.datetime().unwrap().with_timezone(&Local);
+                assert_eq!(at.format("%H:%M").to_string(), "07:30");
+                assert!(at > Local::now());
+            }
+            other => panic!("{other:?}"),
+        }
+        assert!(rules(TYPED).parse("wake me at breakfast").is_none());
+    }
+
+    #[test]
+    fn duration_slots() {
+        let parsed = rules(TYPED)
+            .parse("set a timer for 1 hour and 30 minutes.")
+            .unwrap();
+        assert_eq!(parsed.slots[0].raw_value, "1 hour and 30 minutes");
+        match &parsed.slots[0].value {
+            SlotValue::Duration(duration) => assert_eq!(duration.to_chrono().num_minutes(), 90),
+            other => panic!("{other:?}"),
+        }
+        assert!(rules(TYPED).parse("set a timer for pasta").is_none());
+    }
+
+    const LIGHTS: &str = r#"
+rules:
+  - intent: iot_hue_lightoff
+    patterns:
+      - "(turn|switch) off the lights"
+      - "lights off [in the {room}]"
+    slots:
+      room:
+        entity: house_place
+        values: [kitchen, living, living room]
+  - intent: audio_volume_up
+    patterns: ["volume up [by {amount}]"]
+    slots:
+      amount: snips/number
+  - intent: iot_hue_lighton
+    patterns: ["turn on [the] {what}"]
+"#;
+
+    /// An intent, and the name, text and range of each of its slots
+    type Match = (String, Vec<(String, String, Range)>);
+
+    /// What `input` is parsed as
+    fn parse(input: &str) -> Option<Match> {
+        let parsed = rules(LIGHTS).parse(input)?;
+        assert_eq!(parsed.intent.confidence_score, 1.0);
+        let slots = parsed
+            .slots
+            .into_iter()
+            .map(|slot| (slot.slot_name, slot.raw_value, slot.range))
+            .collect();
+        Some((parsed.intent.intent_name?.name().to_owned(), slots))
+    }
+
+    fn range(start: i64, end: i64) -> Range {
+        Range { start, end }
+    }
+
+    #[test]
+    fn matches_alternatives() {
+        for input in [
+            "turn off the lights",
+            "Switch off the lights.",
+            "switch  off the lights",
+        ] {
+            assert_eq!(
+                parse(input),
+                Some(("iot_hue_lightoff".to_owned(), Vec::new())),
+                "{input}"
+            );
+        }
+        // The whole command has to match
+        assert_eq!(parse("flip off the lights"), None);
+        assert_eq!(parse("turn off the lights now"), None);
+        assert_eq!(parse("please turn off the lights"), None);
+    }
+
+    #[test]
+    fn matches_optional_parts() {
+        assert_eq!(
+            parse("lights off"),
+            Some(("iot_hue_lightoff".to_owned(), Vec::new()))
+        );
+        assert_eq!(
+            parse("volume up"),
+            Some(("audio_volume_up".to_owned(), Vec::new()))
+        );
+        let parsed = rules(LIGHTS).parse("volume up by 5").unwrap();
+        assert_eq!(
+            parsed.slots[0].value,
+            SlotValue::Number(Number { value: 5.0 })
+        );
+        // A number slot only matches numbers
+        assert_eq!(parse("volume up by a lot"), None);
+        assert_eq!(
+            parse("turn on the radio"),
+            Some((
+                "iot_hue_lighton".to_owned(),
+                vec![("what".to_owned(), "radio".to_owned(), range(12, 17))]
+            ))
+        );
+    }
+
+    #[test]
+    fn matches_listed_values_only() {
+        // The longest value wins
+        assert_eq!(
+            parse("lights off in the Living Room"),
+            Some((
+                "iot_hue_lightoff".to_owned(),
+                vec![("room".to_owned(), "Living Room".to_owned(), range(18, 29))]
+            ))
+        );
+        let parsed = rules(LIGHTS).parse("lights off in the kitchen").unwrap();
+        assert_eq!(parsed.slots[0].entity, "house_place");
+        assert_eq!(parse("lights off in the garage"), None);
+        // Values and words are matched whole
+        assert_eq!(parse("lights off in the kitchens"), None);
+        assert_eq!(parse("turn one the radio"), None);
+    }
+
+    #[test]
+    fn rejects_malformed_patterns() {
+        for pattern in [
+            "(turn|switch off",
+            "lights off [in",
+            "wake me at {}",
+            "lights}",
+            "play {song",
+        ] {
+            let definition = RuleDefinition {
+                intent: Intent::AlarmSet,
+                patterns: vec![pattern.to_owned()],
+                slots: HashMap::new(),
+            };
+            assert!(
+                matches!(Rule::compile(definition), Err(RuleError::Pattern { .. })),
+                "{pattern}"
+            );
+        }
+    }
+
+    #[test]
+    fn loads_rules_for_the_language() {
+        let dir = std::env::temp_dir().join(format!("kara-rules-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, contents: &str| std::fs::write(dir.join(name), contents).unwrap();
+        write(
+            "any.yaml",
+            "rules:\n  - intent: general_greet\n    patterns: [hello, hallo]\n",
+        );
+        write(
+            "en.yml",
+            "language: EN\nrules:\n  - intent: general_joke\n    patterns: [hello, bye]\n",
+        );
+        write(
+            "fr.yaml",
+            "language: fr\nrules:\n  - intent: general_joke\n    patterns: [salut]\n",
+        );
+        write("notes.txt", "not: [rules");
+
+        let english = RuleSet::load_dir(&dir, "en");
+        let french = RuleSet::load_dir(&dir, "fr");
+        write("broken.yaml", "rules: [");
+        let broken = RuleSet::load_dir(&dir, "en");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let english = english.unwrap();
+        assert_eq!(english.len(), 2);
+        let intent = |rules: &RuleSet, input: &str| {
+            rules
+                .parse(input)
+                .and_then(|parsed| parsed.intent.intent_name)
+                .map(|intent| intent.name().to_owned())
+        };
+        // Files are tried in name order
+        assert_eq!(intent(&english, "hello").as_deref(), Some("general_greet"));
+        assert_eq!(intent(&english, "bye").as_deref(), Some("general_joke"));
+        assert_eq!(intent(&english, "salut"), None);
+        let french = french.unwrap();
+        assert_eq!(french.len(), 2);
+        assert_eq!(intent(&french, "salut").as_deref(), Some("general_joke"));
+        assert!(matches!(broken, Err(RuleError::Yaml { .. })));
+        assert!(RuleSet::load_dir(dir.join("missing"), "en")
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,160 @@
+//! Compiles rule patterns such as `lights off [in the {room}]` to regular expressions
+use std::{collections::HashMap, iter::Peekable, str::Chars};
+
+use regex::RegexBuilder;
+
+use super::{value, Pattern, RuleError, SlotDefinition, SlotKind};
+
+/// Matches any number
+const NUMBER: &str = r"-?\d+(?:[.,]\d+)?";
+
+pub(super) fn compile(
+    pattern: &str,
+    definitions: &HashMap<String, SlotDefinition>,
+) -> Result<Pattern, RuleError> {
+    let mut compiler = Compiler {
+        chars: pattern.chars().peekable(),
+        definitions,
+        slots: Vec::new(),
+    };
+    let body = compiler
+        .alternatives(None)
+        .map_err(|reason| RuleError::Pattern {
+            pattern: pattern.to_owned(),
+            reason,
+        })?;
+    // Trailing punctuation such as a restored full stop does not change the command
+    let regex = RegexBuilder::new(&format!(r"^(?:{body})\s*\p{{P}}*\s*$"))
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| RuleError::Pattern {
+            pattern: pattern.to_owned(),
+            reason: e.to_string(),
+        })?;
+    Ok(Pattern {
+        regex,
+        slots: compiler.slots,
+    })
+}
+
+struct Compiler<'a> {
+    chars: Peekable<Chars<'a>>,
+    definitions: &'a HashMap<String, SlotDefinition>,
+    slots: Vec<SlotKind>,
+}
+
+impl Compiler<'_> {
+    /// Branches separated by `|`, up to `closing` or the end of the pattern
+    fn alternatives(&mut self, closing: Option<char>) -> Result<String, String> {
+        let mut branches = vec![self.sequence()?];
+        loop {
+            match (self.chars.next(), closing) {
+                (Some('|'), _) => branches.push(self.sequence()?),
+                (Some(c), Some(closing)) if c == closing => break,
+                (None, None) => break,
+                (None, Some(closing)) => return Err(format!("missing '{closing}'")),
+                (Some(c), _) => return Err(format!("unexpected '{c}'")),
+            }
+        }
+        Ok(branches.join("|"))
+    }
+
+    fn sequence(&mut self) -> Result<String, String> {
+        let mut regex = String::new();
+        let mut word = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if matches!(c, '|' | ')' | ']') {
+                break;
+            }
+            self.chars.next();
+            if c.is_whitespace() || matches!(c, '(' | '[' | '{' | '}') {
+                push_word(&mut regex, &mut word);
+            }
+            match c {
+                '(' => regex.push_str(&format!("(?:{})", self.alternatives(Some(')'))?)),
+                '[' => regex.push_str(&format!("(?:{})?", self.alternatives(Some(']'))?)),
+                '{' => regex.push_str(&self.slot()?),
+                '}' => return Err("unexpected '}'".to_owned()),
+                c if c.is_whitespace() => {}
+                c => word.push(c),
+            }
+        }
+        push_word(&mut regex, &mut word);
+        Ok(regex)
+    }
+
+    /// A `{name}` placeholder, after the opening brace
+    fn slot(&mut self) -> Result<String, String> {
+        let mut name = String::new();
+        loop {
+            match self.chars.next() {
+                Some('}') => break,
+                Some(c) => name.push(c),
+                None => return Err("missing '}'".to_owned()),
+            }
+        }
+        let name = name.trim().to_owned();
+        if name.is_empty() {
+            return Err("empty slot name".to_owned());
+        }
+        let (entity, pattern) = match self.definitions.get(&name) {
+            Some(definition) => {
+                let pattern = match (definition.entity(), definition.values()) {
+                    (_, values) if !values.is_empty() => choice(values),
+                    ("snips/number", _) => NUMBER.to_owned(),
+                    ("snips/percentage", _) => format!(r"{NUMBER}\s*(?:%|percent|per cent)"),
+                    ("snips/datetime", _) => value::TIME.to_owned(),
+                    ("snips/duration", _) => value::duration_pattern(),
+                    _ => ".+?".to_owned(),
+                };
+                (definition.entity().to_owned(), pattern)
+            }
+            None => (name.clone(), ".+?".to_owned()),
+        };
+        // Group names must be unique, so groups are numbered and mapped back to slots
+        let group = format!(r"\s*(?P<s{}>{pattern})", self.slots.len());
+        self.slots.push(SlotKind { name, entity });
+        Ok(group)
+    }
+}
+
+/// Appends a literal word to `regex`
+fn push_word(regex: &mut String, word: &mut String) {
+    if word.is_empty() {
+        return;
+    }
+    regex.push_str(r"\s*");
+    regex.push_str(&bounded(&regex::escape(word), word));
+    word.clear();
+}
+
+/// Any of `values`, longest first so that "living room" wins over "living"
+fn choice(values: &[String]) -> String {
+    let mut values: Vec<_> = values.iter().map(|value| value.trim()).collect();
+    values.sort_by_key(|value| std::cmp::Reverse(value.len()));
+    let branches: Vec<_> = values
+        .iter()
+        .map(|value| {
+            let words: Vec<_> = value.split_whitespace().map(regex::escape).collect();
+            bounded(&words.join(r"\s+"), value)
+        })
+        .collect();
+    format!("(?:{})", branches.join("|"))
+}
+
+/// Adds word boundaries to `escaped` where `text` starts or ends with a word character, so
+/// that "on" does not match the start of "one"
+fn bounded(escaped: &str, text: &str) -> String {
+    let is_word = |c: Option<char>| c.map_or(false, |c| c.is_alphanumeric() || c == '_');
+    let start = if is_word(text.chars().next()) {
+        r"\b"
+    } else {
+        ""
+    };
+    let end = if is_word(text.chars().last()) {
+        r"\b"
+    } else {
+        ""
+    };
+    format!("{start}{escaped}{end}")
+}
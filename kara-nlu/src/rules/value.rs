@@ -0,0 +1,238 @@
+//! Typed values of rule slots, read from normalised text such as "7:30 pm" or "1 hour and
+//! 30 minutes"
+use chrono::{DateTime, Duration as Span, TimeZone};
+
+use crate::intents::{Duration, Grain, InstantTime, Precision};
+
+/// The format snips uses for instants
+const INSTANT_FORMAT: &str = "%Y-%m-%d %H:%M:%S %:z";
+
+/// Matches a time of day: "7:30", "19h30", "7 pm" or "7 Uhr"
+pub(super) const TIME: &str =
+    r"\d{1,2}(?:[:h]\d{2})?\s*(?:am|pm|a\.m\.|p\.m\.|uhr)|\d{1,2}[:h]\d{2}";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Unit {
+    Second,
+    Minute,
+    Hour,
+    Day,
+    Week,
+}
+
+/// Words for units of time in every language, each with the unit it is
+const UNITS: &[(&str, Unit)] = &[
+    ("seconds", Unit::Second),
+    ("second", Unit::Second),
+    ("secs", Unit::Second),
+    ("sec", Unit::Second),
+    ("secondes", Unit::Second),
+    ("seconde", Unit::Second),
+    ("sekunden", Unit::Second),
+    ("sekunde", Unit::Second),
+    ("segundos", Unit::Second),
+    ("segundo", Unit::Second),
+    ("minutes", Unit::Minute),
+    ("minute", Unit::Minute),
+    ("mins", Unit::Minute),
+    ("min", Unit::Minute),
+    ("minuten", Unit::Minute),
+    ("minutos", Unit::Minute),
+    ("minuto", Unit::Minute),
+    ("hours", Unit::Hour),
+    ("hour", Unit::Hour),
+    ("hrs", Unit::Hour),
+    ("hr", Unit::Hour),
+    ("heures", Unit::Hour),
+    ("heure", Unit::Hour),
+    ("stunden", Unit::Hour),
+    ("stunde", Unit::Hour),
+    ("horas", Unit::Hour),
+    ("hora", Unit::Hour),
+    ("days", Unit::Day),
+    ("day", Unit::Day),
+    ("jours", Unit::Day),
+    ("jour", Unit::Day),
+    ("tagen", Unit::Day),
+    ("tage", Unit::Day),
+    ("tag", Unit::Day),
+    ("días", Unit::Day),
+    ("día", Unit::Day),
+    ("dias", Unit::Day),
+    ("dia", Unit::Day),
+    ("weeks", Unit::Week),
+    ("week", Unit::Week),
+    ("semaines", Unit::Week),
+    ("semaine", Unit::Week),
+    ("wochen", Unit::Week),
+    ("woche", Unit::Week),
+    ("semanas", Unit::Week),
+    ("semana", Unit::Week),
+];
+
+/// Joins the parts of a duration, e.g. the "and" of "1 hour and 30 minutes"
+const JOINERS: &[&str] = &["and", "et", "und", "y", ","];
+
+/// Matches an amount of time with its unit, or several of them, e.g. "1 hour and 30 minutes"
+pub(super) fn duration_pattern() -> String {
+    let units: Vec<_> = UNITS.iter().map(|(word, _)| regex::escape(word)).collect();
+    let part = format!(r"\d+\s*(?:{})\b", units.join("|"));
+    let joiners: Vec<_> = JOINERS.iter().map(|word| regex::escape(word)).collect();
+    format!(r"{part}(?:\s*(?:(?:{})\s*)?{part})*", joiners.join("|"))
+}
+
+/// The next time it is `raw` after `now`, e.g. tomorrow morning for "7:30 am" said in the
+/// evening
+pub(super) fn instant<Tz: TimeZone>(raw: &str, now: &DateTime<Tz>) -> Option<InstantTime>
+where
+    Tz::Offset: std::fmt::Display,
+{
+    let raw = raw.to_lowercase();
+    let digits_end = raw
+        .find(|c: char| !c.is_ascii_digit() && c != ':' && c != 'h')
+        .unwrap_or(raw.len());
+    let (clock, suffix) = raw.split_at(digits_end);
+    let (hour, minute) = match clock.split_once([':', 'h']) {
+        Some((hour, minute)) => (hour.parse::<u32>().ok()?, minute.parse::<u32>().ok()?),
+        None => (clock.parse::<u32>().ok()?, 0),
+    };
+    let hour = match suffix.trim().replace('.', "").as_str() {
+        "am" if hour == 12 => 0,
+        "pm" if hour < 12 => hour + 12,
+        "am" | "pm" if hour > 12 => return None,
+        _ => hour,
+    };
+    let today = now.naive_local().date().and_hms_opt(hour, minute, 0)?;
+    let mut at = now.timezone().from_local_datetime(&today).earliest()?;
+    if at <= *now {
+        at = now
+            .timezone()
+            .from_local_datetime(&(today + Span::days(1)))
+            .earliest()?;
+    }
+    Some(InstantTime {
+        value: at.format(INSTANT_FORMAT).to_string(),
+        grain: if clock.contains([':', 'h']) {
+            Grain::Minute
+        } else {
+            Grain::Hour
+        },
+        precision: Precision::Exact,
+    })
+}
+
+/// The duration `raw` says, adding up every amount in it
+pub(super) fn duration(raw: &str) -> Option<Duration> {
+    let mut duration = Duration {
+        years: 0,
+        quarters: 0,
+        months: 0,
+        weeks: 0,
+        days: 0,
+        hours: 0,
+        minutes: 0,
+        seconds: 0,
+        precision: Precision::Exact,
+    };
+    let raw = raw.to_lowercase();
+    let mut amount = None;
+    // "1hour" has no space between the amount and the unit
+    for word in raw
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .flat_map(|word| {
+            let split = word
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(word.len());
+            [&word[..split], &word[split..]]
+        })
+    {
+        if word.is_empty() || JOINERS.contains(&word) {
+            continue;
+        }
+        if let Ok(number) = word.parse::<i64>() {
+            amount = Some(number);
+            continue;
+        }
+        let unit = UNITS
+            .iter()
+            .find_map(|(unit_word, unit)| (*unit_word == word).then_some(*unit))?;
+        let amount = amount.take()?;
+        match unit {
+            Unit::Second => duration.seconds += amount,
+            Unit::Minute => duration.minutes += amount,
+            Unit::Hour => duration.hours += amount,
+            Unit::Day => duration.days += amount,
+            Unit::Week => duration.weeks += amount,
+        }
+    }
+    // An amount without a unit, as in "1 hour 30"
+    amount.is_none().then_some(duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::FixedOffset;
+
+    use super::*;
+
+    fn now() -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339("2022-08-01T18:00:00+02:00").unwrap()
+    }
+
+    fn at(raw: &str) -> Option<String> {
+        instant(raw, &now()).map(|instant| instant.value)
+    }
+
+    #[test]
+    fn times_later_today() {
+        assert_eq!(at("19:30").as_deref(), Some("2022-08-01 19:30:00 +02:00"));
+        assert_eq!(at("7:30 pm").as_deref(), Some("2022-08-01 19:30:00 +02:00"));
+        assert_eq!(at("8 p.m.").as_deref(), Some("2022-08-01 20:00:00 +02:00"));
+        assert_eq!(at("19h30").as_deref(), Some("2022-08-01 19:30:00 +02:00"));
+        assert_eq!(at("20 Uhr").as_deref(), Some("2022-08-01 20:00:00 +02:00"));
+    }
+
+    #[test]
+    fn times_that_have_passed_are_tomorrow() {
+        assert_eq!(at("7:30 am").as_deref(), Some("2022-08-02 07:30:00 +02:00"));
+        assert_eq!(at("12 am").as_deref(), Some("2022-08-02 00:00:00 +02:00"));
+        assert_eq!(at("18:00").as_deref(), Some("2022-08-02 18:00:00 +02:00"));
+    }
+
+    #[test]
+    fn grain_follows_what_was_said() {
+        assert_eq!(instant("8 pm", &now()).unwrap().grain, Grain::Hour);
+        assert_eq!(instant("20:00", &now()).unwrap().grain, Grain::Minute);
+    }
+
+    #[test]
+    fn impossible_times() {
+        assert_eq!(at("25:00"), None);
+        assert_eq!(at("7:75"), None);
+        assert_eq!(at("13 pm"), None);
+    }
+
+    #[test]
+    fn durations() {
+        let length = |raw| duration(raw).and_then(|duration| duration.to_std());
+        assert_eq!(
+            length("1 hour and 30 minutes"),
+            Some(std::time::Duration::from_secs(90 * 60))
+        );
+        assert_eq!(
+            length("2 hours, 5 mins"),
+            Some(std::time::Duration::from_secs(125 * 60))
+        );
+        assert_eq!(
+            length("10 Minuten"),
+            Some(std::time::Duration::from_secs(600))
+        );
+        assert_eq!(
+            length("1 semana y 2 días"),
+            Some(std::time::Duration::from_secs(9 * 24 * 3600))
+        );
+        assert_eq!(length("45secs"), Some(std::time::Duration::from_secs(45)));
+        assert_eq!(length("minutes"), None);
+        assert_eq!(length("5 apples"), None);
+    }
+}
@@ -0,0 +1,228 @@
+//! Splits compound commands such as "turn off the lights and play some jazz" into the
+//! commands they are made of.
+use std::ops::Range;
+
+use crate::{intents::Slot, Language};
+
+/// Words that join two commands, longest first within each language so that "and then"
+/// is found before "and"
+fn conjunctions(language: Language) -> &'static [&'static str] {
+    match language {
+        Language::English => &[
+            "and after that",
+            "after that",
+            "and then",
+            "then",
+            "and also",
+            "and",
+        ],
+        Language::French => &[
+            "et après ça",
+            "et ensuite",
+            "et puis",
+            "ensuite",
+            "puis",
+            "et",
+        ],
+        Language::German => &["und danach", "und dann", "danach", "dann", "und"],
+        Language::Spanish => &["y después", "y luego", "después", "luego", "y"],
+    }
+}
+
+pub struct Segmenter {
+    conjunctions: Vec<Vec<&'static str>>,
+}
+
+impl Segmenter {
+    pub fn new(language: Language) -> Self {
+        Self {
+            conjunctions: conjunctions(language)
+                .iter()
+                .map(|conjunction| conjunction.split_whitespace().collect())
+                .collect(),
+        }
+    }
+
+    /// Splits `text` on conjunctions. Conjunctions inside `slots`, such as the "and" in
+    /// "play rock and roll", are left alone. Returns `text` whole if there is nothing to
+    /// split on
+    pub fn split<'a>(&self, text: &'a str, slots: &[Slot]) -> Vec<&'a str> {
+        let protected: Vec<_> = slots.iter().map(|slot| byte_range(text, slot)).collect();
+        let words = words(text);
+        let mut parts = Vec::new();
+        let mut start = 0;
+        let mut index = 0;
+        while index < words.len() {
+            let matched = self.conjunctions.iter().find_map(|conjunction| {
+                let end = index + conjunction.len();
+                // A conjunction needs a command on either side
+                if index == 0 || end >= words.len() {
+                    return None;
+                }
+                let matches = words[index..end]
+                    .iter()
+                    .zip(conjunction)
+                    .all(|(word, expected)| normalise(&text[word.clone()]) == *expected);
+                matches.then_some(end)
+            });
+            match matched {
+                Some(end) => {
+                    let span = words[index].start..words[end - 1].end;
+                    let inside_slot = protected
+                        .iter()
+                        .any(|slot| slot.start < span.end && span.start < slot.end);
+                    if !inside_slot {
+                        parts.push(&text[start..span.start]);
+                        start = span.end;
+                    }
+                    index = end;
+                }
+                None => index += 1,
+            }
+        }
+        parts.push(&text[start..]);
+        let parts: Vec<_> = parts
+            .into_iter()
+            .map(|part| part.trim_matches(|c: char| c.is_whitespace() || c == ','))
+            .filter(|part| !part.is_empty())
+            .collect();
+        if parts.is_empty() {
+            vec![text]
+        } else {
+            parts
+        }
+    }
+}
+
+/// Byte spans of the words in `text`
+fn words(text: &str) -> Vec<Range<usize>> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (index, c) in text.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(word_start)) => {
+                words.push(word_start..index);
+                start = None;
+            }
+            (false, None) => start = Some(index),
+            _ => {}
+        }
+    }
+    if let Some(word_start) = start {
+        words.push(word_start..text.len());
+    }
+    words
+}
+
+fn normalise(word: &str) -> String {
+    word.trim_matches(|c: char| c.is_ascii_punctuation())
+        .to_lowercase()
+}
+
+/// Slot ranges count characters, this converts them to byte offsets in `text`
+fn byte_range(text: &str, slot: &Slot) -> Range<usize> {
+    let offset = |chars: i64| {
+        text.char_indices()
+            .nth(chars.max(0) as usize)
+            .map_or(text.len(), |(index, _)| index)
+    };
+    offset(slot.range.start)..offset(slot.range.end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intents::{Range as SlotRange, SlotValue, Text};
+
+    /// A slot over `value`, which must be in `text`
+    fn slot(text: &str, value: &str) -> Slot {
+        let start = text[..text.find(value).unwrap()].chars().count();
+        Slot {
+            raw_value: value.to_owned(),
+            value: SlotValue::Custom(Text {
+                value: value.to_owned(),
+            }),
+            alternatives: Vec::new(),
+            range: SlotRange {
+                start: start as i64,
+                end: (start + value.chars().count()) as i64,
+            },
+            entity: "music".to_owned(),
+            slot_name: "music".to_owned(),
+        }
+    }
+
+    fn split(text: &str) -> Vec<&str> {
+        Segmenter::new(Language::English).split(text, &[])
+    }
+
+    #[test]
+    fn splits_on_conjunctions() {
+        assert_eq!(
+            split("turn off the lights and play some jazz"),
+            ["turn off the lights", "play some jazz"]
+        );
+        assert_eq!(
+            split("set an alarm for 7:00, then turn off the lights and after that play jazz"),
+            ["set an alarm for 7:00", "turn off the lights", "play jazz"]
+        );
+    }
+
+    #[test]
+    fn longest_conjunction_wins() {
+        assert_eq!(
+            split("turn off the lights and then play some jazz"),
+            ["turn off the lights", "play some jazz"]
+        );
+    }
+
+    #[test]
+    fn needs_a_command_on_either_side() {
+        assert_eq!(split("and play some jazz"), ["and play some jazz"]);
+        assert_eq!(split("play some jazz and"), ["play some jazz and"]);
+        assert_eq!(split("play some jazz"), ["play some jazz"]);
+    }
+
+    #[test]
+    fn ignores_case_and_punctuation() {
+        assert_eq!(
+            split("Turn off the lights. And play some jazz."),
+            ["Turn off the lights.", "play some jazz."]
+        );
+    }
+
+    #[test]
+    fn keeps_slot_values_whole() {
+        let text = "play rock and roll";
+        assert_eq!(
+            Segmenter::new(Language::English).split(text, &[slot(text, "rock and roll")]),
+            [text]
+        );
+        let text = "play rock and roll and turn off the lights";
+        assert_eq!(
+            Segmenter::new(Language::English).split(text, &[slot(text, "rock and roll")]),
+            ["play rock and roll", "turn off the lights"]
+        );
+    }
+
+    #[test]
+    fn slot_ranges_count_characters() {
+        let text = "joue Café et Thé puis éteins la lumière";
+        assert_eq!(
+            Segmenter::new(Language::French).split(text, &[slot(text, "Café et Thé")]),
+            ["joue Café et Thé", "éteins la lumière"]
+        );
+    }
+
+    #[test]
+    fn other_languages() {
+        assert_eq!(
+            Segmenter::new(Language::German).split("mach das Licht aus und dann spiel Musik", &[]),
+            ["mach das Licht aus", "spiel Musik"]
+        );
+        assert_eq!(
+            Segmenter::new(Language::Spanish).split("apaga la luz y luego pon música", &[]),
+            ["apaga la luz", "pon música"]
+        );
+    }
+}